@@ -0,0 +1,480 @@
+mod session;
+
+use ai_manager_core::{
+    config::ConfigManager,
+    core_service::CoreService,
+    event_bus::EventBus,
+    service_manager::{ServiceManager, ServiceStatus},
+};
+use ai_manager_data_service::{connection, migrations, repository::ConversationRepository};
+use ai_manager_external_service::{CalendarProvider, EmailClient, GoogleCalendarClient};
+use ai_manager_llm_service::{UsageStats, UsageTracker};
+use ai_manager_shared::{http, Result, SystemError, CORE_SERVICE_ID, HEALTH_CHECK_TIMEOUT_SECONDS};
+use chrono::{DateTime, Duration, Utc};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use session::{Reply, Session};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Drive the AI Manager from the command line
+#[derive(Debug, Parser)]
+#[command(name = "ai-manager", version)]
+struct Cli {
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Chat with the assistant; `/quit` or end of input exits
+    Chat {
+        #[command(subcommand)]
+        command: Option<ChatCommand>,
+    },
+    /// Work with calendar events
+    Calendar {
+        #[command(subcommand)]
+        command: CalendarCommand,
+    },
+    /// Work with email
+    Email {
+        #[command(subcommand)]
+        command: EmailCommand,
+    },
+    /// Print LLM token usage and cost statistics
+    Usage {
+        /// Usage records exported with `UsageTracker::export_json`
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// Print the status of each service
+    Health {
+        /// Base URL of a running core's health endpoint, e.g.
+        /// http://127.0.0.1:9090; without it a core is started in-process
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ChatCommand {
+    /// Export a stored conversation as Markdown
+    Export {
+        /// Conversation to export; the latest one when not given
+        #[arg(long)]
+        conversation: Option<i64>,
+        /// File to write to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CalendarCommand {
+    /// List upcoming events
+    List {
+        /// How many days ahead to look
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+    },
+    /// Create an event
+    Create {
+        title: String,
+        /// Start time, RFC 3339 (e.g. 2024-05-01T09:00:00Z)
+        #[arg(long)]
+        start: DateTime<Utc>,
+        /// Length of the event in minutes
+        #[arg(long, default_value_t = 60)]
+        duration: i64,
+        #[arg(long)]
+        description: Option<String>,
+        /// Attendee email; may be repeated
+        #[arg(long = "attendee")]
+        attendees: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum EmailCommand {
+    /// Fetch unread email
+    Fetch {
+        /// Only fetch email received on or after this time (RFC 3339)
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    init_logging();
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Chat { command: None } => chat(cli.json).await,
+        Command::Chat {
+            command:
+                Some(ChatCommand::Export {
+                    conversation,
+                    output,
+                }),
+        } => chat_export(conversation, output).await,
+        Command::Calendar { command } => calendar(command, cli.json).await,
+        Command::Email { command } => email(command, cli.json).await,
+        Command::Usage { from } => usage(from, cli.json).await,
+        Command::Health { url } => match url {
+            Some(url) => remote_health(&url, cli.json).await,
+            None => health(cli.json).await,
+        },
+    }
+}
+
+/// Print `value` as JSON, or `text` for people
+fn emit<T: Serialize>(json: bool, value: &T, text: impl FnOnce() -> String) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        println!("{}", text());
+    }
+    Ok(())
+}
+
+async fn chat(json: bool) -> Result<()> {
+    let mut session = Session::start(ConfigManager::new()?.user_id()?).await?;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    loop {
+        if !json {
+            print!("> ");
+            std::io::stdout().flush()?;
+        }
+
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "/quit" || line == "/exit" {
+            break;
+        }
+
+        match session.send(line).await {
+            Ok(reply) if json => println!("{}", serde_json::to_string(&reply)?),
+            Ok(Reply { content, .. }) => println!("{}\n", content),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Render a conversation from the configured database as Markdown
+async fn chat_export(conversation_id: Option<i64>, output: Option<PathBuf>) -> Result<()> {
+    let config = ConfigManager::new()?;
+    let user_id = config.user_id()?;
+    let database = config.get_app_config()?.database;
+    let connection = connection::create_connection(&database).await?;
+    migrations::run_migrations_from_dir(&*connection, database.migrations_path()).await?;
+
+    let markdown = ConversationRepository::new(connection)
+        .export_markdown(&user_id, conversation_id)
+        .await?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, markdown)?;
+            eprintln!("Exported to {}", path.display());
+        }
+        None => print!("{}", markdown),
+    }
+    Ok(())
+}
+
+async fn calendar(command: CalendarCommand, json: bool) -> Result<()> {
+    let calendar = GoogleCalendarClient::new().await?;
+
+    match command {
+        CalendarCommand::List { days } => {
+            let now = Utc::now();
+            let events = calendar
+                .list_events(now, now + Duration::days(days))
+                .await?;
+
+            emit(json, &events, || {
+                if events.is_empty() {
+                    return "No upcoming events".to_string();
+                }
+                events
+                    .iter()
+                    .map(|event| {
+                        format!(
+                            "{} - {}  {}",
+                            event.local_start().format("%Y-%m-%d %H:%M"),
+                            event.local_end().format("%H:%M"),
+                            event.summary
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        CalendarCommand::Create {
+            title,
+            start,
+            duration,
+            description,
+            attendees,
+        } => {
+            let end = start + Duration::minutes(duration);
+            let event_id = calendar
+                .create_event(&title, description.as_deref(), start, end, &attendees)
+                .await?;
+
+            emit(json, &serde_json::json!({ "id": event_id }), || {
+                format!("Created event {}", event_id)
+            })
+        }
+    }
+}
+
+async fn email(command: EmailCommand, json: bool) -> Result<()> {
+    let client = EmailClient::new().await?;
+
+    match command {
+        EmailCommand::Fetch { since } => {
+            let emails = client.fetch_emails(since).await?;
+
+            emit(json, &emails, || {
+                if emails.is_empty() {
+                    return "No unread email".to_string();
+                }
+                emails
+                    .iter()
+                    .map(|email| {
+                        format!(
+                            "{}  {}: {}",
+                            email.timestamp.format("%Y-%m-%d %H:%M"),
+                            email.from,
+                            email.subject
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+    }
+}
+
+async fn usage(from: Option<PathBuf>, json: bool) -> Result<()> {
+    let tracker = UsageTracker::new();
+    if let Some(path) = from {
+        let records = tokio::fs::read_to_string(&path).await?;
+        tracker.import_json(&records).await?;
+    }
+
+    let stats = tracker.get_stats().await;
+    emit(json, &stats, || describe_usage(&stats))
+}
+
+fn describe_usage(stats: &UsageStats) -> String {
+    let mut lines = vec![format!(
+        "{} requests, {} tokens, ${:.4}",
+        stats.total_requests, stats.total_tokens, stats.total_cost
+    )];
+
+    let mut providers: Vec<_> = stats.by_provider.iter().collect();
+    providers.sort_by(|a, b| a.0.cmp(b.0));
+    for (provider, provider_stats) in providers {
+        lines.push(format!(
+            "  {}: {} requests, {} tokens, ${:.4}",
+            provider, provider_stats.requests, provider_stats.tokens, provider_stats.cost
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Start a core service under a service manager and report its status
+async fn health(json: bool) -> Result<()> {
+    let event_bus = Arc::new(EventBus::new());
+    let config_manager = ConfigManager::new()?;
+    let mut service_manager = ServiceManager::new(event_bus.clone());
+
+    let core_bus = event_bus.clone();
+    service_manager
+        .start_service(CORE_SERVICE_ID.to_string(), move || {
+            let event_bus = core_bus.clone();
+            let config_manager = config_manager.clone();
+            async move {
+                let mut core_service = CoreService::new(event_bus, config_manager);
+                core_service.start().await
+            }
+        })
+        .await?;
+
+    let statuses = service_manager.get_service_statuses().await;
+    service_manager.shutdown_all().await?;
+
+    emit(json, &statuses, || describe_statuses(&statuses))
+}
+
+fn describe_statuses(statuses: &HashMap<String, ServiceStatus>) -> String {
+    let mut services: Vec<_> = statuses.iter().collect();
+    services.sort_by(|a, b| a.0.cmp(b.0));
+
+    services
+        .into_iter()
+        .map(|(id, status)| {
+            let status = match status {
+                ServiceStatus::Starting => "starting".to_string(),
+                ServiceStatus::Running => "running".to_string(),
+                ServiceStatus::Stopping => "stopping".to_string(),
+                ServiceStatus::Stopped => "stopped".to_string(),
+                ServiceStatus::Failed { error } => format!("failed: {}", error),
+                ServiceStatus::Restarting => "restarting".to_string(),
+                ServiceStatus::CircuitOpen { until } => {
+                    format!("circuit open until {}", until.format("%H:%M:%S UTC"))
+                }
+            };
+            format!("{}: {}", id, status)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Ask a running core's `/readyz` endpoint whether its services are up
+async fn remote_health(url: &str, json: bool) -> Result<()> {
+    let url = format!("{}/readyz", url.trim_end_matches('/'));
+    let client = http::client(std::time::Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECONDS));
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| SystemError::Network(format!("Failed to reach {}: {}", url, e)))?;
+
+    let ready = response.status().is_success();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| SystemError::Network(format!("Failed to read response: {}", e)))?;
+
+    emit(
+        json,
+        &serde_json::json!({ "ready": ready, "detail": body }),
+        || body.clone(),
+    )?;
+
+    if ready {
+        Ok(())
+    } else {
+        Err(SystemError::ServiceUnavailable {
+            service: CORE_SERVICE_ID.to_string(),
+        })
+    }
+}
+
+fn init_logging() {
+    // Keep stdout for command output
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()),
+        )
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_calendar_create() {
+        let cli = Cli::try_parse_from([
+            "ai-manager",
+            "--json",
+            "calendar",
+            "create",
+            "Standup",
+            "--start",
+            "2024-05-01T09:00:00Z",
+            "--attendee",
+            "a@example.com",
+            "--attendee",
+            "b@example.com",
+        ])
+        .unwrap();
+
+        assert!(cli.json);
+        match cli.command {
+            Command::Calendar {
+                command:
+                    CalendarCommand::Create {
+                        title,
+                        duration,
+                        attendees,
+                        ..
+                    },
+            } => {
+                assert_eq!(title, "Standup");
+                assert_eq!(duration, 60);
+                assert_eq!(attendees.len(), 2);
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_chat_export() {
+        let cli =
+            Cli::try_parse_from(["ai-manager", "chat", "export", "--conversation", "3"]).unwrap();
+        match cli.command {
+            Command::Chat {
+                command:
+                    Some(ChatCommand::Export {
+                        conversation,
+                        output,
+                    }),
+            } => {
+                assert_eq!(conversation, Some(3));
+                assert!(output.is_none());
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+
+        let cli = Cli::try_parse_from(["ai-manager", "chat"]).unwrap();
+        assert!(matches!(cli.command, Command::Chat { command: None }));
+    }
+
+    #[test]
+    fn test_describe_statuses() {
+        let mut statuses = HashMap::new();
+        statuses.insert("llm".to_string(), ServiceStatus::Running);
+        statuses.insert(
+            "core".to_string(),
+            ServiceStatus::Failed {
+                error: "boom".to_string(),
+            },
+        );
+
+        assert_eq!(
+            describe_statuses(&statuses),
+            "core: failed: boom\nllm: running"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use ai_manager_core::{
+    config::ConfigManager,
+    core_service::{run_llm_service, CoreService},
+    event_bus::EventBus,
+};
+use ai_manager_llm_service::UsageTracker;
+use ai_manager_shared::messages::{ResponseType, ServiceMessage};
+use ai_manager_shared::{Result, SystemError, CORE_SERVICE_ID, LLM_REQUEST_TIMEOUT, UI_SERVICE_ID};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+/// How long to wait for the in-process core to register on the bus
+const CORE_STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A reply from the core to one line of user input
+#[derive(Debug, Clone, Serialize)]
+pub struct Reply {
+    pub content: String,
+    pub message_type: ResponseType,
+}
+
+/// Core and LLM services running in this process, with the CLI standing
+/// in for the UI service
+pub struct Session {
+    event_bus: Arc<EventBus>,
+    receiver: mpsc::Receiver<ServiceMessage>,
+    user_id: String,
+    core_handle: JoinHandle<()>,
+    llm_handle: JoinHandle<()>,
+}
+
+impl Session {
+    /// Start the core service and wait until it accepts messages
+    pub async fn start(user_id: String) -> Result<Self> {
+        let event_bus = Arc::new(EventBus::new());
+        let (_sender, receiver) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await?;
+
+        let config_manager = ConfigManager::new()?;
+        let llm_config = config_manager.get_app_config()?.llm;
+        let usage_tracker = Arc::new(UsageTracker::new());
+        let core_usage = usage_tracker.clone();
+        let core_bus = event_bus.clone();
+        let core_handle = tokio::spawn(async move {
+            let mut core_service =
+                CoreService::new(core_bus, config_manager).with_usage_tracker(core_usage);
+            if let Err(e) = core_service.start().await {
+                error!("Core service stopped: {}", e);
+            }
+        });
+
+        let llm_bus = event_bus.clone();
+        let llm_handle = tokio::spawn(async move {
+            // Stopped by aborting it when the session is dropped
+            let shutdown = CancellationToken::new();
+            if let Err(e) = run_llm_service(llm_bus, &llm_config, usage_tracker, shutdown).await {
+                error!("LLM service stopped: {}", e);
+            }
+        });
+
+        let session = Self {
+            event_bus,
+            receiver,
+            user_id,
+            core_handle,
+            llm_handle,
+        };
+        session.wait_for_core().await?;
+        Ok(session)
+    }
+
+    async fn wait_for_core(&self) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + CORE_STARTUP_TIMEOUT;
+        loop {
+            let registered = self.event_bus.get_registered_services().await;
+            if registered.iter().any(|id| id == CORE_SERVICE_ID) {
+                return Ok(());
+            }
+            if self.core_handle.is_finished() || tokio::time::Instant::now() >= deadline {
+                return Err(SystemError::ServiceUnavailable {
+                    service: CORE_SERVICE_ID.to_string(),
+                });
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Send `input` as the user and wait for the core's reply, skipping
+    /// "thinking" notices and their end markers
+    pub async fn send(&mut self, input: &str) -> Result<Reply> {
+        // Drop replies left over from a request that timed out
+        while self.receiver.try_recv().is_ok() {}
+
+        let user_input = ServiceMessage::UserInput {
+            content: input.to_string(),
+            timestamp: chrono::Utc::now(),
+            user_id: self.user_id.clone(),
+            request_id: None,
+            stream: false,
+        };
+        self.event_bus
+            .route_message(user_input, Some(CORE_SERVICE_ID.to_string()))
+            .await?;
+
+        let timeout = Duration::from_secs(LLM_REQUEST_TIMEOUT);
+        loop {
+            let message = tokio::time::timeout(timeout, self.receiver.recv())
+                .await
+                .map_err(|_| SystemError::Timeout)?
+                .ok_or_else(|| {
+                    SystemError::ServiceCommunication("UI service channel closed".to_string())
+                })?;
+
+            match message {
+                ServiceMessage::SystemResponse {
+                    message_type: ResponseType::Thinking | ResponseType::ThinkingDone,
+                    ..
+                } => continue,
+                ServiceMessage::SystemResponse {
+                    content,
+                    message_type,
+                    ..
+                } => {
+                    return Ok(Reply {
+                        content,
+                        message_type,
+                    })
+                }
+                ServiceMessage::SystemError { user_message, .. } => {
+                    return Ok(Reply {
+                        content: user_message,
+                        message_type: ResponseType::Error,
+                    })
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.core_handle.abort();
+        self.llm_handle.abort();
+    }
+}
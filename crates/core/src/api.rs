@@ -0,0 +1,1436 @@
+use crate::event_bus::EventBus;
+use crate::replies::PendingReplies;
+use ai_manager_llm_service::UsageTracker;
+use ai_manager_shared::auth::tokens_match;
+use ai_manager_shared::messages::{
+    CalendarAction, EmailAction, EmailData, ExportFormat, Message, ResponseType, ServiceMessage,
+    SystemEvent, TokenUsage,
+};
+use ai_manager_shared::{
+    ErrorCode, Result, ServerConfig, SystemError, CONTEXT_LOAD_TIMEOUT_SECONDS, CORE_SERVICE_ID,
+    DATA_SERVICE_ID, EXTERNAL_SERVICE_ID, UI_SERVICE_ID,
+};
+use axum::{
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        Extension, Path, Query, Request, State,
+    },
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+#[derive(Clone)]
+struct ApiState {
+    event_bus: Arc<EventBus>,
+    // Requests waiting for the replies the core sends to the UI service
+    replies: PendingReplies,
+    usage_tracker: Arc<UsageTracker>,
+    tokens: Arc<ApiTokens>,
+}
+
+/// Bearer tokens the API accepts
+#[derive(Clone, Default)]
+pub struct ApiTokens {
+    admin: Option<String>,
+    /// Tokens paired with the user they act as
+    users: Vec<(String, String)>,
+}
+
+impl ApiTokens {
+    /// `server.api_token` and `server.api_users`, with secrets resolved
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let tokens = Self {
+            admin: config.api_token.clone(),
+            users: Vec::new(),
+        };
+        config
+            .api_users
+            .iter()
+            .fold(tokens, |tokens, (user_id, token)| {
+                tokens.with_user(user_id, token)
+            })
+    }
+
+    /// Accept `token` for requests on behalf of any user
+    pub fn with_admin(mut self, token: impl Into<String>) -> Self {
+        self.admin = Some(token.into());
+        self
+    }
+
+    /// Accept `token` for requests on behalf of `user_id` only
+    pub fn with_user(mut self, user_id: impl Into<String>, token: impl Into<String>) -> Self {
+        self.users.push((token.into(), user_id.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.admin.is_none() && self.users.is_empty()
+    }
+
+    /// Who `token` authenticates as, if anyone
+    fn authenticate(&self, token: &str) -> Option<Caller> {
+        if self
+            .admin
+            .as_deref()
+            .is_some_and(|admin| tokens_match(admin, token))
+        {
+            return Some(Caller::Admin);
+        }
+        self.users
+            .iter()
+            .find(|(user_token, _)| tokens_match(user_token, token))
+            .map(|(_, user_id)| Caller::User(user_id.clone()))
+    }
+}
+
+/// Who a request's bearer token authenticates as
+#[derive(Debug, Clone, PartialEq)]
+enum Caller {
+    /// `server.api_token`, which may act for any user
+    Admin,
+    /// A token from `server.api_users`
+    User(String),
+}
+
+impl Caller {
+    /// The user a request acts for: the one it names, which a user token
+    /// only allows if it is its own user, or else the token's user
+    fn user_id(&self, requested: Option<String>) -> Result<String> {
+        match (self, requested) {
+            (Caller::Admin, Some(requested)) => Ok(requested),
+            (Caller::Admin, None) => {
+                Err(SystemError::InvalidInput("user_id is required".to_string()))
+            }
+            (Caller::User(own), None) => Ok(own.clone()),
+            (Caller::User(own), Some(requested)) if requested == *own => Ok(requested),
+            (Caller::User(own), Some(requested)) => Err(SystemError::Authentication(format!(
+                "Token for '{}' can't act for '{}'",
+                own, requested
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatRequest {
+    /// Defaults to the user the token belongs to
+    #[serde(default)]
+    pub user_id: Option<String>,
+    pub content: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatResponse {
+    pub content: String,
+    pub message_type: ResponseType,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// Conversation to export; the latest one when not given
+    pub conversation_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateEventRequest {
+    /// Defaults to the user the token belongs to
+    #[serde(default)]
+    pub user_id: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    #[serde(default)]
+    pub attendees: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailActionRequest {
+    /// Defaults to the user the token belongs to
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// One of the processed email's `suggested_actions`, e.g. "Block sender"
+    pub suggestion: String,
+    pub email: EmailData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsQuery {
+    /// Defaults to the user the token belongs to
+    pub user_id: Option<String>,
+}
+
+/// Query-string credentials, for clients such as browsers that can't set
+/// headers on a WebSocket upgrade
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// A frame sent by a WebSocket client
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    /// Input from the connection's user
+    UserInput { content: String },
+    /// Replace the set of `SystemEvent` types forwarded to this connection
+    Subscribe { events: Vec<String> },
+}
+
+/// A frame sent to a WebSocket client
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    SystemResponse {
+        content: String,
+        message_type: ResponseType,
+        timestamp: DateTime<Utc>,
+        request_id: Option<Uuid>,
+    },
+    #[serde(rename = "llm-chunk")]
+    LlmChunk {
+        request_id: Uuid,
+        delta: String,
+    },
+    #[serde(rename = "llm-done")]
+    LlmDone {
+        request_id: Uuid,
+        usage: Option<TokenUsage>,
+    },
+    SystemEvent {
+        event: SystemEvent,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+        request_id: Option<Uuid>,
+    },
+}
+
+/// An error returned from a handler, with its HTTP status
+struct ApiError {
+    status: StatusCode,
+    code: ErrorCode,
+    message: String,
+}
+
+impl ApiError {
+    /// An error the core reported in reply to a chat message
+    fn reply(code: ErrorCode, message: String) -> Self {
+        let status = match code {
+            ErrorCode::AuthFailed => StatusCode::BAD_GATEWAY,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::ProviderDown => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::BadInput => StatusCode::BAD_REQUEST,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            code,
+            message,
+        }
+    }
+}
+
+impl From<SystemError> for ApiError {
+    fn from(error: SystemError) -> Self {
+        let status = match &error {
+            SystemError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            SystemError::Authentication(_) => StatusCode::UNAUTHORIZED,
+            SystemError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            SystemError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            SystemError::RateLimitExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message, "code": self.code }));
+        (self.status, body).into_response()
+    }
+}
+
+/// Build the REST API router. Registers as the UI service on `event_bus` to
+/// receive the core's replies, so it can't share a bus with the desktop UI.
+pub async fn api_router(
+    event_bus: Arc<EventBus>,
+    usage_tracker: Arc<UsageTracker>,
+    tokens: ApiTokens,
+) -> Result<Router> {
+    let (_sender, ui_receiver) = event_bus
+        .register_service(UI_SERVICE_ID.to_string())
+        .await?;
+    let replies = PendingReplies::default();
+    tokio::spawn(replies.clone().dispatch(ui_receiver));
+
+    let state = ApiState {
+        event_bus,
+        replies,
+        usage_tracker,
+        tokens: Arc::new(tokens),
+    };
+
+    Ok(Router::new()
+        .route("/chat", post(chat))
+        .route("/conversations/:user_id", get(conversations))
+        .route("/conversations/:user_id/export", get(export_conversation))
+        .route("/usage", get(usage))
+        .route("/calendar/events", post(create_calendar_event))
+        .route("/email/actions", post(take_email_action))
+        .route("/ws", get(ws_upgrade))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_bearer_token,
+        ))
+        .with_state(state))
+}
+
+/// Serve the REST API on `addr` until the task is dropped
+pub async fn serve_api(
+    addr: SocketAddr,
+    event_bus: Arc<EventBus>,
+    usage_tracker: Arc<UsageTracker>,
+    tokens: ApiTokens,
+) -> Result<()> {
+    let router = api_router(event_bus, usage_tracker, tokens).await?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("REST API listening on http://{}", addr);
+
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+/// Reject requests without a known token, and record who the token belongs
+/// to for the handlers
+async fn require_bearer_token(
+    State(state): State<ApiState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let header_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let caller = header_token
+        .or_else(|| {
+            Query::<TokenQuery>::try_from_uri(request.uri())
+                .ok()
+                .and_then(|query| query.0.token)
+        })
+        .and_then(|token| state.tokens.authenticate(&token));
+
+    match caller {
+        Some(caller) => {
+            request.extensions_mut().insert(caller);
+            next.run(request).await
+        }
+        None => ApiError::from(SystemError::Authentication(
+            "Missing or invalid bearer token".to_string(),
+        ))
+        .into_response(),
+    }
+}
+
+/// Route the input to the core and return its reply
+async fn chat(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+    Json(request): Json<ChatRequest>,
+) -> std::result::Result<Json<ChatResponse>, ApiError> {
+    let user_id = caller.user_id(request.user_id)?;
+    if request.content.trim().is_empty() {
+        return Err(SystemError::InvalidInput("content is empty".to_string()).into());
+    }
+
+    let request_id = Uuid::new_v4();
+    let mut replies = state.replies.expect(request_id, &user_id);
+
+    let user_input = ServiceMessage::UserInput {
+        content: request.content,
+        timestamp: Utc::now(),
+        user_id,
+        request_id: Some(request_id),
+        stream: false,
+    };
+    state
+        .event_bus
+        .route_message(user_input, Some(CORE_SERVICE_ID.to_string()))
+        .await?;
+
+    loop {
+        match replies.next().await? {
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::Thinking | ResponseType::ThinkingDone,
+                ..
+            } => continue,
+            ServiceMessage::SystemResponse {
+                content,
+                message_type,
+                timestamp,
+                ..
+            } => {
+                return Ok(Json(ChatResponse {
+                    content,
+                    message_type,
+                    timestamp,
+                }))
+            }
+            ServiceMessage::SystemError {
+                code, user_message, ..
+            } => return Err(ApiError::reply(code, user_message)),
+            _ => continue,
+        }
+    }
+}
+
+/// The user's recent messages, oldest first, as stored by the data service
+async fn conversations(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+    Path(user_id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> std::result::Result<Json<Vec<Message>>, ApiError> {
+    let user_id = caller.user_id(Some(user_id))?;
+    let request = ServiceMessage::LoadConversationHistory {
+        user_id,
+        limit: query
+            .limit
+            .unwrap_or(ai_manager_shared::CONVERSATION_CONTEXT_MESSAGES),
+        request_id: Uuid::new_v4(),
+    };
+
+    let response = state
+        .event_bus
+        .send_and_await_response(
+            request,
+            Some(DATA_SERVICE_ID.to_string()),
+            Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+        )
+        .await?;
+
+    match response {
+        ServiceMessage::ConversationHistoryResponse { messages, .. } => Ok(Json(messages)),
+        other => Err(SystemError::ServiceCommunication(format!(
+            "Unexpected reply to history request: {}",
+            other.message_type()
+        ))
+        .into()),
+    }
+}
+
+/// A conversation rendered as Markdown
+async fn export_conversation(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+    Path(user_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> std::result::Result<Response, ApiError> {
+    let user_id = caller.user_id(Some(user_id))?;
+    let request = ServiceMessage::ExportConversation {
+        user_id,
+        conversation_id: query.conversation_id,
+        format: ExportFormat::Markdown,
+        spill_to_file: false,
+        request_id: Uuid::new_v4(),
+    };
+
+    let response = state
+        .event_bus
+        .send_and_await_response(
+            request,
+            Some(DATA_SERVICE_ID.to_string()),
+            Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+        )
+        .await?;
+
+    match response {
+        ServiceMessage::ConversationExport { content, .. } => Ok((
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            content,
+        )
+            .into_response()),
+        ServiceMessage::SystemError {
+            code, user_message, ..
+        } => Err(ApiError::reply(code, user_message)),
+        other => Err(SystemError::ServiceCommunication(format!(
+            "Unexpected reply to export request: {}",
+            other.message_type()
+        ))
+        .into()),
+    }
+}
+
+/// Token usage of the token's user, or of every user for the admin token
+async fn usage(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let stats = match caller {
+        Caller::Admin => state.usage_tracker.get_stats().await,
+        Caller::User(user_id) => state.usage_tracker.get_user_stats(&user_id, None).await,
+    };
+    Json(stats)
+}
+
+/// Hand the event to the external service; creation happens asynchronously
+async fn create_calendar_event(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+    Json(request): Json<CreateEventRequest>,
+) -> std::result::Result<StatusCode, ApiError> {
+    let user_id = caller.user_id(request.user_id)?;
+    if request.end_time <= request.start_time {
+        return Err(
+            SystemError::InvalidInput("end_time must be after start_time".to_string()).into(),
+        );
+    }
+
+    debug!(
+        "Creating calendar event '{}' for {}",
+        request.title, user_id
+    );
+    let message = ServiceMessage::CalendarSync {
+        action: CalendarAction::CreateEvent {
+            title: request.title,
+            description: request.description,
+            start_time: request.start_time,
+            end_time: request.end_time,
+            attendees: request.attendees,
+        },
+    };
+    state
+        .event_bus
+        .route_message(message, Some(EXTERNAL_SERVICE_ID.to_string()))
+        .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Carry out a suggested action on a processed email; the external service
+/// acts on it asynchronously
+async fn take_email_action(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+    Json(request): Json<EmailActionRequest>,
+) -> std::result::Result<StatusCode, ApiError> {
+    let user_id = caller.user_id(request.user_id)?;
+    let action =
+        EmailAction::from_suggestion(&request.suggestion, &request.email).ok_or_else(|| {
+            SystemError::InvalidInput(format!(
+                "'{}' is not an action that can be taken",
+                request.suggestion
+            ))
+        })?;
+
+    debug!("Taking email action {:?} for {}", action, user_id);
+    state
+        .event_bus
+        .route_message(
+            ServiceMessage::EmailAction { action },
+            Some(EXTERNAL_SERVICE_ID.to_string()),
+        )
+        .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Upgrade to a WebSocket carrying chat for the user and the system events
+/// the client subscribes to
+async fn ws_upgrade(
+    State(state): State<ApiState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<WsQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    match caller.user_id(query.user_id) {
+        Ok(user_id) => ws.on_upgrade(move |socket| handle_socket(socket, state, user_id)),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+async fn handle_socket(socket: WebSocket, state: ApiState, user_id: String) {
+    debug!("WebSocket connected for {}", user_id);
+    let (mut sink, mut stream) = socket.split();
+    let (frames, mut outgoing) = mpsc::channel::<ServerFrame>(64);
+
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            let text = match serde_json::to_string(&frame) {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("Failed to serialize WebSocket frame: {}", e);
+                    continue;
+                }
+            };
+            if sink.send(WsMessage::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let subscriptions = Arc::new(RwLock::new(HashSet::<String>::new()));
+    let events = tokio::spawn(forward_events(
+        state.event_bus.subscribe_to_events(),
+        subscriptions.clone(),
+        frames.clone(),
+    ));
+
+    while let Some(Ok(message)) = stream.next().await {
+        let text = match message {
+            WsMessage::Text(text) => text,
+            WsMessage::Close(_) => break,
+            _ => continue,
+        };
+
+        let result = match serde_json::from_str::<ClientFrame>(&text) {
+            Ok(ClientFrame::UserInput { content }) => {
+                stream_reply(&state, &user_id, content, &frames).await
+            }
+            Ok(ClientFrame::Subscribe { events }) => {
+                *subscriptions.write().await = events.into_iter().collect();
+                Ok(())
+            }
+            Err(e) => Err(SystemError::InvalidInput(format!("Invalid frame: {}", e))),
+        };
+
+        if let Err(e) = result {
+            let _ = frames
+                .send(ServerFrame::Error {
+                    code: e.code(),
+                    message: e.to_string(),
+                    request_id: None,
+                })
+                .await;
+        }
+    }
+
+    events.abort();
+    drop(frames);
+    let _ = writer.await;
+    debug!("WebSocket closed for {}", user_id);
+}
+
+/// Send bus events whose type is in `subscriptions` to the client
+async fn forward_events(
+    mut events: broadcast::Receiver<SystemEvent>,
+    subscriptions: Arc<RwLock<HashSet<String>>>,
+    frames: mpsc::Sender<ServerFrame>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if !subscriptions.read().await.contains(event.event_type()) {
+                    continue;
+                }
+                if frames
+                    .send(ServerFrame::SystemEvent { event })
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("WebSocket client missed {} system events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Route the input to the core and forward its replies as frames until the
+/// response is complete
+async fn stream_reply(
+    state: &ApiState,
+    user_id: &str,
+    content: String,
+    frames: &mpsc::Sender<ServerFrame>,
+) -> Result<()> {
+    if content.trim().is_empty() {
+        return Err(SystemError::InvalidInput("content is empty".to_string()));
+    }
+
+    let request_id = Uuid::new_v4();
+    let mut replies = state.replies.expect(request_id, user_id);
+
+    let user_input = ServiceMessage::UserInput {
+        content,
+        timestamp: Utc::now(),
+        user_id: user_id.to_string(),
+        request_id: Some(request_id),
+        stream: true,
+    };
+    state
+        .event_bus
+        .route_message(user_input, Some(CORE_SERVICE_ID.to_string()))
+        .await?;
+
+    loop {
+        let (frame, done) = match replies.next().await? {
+            ServiceMessage::LLMResponseChunk {
+                request_id,
+                delta,
+                done: false,
+                ..
+            } => (ServerFrame::LlmChunk { request_id, delta }, false),
+            ServiceMessage::LLMResponseChunk {
+                request_id,
+                delta,
+                usage,
+                ..
+            } => {
+                if !delta.is_empty() {
+                    let frame = ServerFrame::LlmChunk { request_id, delta };
+                    frames.send(frame).await.map_err(|_| {
+                        SystemError::ServiceCommunication("WebSocket connection closed".to_string())
+                    })?;
+                }
+                (ServerFrame::LlmDone { request_id, usage }, true)
+            }
+            ServiceMessage::SystemResponse {
+                content,
+                message_type,
+                timestamp,
+                request_id,
+            } => {
+                // Any reply but a thinking notice is final, e.g. for system
+                // commands, which aren't streamed
+                let done = !matches!(
+                    message_type,
+                    ResponseType::Thinking | ResponseType::ThinkingDone
+                );
+                let frame = ServerFrame::SystemResponse {
+                    content,
+                    message_type,
+                    timestamp,
+                    request_id,
+                };
+                (frame, done)
+            }
+            ServiceMessage::SystemError {
+                code,
+                user_message,
+                request_id,
+            } => {
+                let frame = ServerFrame::Error {
+                    code,
+                    message: user_message,
+                    request_id,
+                };
+                (frame, true)
+            }
+            _ => continue,
+        };
+
+        frames.send(frame).await.map_err(|_| {
+            SystemError::ServiceCommunication("WebSocket connection closed".to_string())
+        })?;
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    async fn test_router(event_bus: Arc<EventBus>) -> Router {
+        let tokens = ApiTokens::default()
+            .with_admin("secret")
+            .with_user("alice", "alice-token");
+        api_router(event_bus, Arc::new(UsageTracker::new()), tokens)
+            .await
+            .unwrap()
+    }
+
+    /// State as `api_router` sets it up, for calling handlers directly
+    async fn test_state(event_bus: Arc<EventBus>) -> ApiState {
+        let (_tx, ui_receiver) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let replies = PendingReplies::default();
+        tokio::spawn(replies.clone().dispatch(ui_receiver));
+        ApiState {
+            event_bus,
+            replies,
+            usage_tracker: Arc::new(UsageTracker::new()),
+            tokens: Arc::new(ApiTokens::default()),
+        }
+    }
+
+    fn authorized(request: axum::http::request::Builder) -> axum::http::request::Builder {
+        request.header(header::AUTHORIZATION, "Bearer secret")
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_token_are_rejected() {
+        let router = test_router(Arc::new(EventBus::new())).await;
+
+        let response = router
+            .clone()
+            .oneshot(Request::get("/usage").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = router
+            .oneshot(
+                Request::get("/usage")
+                    .header(header::AUTHORIZATION, "Bearer wrong")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_usage_returns_stats() {
+        let router = test_router(Arc::new(EventBus::new())).await;
+
+        let response = router
+            .oneshot(
+                authorized(Request::get("/usage"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_user_token_sees_only_own_usage() {
+        let usage_tracker = Arc::new(UsageTracker::new());
+        let usage = TokenUsage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            total_tokens: 15,
+        };
+        for user_id in ["alice", "bob"] {
+            usage_tracker
+                .record_usage(user_id, "openai", "gpt-4", &usage)
+                .await;
+        }
+        let tokens = ApiTokens::default()
+            .with_admin("secret")
+            .with_user("alice", "alice-token");
+        let router = api_router(Arc::new(EventBus::new()), usage_tracker, tokens)
+            .await
+            .unwrap();
+
+        for (token, requests) in [("alice-token", 1), ("secret", 2)] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::get("/usage")
+                        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(stats["total_requests"], requests);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_returns_core_reply() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let router = test_router(event_bus.clone()).await;
+
+        // Stand-in core that answers every input
+        tokio::spawn(async move {
+            while let Some(message) = core_rx.recv().await {
+                if let ServiceMessage::UserInput {
+                    content,
+                    request_id,
+                    ..
+                } = message
+                {
+                    for (content, message_type) in [
+                        ("...".to_string(), ResponseType::Thinking),
+                        (format!("echo: {}", content), ResponseType::Info),
+                    ] {
+                        let reply = ServiceMessage::SystemResponse {
+                            content,
+                            message_type,
+                            timestamp: Utc::now(),
+                            request_id,
+                        };
+                        event_bus
+                            .route_message(reply, Some(UI_SERVICE_ID.to_string()))
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        });
+
+        let response = router
+            .oneshot(
+                authorized(Request::post("/chat"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"user_id": "u1", "content": "hi"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(reply["content"], "echo: hi");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chats_get_their_own_replies() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let tokens = ApiTokens::default()
+            .with_user("alice", "alice-token")
+            .with_user("bob", "bob-token");
+        let router = api_router(event_bus.clone(), Arc::new(UsageTracker::new()), tokens)
+            .await
+            .unwrap();
+
+        // Stand-in core that answers once both inputs are in, in reverse
+        // order, so each reply arrives while the other request is waiting
+        tokio::spawn(async move {
+            let mut inputs = Vec::new();
+            while let Some(ServiceMessage::UserInput {
+                user_id,
+                request_id,
+                ..
+            }) = core_rx.recv().await
+            {
+                inputs.push((user_id, request_id));
+                if inputs.len() < 2 {
+                    continue;
+                }
+                for (user_id, request_id) in inputs.drain(..).rev() {
+                    let reply = ServiceMessage::SystemResponse {
+                        content: format!("for {}", user_id),
+                        message_type: ResponseType::Info,
+                        timestamp: Utc::now(),
+                        request_id,
+                    };
+                    event_bus
+                        .route_message(reply, Some(UI_SERVICE_ID.to_string()))
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let chat = |user_id: &'static str| {
+            let router = router.clone();
+            async move {
+                let request = Request::post("/chat")
+                    .header(header::AUTHORIZATION, format!("Bearer {}-token", user_id))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"content": "hi"}"#))
+                    .unwrap();
+                let response = router.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let reply: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                reply["content"].as_str().unwrap().to_string()
+            }
+        };
+
+        let (alice, bob) = tokio::join!(chat("alice"), chat("bob"));
+        assert_eq!(alice, "for alice");
+        assert_eq!(bob, "for bob");
+    }
+
+    #[tokio::test]
+    async fn test_user_token_acts_only_for_its_user() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let router = test_router(event_bus.clone()).await;
+
+        // Stand-in core that replies with who sent the input
+        tokio::spawn(async move {
+            while let Some(ServiceMessage::UserInput {
+                user_id,
+                request_id,
+                ..
+            }) = core_rx.recv().await
+            {
+                let reply = ServiceMessage::SystemResponse {
+                    content: user_id,
+                    message_type: ResponseType::Info,
+                    timestamp: Utc::now(),
+                    request_id,
+                };
+                event_bus
+                    .route_message(reply, Some(UI_SERVICE_ID.to_string()))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let chat = |body: &'static str| {
+            Request::post("/chat")
+                .header(header::AUTHORIZATION, "Bearer alice-token")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = router
+            .clone()
+            .oneshot(chat(r#"{"content": "hi"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(reply["content"], "alice");
+
+        let response = router
+            .clone()
+            .oneshot(chat(r#"{"user_id": "bob", "content": "hi"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = router
+            .oneshot(
+                Request::get("/conversations/bob")
+                    .header(header::AUTHORIZATION, "Bearer alice-token")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_tokens_from_config() {
+        let config = ServerConfig {
+            api_token: Some("admin-token".to_string()),
+            api_users: HashMap::from([("alice".to_string(), "alice-token".to_string())]),
+            ..Default::default()
+        };
+        let tokens = ApiTokens::from_config(&config);
+
+        assert_eq!(tokens.authenticate("admin-token"), Some(Caller::Admin));
+        assert_eq!(
+            tokens.authenticate("alice-token"),
+            Some(Caller::User("alice".to_string()))
+        );
+        assert_eq!(tokens.authenticate("guess"), None);
+        assert!(ApiTokens::default().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chat_error_reply_maps_to_status() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let router = test_router(event_bus.clone()).await;
+
+        tokio::spawn(async move {
+            while let Some(ServiceMessage::UserInput { request_id, .. }) = core_rx.recv().await {
+                let error = SystemError::RateLimitExceeded {
+                    service: "openai".to_string(),
+                };
+                event_bus
+                    .route_message(ServiceMessage::error_reply(&error, request_id), None)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let response = router
+            .oneshot(
+                authorized(Request::post("/chat"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"user_id": "u1", "content": "hi"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(reply["code"], "RateLimited");
+    }
+
+    #[tokio::test]
+    async fn test_calendar_event_must_end_after_start() {
+        let router = test_router(Arc::new(EventBus::new())).await;
+
+        let response = router
+            .oneshot(
+                authorized(Request::post("/calendar/events"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        r#"{"user_id": "u1", "title": "Standup", "start_time": "2024-05-01T10:00:00Z", "end_time": "2024-05-01T09:00:00Z"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_event_only_for_own_user() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut external_rx) = event_bus
+            .register_service(EXTERNAL_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let router = test_router(event_bus).await;
+
+        let create = |body: &'static str| {
+            Request::post("/calendar/events")
+                .header(header::AUTHORIZATION, "Bearer alice-token")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = router
+            .clone()
+            .oneshot(create(
+                r#"{"user_id": "bob", "title": "Standup", "start_time": "2024-05-01T09:00:00Z", "end_time": "2024-05-01T10:00:00Z"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(external_rx.try_recv().is_err());
+
+        let response = router
+            .oneshot(create(
+                r#"{"title": "Standup", "start_time": "2024-05-01T09:00:00Z", "end_time": "2024-05-01T10:00:00Z"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(matches!(
+            external_rx.try_recv(),
+            Ok(ServiceMessage::CalendarSync { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_block_sender_suggestion_stores_rule() {
+        use ai_manager_external_service::{ExternalService, Service as _};
+        use ai_manager_shared::SenderRule;
+
+        let event_bus = Arc::new(EventBus::new());
+        let (_data_tx, mut data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        // The real external service, with its messages put on the bus
+        let (external_out, mut external_messages) = mpsc::channel(100);
+        let mut external = ExternalService::new(external_out).await.unwrap();
+        let (_external_tx, external_rx) = event_bus
+            .register_service(EXTERNAL_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        tokio::spawn(async move { external.start(external_rx).await });
+        let bus = event_bus.clone();
+        tokio::spawn(async move {
+            while let Some(message) = external_messages.recv().await {
+                let _ = bus.route_message(message, None).await;
+            }
+        });
+
+        let router = test_router(event_bus).await;
+        let take = |suggestion: &str| {
+            let body = serde_json::json!({
+                "suggestion": suggestion,
+                "email": {
+                    "id": "42",
+                    "from": "deals@spam.example",
+                    "to": ["alice@example.com"],
+                    "subject": "You won!",
+                    "body": "Claim your prize",
+                    "timestamp": "2024-05-01T09:00:00Z",
+                    "is_read": false,
+                    "attachments": []
+                }
+            });
+            Request::post("/email/actions")
+                .header(header::AUTHORIZATION, "Bearer alice-token")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(take("Reply")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = router.oneshot(take("Block sender")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), data_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let ServiceMessage::StoreSenderRule { address, rule } = message {
+                assert_eq!(address, "deals@spam.example");
+                assert_eq!(rule, Some(SenderRule::Block));
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_query_parameter_is_accepted() {
+        let router = test_router(Arc::new(EventBus::new())).await;
+
+        let response = router
+            .oneshot(
+                Request::get("/usage?token=secret")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_frame_serialization() {
+        let frame: ClientFrame =
+            serde_json::from_str(r#"{"type": "user_input", "content": "hi"}"#).unwrap();
+        assert!(matches!(frame, ClientFrame::UserInput { content } if content == "hi"));
+
+        let chunk = serde_json::to_value(ServerFrame::LlmChunk {
+            request_id: Uuid::nil(),
+            delta: "Hel".to_string(),
+        })
+        .unwrap();
+        assert_eq!(chunk["type"], "llm-chunk");
+        assert_eq!(chunk["delta"], "Hel");
+    }
+
+    #[tokio::test]
+    async fn test_stream_reply_forwards_llm_chunks() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let state = test_state(event_bus.clone()).await;
+
+        // Stand-in core that answers input with chunks as the LLM service
+        // would stream them, and forwards chunks like the real core does
+        let handler = crate::handlers::LLMResponseHandler::new(event_bus.clone());
+        let llm_bus = event_bus.clone();
+        tokio::spawn(async move {
+            while let Some(message) = core_rx.recv().await {
+                match message {
+                    ServiceMessage::UserInput { request_id, .. } => {
+                        let request_id = request_id.unwrap();
+                        for (delta, done) in [("Hel", false), ("lo", false), ("", true)] {
+                            let chunk = ServiceMessage::LLMResponseChunk {
+                                request_id,
+                                delta: delta.to_string(),
+                                done,
+                                usage: done.then_some(TokenUsage {
+                                    prompt_tokens: 3,
+                                    completion_tokens: 2,
+                                    total_tokens: 5,
+                                }),
+                            };
+                            llm_bus.route_message(chunk, None).await.unwrap();
+                        }
+                    }
+                    chunk @ ServiceMessage::LLMResponseChunk { .. } => {
+                        handler.handle_streaming_response(chunk).await.unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        let (frames_tx, mut frames_rx) = mpsc::channel(16);
+        stream_reply(&state, "alice", "hi".to_string(), &frames_tx)
+            .await
+            .unwrap();
+        drop(frames_tx);
+
+        let mut frames = Vec::new();
+        while let Some(frame) = frames_rx.recv().await {
+            frames.push(serde_json::to_value(frame).unwrap());
+        }
+        let types: Vec<&str> = frames
+            .iter()
+            .map(|frame| frame["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["llm-chunk", "llm-chunk", "system_response", "llm-done"]
+        );
+        assert_eq!(frames[0]["delta"], "Hel");
+        assert_eq!(frames[1]["delta"], "lo");
+        for frame in [&frames[1], &frames[2], &frames[3]] {
+            assert_eq!(frame["request_id"], frames[0]["request_id"]);
+        }
+        assert_eq!(frames[3]["usage"]["total_tokens"], 5);
+    }
+
+    /// Frames `stream_reply` sends for `user_id`'s input
+    async fn streamed_frames(state: &ApiState, user_id: &str) -> Vec<serde_json::Value> {
+        let (frames_tx, mut frames_rx) = mpsc::channel(16);
+        stream_reply(state, user_id, "hi".to_string(), &frames_tx)
+            .await
+            .unwrap();
+        drop(frames_tx);
+
+        let mut frames = Vec::new();
+        while let Some(frame) = frames_rx.recv().await {
+            frames.push(serde_json::to_value(frame).unwrap());
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_streams_get_their_own_replies() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let state = test_state(event_bus.clone()).await;
+
+        // Stand-in core that streams a reply to each input once both are
+        // in, interleaving the two streams
+        tokio::spawn(async move {
+            let mut inputs = Vec::new();
+            while let Some(ServiceMessage::UserInput {
+                user_id,
+                request_id,
+                ..
+            }) = core_rx.recv().await
+            {
+                inputs.push((user_id, request_id.unwrap()));
+                if inputs.len() < 2 {
+                    continue;
+                }
+                for done in [false, true] {
+                    for (user_id, request_id) in &inputs {
+                        let chunk = ServiceMessage::LLMResponseChunk {
+                            request_id: *request_id,
+                            delta: if done { String::new() } else { user_id.clone() },
+                            done,
+                            usage: None,
+                        };
+                        event_bus
+                            .route_message(chunk, Some(UI_SERVICE_ID.to_string()))
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        });
+
+        let (alice, bob) = tokio::join!(
+            streamed_frames(&state, "alice"),
+            streamed_frames(&state, "bob")
+        );
+        for (frames, user_id) in [(alice, "alice"), (bob, "bob")] {
+            let types: Vec<&str> = frames
+                .iter()
+                .map(|frame| frame["type"].as_str().unwrap())
+                .collect();
+            assert_eq!(types, vec!["llm-chunk", "llm-done"]);
+            assert_eq!(frames[0]["delta"], user_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_events_respects_subscriptions() {
+        let (events_tx, events_rx) = broadcast::channel(16);
+        let subscriptions = Arc::new(RwLock::new(HashSet::from(["ServiceStarted".to_string()])));
+        let (frames_tx, mut frames_rx) = mpsc::channel(16);
+        tokio::spawn(forward_events(events_rx, subscriptions, frames_tx));
+
+        events_tx
+            .send(SystemEvent::ServiceStopped {
+                service_id: "llm".to_string(),
+            })
+            .unwrap();
+        events_tx
+            .send(SystemEvent::ServiceStarted {
+                service_id: "data".to_string(),
+            })
+            .unwrap();
+
+        match frames_rx.recv().await.unwrap() {
+            ServerFrame::SystemEvent {
+                event: SystemEvent::ServiceStarted { service_id },
+            } => assert_eq!(service_id, "data"),
+            other => panic!("Unexpected frame: {:?}", other),
+        }
+    }
+}
@@ -1,26 +1,42 @@
 use ai_manager_shared::{AppConfig, Result, SystemError};
 use config::{Config, Environment, File};
+use notify::{EventKind, RecursiveMode, Watcher};
 use serde::Deserialize;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, info, warn};
 
 const DEFAULT_CONFIG_FILE: &str = "config/default.toml";
 const USER_CONFIG_FILE: &str = "config/user.toml";
 const ENV_PREFIX: &str = "AI_MANAGER";
-
+const DEFAULT_CONFIG_HEADER: &str = "\
+# AI Manager configuration
+#
+# Generated with default values on first run. Replace the placeholder
+# API keys and credentials below, or override any value with an
+# AI_MANAGER_<SECTION>__<KEY> environment variable.
+
+";
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+const RELOAD_CHANNEL_CAPACITY: usize = 16;
+
+#[derive(Clone)]
 pub struct ConfigManager {
-    config: Config,
+    config: Arc<RwLock<Config>>,
+    // Files the configuration is built from, re-read on reload
+    sources: Vec<PathBuf>,
+    reload_tx: Arc<Mutex<Option<broadcast::Sender<AppConfig>>>>,
 }
 
 impl ConfigManager {
     /// Create a new configuration manager
     pub fn new() -> Result<Self> {
-        let mut config = Config::builder();
-
         // Load default configuration
         if Path::new(DEFAULT_CONFIG_FILE).exists() {
             debug!("Loading default config from: {}", DEFAULT_CONFIG_FILE);
-            config = config.add_source(File::with_name(DEFAULT_CONFIG_FILE));
         } else {
             warn!("Default config file not found: {}", DEFAULT_CONFIG_FILE);
         }
@@ -28,7 +44,6 @@ impl ConfigManager {
         // Load user configuration (optional)
         if Path::new(USER_CONFIG_FILE).exists() {
             debug!("Loading user config from: {}", USER_CONFIG_FILE);
-            config = config.add_source(File::with_name(USER_CONFIG_FILE));
         } else {
             info!(
                 "User config file not found: {} (this is optional)",
@@ -36,18 +51,10 @@ impl ConfigManager {
             );
         }
 
-        // Load environment variables
-        config = config.add_source(
-            Environment::with_prefix(ENV_PREFIX)
-                .prefix_separator("_")
-                .separator("__"),
-        );
-
-        let config = config
-            .build()
-            .map_err(|e| SystemError::Configuration(format!("Failed to build config: {}", e)))?;
-
-        Ok(Self { config })
+        Self::with_sources(vec![
+            PathBuf::from(DEFAULT_CONFIG_FILE),
+            PathBuf::from(USER_CONFIG_FILE),
+        ])
     }
 
     /// Load configuration from a specific file
@@ -61,25 +68,162 @@ impl ConfigManager {
             )));
         }
 
-        let config = Config::builder()
-            .add_source(File::from(path))
-            .add_source(
-                Environment::with_prefix(ENV_PREFIX)
-                    .prefix_separator("_")
-                    .separator("__"),
-            )
-            .build()
-            .map_err(|e| SystemError::Configuration(format!("Failed to load config: {}", e)))?;
+        Self::with_sources(vec![path.to_path_buf()])
+    }
+
+    /// Serialize a configuration to a TOML file, creating parent directories
+    pub fn save_to_file<P: AsRef<Path>>(path: P, config: &AppConfig) -> Result<()> {
+        let path = path.as_ref();
+        let contents = toml::to_string_pretty(config).map_err(|e| {
+            SystemError::Serialization(format!("Failed to serialize config: {}", e))
+        })?;
+
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+
+        debug!("Saved config to: {}", path.display());
+        Ok(())
+    }
+
+    /// Write `create_default_config()` to `path` unless a file already exists
+    /// there. Returns whether a file was written.
+    pub fn init_default<P: AsRef<Path>>(path: P) -> Result<bool> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Ok(false);
+        }
+
+        Self::save_to_file(path, &create_default_config())?;
+
+        // Prepend a header explaining the placeholders
+        let contents = std::fs::read_to_string(path)?;
+        std::fs::write(path, format!("{}{}", DEFAULT_CONFIG_HEADER, contents))?;
+
+        info!("Created default config at: {}", path.display());
+        Ok(true)
+    }
+
+    fn with_sources(sources: Vec<PathBuf>) -> Result<Self> {
+        let config = build_config(&sources)?;
+
+        Ok(Self {
+            config: Arc::new(RwLock::new(config)),
+            sources,
+            reload_tx: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Watch the config files and broadcast each valid reloaded configuration.
+    /// Invalid edits are logged and the previous configuration is kept.
+    pub fn watch(&self) -> Result<broadcast::Receiver<AppConfig>> {
+        let mut reload_tx = self
+            .reload_tx
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(tx) = reload_tx.as_ref() {
+            return Ok(tx.subscribe());
+        }
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    let _ = event_tx.send(event);
+                }
+            })
+            .map_err(|e| {
+                SystemError::Configuration(format!("Failed to create config watcher: {}", e))
+            })?;
+
+        // Watch directories rather than files so editors that save by
+        // renaming a temp file over the original are still picked up
+        let dirs: HashSet<PathBuf> = self
+            .sources
+            .iter()
+            .map(|path| match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            })
+            .collect();
+        for dir in &dirs {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                warn!("Cannot watch config directory {}: {}", dir.display(), e);
+            }
+        }
+
+        let (tx, rx) = broadcast::channel(RELOAD_CHANNEL_CAPACITY);
+        let manager = self.clone();
+        let reload = tx.clone();
+
+        tokio::spawn(async move {
+            // The watcher stops when dropped, so it lives as long as this task
+            let _watcher = watcher;
+
+            while let Some(event) = event_rx.recv().await {
+                if !manager.is_source_event(&event) {
+                    continue;
+                }
+
+                // Collapse the burst of events a single save produces
+                loop {
+                    match tokio::time::timeout(RELOAD_DEBOUNCE, event_rx.recv()).await {
+                        Ok(Some(_)) => continue,
+                        Ok(None) => return,
+                        Err(_) => break,
+                    }
+                }
+
+                manager.reload(&reload);
+            }
+        });
+
+        info!("Watching configuration files for changes");
+        *reload_tx = Some(tx);
+        Ok(rx)
+    }
+
+    fn is_source_event(&self, event: &notify::Event) -> bool {
+        if matches!(event.kind, EventKind::Access(_)) {
+            return false;
+        }
 
-        Ok(Self { config })
+        event.paths.iter().any(|changed| {
+            self.sources.iter().any(|source| {
+                changed.file_name().is_some() && changed.file_name() == source.file_name()
+            })
+        })
+    }
+
+    /// Rebuild the configuration from its sources, keeping the current one
+    /// if the new one fails to parse or validate
+    fn reload(&self, reload_tx: &broadcast::Sender<AppConfig>) {
+        let reloaded = build_config(&self.sources)
+            .and_then(|config| validate_config(&config).map(|app_config| (config, app_config)));
+
+        match reloaded {
+            Ok((config, app_config)) => {
+                *self.config.write().unwrap_or_else(PoisonError::into_inner) = config;
+                info!("Configuration reloaded");
+                let _ = reload_tx.send(app_config);
+            }
+            Err(e) => {
+                warn!("Ignoring invalid configuration change: {}", e);
+            }
+        }
     }
 
     /// Get the full application configuration
     pub fn get_app_config(&self) -> Result<AppConfig> {
-        self.config
-            .clone()
-            .try_deserialize()
-            .map_err(|e| SystemError::Configuration(format!("Failed to deserialize config: {}", e)))
+        deserialize_app_config(&self.config())
     }
 
     /// Get a specific configuration value
@@ -87,7 +231,7 @@ impl ConfigManager {
     where
         T: for<'de> Deserialize<'de>,
     {
-        self.config.get(key).map_err(|e| {
+        self.config().get(key).map_err(|e| {
             SystemError::Configuration(format!("Failed to get config value '{}': {}", key, e))
         })
     }
@@ -97,12 +241,12 @@ impl ConfigManager {
     where
         T: for<'de> Deserialize<'de>,
     {
-        self.config.get(key).unwrap_or(default)
+        self.config().get(key).unwrap_or(default)
     }
 
     /// Check if a configuration key exists
     pub fn has_key(&self, key: &str) -> bool {
-        self.config.get::<Option<serde_json::Value>>(key).is_ok()
+        has_key(&self.config(), key)
     }
 
     /// Get database connection string
@@ -116,27 +260,44 @@ impl ConfigManager {
     /// Get LLM API key for a provider
     pub fn get_llm_api_key(&self, provider: &str) -> Result<String> {
         let key = format!("llm.providers.{}.api_key", provider);
-        self.get(&key).map_err(|_| {
+        let api_key: String = self.get(&key).map_err(|_| {
             SystemError::Configuration(format!("LLM API key not found for provider: {}", provider))
-        })
+        })?;
+        resolve_secret(&api_key, &key)
     }
 
     /// Get LLM configuration for a provider
     pub fn get_llm_config(&self, provider: &str) -> Result<ai_manager_shared::LLMProviderConfig> {
         let key = format!("llm.providers.{}", provider);
-        self.get(&key)
+        let mut llm_config: ai_manager_shared::LLMProviderConfig = self.get(&key)?;
+        llm_config.api_key = resolve_secret(&llm_config.api_key, &format!("{}.api_key", key))?;
+        Ok(llm_config)
+    }
+
+    /// The configured `ui.user_id`. Without one, an anonymous id is
+    /// generated and saved to the last config file so it stays the same
+    /// across runs.
+    pub fn user_id(&self) -> Result<String> {
+        if let Ok(Some(user_id)) = self.get::<Option<String>>("ui.user_id") {
+            if !user_id.trim().is_empty() {
+                return Ok(user_id);
+            }
+        }
+
+        let user_id = format!("anon-{}", uuid::Uuid::new_v4());
+        let path = self.sources.last().ok_or_else(|| {
+            SystemError::Configuration("No config file to save the user id to".to_string())
+        })?;
+        save_user_id(path, &user_id)?;
+        info!("Generated anonymous user id, saved to {}", path.display());
+
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = build_config(&self.sources)?;
+        Ok(user_id)
     }
 
     /// Validate configuration
     pub fn validate(&self) -> Result<()> {
-        let _config = self.get_app_config()?;
-
-        // Validate required fields
-        if !self.has_key("llm.default_provider") {
-            return Err(SystemError::Configuration(
-                "Missing required config: llm.default_provider".to_string(),
-            ));
-        }
+        validate_config(&self.config())?;
 
         // Validate database configuration
         let _db_url = self.get_database_url()?;
@@ -146,6 +307,145 @@ impl ConfigManager {
     }
 }
 
+/// Set `ui.user_id` in the TOML file at `path`, keeping its other settings
+fn save_user_id(path: &Path, user_id: &str) -> Result<()> {
+    let mut document: toml::Table = if path.exists() {
+        std::fs::read_to_string(path)?.parse().map_err(|e| {
+            SystemError::Configuration(format!("Failed to parse {}: {}", path.display(), e))
+        })?
+    } else {
+        toml::Table::new()
+    };
+
+    let ui = document
+        .entry("ui")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| {
+            SystemError::Configuration(format!("[ui] in {} is not a table", path.display()))
+        })?;
+    ui.insert(
+        "user_id".to_string(),
+        toml::Value::String(user_id.to_string()),
+    );
+
+    let contents = toml::to_string_pretty(&document)
+        .map_err(|e| SystemError::Serialization(format!("Failed to serialize config: {}", e)))?;
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+fn build_config(sources: &[PathBuf]) -> Result<Config> {
+    let mut builder = Config::builder();
+
+    for path in sources.iter().filter(|path| path.exists()) {
+        builder = builder.add_source(File::from(path.as_path()));
+    }
+
+    // Load environment variables
+    builder
+        .add_source(
+            Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator("_")
+                .separator("__"),
+        )
+        .build()
+        .map_err(|e| SystemError::Configuration(format!("Failed to build config: {}", e)))
+}
+
+fn deserialize_app_config(config: &Config) -> Result<AppConfig> {
+    let mut app_config: AppConfig = config
+        .clone()
+        .try_deserialize()
+        .map_err(|e| SystemError::Configuration(format!("Failed to deserialize config: {}", e)))?;
+
+    resolve_secrets(&mut app_config)?;
+    Ok(app_config)
+}
+
+/// Expand a secret reference: `${VAR}` reads an environment variable and
+/// `file:<path>` reads a file (e.g. a mounted Docker/Kubernetes secret).
+/// Any other value is returned unchanged. `field` names the config key in errors.
+pub fn resolve_secret(value: &str, field: &str) -> Result<String> {
+    if let Some(var) = value
+        .strip_prefix("${")
+        .and_then(|rest| rest.strip_suffix('}'))
+    {
+        return std::env::var(var).map_err(|_| {
+            SystemError::Configuration(format!(
+                "Environment variable {} referenced by {} is not set",
+                var, field
+            ))
+        });
+    }
+
+    if let Some(path) = value.strip_prefix("file:") {
+        return std::fs::read_to_string(path)
+            .map(|secret| secret.trim_end_matches(['\r', '\n']).to_string())
+            .map_err(|e| {
+                SystemError::Configuration(format!(
+                    "Failed to read secret file {} referenced by {}: {}",
+                    path, field, e
+                ))
+            });
+    }
+
+    Ok(value.to_string())
+}
+
+fn resolve_secrets(config: &mut AppConfig) -> Result<()> {
+    for (name, provider) in config.llm.providers.iter_mut() {
+        let field = format!("llm.providers.{}.api_key", name);
+        provider.api_key = resolve_secret(&provider.api_key, &field)?;
+        for (i, api_key) in provider.extra_api_keys.iter_mut().enumerate() {
+            let field = format!("llm.providers.{}.extra_api_keys[{}]", name, i);
+            *api_key = resolve_secret(api_key, &field)?;
+        }
+    }
+
+    if let Some(email) = config.external_services.email.as_mut() {
+        for account in email.accounts.iter_mut() {
+            let field = format!("external_services.email.accounts.{}.password", account.name);
+            account.password = resolve_secret(&account.password, &field)?;
+        }
+    }
+
+    if let Some(token) = config.server.api_token.as_mut() {
+        *token = resolve_secret(token, "server.api_token")?;
+    }
+    for (user_id, token) in config.server.api_users.iter_mut() {
+        *token = resolve_secret(token, &format!("server.api_users.{}", user_id))?;
+    }
+    if let Some(token) = config.server.service_token.as_mut() {
+        *token = resolve_secret(token, "server.service_token")?;
+    }
+
+    Ok(())
+}
+
+fn has_key(config: &Config, key: &str) -> bool {
+    config.get::<Option<serde_json::Value>>(key).is_ok()
+}
+
+fn validate_config(config: &Config) -> Result<AppConfig> {
+    let app_config = deserialize_app_config(config)?;
+
+    // Validate required fields
+    if !has_key(config, "llm.default_provider") {
+        return Err(SystemError::Configuration(
+            "Missing required config: llm.default_provider".to_string(),
+        ));
+    }
+
+    Ok(app_config)
+}
+
 impl Default for ConfigManager {
     fn default() -> Self {
         Self::new().expect("Failed to create default config manager")
@@ -166,6 +466,10 @@ pub fn create_default_config() -> AppConfig {
             model: "gpt-3.5-turbo".to_string(),
             max_tokens: Some(2000),
             temperature: Some(0.7),
+            models: vec!["gpt-4".to_string(), "gpt-4-turbo".to_string()],
+            azure: None,
+            extra_api_keys: vec![],
+            live_health_check: false,
         },
     );
 
@@ -173,12 +477,17 @@ pub fn create_default_config() -> AppConfig {
         llm: LLMConfig {
             default_provider: "openai".to_string(),
             providers: llm_providers,
+            summarization: SummarizationConfig::default(),
         },
         database: DatabaseConfig {
             database_type: DatabaseType::SQLite,
             connection_string: "sqlite:data/ai_manager.db".to_string(),
             max_connections: Some(10),
+            connect_timeout_secs: Some(DATABASE_CONNECT_TIMEOUT_SECONDS),
+            query_timeout_secs: Some(DEFAULT_QUERY_TIMEOUT_SECONDS),
+            slow_query_ms: Some(SLOW_QUERY_THRESHOLD_MS),
             enable_logging: false,
+            migrations_dir: None,
         },
         external_services: ExternalServicesConfig {
             google_calendar: None,
@@ -195,12 +504,20 @@ pub fn create_default_config() -> AppConfig {
                 height: DEFAULT_WINDOW_HEIGHT,
             },
             enable_system_tray: true,
+            user_id: None,
         },
         logging: LoggingConfig {
             level: "info".to_string(),
             file_logging: true,
             log_file_path: Some("logs/ai_manager.log".to_string()),
         },
+        health: HealthThresholds::default(),
+        server: ServerConfig::default(),
+        input_guard: InputGuardConfig::default(),
+        retention: RetentionConfig::default(),
+        http: HttpConfig::default(),
+        export: ExportConfig::default(),
+        rate_limit: RateLimitConfig::default(),
     }
 }
 
@@ -243,4 +560,139 @@ connection_string = "sqlite::memory:"
         let api_key: String = config_manager.get("llm.providers.openai.api_key").unwrap();
         assert_eq!(api_key, "test-key");
     }
+
+    #[tokio::test]
+    async fn test_watch_reloads_valid_changes_only() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("user.toml");
+        let default_config = include_str!("../../../config/default.toml");
+        fs::write(&config_path, default_config).unwrap();
+
+        let config_manager = ConfigManager::from_file(&config_path).unwrap();
+        let mut reloads = config_manager.watch().unwrap();
+
+        // An invalid edit is ignored
+        fs::write(&config_path, "[llm]\ndefault_provider = \"openai\"\n").unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(reloads.try_recv().is_err());
+        assert_eq!(
+            config_manager.get_app_config().unwrap().logging.level,
+            "info"
+        );
+
+        // A valid edit is broadcast once and applied
+        fs::write(
+            &config_path,
+            default_config.replace("level = \"info\"", "level = \"debug\""),
+        )
+        .unwrap();
+        let reloaded = tokio::time::timeout(Duration::from_secs(5), reloads.recv())
+            .await
+            .expect("config reload timed out")
+            .unwrap();
+
+        assert_eq!(reloaded.logging.level, "debug");
+        assert_eq!(
+            config_manager.get_app_config().unwrap().logging.level,
+            "debug"
+        );
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(reloads.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_init_default_round_trip() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config").join("default.toml");
+
+        assert!(ConfigManager::init_default(&config_path).unwrap());
+        assert!(!ConfigManager::init_default(&config_path).unwrap());
+
+        let contents = fs::read_to_string(&config_path).unwrap();
+        assert!(contents.starts_with("# AI Manager configuration"));
+
+        let config_manager = ConfigManager::from_file(&config_path).unwrap();
+        assert_eq!(
+            config_manager.get_app_config().unwrap(),
+            create_default_config()
+        );
+    }
+
+    #[test]
+    fn test_generated_user_id_is_saved() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("default.toml");
+        ConfigManager::init_default(&config_path).unwrap();
+
+        let config_manager = ConfigManager::from_file(&config_path).unwrap();
+        let user_id = config_manager.user_id().unwrap();
+        assert!(user_id.starts_with("anon-"));
+        assert_eq!(config_manager.user_id().unwrap(), user_id);
+
+        // Kept across runs, along with the rest of the file
+        let reloaded = ConfigManager::from_file(&config_path).unwrap();
+        assert_eq!(reloaded.user_id().unwrap(), user_id);
+        assert_eq!(
+            reloaded.get_app_config().unwrap().llm,
+            create_default_config().llm
+        );
+    }
+
+    #[test]
+    fn test_resolve_secret_references() {
+        std::env::set_var("AI_MANAGER_TEST_SECRET_KEY", "sk-from-env");
+        assert_eq!(
+            resolve_secret(
+                "${AI_MANAGER_TEST_SECRET_KEY}",
+                "llm.providers.openai.api_key"
+            )
+            .unwrap(),
+            "sk-from-env"
+        );
+
+        let err = resolve_secret(
+            "${AI_MANAGER_TEST_UNSET_KEY}",
+            "llm.providers.openai.api_key",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("AI_MANAGER_TEST_UNSET_KEY"));
+        assert!(err.contains("llm.providers.openai.api_key"));
+
+        let dir = tempdir().unwrap();
+        let secret_path = dir.path().join("api_key");
+        fs::write(&secret_path, "sk-from-file\n").unwrap();
+        let reference = format!("file:{}", secret_path.display());
+        assert_eq!(
+            resolve_secret(&reference, "llm.providers.openai.api_key").unwrap(),
+            "sk-from-file"
+        );
+
+        assert_eq!(
+            resolve_secret("plain-value", "field").unwrap(),
+            "plain-value"
+        );
+    }
+
+    #[test]
+    fn test_app_config_resolves_and_redacts_secrets() {
+        std::env::set_var("AI_MANAGER_TEST_OPENAI_KEY", "sk-resolved");
+
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("user.toml");
+        let contents = include_str!("../../../config/default.toml")
+            .replace("your-openai-api-key-here", "${AI_MANAGER_TEST_OPENAI_KEY}");
+        fs::write(&config_path, contents).unwrap();
+
+        let config_manager = ConfigManager::from_file(&config_path).unwrap();
+        let app_config = config_manager.get_app_config().unwrap();
+        let openai = &app_config.llm.providers["openai"];
+
+        assert_eq!(openai.api_key, "sk-resolved");
+        assert_eq!(
+            config_manager.get_llm_api_key("openai").unwrap(),
+            "sk-resolved"
+        );
+        assert!(!format!("{:?}", app_config).contains("sk-resolved"));
+    }
 }
@@ -0,0 +1,545 @@
+use crate::config::ConfigManager;
+use crate::event_bus::{EventBus, MessageSender};
+use crate::handlers::{LLMResponseHandler, SystemEventHandler, UserInputHandler};
+use crate::health::HealthChecker;
+use crate::input_guard::{InputGuard, LlmModeration};
+use crate::rate_limiter::UserRateLimiter;
+use crate::summarizer::ConversationSummarizer;
+use ai_manager_llm_service::{LLMService, LlmServiceRunner, Service as _, UsageTracker};
+use ai_manager_shared::{
+    LLMConfig, Result, ServiceMessage, CORE_SERVICE_ID, LLM_SERVICE_ID, UI_SERVICE_ID,
+};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn, Instrument};
+
+/// Register the LLM service on `event_bus` and answer LLM requests until
+/// its queue closes or `shutdown` is cancelled. Token usage is recorded in
+/// `usage_tracker`.
+pub async fn run_llm_service(
+    event_bus: Arc<EventBus>,
+    llm_config: &LLMConfig,
+    usage_tracker: Arc<UsageTracker>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let llm = LLMService::from_config(llm_config)?;
+    let (_tx, rx) = event_bus
+        .register_service(LLM_SERVICE_ID.to_string())
+        .await?;
+
+    let mut runner = LlmServiceRunner::new(llm, llm_reply_sender(&event_bus))
+        .with_usage_tracker(usage_tracker)
+        .with_shutdown(shutdown);
+    runner.start(rx).await
+}
+
+/// Where the LLM service's replies go. Its errors pass through the core,
+/// which clears the thinking notice before handing them on to the UI.
+fn llm_reply_sender(event_bus: &Arc<EventBus>) -> MessageSender {
+    event_bus.routing_sender_via(|message| {
+        matches!(message, ServiceMessage::SystemError { .. }).then(|| CORE_SERVICE_ID.to_string())
+    })
+}
+
+/// Dispatches messages addressed to the core service to its handlers
+#[allow(dead_code)]
+pub struct CoreService {
+    event_bus: Arc<EventBus>,
+    config_manager: ConfigManager,
+    user_input_handler: UserInputHandler,
+    llm_response_handler: LLMResponseHandler,
+    system_event_handler: SystemEventHandler,
+    usage_tracker: Option<Arc<UsageTracker>>,
+    shutdown: CancellationToken,
+}
+
+impl CoreService {
+    pub fn new(event_bus: Arc<EventBus>, config_manager: ConfigManager) -> Self {
+        let user_input_handler = UserInputHandler::new(event_bus.clone());
+        let llm_response_handler = LLMResponseHandler::new(event_bus.clone());
+        let system_event_handler = SystemEventHandler::new(event_bus.clone());
+
+        Self {
+            event_bus,
+            config_manager,
+            user_input_handler,
+            llm_response_handler,
+            system_event_handler,
+            usage_tracker: None,
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Stop taking messages and exit once those queued are handled when
+    /// `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Usage recorded by the LLM service, reported to users by `/usage`
+    pub fn with_usage_tracker(mut self, usage_tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = Some(usage_tracker);
+        self
+    }
+
+    /// Register with the event bus and process messages until shut down
+    pub async fn start(&mut self) -> Result<()> {
+        info!("Starting core service components");
+
+        // Register core service with event bus
+        let (_tx, mut rx) = self
+            .event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await?;
+
+        // Start system event handler
+        self.system_event_handler.start().await?;
+        info!("✓ System event handler started");
+
+        // Create references to handlers
+        let event_bus = self.event_bus.clone();
+        let app_config = self.config_manager.get_app_config().ok();
+        let mut user_input_handler = UserInputHandler::new(event_bus.clone());
+        let mut llm_response_handler = LLMResponseHandler::new(event_bus.clone());
+        if let Some(config) = &app_config {
+            let mut input_guard = InputGuard::from_config(&config.input_guard);
+            if config.input_guard.enabled && config.input_guard.moderation {
+                input_guard =
+                    input_guard.with_rule(LlmModeration::new(event_bus.clone(), &config.llm));
+            }
+            user_input_handler = user_input_handler
+                .with_llm_config(config.llm.clone())
+                .with_input_guard(input_guard);
+            if let Some(rate_limiter) = UserRateLimiter::from_config(&config.rate_limit) {
+                user_input_handler = user_input_handler.with_rate_limiter(Arc::new(rate_limiter));
+            }
+            let summarizer = ConversationSummarizer::new(event_bus.clone(), &config.llm);
+            llm_response_handler = llm_response_handler.with_summarizer(Arc::new(summarizer));
+        }
+        if let Some(usage_tracker) = &self.usage_tracker {
+            user_input_handler = user_input_handler.with_usage_tracker(usage_tracker.clone());
+        }
+        let thresholds = app_config.map(|config| config.health).unwrap_or_default();
+        let mut health_checker = HealthChecker::new().with_thresholds(thresholds);
+
+        // Start message processing loop
+        info!("📨 Core service message loop started");
+
+        let mut draining = false;
+        loop {
+            let message = if draining {
+                rx.recv().await
+            } else {
+                tokio::select! {
+                    message = rx.recv() => message,
+                    _ = self.shutdown.cancelled() => {
+                        // Take no more messages, but handle the ones already queued
+                        info!("Core service shutting down");
+                        draining = true;
+                        rx.close();
+                        continue;
+                    }
+                }
+            };
+            let Some(message) = message else { break };
+
+            if let ServiceMessage::ShutdownService { service_id } = &message {
+                info!("Shutdown request for service: {}", service_id);
+                break; // Exit the loop to shutdown
+            }
+
+            let span = message.span();
+            let result = async {
+                debug!("Core service received message: {:?}", message);
+
+                match &message {
+                    ServiceMessage::UserInput { .. }
+                    | ServiceMessage::RegenerateResponse { .. } => {
+                        let result = if matches!(message, ServiceMessage::UserInput { .. }) {
+                            user_input_handler.handle_user_input(message.clone()).await
+                        } else {
+                            user_input_handler.handle_regenerate(message.clone()).await
+                        };
+                        if let Err(e) = &result {
+                            // Don't leave the user waiting for a reply that won't come
+                            let error_reply = ServiceMessage::error_reply(e, message.request_id());
+                            if let Err(e) = event_bus
+                                .route_message(error_reply, Some(UI_SERVICE_ID.to_string()))
+                                .await
+                            {
+                                warn!("Failed to report error to UI: {}", e);
+                            }
+                        }
+                        result
+                    }
+                    ServiceMessage::LLMResponse { .. } => {
+                        llm_response_handler
+                            .handle_llm_response(message.clone())
+                            .await
+                    }
+                    ServiceMessage::LLMResponseChunk { .. } => {
+                        llm_response_handler
+                            .handle_streaming_response(message.clone())
+                            .await
+                    }
+                    ServiceMessage::SystemError { .. } => {
+                        llm_response_handler.handle_llm_error(message.clone()).await
+                    }
+                    ServiceMessage::ServiceHealthCheck { service_id } => {
+                        Self::handle_health_check(service_id, &event_bus, &mut health_checker).await
+                    }
+                    _ => {
+                        warn!("Unhandled message type in core service: {:?}", message);
+                        Ok(())
+                    }
+                }
+            }
+            .instrument(span.clone())
+            .await;
+
+            if let Err(e) = result {
+                span.in_scope(|| error!("Error processing message in core service: {}", e));
+            }
+        }
+
+        info!("📪 Core service message loop ended");
+        Ok(())
+    }
+
+    async fn handle_health_check(
+        service_id: &str,
+        event_bus: &EventBus,
+        health_checker: &mut HealthChecker,
+    ) -> Result<()> {
+        debug!("Processing health check for service: {}", service_id);
+
+        // A backed-up queue shows up as a degraded service
+        let queue_depth = event_bus
+            .queue_depth(&CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap_or(0);
+        health_checker.set_message_queue_length(queue_depth);
+        let report = health_checker.check_health(CORE_SERVICE_ID).await?;
+
+        // Reply with our own id, since broadcast checks carry a wildcard id
+        let health_response = ServiceMessage::ServiceHealthResponse {
+            service_id: CORE_SERVICE_ID.to_string(),
+            status: report.status,
+        };
+
+        event_bus.route_message(health_response, None).await
+    }
+}
+
+impl Drop for CoreService {
+    fn drop(&mut self) {
+        info!("Core service dropping");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_manager_llm_service::MockProvider;
+    use ai_manager_shared::{
+        ErrorCode, ResponseType, SystemError, TokenUsage, DEFAULT_LLM_PROVIDER,
+    };
+    use chrono::Utc;
+    use std::time::Duration;
+    use tokio::time::timeout;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_llm_error_clears_thinking() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        // An LLM service whose provider rejects every request
+        let mut llm = LLMService::new();
+        let provider = MockProvider::new(DEFAULT_LLM_PROVIDER)
+            .with_error(SystemError::Authentication("bad key".to_string()));
+        llm.add_provider(DEFAULT_LLM_PROVIDER.to_string(), Box::new(provider));
+        llm.set_default_provider(DEFAULT_LLM_PROVIDER.to_string())
+            .unwrap();
+        let (_llm_tx, llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let mut runner = LlmServiceRunner::new(llm, llm_reply_sender(&event_bus));
+        tokio::spawn(async move { runner.start(llm_rx).await });
+
+        let shutdown = CancellationToken::new();
+        let mut core = CoreService::new(event_bus.clone(), ConfigManager::new().unwrap())
+            .with_shutdown(shutdown.clone());
+        let core = tokio::spawn(async move { core.start().await });
+        while event_bus
+            .queue_depth(&CORE_SERVICE_ID.to_string())
+            .await
+            .is_none()
+        {
+            tokio::task::yield_now().await;
+        }
+
+        let request_id = Uuid::new_v4();
+        let input = ServiceMessage::UserInput {
+            content: "Hello".to_string(),
+            timestamp: Utc::now(),
+            user_id: "alice".to_string(),
+            request_id: Some(request_id),
+            stream: false,
+        };
+        event_bus.route_message(input, None).await.unwrap();
+
+        // Thinking starts and ends before the error reaches the UI
+        let mut replies = Vec::new();
+        while replies.len() < 3 {
+            let reply = timeout(Duration::from_secs(5), ui_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(reply.trace_id(), Some(request_id));
+            replies.push(reply);
+        }
+        assert!(matches!(
+            replies.as_slice(),
+            [
+                ServiceMessage::SystemResponse {
+                    message_type: ResponseType::Thinking,
+                    ..
+                },
+                ServiceMessage::SystemResponse {
+                    message_type: ResponseType::ThinkingDone,
+                    ..
+                },
+                ServiceMessage::SystemError {
+                    code: ErrorCode::AuthFailed,
+                    ..
+                },
+            ]
+        ));
+
+        shutdown.cancel();
+        core.await.unwrap().unwrap();
+    }
+
+    /// Run the real data service on an in-memory database, with its replies
+    /// put back on the bus
+    async fn spawn_data_service(event_bus: &Arc<EventBus>) {
+        use ai_manager_data_service::{DataService, Service as _};
+        use ai_manager_shared::{types::DatabaseConfig, DATA_SERVICE_ID};
+
+        let (data_reply_tx, mut data_replies) = tokio::sync::mpsc::channel(100);
+        let mut data = DataService::new(&DatabaseConfig::sqlite(":memory:"), data_reply_tx)
+            .await
+            .unwrap();
+        let (_data_tx, data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        tokio::spawn(async move { data.start(data_rx).await });
+        let bus = event_bus.clone();
+        tokio::spawn(async move {
+            while let Some(reply) = data_replies.recv().await {
+                bus.route_message(reply, None).await.unwrap();
+            }
+        });
+    }
+
+    /// Start the core service and wait until it takes messages
+    async fn spawn_core(
+        event_bus: &Arc<EventBus>,
+        shutdown: &CancellationToken,
+    ) -> tokio::task::JoinHandle<Result<()>> {
+        let mut core = CoreService::new(event_bus.clone(), ConfigManager::new().unwrap())
+            .with_shutdown(shutdown.clone());
+        let core = tokio::spawn(async move { core.start().await });
+        while event_bus
+            .queue_depth(&CORE_SERVICE_ID.to_string())
+            .await
+            .is_none()
+        {
+            tokio::task::yield_now().await;
+        }
+        core
+    }
+
+    /// Send `content` as alice's input and answer it with `reply` as the
+    /// LLM service would, returning the request's context
+    async fn chat_turn(
+        event_bus: &Arc<EventBus>,
+        ui_rx: &mut tokio::sync::mpsc::Receiver<ServiceMessage>,
+        llm_rx: &mut tokio::sync::mpsc::Receiver<ServiceMessage>,
+        content: &str,
+        reply: &str,
+    ) -> Vec<String> {
+        let input = ServiceMessage::UserInput {
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            user_id: "alice".to_string(),
+            request_id: Some(Uuid::new_v4()),
+            stream: false,
+        };
+        event_bus.route_message(input, None).await.unwrap();
+
+        let Some(ServiceMessage::LLMRequest {
+            context,
+            request_id,
+            ..
+        }) = timeout(Duration::from_secs(5), llm_rx.recv())
+            .await
+            .unwrap()
+        else {
+            panic!("expected an LLM request");
+        };
+        let response = ServiceMessage::LLMResponse {
+            content: reply.to_string(),
+            model: None,
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            request_id,
+            user_id: "alice".to_string(),
+            streamed: false,
+        };
+        event_bus
+            .route_message(response, Some(CORE_SERVICE_ID.to_string()))
+            .await
+            .unwrap();
+        loop {
+            let reply = timeout(Duration::from_secs(5), ui_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let ServiceMessage::SystemResponse {
+                message_type: ResponseType::Success,
+                ..
+            } = reply
+            {
+                break;
+            }
+        }
+        context
+    }
+
+    #[tokio::test]
+    async fn test_every_turn_loaded_as_context() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        spawn_data_service(&event_bus).await;
+        let shutdown = CancellationToken::new();
+        let core = spawn_core(&event_bus, &shutdown).await;
+
+        let context = chat_turn(
+            &event_bus,
+            &mut ui_rx,
+            &mut llm_rx,
+            "Book lunch with Sam",
+            "Booked for noon.",
+        )
+        .await;
+        assert!(context.is_empty());
+        chat_turn(
+            &event_bus,
+            &mut ui_rx,
+            &mut llm_rx,
+            "Move it to 1pm",
+            "Moved to 1pm.",
+        )
+        .await;
+        let context = chat_turn(
+            &event_bus,
+            &mut ui_rx,
+            &mut llm_rx,
+            "Who is coming?",
+            "Sam.",
+        )
+        .await;
+
+        assert_eq!(
+            context,
+            vec![
+                "User: Book lunch with Sam",
+                "Assistant: Booked for noon.",
+                "User: Move it to 1pm",
+                "Assistant: Moved to 1pm.",
+            ]
+        );
+
+        shutdown.cancel();
+        core.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_double_submitted_input_makes_one_provider_call() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        spawn_data_service(&event_bus).await;
+
+        // A slow provider, so the second request arrives while the first
+        // is still in flight
+        let mut llm = LLMService::new();
+        let provider =
+            MockProvider::new(DEFAULT_LLM_PROVIDER).with_latency(Duration::from_millis(500));
+        llm.add_provider(DEFAULT_LLM_PROVIDER.to_string(), Box::new(provider));
+        llm.set_default_provider(DEFAULT_LLM_PROVIDER.to_string())
+            .unwrap();
+        let usage_tracker = Arc::new(UsageTracker::new());
+        let (_llm_tx, llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let mut runner = LlmServiceRunner::new(llm, llm_reply_sender(&event_bus))
+            .with_usage_tracker(usage_tracker.clone());
+        tokio::spawn(async move { runner.start(llm_rx).await });
+
+        let shutdown = CancellationToken::new();
+        let core = spawn_core(&event_bus, &shutdown).await;
+
+        for _ in 0..2 {
+            let input = ServiceMessage::UserInput {
+                content: "What's on today?".to_string(),
+                timestamp: Utc::now(),
+                user_id: "alice".to_string(),
+                request_id: Some(Uuid::new_v4()),
+                stream: false,
+            };
+            event_bus.route_message(input, None).await.unwrap();
+        }
+
+        let mut answers = 0;
+        while answers < 2 {
+            let reply = timeout(Duration::from_secs(5), ui_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let ServiceMessage::SystemResponse {
+                message_type: ResponseType::Success,
+                ..
+            } = reply
+            {
+                answers += 1;
+            }
+        }
+        assert_eq!(usage_tracker.get_stats().await.total_requests, 1);
+
+        shutdown.cancel();
+        core.await.unwrap().unwrap();
+    }
+}
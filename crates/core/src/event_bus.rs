@@ -1,11 +1,18 @@
+use crate::service_registry::{
+    standard_service_for, ServiceDescriptor, ServiceInfo, ServiceRegistry,
+};
 use ai_manager_shared::{
-    Result, ServiceId, ServiceMessage, SystemError, SystemEvent, BROADCAST_CHANNEL_CAPACITY,
+    auth, MessageTransport, Result, ServiceHealth, ServiceId, ServiceMessage, SystemError,
+    SystemEvent, ALL_SERVICES_ID, BROADCAST_CHANNEL_CAPACITY, CORE_SERVICE_ID,
     MESSAGE_QUEUE_CAPACITY,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::{SendTimeoutError, TrySendError};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 pub type MessageSender = mpsc::Sender<ServiceMessage>;
 pub type MessageReceiver = mpsc::Receiver<ServiceMessage>;
@@ -17,11 +24,23 @@ pub struct EventBus {
     // Service message senders
     service_senders: Arc<RwLock<HashMap<ServiceId, MessageSender>>>,
 
+    // What each registered service is and handles
+    registry: Arc<RwLock<ServiceRegistry>>,
+
     // System event broadcaster
     event_broadcaster: EventSender,
 
+    // Callers waiting for a service's health response
+    health_waiters: Arc<RwLock<HashMap<ServiceId, Vec<oneshot::Sender<ServiceHealth>>>>>,
+
+    // Callers waiting for the response to a request, keyed by request id
+    response_waiters: Arc<RwLock<HashMap<Uuid, oneshot::Sender<ServiceMessage>>>>,
+
     // Bus statistics
     stats: Arc<RwLock<EventBusStats>>,
+
+    // Secret remote services must prove they hold before registering
+    service_token: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -29,6 +48,83 @@ pub struct EventBusStats {
     pub messages_routed: u64,
     pub events_broadcast: u64,
     pub routing_errors: u64,
+    /// Messages routed, by message variant
+    pub messages_by_type: HashMap<&'static str, u64>,
+    /// Time taken to hand messages to their target's queue
+    pub routing_latency: LatencyHistogram,
+}
+
+impl EventBusStats {
+    fn record_routed(&mut self, message_type: &'static str, latency: Duration) {
+        self.messages_routed += 1;
+        *self.messages_by_type.entry(message_type).or_insert(0) += 1;
+        self.routing_latency.observe(latency);
+    }
+}
+
+/// Upper bounds of the routing latency buckets
+const LATENCY_BUCKETS: [Duration; 8] = [
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(1000),
+];
+
+/// Latency histogram with fixed bucket bounds
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    /// Upper bound of each bucket
+    pub bounds: Vec<Duration>,
+    /// Observations per bucket; the extra last entry counts those above every bound
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn observe(&mut self, latency: Duration) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum += latency;
+    }
+
+    /// Mean observed latency, if anything has been observed
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_nanos((self.sum.as_nanos() / self.count as u128) as u64))
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            bounds: LATENCY_BUCKETS.to_vec(),
+            counts: vec![0; LATENCY_BUCKETS.len() + 1],
+            count: 0,
+            sum: Duration::ZERO,
+        }
+    }
+}
+
+/// How to deliver a message when the target's queue is full
+#[derive(Debug, Clone, Copy)]
+enum Delivery {
+    Wait,
+    WaitFor(Duration),
+    Try,
+}
+
+fn queue_depth(tx: &MessageSender) -> usize {
+    tx.max_capacity() - tx.capacity()
 }
 
 impl Default for EventBus {
@@ -43,22 +139,46 @@ impl EventBus {
 
         Self {
             service_senders: Arc::new(RwLock::new(HashMap::new())),
+            registry: Arc::new(RwLock::new(ServiceRegistry::new())),
             event_broadcaster: event_tx,
+            health_waiters: Arc::new(RwLock::new(HashMap::new())),
+            response_waiters: Arc::new(RwLock::new(HashMap::new())),
             stats: Arc::new(RwLock::new(EventBusStats::default())),
+            service_token: None,
         }
     }
 
-    /// Register a service with the event bus
+    /// Require services registering over a transport to authenticate with
+    /// `token`
+    pub fn with_service_token(mut self, token: Option<String>) -> Self {
+        self.service_token = token;
+        self
+    }
+
+    /// Register a service with the event bus. A standard service id
+    /// handles the messages that service does; see `register_described_service`
+    /// to advertise other capabilities.
     pub async fn register_service(
         &self,
         service_id: ServiceId,
+    ) -> Result<(MessageSender, MessageReceiver)> {
+        self.register_described_service(ServiceDescriptor::new(service_id))
+            .await
+    }
+
+    /// Register a service along with its capabilities and version
+    pub async fn register_described_service(
+        &self,
+        descriptor: ServiceDescriptor,
     ) -> Result<(MessageSender, MessageReceiver)> {
         let (tx, rx) = mpsc::channel(MESSAGE_QUEUE_CAPACITY);
+        let service_id = descriptor.service_id.clone();
 
         {
             let mut senders = self.service_senders.write().await;
             senders.insert(service_id.clone(), tx.clone());
         }
+        self.registry.write().await.register(descriptor);
 
         info!("Service '{}' registered with event bus", service_id);
 
@@ -69,12 +189,92 @@ impl EventBus {
         Ok((tx, rx))
     }
 
+    /// A sender whose messages are routed to their usual targets, for
+    /// services that reply on a plain channel rather than through the bus
+    pub fn routing_sender(self: &Arc<Self>) -> MessageSender {
+        self.routing_sender_via(|_| None)
+    }
+
+    /// Like `routing_sender`, but messages `target` picks a service for go
+    /// there instead of to their usual target
+    pub fn routing_sender_via(
+        self: &Arc<Self>,
+        target: fn(&ServiceMessage) -> Option<ServiceId>,
+    ) -> MessageSender {
+        let (tx, mut rx) = mpsc::channel(MESSAGE_QUEUE_CAPACITY);
+        let event_bus = self.clone();
+
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let service = target(&message);
+                if let Err(e) = event_bus.route_message(message, service).await {
+                    warn!("Failed to route service reply: {}", e);
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Register `service_id` as reachable over `transport`: messages for it
+    /// are sent down the transport and whatever comes back is routed on the
+    /// bus. With a service token, the peer must first authenticate as
+    /// `service_id` or `SystemError::Authentication` is returned. Runs until
+    /// the transport closes, then unregisters the service and returns an
+    /// error so a service manager can reconnect.
+    pub async fn register_transport(
+        self: &Arc<Self>,
+        service_id: ServiceId,
+        mut transport: Box<dyn MessageTransport>,
+    ) -> Result<()> {
+        if let Some(token) = &self.service_token {
+            let peer = auth::authenticate(transport.as_mut(), CORE_SERVICE_ID, token).await?;
+            if peer != service_id {
+                return Err(SystemError::Authentication(format!(
+                    "Expected service '{}', but '{}' authenticated",
+                    service_id, peer
+                )));
+            }
+            debug!("Service '{}' authenticated", service_id);
+        }
+
+        let (_tx, mut rx) = self.register_service(service_id.clone()).await?;
+
+        let result = loop {
+            tokio::select! {
+                message = rx.recv() => {
+                    let Some(message) = message else { break Ok(()) };
+                    if let Err(e) = transport.send(message).await {
+                        break Err(e);
+                    }
+                }
+                message = transport.recv() => {
+                    match message {
+                        Ok(Some(message)) => {
+                            if let Err(e) = self.route_message(message, None).await {
+                                warn!("Failed to route message from '{}': {}", service_id, e);
+                            }
+                        }
+                        Ok(None) => break Err(SystemError::ServiceUnavailable {
+                            service: service_id.clone(),
+                        }),
+                        Err(e) => break Err(e),
+                    }
+                }
+            }
+        };
+
+        self.unregister_service(&service_id).await?;
+        result
+    }
+
     /// Unregister a service from the event bus
     pub async fn unregister_service(&self, service_id: &ServiceId) -> Result<()> {
         {
             let mut senders = self.service_senders.write().await;
             senders.remove(service_id);
         }
+        self.registry.write().await.unregister(service_id);
 
         info!("Service '{}' unregistered from event bus", service_id);
 
@@ -87,18 +287,68 @@ impl EventBus {
         Ok(())
     }
 
-    /// Route a message to the appropriate service
+    /// Route a message to the appropriate service, waiting for room in its
+    /// queue if it is full
     pub async fn route_message(
         &self,
         message: ServiceMessage,
         target_service: Option<ServiceId>,
+    ) -> Result<()> {
+        self.route(message, target_service, Delivery::Wait).await
+    }
+
+    /// Route a message, giving up with `SystemError::Timeout` if the target's
+    /// queue stays full for longer than `timeout`
+    pub async fn route_message_with_timeout(
+        &self,
+        message: ServiceMessage,
+        target_service: Option<ServiceId>,
+        timeout: Duration,
+    ) -> Result<()> {
+        self.route(message, target_service, Delivery::WaitFor(timeout))
+            .await
+    }
+
+    /// Route a message without waiting, failing with
+    /// `SystemError::RateLimitExceeded` if the target's queue is full
+    pub async fn try_route_message(
+        &self,
+        message: ServiceMessage,
+        target_service: Option<ServiceId>,
+    ) -> Result<()> {
+        self.route(message, target_service, Delivery::Try).await
+    }
+
+    async fn route(
+        &self,
+        message: ServiceMessage,
+        target_service: Option<ServiceId>,
+        delivery: Delivery,
     ) -> Result<()> {
         debug!("Routing message: {:?}", message);
+        let started = Instant::now();
+        let message_type = message.message_type();
+
+        // Health responses with someone waiting on them are answered directly
+        if let ServiceMessage::ServiceHealthResponse { service_id, status } = &message {
+            if self.complete_health_check(service_id, status).await {
+                return Ok(());
+            }
+        }
+
+        // Responses someone is awaiting go straight back to the caller
+        if let Some(request_id) = message.in_reply_to() {
+            let waiter = self.response_waiters.write().await.remove(&request_id);
+            if let Some(waiter) = waiter {
+                let _ = waiter.send(message);
+                return Ok(());
+            }
+        }
 
         // Determine target service if not specified
         let target = match target_service {
             Some(service) => service,
-            None => self.determine_target_service(&message)?,
+            None => self.determine_target_service(&message).await?,
         };
 
         // Get sender for target service
@@ -110,7 +360,32 @@ impl EventBus {
         match sender {
             Some(tx) => {
                 // Attempt to send message
-                if let Err(e) = tx.send(message.clone()).await {
+                let sent = match delivery {
+                    Delivery::Wait => tx.send(message).await.map_err(|e| e.to_string()),
+                    Delivery::WaitFor(timeout) => match tx.send_timeout(message, timeout).await {
+                        Ok(()) => Ok(()),
+                        Err(SendTimeoutError::Timeout(_)) => {
+                            warn!(
+                                "Queue for service '{}' stayed full for {:?}",
+                                target, timeout
+                            );
+                            self.stats.write().await.routing_errors += 1;
+                            return Err(SystemError::Timeout);
+                        }
+                        Err(e) => Err(e.to_string()),
+                    },
+                    Delivery::Try => match tx.try_send(message) {
+                        Ok(()) => Ok(()),
+                        Err(TrySendError::Full(_)) => {
+                            warn!("Queue for service '{}' is full", target);
+                            self.stats.write().await.routing_errors += 1;
+                            return Err(SystemError::RateLimitExceeded { service: target });
+                        }
+                        Err(e) => Err(e.to_string()),
+                    },
+                };
+
+                if let Err(e) = sent {
                     error!("Failed to route message to service '{}': {}", target, e);
 
                     // Update error stats
@@ -128,7 +403,7 @@ impl EventBus {
                 // Update success stats
                 {
                     let mut stats = self.stats.write().await;
-                    stats.messages_routed += 1;
+                    stats.record_routed(message_type, started.elapsed());
                 }
 
                 debug!("Message routed successfully to service '{}'", target);
@@ -149,6 +424,233 @@ impl EventBus {
         }
     }
 
+    /// Number of messages waiting in a service's queue
+    pub async fn queue_depth(&self, service_id: &ServiceId) -> Option<usize> {
+        let senders = self.service_senders.read().await;
+        senders.get(service_id).map(queue_depth)
+    }
+
+    /// Number of messages waiting in each service's queue
+    pub async fn queue_depths(&self) -> HashMap<ServiceId, usize> {
+        let senders = self.service_senders.read().await;
+        senders
+            .iter()
+            .map(|(id, tx)| (id.clone(), queue_depth(tx)))
+            .collect()
+    }
+
+    /// Send a health check to a service and wait for its response
+    pub async fn check_service_health(
+        &self,
+        service_id: &ServiceId,
+        timeout: Duration,
+    ) -> Result<ServiceHealth> {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut waiters = self.health_waiters.write().await;
+            waiters.entry(service_id.clone()).or_default().push(tx);
+        }
+
+        let request = ServiceMessage::ServiceHealthCheck {
+            service_id: service_id.clone(),
+        };
+        if let Err(e) = self.route_message(request, Some(service_id.clone())).await {
+            drop(rx);
+            self.remove_closed_health_waiters(service_id).await;
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(status)) => Ok(status),
+            Ok(Err(_)) | Err(_) => {
+                self.remove_closed_health_waiters(service_id).await;
+                Err(SystemError::Timeout)
+            }
+        }
+    }
+
+    /// Check the health of several services at once, waiting up to
+    /// `timeout` in total
+    pub async fn check_services_health(
+        &self,
+        service_ids: &[ServiceId],
+        timeout: Duration,
+    ) -> Vec<(ServiceId, Result<ServiceHealth>)> {
+        let checks = service_ids.iter().map(|service_id| async move {
+            (
+                service_id.clone(),
+                self.check_service_health(service_id, timeout).await,
+            )
+        });
+        futures::future::join_all(checks).await
+    }
+
+    /// Route a request and wait for the response carrying its request id
+    pub async fn send_and_await_response(
+        &self,
+        message: ServiceMessage,
+        target_service: Option<ServiceId>,
+        timeout: Duration,
+    ) -> Result<ServiceMessage> {
+        let request_id = message.request_id().ok_or_else(|| {
+            SystemError::InvalidInput(
+                "Message has no request id to await a response for".to_string(),
+            )
+        })?;
+
+        let target = match &target_service {
+            Some(target) => target.clone(),
+            None => self.determine_target_service(&message).await?,
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.response_waiters.write().await.insert(request_id, tx);
+
+        if let Err(e) = self.route_message(message, Some(target.clone())).await {
+            self.response_waiters.write().await.remove(&request_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) | Err(_) => {
+                self.response_waiters.write().await.remove(&request_id);
+                warn!("No response from '{}' to request {}", target, request_id);
+                self.broadcast_event(SystemEvent::RequestTimedOut { request_id, target })
+                    .await;
+                Err(SystemError::Timeout)
+            }
+        }
+    }
+
+    /// Send a message to every registered service. Each service's delivery
+    /// result is reported; one failure doesn't stop delivery to the others.
+    pub async fn broadcast_message(&self, message: ServiceMessage) -> Vec<(ServiceId, Result<()>)> {
+        debug!("Broadcasting message: {:?}", message);
+        let started = Instant::now();
+        let message_type = message.message_type();
+
+        let mut senders: Vec<(ServiceId, MessageSender)> = {
+            let senders = self.service_senders.read().await;
+            senders
+                .iter()
+                .map(|(id, tx)| (id.clone(), tx.clone()))
+                .collect()
+        };
+        senders.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let deliveries = senders.into_iter().map(|(service_id, tx)| {
+            let message = message.clone();
+            async move {
+                let result = tx.send(message).await.map_err(|e| {
+                    SystemError::ServiceCommunication(format!(
+                        "Failed to send message to service '{}': {}",
+                        service_id, e
+                    ))
+                });
+                (service_id, result)
+            }
+        });
+        let results = futures::future::join_all(deliveries).await;
+        let latency = started.elapsed();
+
+        {
+            let mut stats = self.stats.write().await;
+            for (service_id, result) in &results {
+                match result {
+                    Ok(()) => stats.record_routed(message_type, latency),
+                    Err(e) => {
+                        error!("Failed to broadcast message to '{}': {}", service_id, e);
+                        stats.routing_errors += 1;
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Broadcast a health check and collect every service's response
+    pub async fn check_all_services_health(
+        &self,
+        timeout: Duration,
+    ) -> Vec<(ServiceId, Result<ServiceHealth>)> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        // Register waiters before broadcasting so no response is missed
+        let service_ids = self.get_registered_services().await;
+        let mut pending = HashMap::new();
+        {
+            let mut waiters = self.health_waiters.write().await;
+            for service_id in service_ids {
+                let (tx, rx) = oneshot::channel();
+                waiters.entry(service_id.clone()).or_default().push(tx);
+                pending.insert(service_id, rx);
+            }
+        }
+
+        let request = ServiceMessage::ServiceHealthCheck {
+            service_id: ALL_SERVICES_ID.to_string(),
+        };
+        let deliveries = self.broadcast_message(request).await;
+
+        let mut results = Vec::with_capacity(deliveries.len());
+        for (service_id, delivery) in deliveries {
+            let Some(rx) = pending.remove(&service_id) else {
+                continue;
+            };
+
+            let result = match delivery {
+                Err(e) => {
+                    drop(rx);
+                    Err(e)
+                }
+                Ok(()) => match tokio::time::timeout_at(deadline, rx).await {
+                    Ok(Ok(status)) => Ok(status),
+                    Ok(Err(_)) | Err(_) => Err(SystemError::Timeout),
+                },
+            };
+            if result.is_err() {
+                self.remove_closed_health_waiters(&service_id).await;
+            }
+            results.push((service_id, result));
+        }
+
+        // Services that unregistered before the broadcast reached them
+        for (service_id, rx) in pending {
+            drop(rx);
+            self.remove_closed_health_waiters(&service_id).await;
+        }
+
+        results
+    }
+
+    /// Forget the waiters for `service_id` that gave up, leaving any other
+    /// checks still waiting on the service
+    async fn remove_closed_health_waiters(&self, service_id: &ServiceId) {
+        let mut waiters = self.health_waiters.write().await;
+        if let Some(senders) = waiters.get_mut(service_id) {
+            senders.retain(|tx| !tx.is_closed());
+            if senders.is_empty() {
+                waiters.remove(service_id);
+            }
+        }
+    }
+
+    /// Hand a health response to anyone waiting for it
+    async fn complete_health_check(&self, service_id: &ServiceId, status: &ServiceHealth) -> bool {
+        let waiters = self.health_waiters.write().await.remove(service_id);
+        match waiters {
+            Some(waiters) if !waiters.is_empty() => {
+                for waiter in waiters {
+                    let _ = waiter.send(status.clone());
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Broadcast a system event to all subscribers
     pub async fn broadcast_event(&self, event: SystemEvent) {
         debug!("Broadcasting event: {:?}", event);
@@ -178,44 +680,69 @@ impl EventBus {
         senders.keys().cloned().collect()
     }
 
-    /// Determine the target service for a message based on message type
-    fn determine_target_service(&self, message: &ServiceMessage) -> Result<ServiceId> {
-        use ai_manager_shared::*;
-
-        let target =
-            match message {
-                // Messages going to LLM service
-                ServiceMessage::LLMRequest { .. } => LLM_SERVICE_ID,
-
-                // Messages going to data service
-                ServiceMessage::StoreConversation { .. }
-                | ServiceMessage::LoadUserProfile { .. } => DATA_SERVICE_ID,
-
-                // Messages going to external service
-                ServiceMessage::CalendarSync { .. } | ServiceMessage::EmailProcess { .. } => {
-                    EXTERNAL_SERVICE_ID
-                }
+    /// What `service_id` registered as, if it is registered
+    pub async fn describe_service(&self, service_id: &str) -> Option<ServiceInfo> {
+        self.registry.read().await.get(service_id).cloned()
+    }
 
-                // Messages going to UI service
-                ServiceMessage::SystemResponse { .. }
-                | ServiceMessage::UserProfileResponse { .. } => UI_SERVICE_ID,
+    /// Every registered service, by id
+    pub async fn describe_services(&self) -> Vec<ServiceInfo> {
+        self.registry
+            .read()
+            .await
+            .services()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
 
-                // Messages going to core service
-                ServiceMessage::UserInput { .. }
-                | ServiceMessage::LLMResponse { .. }
-                | ServiceMessage::ServiceHealthResponse { .. } => CORE_SERVICE_ID,
+    /// Registered services handling `message_type`, as named by
+    /// `ServiceMessage::message_type`, earliest registered first
+    pub async fn services_handling(&self, message_type: &str) -> Vec<ServiceId> {
+        self.registry
+            .read()
+            .await
+            .services_handling(message_type)
+            .into_iter()
+            .map(|info| info.service_id.clone())
+            .collect()
+    }
 
-                // Health check messages - broadcast to all
-                ServiceMessage::ServiceHealthCheck { .. } => {
-                    return Err(SystemError::InvalidInput(
-                        "Health check messages should be broadcast, not routed".to_string(),
-                    ));
-                }
+    /// Determine the target service for a message: a registered service
+    /// advertising that it handles the message's type, preferring the
+    /// standard service for it. Falls back to the standard service if it is
+    /// registered without advertising the capability.
+    async fn determine_target_service(&self, message: &ServiceMessage) -> Result<ServiceId> {
+        match message {
+            // Health check messages - broadcast to all
+            ServiceMessage::ServiceHealthCheck { .. } => {
+                return Err(SystemError::InvalidInput(
+                    "Health check messages should be sent with broadcast_message, not routed"
+                        .to_string(),
+                ));
+            }
+            ServiceMessage::ShutdownService { service_id } => return Ok(service_id.clone()),
+            _ => {}
+        }
 
-                ServiceMessage::ShutdownService { service_id } => service_id,
-            };
+        let message_type = message.message_type();
+        let standard = standard_service_for(message_type);
+        let candidates = self.services_handling(message_type).await;
+        if let Some(standard) = standard.filter(|id| candidates.iter().any(|c| c == id)) {
+            return Ok(standard.to_string());
+        }
+        if let Some(first) = candidates.into_iter().next() {
+            return Ok(first);
+        }
 
-        Ok(target.to_string())
+        match standard {
+            Some(standard) if self.service_senders.read().await.contains_key(standard) => {
+                Ok(standard.to_string())
+            }
+            _ => Err(SystemError::ServiceUnavailable {
+                service: standard.unwrap_or(message_type).to_string(),
+            }),
+        }
     }
 }
 
@@ -225,6 +752,8 @@ impl Clone for EventBusStats {
             messages_routed: self.messages_routed,
             events_broadcast: self.events_broadcast,
             routing_errors: self.routing_errors,
+            messages_by_type: self.messages_by_type.clone(),
+            routing_latency: self.routing_latency.clone(),
         }
     }
 }
@@ -232,6 +761,7 @@ impl Clone for EventBusStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ai_manager_shared::ChannelTransport;
     use tokio::time::{timeout, Duration};
 
     #[tokio::test]
@@ -246,6 +776,72 @@ mod tests {
         assert!(services.contains(&service_id));
     }
 
+    #[tokio::test]
+    async fn test_service_capabilities_discoverable() {
+        let bus = EventBus::new();
+        bus.register_service(ai_manager_shared::LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        bus.register_described_service(
+            ServiceDescriptor::new("llm-local")
+                .with_capabilities(["LLMRequest"])
+                .with_version("0.2.0"),
+        )
+        .await
+        .unwrap();
+
+        let info = bus.describe_service("llm-local").await.unwrap();
+        assert_eq!(info.version.as_deref(), Some("0.2.0"));
+        assert_eq!(
+            bus.services_handling("LLMRequest").await,
+            vec![
+                ai_manager_shared::LLM_SERVICE_ID.to_string(),
+                "llm-local".to_string()
+            ]
+        );
+
+        bus.unregister_service(&"llm-local".to_string())
+            .await
+            .unwrap();
+        assert!(bus.describe_service("llm-local").await.is_none());
+        assert_eq!(bus.services_handling("LLMRequest").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_routes_to_any_service_handling_message() {
+        let bus = EventBus::new();
+        let request = || ServiceMessage::LoadUserProfile {
+            user_id: "alice".to_string(),
+            request_id: Uuid::new_v4(),
+        };
+
+        let result = bus.route_message(request(), None).await;
+        assert!(matches!(
+            result,
+            Err(SystemError::ServiceUnavailable { ref service })
+                if service == ai_manager_shared::DATA_SERVICE_ID
+        ));
+
+        // A differently named instance is found by its capabilities
+        let (_tx, mut replica_rx) = bus
+            .register_described_service(
+                ServiceDescriptor::new("data-replica").with_capabilities(["LoadUserProfile"]),
+            )
+            .await
+            .unwrap();
+        bus.route_message(request(), None).await.unwrap();
+        assert!(replica_rx.try_recv().is_ok());
+
+        // The standard service is preferred once it is present
+        let (_tx, mut data_rx) = bus
+            .register_service(ai_manager_shared::DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        bus.route_message(request(), None).await.unwrap();
+        assert!(data_rx.try_recv().is_ok());
+        assert!(replica_rx.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn test_message_routing() {
         let bus = EventBus::new();
@@ -259,6 +855,8 @@ mod tests {
             content: "Hello".to_string(),
             timestamp: chrono::Utc::now(),
             user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
         };
 
         bus.route_message(message.clone(), Some(service_id))
@@ -285,4 +883,472 @@ mod tests {
         let received = timeout(Duration::from_millis(100), event_rx.recv()).await;
         assert!(received.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_check_service_health() {
+        let bus = Arc::new(EventBus::new());
+        let service_id = "probe".to_string();
+        let (_tx, mut rx) = bus.register_service(service_id.clone()).await.unwrap();
+
+        let responder_bus = bus.clone();
+        tokio::spawn(async move {
+            while let Some(ServiceMessage::ServiceHealthCheck { service_id }) = rx.recv().await {
+                let response = ServiceMessage::ServiceHealthResponse {
+                    service_id,
+                    status: ServiceHealth::Degraded {
+                        reason: "slow".to_string(),
+                    },
+                };
+                responder_bus.route_message(response, None).await.unwrap();
+            }
+        });
+
+        let status = bus
+            .check_service_health(&service_id, Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert!(matches!(status, ServiceHealth::Degraded { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_check_service_health_times_out() {
+        let bus = EventBus::new();
+        let service_id = "silent".to_string();
+        let (_tx, _rx) = bus.register_service(service_id.clone()).await.unwrap();
+
+        let result = bus
+            .check_service_health(&service_id, Duration::from_millis(20))
+            .await;
+        assert!(matches!(result, Err(SystemError::Timeout)));
+    }
+
+    fn llm_request(request_id: Uuid) -> ServiceMessage {
+        ServiceMessage::LLMRequest {
+            prompt: "Hello".to_string(),
+            context: vec![],
+            provider: "openai".to_string(),
+            request_id,
+            user_id: "test-user".to_string(),
+            model: None,
+            temperature: None,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_and_await_response() {
+        let bus = Arc::new(EventBus::new());
+        let (_tx, mut rx) = bus
+            .register_service(ai_manager_shared::LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let responder_bus = bus.clone();
+        tokio::spawn(async move {
+            while let Some(ServiceMessage::LLMRequest {
+                request_id,
+                user_id,
+                ..
+            }) = rx.recv().await
+            {
+                let response = ServiceMessage::LLMResponse {
+                    content: "Hi there".to_string(),
+                    model: None,
+                    usage: ai_manager_shared::TokenUsage {
+                        prompt_tokens: 1,
+                        completion_tokens: 2,
+                        total_tokens: 3,
+                    },
+                    request_id,
+                    user_id,
+                    streamed: false,
+                };
+                responder_bus.route_message(response, None).await.unwrap();
+            }
+        });
+
+        let request_id = Uuid::new_v4();
+        let response = bus
+            .send_and_await_response(llm_request(request_id), None, Duration::from_millis(500))
+            .await
+            .unwrap();
+
+        match response {
+            ServiceMessage::LLMResponse {
+                content,
+                request_id: response_id,
+                ..
+            } => {
+                assert_eq!(content, "Hi there");
+                assert_eq!(response_id, request_id);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(bus.response_waiters.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_routing_sender_routes_to_target() {
+        let bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = bus
+            .register_service(ai_manager_shared::CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let sender = bus.routing_sender();
+        sender
+            .send(ServiceMessage::LLMResponseChunk {
+                request_id: Uuid::new_v4(),
+                delta: "Hi".to_string(),
+                done: false,
+                usage: None,
+            })
+            .await
+            .unwrap();
+
+        let routed = timeout(Duration::from_secs(1), core_rx.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            routed,
+            Some(ServiceMessage::LLMResponseChunk { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_register_transport_pumps_both_ways() {
+        let bus = Arc::new(EventBus::new());
+        let (_tx, mut core_rx) = bus
+            .register_service(ai_manager_shared::CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let (local, mut remote) = ChannelTransport::pair(10);
+        let bridge_bus = bus.clone();
+        let bridge = tokio::spawn(async move {
+            bridge_bus
+                .register_transport(
+                    ai_manager_shared::DATA_SERVICE_ID.to_string(),
+                    Box::new(local),
+                )
+                .await
+        });
+
+        while !bus
+            .get_registered_services()
+            .await
+            .contains(&ai_manager_shared::DATA_SERVICE_ID.to_string())
+        {
+            tokio::task::yield_now().await;
+        }
+
+        bus.route_message(
+            ServiceMessage::ClearConversation {
+                user_id: "alice".to_string(),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            remote.recv().await.unwrap(),
+            Some(ServiceMessage::ClearConversation { .. })
+        ));
+
+        remote
+            .send(ServiceMessage::LLMResponseChunk {
+                request_id: Uuid::new_v4(),
+                delta: "Hi".to_string(),
+                done: false,
+                usage: None,
+            })
+            .await
+            .unwrap();
+        let routed = timeout(Duration::from_secs(1), core_rx.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            routed,
+            Some(ServiceMessage::LLMResponseChunk { .. })
+        ));
+
+        // Closing the far end unregisters the service
+        drop(remote);
+        assert!(bridge.await.unwrap().is_err());
+        assert!(!bus
+            .get_registered_services()
+            .await
+            .contains(&ai_manager_shared::DATA_SERVICE_ID.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_register_transport_requires_service_token() {
+        let bus = Arc::new(EventBus::new().with_service_token(Some("secret".to_string())));
+        let service_id = ai_manager_shared::DATA_SERVICE_ID.to_string();
+
+        let (local, mut remote) = ChannelTransport::pair(10);
+        let (registered, _) = tokio::join!(
+            bus.register_transport(service_id.clone(), Box::new(local)),
+            auth::authenticate(&mut remote, &service_id, "guess"),
+        );
+        assert!(matches!(registered, Err(SystemError::Authentication(_))));
+        assert!(!bus.get_registered_services().await.contains(&service_id));
+
+        // With the right token the service is registered
+        let (local, mut remote) = ChannelTransport::pair(10);
+        let bridge_bus = bus.clone();
+        let bridge = tokio::spawn(async move {
+            bridge_bus
+                .register_transport(service_id, Box::new(local))
+                .await
+        });
+        assert_eq!(
+            auth::authenticate(&mut remote, "data", "secret")
+                .await
+                .unwrap(),
+            CORE_SERVICE_ID
+        );
+        while !bus
+            .get_registered_services()
+            .await
+            .contains(&"data".to_string())
+        {
+            tokio::task::yield_now().await;
+        }
+
+        drop(remote);
+        assert!(bridge.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_and_await_response_times_out() {
+        let bus = EventBus::new();
+        let (_tx, _rx) = bus
+            .register_service(ai_manager_shared::LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let mut events = bus.subscribe_to_events();
+        let request_id = Uuid::new_v4();
+
+        let result = bus
+            .send_and_await_response(llm_request(request_id), None, Duration::from_millis(20))
+            .await;
+
+        assert!(matches!(result, Err(SystemError::Timeout)));
+        assert!(bus.response_waiters.read().await.is_empty());
+
+        let mut timed_out = None;
+        while let Ok(event) = events.try_recv() {
+            if let SystemEvent::RequestTimedOut { request_id, target } = event {
+                timed_out = Some((request_id, target));
+            }
+        }
+        assert_eq!(
+            timed_out,
+            Some((request_id, ai_manager_shared::LLM_SERVICE_ID.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_message_reports_partial_failures() {
+        let bus = EventBus::new();
+        let (_tx_a, mut rx_a) = bus.register_service("a".to_string()).await.unwrap();
+        let (_tx_b, rx_b) = bus.register_service("b".to_string()).await.unwrap();
+        let (_tx_c, mut rx_c) = bus.register_service("c".to_string()).await.unwrap();
+
+        // "b" has gone away without unregistering
+        drop(rx_b);
+
+        let results = bus
+            .broadcast_message(ServiceMessage::ShutdownService {
+                service_id: ALL_SERVICES_ID.to_string(),
+            })
+            .await;
+
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert!(results[0].1.is_ok());
+        assert!(results[1].1.is_err());
+        assert!(results[2].1.is_ok());
+
+        assert!(rx_a.try_recv().is_ok());
+        assert!(rx_c.try_recv().is_ok());
+
+        let stats = bus.get_stats().await;
+        assert_eq!(stats.messages_routed, 2);
+        assert_eq!(stats.routing_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_all_services_health() {
+        let bus = Arc::new(EventBus::new());
+
+        for id in ["healthy", "degraded"] {
+            let (_tx, mut rx) = bus.register_service(id.to_string()).await.unwrap();
+            let responder_bus = bus.clone();
+            tokio::spawn(async move {
+                while let Some(ServiceMessage::ServiceHealthCheck { .. }) = rx.recv().await {
+                    let status = if id == "healthy" {
+                        ServiceHealth::Healthy
+                    } else {
+                        ServiceHealth::Degraded {
+                            reason: "slow".to_string(),
+                        }
+                    };
+                    let response = ServiceMessage::ServiceHealthResponse {
+                        service_id: id.to_string(),
+                        status,
+                    };
+                    responder_bus.route_message(response, None).await.unwrap();
+                }
+            });
+        }
+        let (_tx, _rx) = bus.register_service("silent".to_string()).await.unwrap();
+
+        let results = bus
+            .check_all_services_health(Duration::from_millis(200))
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(
+            matches!(results[0], (ref id, Ok(ServiceHealth::Degraded { .. })) if id == "degraded")
+        );
+        assert!(matches!(results[1], (ref id, Ok(ServiceHealth::Healthy)) if id == "healthy"));
+        assert!(matches!(results[2], (ref id, Err(SystemError::Timeout)) if id == "silent"));
+        assert!(bus.health_waiters.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_health_check_keeps_other_waiters() {
+        let bus = Arc::new(EventBus::new());
+        let (_tx, mut rx) = bus.register_service("slow".to_string()).await.unwrap();
+
+        let long_bus = bus.clone();
+        let long_check = tokio::spawn(async move {
+            long_bus
+                .check_service_health(&"slow".to_string(), Duration::from_secs(5))
+                .await
+        });
+        assert!(rx.recv().await.is_some());
+
+        // A shorter check on the same service times out first
+        let short = bus
+            .check_service_health(&"slow".to_string(), Duration::from_millis(50))
+            .await;
+        assert!(matches!(short, Err(SystemError::Timeout)));
+        assert_eq!(bus.health_waiters.read().await["slow"].len(), 1);
+
+        let response = ServiceMessage::ServiceHealthResponse {
+            service_id: "slow".to_string(),
+            status: ServiceHealth::Healthy,
+        };
+        bus.route_message(response, None).await.unwrap();
+
+        assert!(matches!(
+            long_check.await.unwrap(),
+            Ok(ServiceHealth::Healthy)
+        ));
+        assert!(bus.health_waiters.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stats_by_message_type() {
+        let bus = EventBus::new();
+        let (_tx, _rx) = bus
+            .register_service(ai_manager_shared::CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        for _ in 0..3 {
+            let message = ServiceMessage::UserInput {
+                content: "Hello".to_string(),
+                timestamp: chrono::Utc::now(),
+                user_id: "test-user".to_string(),
+                request_id: None,
+                stream: false,
+            };
+            bus.route_message(message, None).await.unwrap();
+        }
+        bus.route_message(llm_request(Uuid::new_v4()), Some("core".to_string()))
+            .await
+            .unwrap();
+
+        let stats = bus.get_stats().await;
+        assert_eq!(stats.messages_routed, 4);
+        assert_eq!(stats.messages_by_type["UserInput"], 3);
+        assert_eq!(stats.messages_by_type["LLMRequest"], 1);
+        assert_eq!(stats.routing_latency.count, 4);
+        assert_eq!(stats.routing_latency.counts.iter().sum::<u64>(), 4);
+        assert!(stats.routing_latency.mean().is_some());
+    }
+
+    #[test]
+    fn test_latency_histogram_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.observe(Duration::from_micros(50));
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_secs(5));
+
+        assert_eq!(histogram.counts[0], 1);
+        assert_eq!(histogram.counts[3], 1);
+        assert_eq!(histogram.counts[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(histogram.count, 3);
+    }
+
+    #[test]
+    fn test_latency_histogram_mean_past_u32_count() {
+        let histogram = LatencyHistogram {
+            count: u64::from(u32::MAX) + 1,
+            sum: Duration::from_secs(1 << 32),
+            ..LatencyHistogram::default()
+        };
+
+        assert_eq!(histogram.mean(), Some(Duration::from_secs(1)));
+    }
+
+    fn user_input() -> ServiceMessage {
+        ServiceMessage::UserInput {
+            content: "Hello".to_string(),
+            timestamp: chrono::Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_try_route_message_reports_full_queue() {
+        let bus = EventBus::new();
+        let service_id = ai_manager_shared::CORE_SERVICE_ID.to_string();
+        let (_tx, mut rx) = bus.register_service(service_id.clone()).await.unwrap();
+
+        for _ in 0..MESSAGE_QUEUE_CAPACITY {
+            bus.try_route_message(user_input(), None).await.unwrap();
+        }
+        assert_eq!(
+            bus.queue_depth(&service_id).await,
+            Some(MESSAGE_QUEUE_CAPACITY)
+        );
+
+        let result = bus.try_route_message(user_input(), None).await;
+        assert!(matches!(
+            result,
+            Err(SystemError::RateLimitExceeded { ref service }) if *service == service_id
+        ));
+
+        let result = bus
+            .route_message_with_timeout(user_input(), None, Duration::from_millis(20))
+            .await;
+        assert!(matches!(result, Err(SystemError::Timeout)));
+        assert_eq!(bus.get_stats().await.routing_errors, 2);
+
+        // Draining one message makes room again
+        rx.recv().await.unwrap();
+        bus.try_route_message(user_input(), None).await.unwrap();
+        assert_eq!(
+            bus.queue_depths().await.get(&service_id),
+            Some(&MESSAGE_QUEUE_CAPACITY)
+        );
+    }
 }
@@ -1,28 +1,46 @@
 use crate::event_bus::EventBus;
+use crate::summarizer::ConversationSummarizer;
 use ai_manager_shared::{
     Message, MessageRole, ResponseType, Result, ServiceMessage, SystemError, DATA_SERVICE_ID,
     UI_SERVICE_ID,
 };
 use chrono::Utc;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// Shown when a reply can't be saved because the data service is down
+pub const HISTORY_UNAVAILABLE_MESSAGE: &str =
+    "Your conversation history is temporarily unavailable, so this reply wasn't saved.";
+
 pub struct LLMResponseHandler {
     event_bus: Arc<EventBus>,
+    summarizer: Option<Arc<ConversationSummarizer>>,
 }
 
 impl LLMResponseHandler {
     pub fn new(event_bus: Arc<EventBus>) -> Self {
-        Self { event_bus }
+        Self {
+            event_bus,
+            summarizer: None,
+        }
+    }
+
+    /// Summarize conversations that have grown long after storing replies
+    pub fn with_summarizer(mut self, summarizer: Arc<ConversationSummarizer>) -> Self {
+        self.summarizer = Some(summarizer);
+        self
     }
 
     /// Handle LLM response and route to UI and data services
     pub async fn handle_llm_response(&self, llm_response: ServiceMessage) -> Result<()> {
         if let ServiceMessage::LLMResponse {
             content,
+            model,
             usage,
             request_id,
+            user_id,
+            streamed,
         } = llm_response
         {
             info!("Processing LLM response for request {}", request_id);
@@ -31,17 +49,22 @@ impl LLMResponseHandler {
                 content, usage.total_tokens
             );
 
-            // Create system response for UI
-            let ui_response = ServiceMessage::SystemResponse {
-                content: content.clone(),
-                message_type: ResponseType::Success,
-                timestamp: Utc::now(),
-            };
+            // A streamed response already reached the UI in chunks
+            if !streamed {
+                self.end_thinking(request_id).await?;
 
-            // Route response to UI
-            self.event_bus
-                .route_message(ui_response, Some(UI_SERVICE_ID.to_string()))
-                .await?;
+                // Create system response for UI
+                let ui_response = ServiceMessage::SystemResponse {
+                    content: content.clone(),
+                    message_type: ResponseType::Success,
+                    timestamp: Utc::now(),
+                    request_id: Some(request_id),
+                };
+
+                // Route response to UI. Store it even if the UI is gone, so
+                // it shows up in the history later.
+                self.route_if_available(ui_response, UI_SERVICE_ID).await?;
+            }
 
             // Create message for conversation storage
             let message = Message {
@@ -51,20 +74,31 @@ impl LLMResponseHandler {
                 role: MessageRole::Assistant,
                 metadata: Some(serde_json::json!({
                     "request_id": request_id,
+                    "model": model,
                     "token_usage": usage,
                 })),
             };
 
             // Store conversation in data service
-            // Note: In a real implementation, we'd need to track the user_id from the original request
             let store_request = ServiceMessage::StoreConversation {
-                user_id: "current_user".to_string(), // TODO: Get actual user ID
+                user_id: user_id.clone(),
                 messages: vec![message],
             };
 
-            self.event_bus
-                .route_message(store_request, Some(DATA_SERVICE_ID.to_string()))
-                .await?;
+            if !self
+                .route_if_available(store_request, DATA_SERVICE_ID)
+                .await?
+            {
+                let notice = ServiceMessage::SystemResponse {
+                    content: HISTORY_UNAVAILABLE_MESSAGE.to_string(),
+                    message_type: ResponseType::Warning,
+                    timestamp: Utc::now(),
+                    request_id: Some(request_id),
+                };
+                self.route_if_available(notice, UI_SERVICE_ID).await?;
+            } else if let Some(summarizer) = &self.summarizer {
+                summarizer.spawn(user_id);
+            }
 
             info!("LLM response processed and routed successfully");
             Ok(())
@@ -76,41 +110,97 @@ impl LLMResponseHandler {
         }
     }
 
-    /// Handle LLM errors
-    pub async fn handle_llm_error(
-        &self,
-        provider: &str,
-        error_message: &str,
-        request_id: Uuid,
-    ) -> Result<()> {
-        error!(
-            "LLM error from provider '{}' for request {}: {}",
-            provider, request_id, error_message
-        );
-
-        // Create error response for UI
-        let error_response = ServiceMessage::SystemResponse {
-            content: format!(
-                "Sorry, I encountered an error while processing your request: {}",
-                error_message
-            ),
-            message_type: ResponseType::Error,
-            timestamp: Utc::now(),
+    /// Handle an error the LLM service replied with, clearing the thinking
+    /// notice for its request before passing it on to the UI. The error
+    /// only carries a friendly message for its code; details stay in the log.
+    pub async fn handle_llm_error(&self, error: ServiceMessage) -> Result<()> {
+        let ServiceMessage::SystemError {
+            code, request_id, ..
+        } = &error
+        else {
+            return Err(SystemError::InvalidInput(format!(
+                "Expected a SystemError, got {}",
+                error.message_type()
+            )));
         };
 
-        // Route error to UI
-        self.event_bus
-            .route_message(error_response, Some(UI_SERVICE_ID.to_string()))
-            .await?;
+        error!("LLM request {:?} failed with {:?}", request_id, code);
 
-        Ok(())
+        if let Some(request_id) = *request_id {
+            self.end_thinking(request_id).await?;
+        }
+
+        self.route_if_available(error, UI_SERVICE_ID)
+            .await
+            .map(|_| ())
     }
 
-    /// Handle streaming LLM responses (for future implementation)
-    pub async fn handle_streaming_response(&self, _chunk: &str, _request_id: Uuid) -> Result<()> {
-        // TODO: Implement streaming response handling
-        // This would send partial responses to the UI as they arrive
-        Ok(())
+    /// Forward an `LLMResponseChunk` to the UI, clearing the thinking notice
+    /// once the last one arrives
+    pub async fn handle_streaming_response(&self, chunk: ServiceMessage) -> Result<()> {
+        let ServiceMessage::LLMResponseChunk {
+            request_id,
+            done,
+            ref usage,
+            ..
+        } = chunk
+        else {
+            return Err(SystemError::InvalidInput(format!(
+                "Expected an LLMResponseChunk, got {}",
+                chunk.message_type()
+            )));
+        };
+
+        if done {
+            debug!(
+                "Stream for request {} finished ({} tokens)",
+                request_id,
+                usage.as_ref().map_or(0, |usage| usage.total_tokens)
+            );
+            self.end_thinking(request_id).await?;
+        } else {
+            debug!("Forwarding stream chunk for request {}", request_id);
+        }
+
+        self.route_if_available(chunk, UI_SERVICE_ID)
+            .await
+            .map(|_| ())
+    }
+
+    /// Tell the UI to clear the thinking notice for `request_id`
+    async fn end_thinking(&self, request_id: Uuid) -> Result<()> {
+        let done = ServiceMessage::SystemResponse {
+            content: String::new(),
+            message_type: ResponseType::ThinkingDone,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+        };
+
+        self.route_if_available(done, UI_SERVICE_ID)
+            .await
+            .map(|_| ())
+    }
+
+    /// Route `message` to `target`, returning whether it was delivered. A
+    /// service that isn't registered, e.g. because it is still starting,
+    /// is logged rather than failing the whole response.
+    async fn route_if_available(&self, message: ServiceMessage, target: &str) -> Result<bool> {
+        let message_type = message.message_type();
+        match self
+            .event_bus
+            .route_message(message, Some(target.to_string()))
+            .await
+        {
+            Ok(()) => Ok(true),
+            Err(SystemError::ServiceUnavailable { service }) => {
+                warn!(
+                    "Dropped {}: service '{}' is unavailable",
+                    message_type, service
+                );
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
     }
 }
 
@@ -137,32 +227,325 @@ mod tests {
 
         let llm_response = ServiceMessage::LLMResponse {
             content: "Hello! How can I help you today?".to_string(),
+            model: None,
             usage: TokenUsage {
                 prompt_tokens: 10,
                 completion_tokens: 8,
                 total_tokens: 18,
             },
             request_id: Uuid::new_v4(),
+            user_id: "test-user".to_string(),
+            streamed: false,
         };
 
         let result = handler.handle_llm_response(llm_response).await;
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_unavailable_data_service_reported() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = LLMResponseHandler::new(event_bus.clone());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let request_id = Uuid::new_v4();
+        let llm_response = ServiceMessage::LLMResponse {
+            content: "Hello!".to_string(),
+            model: None,
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            request_id,
+            user_id: "test-user".to_string(),
+            streamed: false,
+        };
+        handler.handle_llm_response(llm_response).await.unwrap();
+
+        // The reply still reaches the UI, followed by a warning
+        ui_rx.recv().await.unwrap();
+        assert!(matches!(
+            ui_rx.recv().await.unwrap(),
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::Success,
+                ..
+            }
+        ));
+        assert!(matches!(
+            ui_rx.recv().await.unwrap(),
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::Warning,
+                request_id: Some(id),
+                ..
+            } if id == request_id
+        ));
+
+        // Without a UI the response is dropped rather than failing
+        drop(ui_rx);
+        event_bus
+            .unregister_service(&UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let llm_response = ServiceMessage::LLMResponse {
+            content: "Hello again!".to_string(),
+            model: None,
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            request_id,
+            user_id: "test-user".to_string(),
+            streamed: false,
+        };
+        assert!(handler.handle_llm_response(llm_response).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_response_only_stored() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = LLMResponseHandler::new(event_bus.clone());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, mut data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let llm_response = ServiceMessage::LLMResponse {
+            content: "Hello!".to_string(),
+            model: None,
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            request_id: Uuid::new_v4(),
+            user_id: "test-user".to_string(),
+            streamed: true,
+        };
+        handler.handle_llm_response(llm_response).await.unwrap();
+
+        // The chunks already showed it, so the UI isn't sent it again
+        match data_rx.recv().await.unwrap() {
+            ServiceMessage::StoreConversation { messages, .. } => {
+                assert_eq!(messages[0].content, "Hello!")
+            }
+            other => panic!("expected StoreConversation, got {:?}", other),
+        }
+        assert!(ui_rx.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn test_llm_error_handling() {
         let event_bus = Arc::new(EventBus::new());
         let handler = LLMResponseHandler::new(event_bus.clone());
 
         // Register UI service to receive error messages
-        let _ui_service = event_bus
+        let (_ui_tx, mut ui_rx) = event_bus
             .register_service(UI_SERVICE_ID.to_string())
             .await
             .unwrap();
 
+        let error = SystemError::RateLimitExceeded {
+            service: "openai".to_string(),
+        };
+        let request_id = Uuid::new_v4();
         let result = handler
-            .handle_llm_error("openai", "API rate limit exceeded", Uuid::new_v4())
+            .handle_llm_error(ServiceMessage::error_reply(&error, Some(request_id)))
             .await;
         assert!(result.is_ok());
+
+        // Thinking ends, then the error arrives by code without the details
+        assert!(matches!(
+            ui_rx.recv().await.unwrap(),
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::ThinkingDone,
+                request_id: Some(id),
+                ..
+            } if id == request_id
+        ));
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemError {
+                code, user_message, ..
+            } => {
+                assert_eq!(code, ai_manager_shared::ErrorCode::RateLimited);
+                assert!(!user_message.contains("openai"));
+            }
+            other => panic!("expected a SystemError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_chunks_forwarded_to_ui() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = LLMResponseHandler::new(event_bus.clone());
+
+        let (_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let request_id = Uuid::new_v4();
+        let chunk = |delta: &str, done: bool| ServiceMessage::LLMResponseChunk {
+            request_id,
+            delta: delta.to_string(),
+            done,
+            usage: done.then_some(TokenUsage {
+                prompt_tokens: 3,
+                completion_tokens: 2,
+                total_tokens: 5,
+            }),
+        };
+        for (delta, done) in [("Hel", false), ("lo", false), ("", true)] {
+            handler
+                .handle_streaming_response(chunk(delta, done))
+                .await
+                .unwrap();
+        }
+
+        let mut streamed = String::new();
+        for _ in 0..2 {
+            match ui_rx.recv().await.unwrap() {
+                ServiceMessage::LLMResponseChunk {
+                    request_id: chunk_id,
+                    delta,
+                    done: false,
+                    ..
+                } => {
+                    assert_eq!(chunk_id, request_id);
+                    streamed.push_str(&delta);
+                }
+                other => panic!("expected a stream chunk, got {:?}", other),
+            }
+        }
+        assert_eq!(streamed, "Hello");
+
+        assert!(matches!(
+            ui_rx.recv().await.unwrap(),
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::ThinkingDone,
+                request_id: Some(id),
+                ..
+            } if id == request_id
+        ));
+
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::LLMResponseChunk {
+                request_id: end_id,
+                done: true,
+                usage: Some(usage),
+                ..
+            } => {
+                assert_eq!(end_id, request_id);
+                assert_eq!(usage.total_tokens, 5);
+            }
+            other => panic!("expected the last chunk, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conversation_stored_under_requesting_user() {
+        let event_bus = Arc::new(EventBus::new());
+        let input_handler = crate::handlers::UserInputHandler::new(event_bus.clone());
+        let response_handler = LLMResponseHandler::new(event_bus.clone());
+
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(ai_manager_shared::LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, mut data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let user_input = ServiceMessage::UserInput {
+            content: "What's on my calendar?".to_string(),
+            timestamp: Utc::now(),
+            user_id: "alice".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        let input = tokio::spawn(async move { input_handler.handle_user_input(user_input).await });
+
+        // Answer the history lookup with an empty history
+        match data_rx.recv().await.unwrap() {
+            ServiceMessage::LoadConversationHistory {
+                user_id,
+                request_id,
+                ..
+            } => {
+                assert_eq!(user_id, "alice");
+                let response = ServiceMessage::ConversationHistoryResponse {
+                    user_id,
+                    messages: vec![],
+                    request_id,
+                };
+                event_bus.route_message(response, None).await.unwrap();
+            }
+            other => panic!("expected a history request, got {:?}", other),
+        }
+        input.await.unwrap().unwrap();
+
+        // Play the LLM service: echo the request's user id in the response
+        let (request_id, user_id) = match llm_rx.recv().await.unwrap() {
+            ServiceMessage::LLMRequest {
+                request_id,
+                user_id,
+                ..
+            } => (request_id, user_id),
+            other => panic!("expected an LLM request, got {:?}", other),
+        };
+        let llm_response = ServiceMessage::LLMResponse {
+            content: "Nothing today.".to_string(),
+            model: None,
+            usage: TokenUsage {
+                prompt_tokens: 5,
+                completion_tokens: 3,
+                total_tokens: 8,
+            },
+            request_id,
+            user_id,
+            streamed: false,
+        };
+        response_handler
+            .handle_llm_response(llm_response)
+            .await
+            .unwrap();
+
+        match data_rx.recv().await.unwrap() {
+            ServiceMessage::StoreConversation { user_id, .. } => assert_eq!(user_id, "alice"),
+            other => panic!("expected StoreConversation, got {:?}", other),
+        }
+
+        // Thinking, its end, and the answer all carry the LLM request's id
+        let mut lifecycle = Vec::new();
+        while let Ok(ServiceMessage::SystemResponse {
+            message_type,
+            request_id: response_id,
+            ..
+        }) = ui_rx.try_recv()
+        {
+            assert_eq!(response_id, Some(request_id));
+            lifecycle.push(message_type);
+        }
+        assert!(matches!(
+            lifecycle.as_slice(),
+            [
+                ResponseType::Thinking,
+                ResponseType::ThinkingDone,
+                ResponseType::Success
+            ]
+        ));
     }
 }
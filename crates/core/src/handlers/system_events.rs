@@ -3,6 +3,7 @@ use ai_manager_shared::{Result, SystemEvent};
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 pub struct SystemEventHandler {
     event_bus: Arc<EventBus>,
@@ -88,6 +89,11 @@ impl SystemEventHandler {
                 debug!("Message routed from '{}' to '{}'", from, to);
                 Self::on_message_received(&from, &to, event_bus).await?;
             }
+
+            SystemEvent::RequestTimedOut { request_id, target } => {
+                warn!("Request {} to '{}' timed out", request_id, target);
+                Self::on_request_timed_out(request_id, &target, event_bus).await?;
+            }
         }
 
         Ok(())
@@ -159,6 +165,24 @@ impl SystemEventHandler {
         Ok(())
     }
 
+    /// Handle request timed out event
+    async fn on_request_timed_out(
+        request_id: Uuid,
+        target: &str,
+        _event_bus: &EventBus,
+    ) -> Result<()> {
+        warn!(
+            "⏱️  Service '{}' never answered request {}",
+            target, request_id
+        );
+
+        // TODO: Additional timeout handling could be added here
+        // - Count timeouts per service to spot stuck flows
+        // - Notify operators when a service keeps timing out
+
+        Ok(())
+    }
+
     /// Get event handler statistics
     pub fn is_running(&self) -> bool {
         self.handler_task.is_some()
@@ -222,6 +246,10 @@ mod tests {
                 service_id: "service3".to_string(),
                 error: "Test error".to_string(),
             },
+            SystemEvent::RequestTimedOut {
+                request_id: Uuid::new_v4(),
+                target: "service4".to_string(),
+            },
         ];
 
         for event in events {
@@ -1,20 +1,72 @@
 use crate::event_bus::EventBus;
-use ai_manager_shared::{ResponseType, Result, ServiceMessage, SystemError, LLM_SERVICE_ID};
+use crate::health::{describe_health, SystemHealth};
+use crate::input_guard::{GuardVerdict, InputGuard};
+use crate::rate_limiter::{UserRateLimiter, RATE_LIMITED_MESSAGE};
+use ai_manager_llm_service::{UsageStats, UsageTracker};
+use ai_manager_shared::{
+    ExportFormat, InputGuardConfig, LLMConfig, Message, MessageRole, ResponseType, Result,
+    ServiceHealth, ServiceMessage, SystemError, UserPreferences, CONTEXT_LOAD_TIMEOUT_SECONDS,
+    CONVERSATION_CONTEXT_MESSAGES, CORE_SERVICE_ID, DATA_SERVICE_ID, DEFAULT_LLM_PROVIDER,
+    HEALTH_CHECK_TIMEOUT_SECONDS, LLM_SERVICE_ID, MAX_PROMPT_LENGTH,
+};
 
 #[cfg(test)]
 use ai_manager_shared::UI_SERVICE_ID;
-use chrono::Utc;
+use chrono::{Datelike, TimeZone, Utc};
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::time::Duration;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
+/// Shown when the LLM service isn't running, e.g. while it starts up
+pub const ASSISTANT_UNAVAILABLE_MESSAGE: &str =
+    "The assistant is temporarily unavailable, please try again.";
+
+/// Shown when a message can't be answered again, e.g. because it is no
+/// longer in the current conversation
+pub const REGENERATE_FAILED_MESSAGE: &str = "That message can't be answered again.";
+
 pub struct UserInputHandler {
     event_bus: Arc<EventBus>,
+    llm_config: Option<LLMConfig>,
+    usage_tracker: Option<Arc<UsageTracker>>,
+    input_guard: InputGuard,
+    rate_limiter: Option<Arc<UserRateLimiter>>,
 }
 
 impl UserInputHandler {
     pub fn new(event_bus: Arc<EventBus>) -> Self {
-        Self { event_bus }
+        Self {
+            event_bus,
+            llm_config: None,
+            usage_tracker: None,
+            input_guard: InputGuard::from_config(&InputGuardConfig::default()),
+            rate_limiter: None,
+        }
+    }
+
+    /// Providers and models users may choose between
+    pub fn with_llm_config(mut self, llm_config: LLMConfig) -> Self {
+        self.llm_config = Some(llm_config);
+        self
+    }
+
+    /// Token usage reported by `/usage`
+    pub fn with_usage_tracker(mut self, usage_tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = Some(usage_tracker);
+        self
+    }
+
+    /// Screens messages before they are sent to the LLM
+    pub fn with_input_guard(mut self, input_guard: InputGuard) -> Self {
+        self.input_guard = input_guard;
+        self
+    }
+
+    /// Limits how often each user's messages are sent to the LLM
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<UserRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
     }
 
     /// Handle user input and route to appropriate services
@@ -23,74 +75,364 @@ impl UserInputHandler {
             content,
             timestamp: _,
             user_id,
+            request_id,
+            stream,
         } = user_input
         {
-            info!("Processing user input from user '{}': {}", user_id, content);
+            // Every step of the request logs under its id, and every reply
+            // carries it
+            let request_id = request_id.unwrap_or_else(Uuid::new_v4);
+            let span = info_span!("user_input", %request_id, %user_id);
+            self.process_input(content, user_id, request_id, stream)
+                .instrument(span)
+                .await
+        } else {
+            error!("Invalid message type for user input handler");
+            Err(SystemError::InvalidInput(
+                "Expected UserInput message".to_string(),
+            ))
+        }
+    }
+
+    async fn process_input(
+        &self,
+        content: String,
+        user_id: String,
+        request_id: Uuid,
+        stream: bool,
+    ) -> Result<()> {
+        info!("Processing user input from user '{}': {}", user_id, content);
+
+        // Basic input validation
+        if content.trim().is_empty() {
+            let response = ServiceMessage::SystemResponse {
+                content: "Please provide a non-empty message.".to_string(),
+                message_type: ResponseType::Warning,
+                timestamp: Utc::now(),
+                request_id: Some(request_id),
+            };
+
+            return self.event_bus.route_message(response, None).await;
+        }
+
+        // Check for system commands
+        if content.starts_with('/') {
+            return self
+                .handle_system_command(&content, &user_id, request_id)
+                .await;
+        }
 
-            // Basic input validation
-            if content.trim().is_empty() {
+        if !self.within_rate_limit(&user_id, request_id).await? {
+            return Ok(());
+        }
+
+        let content = match self.input_guard.check(content, &user_id).await {
+            GuardVerdict::Allow(content) => content,
+            GuardVerdict::Reject(reason) => {
                 let response = ServiceMessage::SystemResponse {
-                    content: "Please provide a non-empty message.".to_string(),
+                    content: reason,
                     message_type: ResponseType::Warning,
                     timestamp: Utc::now(),
+                    request_id: Some(request_id),
                 };
-
                 return self.event_bus.route_message(response, None).await;
             }
+        };
 
-            // Check for system commands
-            if content.starts_with('/') {
-                return self.handle_system_command(&content, &user_id).await;
-            }
+        self.send_thinking(request_id).await?;
 
-            // Send thinking response
-            let thinking_response = ServiceMessage::SystemResponse {
-                content: "Thinking...".to_string(),
-                message_type: ResponseType::Thinking,
-                timestamp: Utc::now(),
-            };
-            self.event_bus
-                .route_message(thinking_response, None)
-                .await?;
-
-            // Create LLM request
-            let llm_request = ServiceMessage::LLMRequest {
-                prompt: content,
-                context: vec![],                // TODO: Add conversation context
-                provider: "openai".to_string(), // TODO: Get from config
-                request_id: Uuid::new_v4(),
-            };
+        // Include recent conversation so the assistant remembers it
+        let history = self.load_history(&user_id).await;
+
+        // Stored so the message can be answered again later
+        let message = Message {
+            id: Uuid::new_v4(),
+            content: content.clone(),
+            timestamp: Utc::now(),
+            role: MessageRole::User,
+            metadata: Some(serde_json::json!({ "request_id": request_id })),
+        };
+        self.store_user_message(&user_id, message).await;
 
-            // Route to LLM service
-            self.event_bus
-                .route_message(llm_request, Some(LLM_SERVICE_ID.to_string()))
-                .await?;
+        self.ask_llm(content, &history, user_id, request_id, stream)
+            .await
+    }
 
-            debug!("User input routed to LLM service");
-            Ok(())
+    /// Answer one of the user's earlier messages again, dropping everything
+    /// said after it
+    pub async fn handle_regenerate(&self, regenerate: ServiceMessage) -> Result<()> {
+        if let ServiceMessage::RegenerateResponse {
+            user_id,
+            from_message_id,
+        } = regenerate
+        {
+            let request_id = Uuid::new_v4();
+            let span = info_span!("regenerate", %request_id, %user_id);
+            self.regenerate(user_id, from_message_id, request_id)
+                .instrument(span)
+                .await
         } else {
-            error!("Invalid message type for user input handler");
+            error!("Invalid message type for regenerate handler");
             Err(SystemError::InvalidInput(
-                "Expected UserInput message".to_string(),
+                "Expected RegenerateResponse message".to_string(),
             ))
         }
     }
 
+    async fn regenerate(
+        &self,
+        user_id: String,
+        from_message_id: Uuid,
+        request_id: Uuid,
+    ) -> Result<()> {
+        info!(
+            "Regenerating the reply to message {} for user '{}'",
+            from_message_id, user_id
+        );
+        if !self.within_rate_limit(&user_id, request_id).await? {
+            return Ok(());
+        }
+
+        // One more than usual, as the message itself becomes the prompt
+        let truncate = ServiceMessage::TruncateConversation {
+            user_id: user_id.clone(),
+            after_message_id: from_message_id,
+            limit: CONVERSATION_CONTEXT_MESSAGES + 1,
+            request_id: Uuid::new_v4(),
+        };
+        let response = self
+            .event_bus
+            .send_and_await_response(
+                truncate,
+                Some(DATA_SERVICE_ID.to_string()),
+                Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+            )
+            .await;
+
+        let mut history = match response {
+            Ok(ServiceMessage::ConversationHistoryResponse { messages, .. }) => messages,
+            Ok(other) => {
+                warn!("Cannot regenerate, unexpected reply: {:?}", other);
+                return self.report_regenerate_failed().await;
+            }
+            Err(e) => {
+                warn!("Cannot regenerate: {}", e);
+                return self.report_regenerate_failed().await;
+            }
+        };
+
+        let prompt = match history.pop() {
+            Some(message)
+                if message.id == from_message_id && matches!(message.role, MessageRole::User) =>
+            {
+                message.content
+            }
+            _ => {
+                warn!(
+                    "Message {} is not one of the user's own messages",
+                    from_message_id
+                );
+                return self.report_regenerate_failed().await;
+            }
+        };
+
+        self.send_thinking(request_id).await?;
+        self.ask_llm(prompt, &history, user_id, request_id, false)
+            .await
+    }
+
+    async fn report_regenerate_failed(&self) -> Result<()> {
+        let response = ServiceMessage::SystemResponse {
+            content: REGENERATE_FAILED_MESSAGE.to_string(),
+            message_type: ResponseType::Warning,
+            timestamp: Utc::now(),
+            request_id: None,
+        };
+        self.event_bus.route_message(response, None).await
+    }
+
+    /// Show a thinking notice; the LLM response handler clears it
+    async fn send_thinking(&self, request_id: Uuid) -> Result<()> {
+        let thinking_response = ServiceMessage::SystemResponse {
+            content: "Thinking...".to_string(),
+            message_type: ResponseType::Thinking,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+        };
+        self.event_bus.route_message(thinking_response, None).await
+    }
+
+    /// Append the user's message to their history. If the data service is
+    /// down the message is only missing from the history.
+    async fn store_user_message(&self, user_id: &str, message: Message) {
+        let store = ServiceMessage::StoreConversation {
+            user_id: user_id.to_string(),
+            messages: vec![message],
+        };
+        if let Err(e) = self
+            .event_bus
+            .route_message(store, Some(DATA_SERVICE_ID.to_string()))
+            .await
+        {
+            warn!("User message not stored: {}", e);
+        }
+    }
+
+    /// Send `content` to the LLM service with `history` as context, using
+    /// the user's preferences
+    async fn ask_llm(
+        &self,
+        content: String,
+        history: &[Message],
+        user_id: String,
+        request_id: Uuid,
+        stream: bool,
+    ) -> Result<()> {
+        let preferences = self.load_preferences(&user_id).await;
+
+        let mut context = build_context(history, &content);
+        if let Some(language) = &preferences.language {
+            context.insert(0, format!("System: Reply in {}.", language));
+        }
+
+        // Create LLM request with the user's chosen provider and model
+        let (provider, model) = self.choose_llm(&preferences);
+        let llm_request = ServiceMessage::LLMRequest {
+            prompt: content,
+            context,
+            provider,
+            request_id,
+            user_id,
+            model,
+            temperature: preferences.temperature,
+            stream,
+        };
+
+        // Route to LLM service
+        match self
+            .event_bus
+            .route_message(llm_request, Some(LLM_SERVICE_ID.to_string()))
+            .await
+        {
+            Ok(()) => {}
+            Err(SystemError::ServiceUnavailable { service }) => {
+                warn!(
+                    "Cannot answer request: service '{}' is unavailable",
+                    service
+                );
+                return self.report_assistant_unavailable(request_id).await;
+            }
+            Err(e) => return Err(e),
+        }
+
+        debug!("User input routed to LLM service");
+        Ok(())
+    }
+
+    /// Replace the thinking notice for `request_id` with an apology
+    async fn report_assistant_unavailable(&self, request_id: Uuid) -> Result<()> {
+        let done = ServiceMessage::SystemResponse {
+            content: String::new(),
+            message_type: ResponseType::ThinkingDone,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+        };
+        self.event_bus.route_message(done, None).await?;
+
+        let response = ServiceMessage::SystemResponse {
+            content: ASSISTANT_UNAVAILABLE_MESSAGE.to_string(),
+            message_type: ResponseType::Warning,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+        };
+        self.event_bus.route_message(response, None).await
+    }
+
+    /// Fetch the user's recent messages from the data service. Falls back to
+    /// no history if the data service can't be reached.
+    async fn load_history(&self, user_id: &str) -> Vec<Message> {
+        let request = ServiceMessage::LoadConversationHistory {
+            user_id: user_id.to_string(),
+            limit: CONVERSATION_CONTEXT_MESSAGES,
+            request_id: Uuid::new_v4(),
+        };
+
+        let response = self
+            .event_bus
+            .send_and_await_response(
+                request,
+                Some(DATA_SERVICE_ID.to_string()),
+                Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+            )
+            .await;
+
+        match response {
+            Ok(ServiceMessage::ConversationHistoryResponse { messages, .. }) => messages,
+            Ok(other) => {
+                warn!("Unexpected reply to history request: {:?}", other);
+                Vec::new()
+            }
+            Err(e) => {
+                warn!("Continuing without conversation context: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Take a request from the user's rate limit, warning them if they are
+    /// over it
+    async fn within_rate_limit(&self, user_id: &str, request_id: Uuid) -> Result<bool> {
+        let Some(rate_limiter) = &self.rate_limiter else {
+            return Ok(true);
+        };
+        if rate_limiter.try_acquire(user_id) {
+            return Ok(true);
+        }
+
+        warn!("User '{}' is over their rate limit", user_id);
+        let response = ServiceMessage::SystemResponse {
+            content: RATE_LIMITED_MESSAGE.to_string(),
+            message_type: ResponseType::Warning,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+        };
+        self.event_bus.route_message(response, None).await?;
+        Ok(false)
+    }
+
     /// Handle system commands (commands starting with /)
-    async fn handle_system_command(&self, command: &str, _user_id: &str) -> Result<()> {
+    async fn handle_system_command(
+        &self,
+        command: &str,
+        user_id: &str,
+        request_id: Uuid,
+    ) -> Result<()> {
         debug!("Processing system command: {}", command);
 
-        let response_content = match command {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.next();
+
+        let response_content = match name {
             "/help" => {
-                "Available commands:\n/help - Show this help\n/status - Show system status\n/clear - Clear conversation history".to_string()
+                "Available commands:\n/help - Show this help\n/status - Show system status\n/clear - Clear conversation history\n/provider [name] - Show or switch the LLM provider\n/model [name] - Show or switch the model\n/usage [today|month] - Show your token usage\n/export [md|json|jsonl] - Export your conversations".to_string()
             }
             "/status" => {
                 self.get_system_status().await
             }
             "/clear" => {
-                // TODO: Implement conversation clearing
+                let clear_request = ServiceMessage::ClearConversation {
+                    user_id: user_id.to_string(),
+                };
+                self.event_bus
+                    .route_message(clear_request, Some(DATA_SERVICE_ID.to_string()))
+                    .await?;
                 "Conversation history cleared.".to_string()
             }
+            "/provider" => self.select_provider(user_id, argument).await?,
+            "/model" => self.select_model(user_id, argument).await?,
+            "/usage" => self.describe_usage(user_id, argument).await,
+            "/export" => self.export_conversations(user_id, argument).await,
             _ => {
                 format!("Unknown command: {}. Type /help for available commands.", command)
             }
@@ -100,26 +442,362 @@ impl UserInputHandler {
             content: response_content,
             message_type: ResponseType::Info,
             timestamp: Utc::now(),
+            request_id: Some(request_id),
         };
 
         self.event_bus.route_message(response, None).await
     }
 
+    /// The user's stored preferences. Falls back to the defaults if the
+    /// data service can't be reached.
+    async fn load_preferences(&self, user_id: &str) -> UserPreferences {
+        let request = ServiceMessage::LoadUserProfile {
+            user_id: user_id.to_string(),
+            request_id: Uuid::new_v4(),
+        };
+
+        let response = self
+            .event_bus
+            .send_and_await_response(
+                request,
+                Some(DATA_SERVICE_ID.to_string()),
+                Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+            )
+            .await;
+
+        match response {
+            Ok(ServiceMessage::UserProfileResponse { profile, .. }) => profile
+                .map(|profile| UserPreferences::from_value(&profile.preferences))
+                .unwrap_or_default(),
+            Ok(other) => {
+                warn!("Unexpected reply to profile request: {:?}", other);
+                UserPreferences::default()
+            }
+            Err(e) => {
+                warn!("Continuing without user preferences: {}", e);
+                UserPreferences::default()
+            }
+        }
+    }
+
+    /// Provider and model to answer with under `preferences`. A preferred
+    /// provider or model that is no longer configured is ignored.
+    fn choose_llm(&self, preferences: &UserPreferences) -> (String, Option<String>) {
+        let Some(config) = &self.llm_config else {
+            let provider = preferences
+                .preferred_provider
+                .clone()
+                .unwrap_or_else(|| DEFAULT_LLM_PROVIDER.to_string());
+            return (provider, preferences.preferred_model.clone());
+        };
+
+        let provider = preferences
+            .preferred_provider
+            .clone()
+            .filter(|provider| config.providers.contains_key(provider))
+            .unwrap_or_else(|| config.default_provider.clone());
+        let model = preferences.preferred_model.clone().filter(|model| {
+            config
+                .providers
+                .get(&provider)
+                .is_some_and(|provider| provider.offers_model(model))
+        });
+        (provider, model)
+    }
+
+    /// Describe the user's current provider and model
+    async fn describe_selection(&self, user_id: &str) -> String {
+        let preferences = self.load_preferences(user_id).await;
+        let (provider, model) = self.choose_llm(&preferences);
+        let model = model.or_else(|| {
+            self.llm_config
+                .as_ref()
+                .and_then(|config| config.providers.get(&provider))
+                .map(|provider| provider.model.clone())
+        });
+        match model {
+            Some(model) => format!("Provider: {} (model: {})", provider, model),
+            None => format!("Provider: {}", provider),
+        }
+    }
+
+    /// `/provider [name]`: switch to a configured provider and its default
+    /// model, or show the current selection
+    async fn select_provider(&self, user_id: &str, name: Option<&str>) -> Result<String> {
+        let Some(name) = name else {
+            return Ok(self.describe_selection(user_id).await);
+        };
+
+        let mut providers: Vec<&String> = self
+            .llm_config
+            .iter()
+            .flat_map(|config| config.providers.keys())
+            .collect();
+        if !providers.iter().any(|provider| *provider == name) {
+            providers.sort();
+            let available: Vec<&str> = providers.iter().map(|p| p.as_str()).collect();
+            return Ok(format!(
+                "Unknown provider '{}'. Available: {}",
+                name,
+                available.join(", ")
+            ));
+        }
+
+        self.save_preferences(
+            user_id,
+            serde_json::json!({ "preferred_provider": name, "preferred_model": null }),
+        )
+        .await?;
+
+        Ok(format!(
+            "Switched. {}",
+            self.describe_selection(user_id).await
+        ))
+    }
+
+    /// `/model [name]`: switch to a model the current provider offers, or
+    /// show the current selection
+    async fn select_model(&self, user_id: &str, name: Option<&str>) -> Result<String> {
+        let Some(name) = name else {
+            return Ok(self.describe_selection(user_id).await);
+        };
+
+        let preferences = self.load_preferences(user_id).await;
+        let (provider, _) = self.choose_llm(&preferences);
+        let provider_config = self
+            .llm_config
+            .as_ref()
+            .and_then(|config| config.providers.get(&provider));
+        match provider_config {
+            Some(config) if config.offers_model(name) => {}
+            Some(config) => {
+                let mut available = vec![config.model.as_str()];
+                available.extend(config.models.iter().map(|m| m.as_str()));
+                return Ok(format!(
+                    "Model '{}' isn't available for {}. Available: {}",
+                    name,
+                    provider,
+                    available.join(", ")
+                ));
+            }
+            None => return Ok(format!("Provider '{}' is not configured.", provider)),
+        }
+
+        self.save_preferences(user_id, serde_json::json!({ "preferred_model": name }))
+            .await?;
+
+        Ok(format!(
+            "Switched. {}",
+            self.describe_selection(user_id).await
+        ))
+    }
+
+    /// Merge `preferences` into the user's stored profile
+    async fn save_preferences(&self, user_id: &str, preferences: serde_json::Value) -> Result<()> {
+        let update = ServiceMessage::UpdateUserPreferences {
+            user_id: user_id.to_string(),
+            preferences,
+        };
+        self.event_bus
+            .route_message(update, Some(DATA_SERVICE_ID.to_string()))
+            .await
+    }
+
+    /// `/usage [today|month]`: the user's requests, tokens and estimated
+    /// cost, all time or since the start of the current UTC day or month
+    async fn describe_usage(&self, user_id: &str, range: Option<&str>) -> String {
+        let Some(usage_tracker) = &self.usage_tracker else {
+            return "Usage tracking is not available.".to_string();
+        };
+
+        let today = Utc::now().date_naive();
+        let (label, since) = match range {
+            None => ("all time", None),
+            Some("today") => ("today", Some(today)),
+            Some("month") => ("this month", today.with_day(1)),
+            Some(other) => {
+                return format!(
+                    "Unknown usage range: {}. Use /usage, /usage today or /usage month.",
+                    other
+                )
+            }
+        };
+        let since = since
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .map(|start| Utc.from_utc_datetime(&start));
+
+        let stats = usage_tracker.get_user_stats(user_id, since).await;
+        format_usage(label, &stats)
+    }
+
+    /// `/export [md|json|jsonl]`: the user's conversations, inline or, if
+    /// large, the path of the file the data service wrote them to.
+    /// Markdown covers the latest conversation.
+    async fn export_conversations(&self, user_id: &str, format: Option<&str>) -> String {
+        let format = match format {
+            None => ExportFormat::Markdown,
+            Some(name) => match ExportFormat::from_name(name) {
+                Some(format) => format,
+                None => {
+                    return format!(
+                        "Unknown export format: {}. Use /export md, /export json or /export jsonl.",
+                        name
+                    )
+                }
+            },
+        };
+
+        let request = ServiceMessage::ExportConversation {
+            user_id: user_id.to_string(),
+            conversation_id: None,
+            format,
+            spill_to_file: true,
+            request_id: Uuid::new_v4(),
+        };
+        let response = self
+            .event_bus
+            .send_and_await_response(
+                request,
+                Some(DATA_SERVICE_ID.to_string()),
+                Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+            )
+            .await;
+
+        match response {
+            Ok(ServiceMessage::ConversationExport {
+                path: Some(path), ..
+            }) => format!("Exported your conversations to {}", path),
+            Ok(ServiceMessage::ConversationExport { content, .. }) => content,
+            Ok(ServiceMessage::SystemError { user_message, .. }) => {
+                format!("Export failed: {}", user_message)
+            }
+            Ok(other) => {
+                warn!("Unexpected reply to export request: {:?}", other);
+                "Export failed.".to_string()
+            }
+            Err(e) => {
+                warn!("Export for user {} failed: {}", user_id, e);
+                "Export failed.".to_string()
+            }
+        }
+    }
+
+    /// Health of every service. The core service is answering this, so it
+    /// isn't asked; its queue would hold the check until we're done.
+    async fn check_health(&self) -> SystemHealth {
+        let mut service_ids = self.event_bus.services_handling("ServiceHealthCheck").await;
+        let includes_core = service_ids.iter().any(|id| id == CORE_SERVICE_ID);
+        service_ids.retain(|id| id != CORE_SERVICE_ID);
+
+        let mut results = self
+            .event_bus
+            .check_services_health(
+                &service_ids,
+                Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECONDS),
+            )
+            .await;
+        if includes_core {
+            results.push((CORE_SERVICE_ID.to_string(), Ok(ServiceHealth::Healthy)));
+        }
+        SystemHealth::from_results(results)
+    }
+
     /// Get system status information
     async fn get_system_status(&self) -> String {
         let services = self.event_bus.get_registered_services().await;
         let stats = self.event_bus.get_stats().await;
 
-        format!(
+        let mut status = format!(
             "System Status:\n• Registered services: {}\n• Messages routed: {}\n• Events broadcast: {}\n• Routing errors: {}",
             services.len(),
             stats.messages_routed,
             stats.events_broadcast,
             stats.routing_errors
-        )
+        );
+
+        if let Some(mean) = stats.routing_latency.mean() {
+            status.push_str(&format!("\n• Mean routing latency: {:?}", mean));
+        }
+
+        let health = self.check_health().await;
+        status.push_str(&format!("\n• Health: {}", describe_health(&health.overall)));
+        for (service_id, service_health) in &health.per_service {
+            status.push_str(&format!(
+                "\n  - {}: {}",
+                service_id,
+                describe_health(service_health)
+            ));
+        }
+
+        // Busiest message types first
+        let mut message_types: Vec<(&str, u64)> = stats
+            .messages_by_type
+            .iter()
+            .map(|(message_type, count)| (*message_type, *count))
+            .collect();
+        message_types.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (message_type, count) in message_types.into_iter().take(5) {
+            status.push_str(&format!("\n  - {}: {}", message_type, count));
+        }
+
+        status
     }
 }
 
+fn format_usage(label: &str, stats: &UsageStats) -> String {
+    if stats.total_requests == 0 {
+        return format!("No usage recorded ({}).", label);
+    }
+
+    let mut usage = format!(
+        "Usage ({}):\n• Requests: {}\n• Tokens: {}\n• Estimated cost: ${:.4}",
+        label, stats.total_requests, stats.total_tokens, stats.total_cost
+    );
+
+    // Heaviest models first
+    let mut models: Vec<_> = stats.by_model.iter().collect();
+    models.sort_by(|a, b| b.1.tokens.cmp(&a.1.tokens).then(a.0.cmp(b.0)));
+    for (model, model_stats) in models {
+        usage.push_str(&format!(
+            "\n  - {} ({}): {} requests, {} tokens, ${:.4}",
+            model, model_stats.provider, model_stats.requests, model_stats.tokens, model_stats.cost
+        ));
+    }
+
+    usage
+}
+
+/// Format conversation history as context for a prompt, oldest first.
+/// The newest messages are kept and older ones dropped once the prompt and
+/// context together would exceed `MAX_PROMPT_LENGTH` characters.
+pub fn build_context(history: &[Message], prompt: &str) -> Vec<String> {
+    let mut budget = MAX_PROMPT_LENGTH.saturating_sub(prompt.chars().count());
+    let mut context = Vec::new();
+
+    for message in history.iter().rev() {
+        let turn = format_turn(message);
+        let length = turn.chars().count();
+        if length > budget {
+            break;
+        }
+        budget -= length;
+        context.push(turn);
+    }
+
+    context.reverse();
+    context
+}
+
+/// A message as a `Role: content` line of a transcript
+pub fn format_turn(message: &Message) -> String {
+    let role = match message.role {
+        MessageRole::User => "User",
+        MessageRole::Assistant => "Assistant",
+        MessageRole::System => "System",
+    };
+    format!("{}: {}", role, message.content)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,6 +822,8 @@ mod tests {
             content: "Hello, AI!".to_string(),
             timestamp: Utc::now(),
             user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
         };
 
         let result = handler.handle_user_input(user_input).await;
@@ -153,24 +833,613 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_unavailable_llm_service_reported() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = UserInputHandler::new(event_bus.clone());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let user_input = ServiceMessage::UserInput {
+            content: "Hello, AI!".to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(user_input).await.unwrap();
+
+        // Thinking starts and ends, then the apology arrives
+        ui_rx.recv().await.unwrap();
+        assert!(matches!(
+            ui_rx.recv().await.unwrap(),
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::ThinkingDone,
+                ..
+            }
+        ));
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemResponse {
+                content,
+                message_type: ResponseType::Warning,
+                ..
+            } => assert_eq!(content, ASSISTANT_UNAVAILABLE_MESSAGE),
+            other => panic!("expected a warning, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_system_commands() {
         let event_bus = Arc::new(EventBus::new());
         let handler = UserInputHandler::new(event_bus.clone());
 
         // Register UI service to receive system responses
-        let _ui_service = event_bus
+        let (_ui_tx, mut ui_rx) = event_bus
             .register_service(UI_SERVICE_ID.to_string())
             .await
             .unwrap();
 
+        let request_id = Uuid::new_v4();
         let help_command = ServiceMessage::UserInput {
             content: "/help".to_string(),
             timestamp: Utc::now(),
             user_id: "test-user".to_string(),
+            request_id: Some(request_id),
+            stream: false,
         };
 
         let result = handler.handle_user_input(help_command).await;
         assert!(result.is_ok());
+
+        // The reply carries the input's request id
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemResponse {
+                request_id: reply_id,
+                ..
+            } => assert_eq!(reply_id, Some(request_id)),
+            other => panic!("expected a system response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clear_command_routes_to_data_service() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = UserInputHandler::new(event_bus.clone());
+
+        let (_data_tx, mut data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let _ui_service = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let clear_command = ServiceMessage::UserInput {
+            content: "/clear".to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+
+        let result = handler.handle_user_input(clear_command).await;
+        assert!(result.is_ok());
+
+        match data_rx.try_recv() {
+            Ok(ServiceMessage::ClearConversation { user_id }) => {
+                assert_eq!(user_id, "test-user");
+            }
+            other => panic!("Expected ClearConversation, got {:?}", other),
+        }
+    }
+
+    fn history_message(role: MessageRole, content: &str) -> Message {
+        Message {
+            id: Uuid::new_v4(),
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            role,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_build_context_drops_oldest_turns_over_budget() {
+        let history = vec![
+            history_message(MessageRole::User, &"a".repeat(MAX_PROMPT_LENGTH / 2)),
+            history_message(MessageRole::Assistant, "Sure."),
+            history_message(MessageRole::User, "And tomorrow?"),
+        ];
+
+        let context = build_context(&history, &"p".repeat(MAX_PROMPT_LENGTH / 2));
+
+        assert_eq!(context, vec!["Assistant: Sure.", "User: And tomorrow?"]);
+    }
+
+    #[tokio::test]
+    async fn test_history_added_to_llm_request_context() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = UserInputHandler::new(event_bus.clone());
+
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let _ui_service = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        spawn_data_service(
+            event_bus.clone(),
+            data_rx,
+            serde_json::json!({}),
+            vec![
+                history_message(MessageRole::User, "Book lunch with Sam"),
+                history_message(MessageRole::Assistant, "Booked for noon."),
+            ],
+        );
+
+        let user_input = ServiceMessage::UserInput {
+            content: "Move it to 1pm".to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(user_input).await.unwrap();
+
+        match llm_rx.recv().await.unwrap() {
+            ServiceMessage::LLMRequest { context, .. } => {
+                assert_eq!(
+                    context,
+                    vec!["User: Book lunch with Sam", "Assistant: Booked for noon."]
+                );
+            }
+            other => panic!("expected an LLM request, got {:?}", other),
+        }
+    }
+
+    fn handler_with_providers(event_bus: Arc<EventBus>) -> UserInputHandler {
+        let mut config = crate::config::create_default_config().llm;
+        let mut claude = config.providers["openai"].clone();
+        claude.model = "claude-3-haiku-20240307".to_string();
+        claude.models = vec![];
+        config.providers.insert("claude".to_string(), claude);
+        UserInputHandler::new(event_bus).with_llm_config(config)
+    }
+
+    async fn command_reply(
+        handler: &UserInputHandler,
+        ui_rx: &mut tokio::sync::mpsc::Receiver<ServiceMessage>,
+        command: &str,
+    ) -> String {
+        let input = ServiceMessage::UserInput {
+            content: command.to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(input).await.unwrap();
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemResponse { content, .. } => content,
+            other => panic!("expected a system response, got {:?}", other),
+        }
+    }
+
+    /// Play the data service for one user: keep their preferences and
+    /// answer profile and history requests
+    fn spawn_data_service(
+        event_bus: Arc<EventBus>,
+        mut data_rx: tokio::sync::mpsc::Receiver<ServiceMessage>,
+        mut preferences: serde_json::Value,
+        history: Vec<Message>,
+    ) {
+        tokio::spawn(async move {
+            while let Some(message) = data_rx.recv().await {
+                let reply = match message {
+                    ServiceMessage::LoadUserProfile {
+                        user_id,
+                        request_id,
+                    } => ServiceMessage::UserProfileResponse {
+                        profile: Some(ai_manager_shared::UserProfile {
+                            id: user_id,
+                            name: None,
+                            preferences: preferences.clone(),
+                            created_at: Utc::now(),
+                            updated_at: Utc::now(),
+                        }),
+                        request_id,
+                    },
+                    ServiceMessage::UpdateUserPreferences {
+                        preferences: updates,
+                        ..
+                    } => {
+                        let stored = preferences.as_object_mut().unwrap();
+                        for (key, value) in updates.as_object().unwrap() {
+                            if value.is_null() {
+                                stored.remove(key);
+                            } else {
+                                stored.insert(key.clone(), value.clone());
+                            }
+                        }
+                        continue;
+                    }
+                    ServiceMessage::LoadConversationHistory {
+                        user_id,
+                        request_id,
+                        ..
+                    } => ServiceMessage::ConversationHistoryResponse {
+                        user_id,
+                        messages: history.clone(),
+                        request_id,
+                    },
+                    ServiceMessage::TruncateConversation {
+                        user_id,
+                        after_message_id,
+                        request_id,
+                        ..
+                    } => {
+                        let kept = history
+                            .iter()
+                            .position(|message| message.id == after_message_id)
+                            .map_or(0, |position| position + 1);
+                        ServiceMessage::ConversationHistoryResponse {
+                            user_id,
+                            messages: history[..kept].to_vec(),
+                            request_id,
+                        }
+                    }
+                    // Pretend JSON exports are too large to inline
+                    ServiceMessage::ExportConversation {
+                        user_id,
+                        format,
+                        request_id,
+                        ..
+                    } => ServiceMessage::ConversationExport {
+                        content: match format {
+                            ExportFormat::Json => String::new(),
+                            _ => history
+                                .iter()
+                                .map(|message| message.content.as_str())
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        },
+                        path: (format == ExportFormat::Json)
+                            .then(|| format!("exports/{}.json", user_id)),
+                        user_id,
+                        request_id,
+                    },
+                    _ => continue,
+                };
+                event_bus.route_message(reply, None).await.unwrap();
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_provider_and_model_commands() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = handler_with_providers(event_bus.clone());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        spawn_data_service(event_bus.clone(), data_rx, serde_json::json!({}), vec![]);
+
+        let reply = command_reply(&handler, &mut ui_rx, "/model").await;
+        assert_eq!(reply, "Provider: openai (model: gpt-3.5-turbo)");
+
+        let reply = command_reply(&handler, &mut ui_rx, "/provider mystery").await;
+        assert!(reply.starts_with("Unknown provider 'mystery'"));
+        let reply = command_reply(&handler, &mut ui_rx, "/model gpt-4").await;
+        assert!(reply.ends_with("(model: gpt-4)"));
+        let reply = command_reply(&handler, &mut ui_rx, "/provider claude").await;
+        assert!(reply.ends_with("Provider: claude (model: claude-3-haiku-20240307)"));
+        let reply = command_reply(&handler, &mut ui_rx, "/model gpt-4").await;
+        assert!(reply.starts_with("Model 'gpt-4' isn't available for claude"));
+
+        // Later requests use the stored selection
+        let input = ServiceMessage::UserInput {
+            content: "Hello".to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(input).await.unwrap();
+        match llm_rx.recv().await.unwrap() {
+            ServiceMessage::LLMRequest {
+                provider, model, ..
+            } => {
+                assert_eq!(provider, "claude");
+                assert_eq!(model, None);
+            }
+            other => panic!("expected an LLM request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preferences_applied_to_llm_request() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = handler_with_providers(event_bus.clone());
+        let _ui_service = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let preferences = serde_json::json!({
+            "preferred_model": "gpt-4-turbo",
+            "temperature": 0.2,
+            "language": "Japanese",
+        });
+        spawn_data_service(event_bus.clone(), data_rx, preferences, vec![]);
+
+        let input = ServiceMessage::UserInput {
+            content: "Hello".to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(input).await.unwrap();
+
+        match llm_rx.recv().await.unwrap() {
+            ServiceMessage::LLMRequest {
+                provider,
+                model,
+                temperature,
+                context,
+                ..
+            } => {
+                // Unset fields fall back to the configured defaults
+                assert_eq!(provider, "openai");
+                assert_eq!(model.as_deref(), Some("gpt-4-turbo"));
+                assert_eq!(temperature, Some(0.2));
+                assert_eq!(context, vec!["System: Reply in Japanese."]);
+            }
+            other => panic!("expected an LLM request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usage_command() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let usage_tracker = Arc::new(UsageTracker::new());
+        let handler = UserInputHandler::new(event_bus).with_usage_tracker(usage_tracker.clone());
+
+        let reply = command_reply(&handler, &mut ui_rx, "/usage").await;
+        assert_eq!(reply, "No usage recorded (all time).");
+
+        let usage = ai_manager_shared::TokenUsage {
+            prompt_tokens: 100,
+            completion_tokens: 50,
+            total_tokens: 150,
+        };
+        usage_tracker
+            .record_usage("test-user", "openai", "gpt-4", &usage)
+            .await;
+        usage_tracker
+            .record_usage("someone-else", "openai", "gpt-4", &usage)
+            .await;
+
+        let reply = command_reply(&handler, &mut ui_rx, "/usage today").await;
+        assert!(reply.starts_with("Usage (today):"));
+        assert!(reply.contains("Requests: 1"));
+        assert!(reply.contains("Tokens: 150"));
+        assert!(reply.contains("gpt-4 (openai): 1 requests"));
+
+        let reply = command_reply(&handler, &mut ui_rx, "/usage month").await;
+        assert!(reply.contains("Requests: 1"));
+        let reply = command_reply(&handler, &mut ui_rx, "/usage yesterday").await;
+        assert!(reply.starts_with("Unknown usage range"));
+    }
+
+    #[tokio::test]
+    async fn test_export_command() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = UserInputHandler::new(event_bus.clone());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let history = vec![
+            history_message(MessageRole::User, "Hi"),
+            history_message(MessageRole::Assistant, "Hello!"),
+        ];
+        spawn_data_service(event_bus.clone(), data_rx, serde_json::json!({}), history);
+
+        let reply = command_reply(&handler, &mut ui_rx, "/export jsonl").await;
+        assert_eq!(reply, "Hi\nHello!");
+        let reply = command_reply(&handler, &mut ui_rx, "/export json").await;
+        assert_eq!(
+            reply,
+            "Exported your conversations to exports/test-user.json"
+        );
+        let reply = command_reply(&handler, &mut ui_rx, "/export pdf").await;
+        assert!(reply.starts_with("Unknown export format: pdf."));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_input_not_forwarded() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let handler = UserInputHandler::new(event_bus)
+            .with_rate_limiter(Arc::new(UserRateLimiter::new(1, 1)));
+
+        let input = |content: &str| ServiceMessage::UserInput {
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(input("First")).await.unwrap();
+        assert!(matches!(
+            llm_rx.recv().await.unwrap(),
+            ServiceMessage::LLMRequest { .. }
+        ));
+
+        handler.handle_user_input(input("Second")).await.unwrap();
+        // Commands aren't limited
+        handler.handle_user_input(input("/help")).await.unwrap();
+
+        let warning = loop {
+            match ui_rx.recv().await.unwrap() {
+                ServiceMessage::SystemResponse {
+                    content,
+                    message_type: ResponseType::Warning,
+                    ..
+                } => break content,
+                _ => continue,
+            }
+        };
+        assert_eq!(warning, RATE_LIMITED_MESSAGE);
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemResponse { content, .. } => {
+                assert!(content.starts_with("Available commands"))
+            }
+            other => panic!("expected the help text, got {:?}", other),
+        }
+        assert!(llm_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_input_not_forwarded() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let input_guard = InputGuard::from_config(&InputGuardConfig {
+            max_length: 10,
+            ..InputGuardConfig::default()
+        });
+        let handler = UserInputHandler::new(event_bus).with_input_guard(input_guard);
+
+        let input = ServiceMessage::UserInput {
+            content: "This message is too long".to_string(),
+            timestamp: Utc::now(),
+            user_id: "test-user".to_string(),
+            request_id: None,
+            stream: false,
+        };
+        handler.handle_user_input(input).await.unwrap();
+
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemResponse {
+                content,
+                message_type,
+                ..
+            } => {
+                assert!(matches!(message_type, ResponseType::Warning));
+                assert!(content.contains("the limit is 10"));
+            }
+            other => panic!("expected a warning, got {:?}", other),
+        }
+        assert!(llm_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_answers_earlier_message_again() {
+        let event_bus = Arc::new(EventBus::new());
+        let handler = UserInputHandler::new(event_bus.clone());
+        let (_ui_tx, mut ui_rx) = event_bus
+            .register_service(UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_data_tx, data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let history = vec![
+            history_message(MessageRole::User, "Book lunch with Sam"),
+            history_message(MessageRole::Assistant, "Booked for noon."),
+            history_message(MessageRole::User, "Move it to 1pm"),
+            history_message(MessageRole::Assistant, "Moved."),
+        ];
+        let (question, answer) = (history[2].id, history[3].id);
+        spawn_data_service(event_bus.clone(), data_rx, serde_json::json!({}), history);
+
+        let regenerate = ServiceMessage::RegenerateResponse {
+            user_id: "test-user".to_string(),
+            from_message_id: question,
+        };
+        handler.handle_regenerate(regenerate).await.unwrap();
+
+        match llm_rx.recv().await.unwrap() {
+            ServiceMessage::LLMRequest {
+                prompt, context, ..
+            } => {
+                assert_eq!(prompt, "Move it to 1pm");
+                assert_eq!(
+                    context,
+                    vec!["User: Book lunch with Sam", "Assistant: Booked for noon."]
+                );
+            }
+            other => panic!("expected an LLM request, got {:?}", other),
+        }
+        ui_rx.recv().await.unwrap();
+
+        // Only the user's own messages can be answered again
+        let regenerate = ServiceMessage::RegenerateResponse {
+            user_id: "test-user".to_string(),
+            from_message_id: answer,
+        };
+        handler.handle_regenerate(regenerate).await.unwrap();
+        match ui_rx.recv().await.unwrap() {
+            ServiceMessage::SystemResponse { content, .. } => {
+                assert_eq!(content, REGENERATE_FAILED_MESSAGE)
+            }
+            other => panic!("expected a warning, got {:?}", other),
+        }
+        assert!(llm_rx.try_recv().is_err());
     }
 }
@@ -1,6 +1,17 @@
-use ai_manager_shared::{Result, ServiceHealth};
+use crate::event_bus::{EventBus, EventBusStats};
+use crate::service_manager::{ServiceManager, ServiceStatus};
+use ai_manager_llm_service::metrics::{self as llm_metrics, RequestLabels, RequestLatency};
+use ai_manager_shared::{HealthThresholds, Result, ServiceHealth, ServiceId};
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use sysinfo::{Pid, System};
+use tokio::sync::RwLock;
+use tracing::info;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthReport {
@@ -54,11 +65,70 @@ impl Default for HealthMetrics {
     }
 }
 
+/// Source of process resource usage for health checks
+pub trait ProcessMetrics: Send + Sync {
+    /// Refresh the underlying readings before they are sampled
+    fn refresh(&mut self);
+
+    /// Resident memory of the process in megabytes
+    fn memory_usage_mb(&self) -> f64;
+
+    /// CPU usage of the process as a percentage since the last refresh
+    fn cpu_usage_percent(&self) -> f64;
+}
+
+/// Reads this process's RSS and CPU usage through `sysinfo`
+pub struct SysinfoMetrics {
+    system: System,
+    pid: Option<Pid>,
+}
+
+impl SysinfoMetrics {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            pid: sysinfo::get_current_pid().ok(),
+        }
+    }
+}
+
+impl Default for SysinfoMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessMetrics for SysinfoMetrics {
+    fn refresh(&mut self) {
+        if let Some(pid) = self.pid {
+            self.system.refresh_process(pid);
+        }
+    }
+
+    fn memory_usage_mb(&self) -> f64 {
+        self.pid
+            .and_then(|pid| self.system.process(pid))
+            .map(|process| process.memory() as f64 / (1024.0 * 1024.0))
+            .unwrap_or(0.0)
+    }
+
+    fn cpu_usage_percent(&self) -> f64 {
+        // sysinfo reports 0% until it has two samples to compare
+        self.pid
+            .and_then(|pid| self.system.process(pid))
+            .map(|process| process.cpu_usage() as f64)
+            .unwrap_or(0.0)
+    }
+}
+
 pub struct HealthChecker {
     start_time: Instant,
     last_check: Option<Instant>,
     error_count: u64,
     last_error: Option<String>,
+    message_queue_length: usize,
+    thresholds: HealthThresholds,
+    process_metrics: Box<dyn ProcessMetrics>,
 }
 
 impl HealthChecker {
@@ -68,9 +138,24 @@ impl HealthChecker {
             last_check: None,
             error_count: 0,
             last_error: None,
+            message_queue_length: 0,
+            thresholds: HealthThresholds::default(),
+            process_metrics: Box::new(SysinfoMetrics::new()),
         }
     }
 
+    /// Set the limits used to classify health
+    pub fn with_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Replace the source of memory and CPU readings
+    pub fn with_process_metrics(mut self, metrics: Box<dyn ProcessMetrics>) -> Self {
+        self.process_metrics = metrics;
+        self
+    }
+
     /// Perform a health check
     pub async fn check_health(&mut self, service_id: &str) -> Result<HealthReport> {
         let now = Instant::now();
@@ -97,20 +182,24 @@ impl HealthChecker {
         self.last_error = Some(error.to_string());
     }
 
+    /// Report the service's current message queue length
+    pub fn set_message_queue_length(&mut self, length: usize) {
+        self.message_queue_length = length;
+    }
+
     /// Get uptime duration
     pub fn uptime(&self) -> Duration {
         Instant::now().duration_since(self.start_time)
     }
 
     /// Collect system metrics
-    async fn collect_metrics(&self) -> Result<HealthMetrics> {
-        // In a real implementation, we would collect actual system metrics
-        // For now, we'll return mock data
+    async fn collect_metrics(&mut self) -> Result<HealthMetrics> {
+        self.process_metrics.refresh();
 
         Ok(HealthMetrics {
-            memory_usage_mb: self.get_memory_usage(),
-            cpu_usage_percent: self.get_cpu_usage(),
-            message_queue_length: 0, // Would be set by the service
+            memory_usage_mb: self.process_metrics.memory_usage_mb(),
+            cpu_usage_percent: self.process_metrics.cpu_usage_percent(),
+            message_queue_length: self.message_queue_length,
             error_count: self.error_count,
             last_error: self.last_error.clone(),
         })
@@ -118,29 +207,31 @@ impl HealthChecker {
 
     /// Determine health status based on metrics
     fn determine_health_status(&self, metrics: &HealthMetrics) -> ServiceHealth {
+        let thresholds = &self.thresholds;
+
         // High error rate
-        if metrics.error_count > 10 {
+        if metrics.error_count > thresholds.max_error_count {
             return ServiceHealth::Unhealthy {
                 error: format!("High error count: {}", metrics.error_count),
             };
         }
 
         // High memory usage
-        if metrics.memory_usage_mb > 500.0 {
+        if metrics.memory_usage_mb > thresholds.max_memory_mb {
             return ServiceHealth::Degraded {
                 reason: format!("High memory usage: {:.1} MB", metrics.memory_usage_mb),
             };
         }
 
         // High CPU usage
-        if metrics.cpu_usage_percent > 80.0 {
+        if metrics.cpu_usage_percent > thresholds.max_cpu_percent {
             return ServiceHealth::Degraded {
                 reason: format!("High CPU usage: {:.1}%", metrics.cpu_usage_percent),
             };
         }
 
         // Large message queue
-        if metrics.message_queue_length > 100 {
+        if metrics.message_queue_length > thresholds.max_queue_length {
             return ServiceHealth::Degraded {
                 reason: format!("Large message queue: {}", metrics.message_queue_length),
             };
@@ -148,33 +239,451 @@ impl HealthChecker {
 
         ServiceHealth::Healthy
     }
+}
 
-    /// Get current memory usage (mock implementation)
-    fn get_memory_usage(&self) -> f64 {
-        // In a real implementation, this would use system APIs
-        50.0 // Mock 50MB usage
+impl Default for HealthChecker {
+    fn default() -> Self {
+        Self::new()
     }
+}
+
+/// Health of every service that answers health checks, and of the system
+/// as a whole: healthy only if every service is, unhealthy if any is
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemHealth {
+    pub overall: ServiceHealth,
+    pub per_service: BTreeMap<ServiceId, ServiceHealth>,
+}
+
+impl SystemHealth {
+    /// Aggregate health check results. A service whose check failed, e.g.
+    /// because it didn't answer in time, counts as unhealthy.
+    pub fn from_results(
+        results: impl IntoIterator<Item = (ServiceId, Result<ServiceHealth>)>,
+    ) -> Self {
+        let per_service: BTreeMap<ServiceId, ServiceHealth> = results
+            .into_iter()
+            .map(|(service_id, result)| {
+                let status = result.unwrap_or_else(|e| ServiceHealth::Unhealthy {
+                    error: format!("No health response: {}", e),
+                });
+                (service_id, status)
+            })
+            .collect();
+
+        let unhealthy: Vec<&str> = per_service
+            .iter()
+            .filter(|(_, status)| matches!(status, ServiceHealth::Unhealthy { .. }))
+            .map(|(id, _)| id.as_str())
+            .collect();
+        let degraded: Vec<&str> = per_service
+            .iter()
+            .filter(|(_, status)| matches!(status, ServiceHealth::Degraded { .. }))
+            .map(|(id, _)| id.as_str())
+            .collect();
+
+        let overall = if !unhealthy.is_empty() {
+            ServiceHealth::Unhealthy {
+                error: format!("unhealthy: {}", unhealthy.join(", ")),
+            }
+        } else if !degraded.is_empty() {
+            ServiceHealth::Degraded {
+                reason: format!("degraded: {}", degraded.join(", ")),
+            }
+        } else {
+            ServiceHealth::Healthy
+        };
 
-    /// Get current CPU usage (mock implementation)
-    fn get_cpu_usage(&self) -> f64 {
-        // In a real implementation, this would use system APIs
-        5.0 // Mock 5% CPU usage
+        Self {
+            overall,
+            per_service,
+        }
     }
 }
 
-impl Default for HealthChecker {
-    fn default() -> Self {
-        Self::new()
+/// Ask every registered service that answers health checks for its health
+pub async fn check_system_health(event_bus: &EventBus, timeout: Duration) -> SystemHealth {
+    let service_ids = event_bus.services_handling("ServiceHealthCheck").await;
+    SystemHealth::from_results(event_bus.check_services_health(&service_ids, timeout).await)
+}
+
+/// One-line description of a service's health
+pub fn describe_health(status: &ServiceHealth) -> String {
+    match status {
+        ServiceHealth::Healthy => "healthy".to_string(),
+        ServiceHealth::Degraded { reason } => format!("degraded ({})", reason),
+        ServiceHealth::Unhealthy { error } => format!("unhealthy ({})", error),
+    }
+}
+
+#[derive(Clone)]
+struct HttpState {
+    service_manager: Arc<RwLock<ServiceManager>>,
+    event_bus: Arc<EventBus>,
+    started_at: Instant,
+}
+
+/// Build the router serving `/healthz`, `/readyz`, `/status` and `/metrics`
+pub fn http_router(
+    service_manager: Arc<RwLock<ServiceManager>>,
+    event_bus: Arc<EventBus>,
+) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/status", get(status))
+        .route("/metrics", get(metrics))
+        .with_state(HttpState {
+            service_manager,
+            event_bus,
+            started_at: Instant::now(),
+        })
+}
+
+/// Serve the health and metrics endpoints on `addr` until the task is dropped
+pub async fn serve_http(
+    addr: SocketAddr,
+    service_manager: Arc<RwLock<ServiceManager>>,
+    event_bus: Arc<EventBus>,
+) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Health endpoint listening on http://{}", addr);
+
+    axum::serve(listener, http_router(service_manager, event_bus)).await?;
+    Ok(())
+}
+
+/// Liveness: fails while any service is failed or has its circuit open
+async fn healthz(State(state): State<HttpState>) -> (StatusCode, String) {
+    let statuses = state
+        .service_manager
+        .read()
+        .await
+        .get_service_statuses()
+        .await;
+    let unhealthy = unhealthy_services(&statuses);
+
+    if unhealthy.is_empty() {
+        (StatusCode::OK, "ok".to_string())
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("unhealthy: {}", unhealthy.join(", ")),
+        )
+    }
+}
+
+/// Readiness: every service is running and reachable on the event bus
+async fn readyz(State(state): State<HttpState>) -> (StatusCode, String) {
+    let statuses = state
+        .service_manager
+        .read()
+        .await
+        .get_service_statuses()
+        .await;
+    let registered = state.event_bus.get_registered_services().await;
+
+    let mut not_ready: Vec<ServiceId> = statuses
+        .iter()
+        .filter(|(id, status)| {
+            !matches!(status, ServiceStatus::Running) || !registered.contains(id)
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    not_ready.sort();
+
+    if statuses.is_empty() {
+        (StatusCode::SERVICE_UNAVAILABLE, "no services".to_string())
+    } else if not_ready.is_empty() {
+        (StatusCode::OK, "ready".to_string())
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("not ready: {}", not_ready.join(", ")),
+        )
     }
 }
 
+/// Every service's own report of its health, as JSON. Fails while any
+/// service is unhealthy.
+async fn status(State(state): State<HttpState>) -> (StatusCode, Json<SystemHealth>) {
+    let health = check_system_health(
+        &state.event_bus,
+        Duration::from_secs(ai_manager_shared::HEALTH_CHECK_TIMEOUT_SECONDS),
+    )
+    .await;
+
+    let code = match health.overall {
+        ServiceHealth::Unhealthy { .. } => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (code, Json(health))
+}
+
+async fn metrics(State(state): State<HttpState>) -> (StatusCode, String) {
+    let (statuses, restart_counts) = {
+        let manager = state.service_manager.read().await;
+        (
+            manager.get_service_statuses().await,
+            manager.get_restart_counts().await,
+        )
+    };
+    let stats = state.event_bus.get_stats().await;
+
+    let mut text = render_metrics(
+        &stats,
+        &statuses,
+        &restart_counts,
+        state.started_at.elapsed(),
+    );
+    render_llm_metrics(&mut text, &llm_metrics::global().snapshot());
+    (StatusCode::OK, text)
+}
+
+/// Services that are failed or waiting out a restart cooldown, sorted by id
+fn unhealthy_services(statuses: &HashMap<ServiceId, ServiceStatus>) -> Vec<ServiceId> {
+    let mut unhealthy: Vec<ServiceId> = statuses
+        .iter()
+        .filter(|(_, status)| {
+            matches!(
+                status,
+                ServiceStatus::Failed { .. } | ServiceStatus::CircuitOpen { .. }
+            )
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    unhealthy.sort();
+    unhealthy
+}
+
+/// Render bus statistics and per-service state in Prometheus text format
+fn render_metrics(
+    stats: &EventBusStats,
+    statuses: &HashMap<ServiceId, ServiceStatus>,
+    restart_counts: &HashMap<ServiceId, u32>,
+    uptime: Duration,
+) -> String {
+    let mut out = String::new();
+
+    let counters = [
+        (
+            "ai_manager_messages_routed_total",
+            "Messages routed by the event bus",
+            stats.messages_routed,
+        ),
+        (
+            "ai_manager_events_broadcast_total",
+            "System events broadcast by the event bus",
+            stats.events_broadcast,
+        ),
+        (
+            "ai_manager_routing_errors_total",
+            "Messages the event bus failed to route",
+            stats.routing_errors,
+        ),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    let mut message_types: Vec<(&&str, &u64)> = stats.messages_by_type.iter().collect();
+    message_types.sort();
+
+    let _ = writeln!(out, "# HELP ai_manager_messages_routed_by_type_total Messages routed by the event bus, by message type");
+    let _ = writeln!(
+        out,
+        "# TYPE ai_manager_messages_routed_by_type_total counter"
+    );
+    for (message_type, count) in message_types {
+        let _ = writeln!(
+            out,
+            "ai_manager_messages_routed_by_type_total{{type=\"{}\"}} {}",
+            message_type, count
+        );
+    }
+
+    let latency = &stats.routing_latency;
+    let _ = writeln!(
+        out,
+        "# HELP ai_manager_routing_latency_seconds Time to hand a message to its target's queue"
+    );
+    let _ = writeln!(out, "# TYPE ai_manager_routing_latency_seconds histogram");
+    let mut cumulative = 0;
+    for (bound, count) in latency.bounds.iter().zip(&latency.counts) {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "ai_manager_routing_latency_seconds_bucket{{le=\"{}\"}} {}",
+            bound.as_secs_f64(),
+            cumulative
+        );
+    }
+    let _ = writeln!(
+        out,
+        "ai_manager_routing_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+        latency.count
+    );
+    let _ = writeln!(
+        out,
+        "ai_manager_routing_latency_seconds_sum {}",
+        latency.sum.as_secs_f64()
+    );
+    let _ = writeln!(
+        out,
+        "ai_manager_routing_latency_seconds_count {}",
+        latency.count
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP ai_manager_uptime_seconds Time since the core service started"
+    );
+    let _ = writeln!(out, "# TYPE ai_manager_uptime_seconds gauge");
+    let _ = writeln!(out, "ai_manager_uptime_seconds {}", uptime.as_secs());
+
+    let mut service_ids: Vec<&ServiceId> = statuses.keys().collect();
+    service_ids.sort();
+
+    let _ = writeln!(
+        out,
+        "# HELP ai_manager_service_up Whether a service is running (1) or not (0)"
+    );
+    let _ = writeln!(out, "# TYPE ai_manager_service_up gauge");
+    for id in &service_ids {
+        let up = matches!(statuses[*id], ServiceStatus::Running) as u8;
+        let _ = writeln!(
+            out,
+            "ai_manager_service_up{{service=\"{}\"}} {}",
+            escape_label(id),
+            up
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP ai_manager_service_restarts_total Restarts since the service was started"
+    );
+    let _ = writeln!(out, "# TYPE ai_manager_service_restarts_total counter");
+    for id in &service_ids {
+        let _ = writeln!(
+            out,
+            "ai_manager_service_restarts_total{{service=\"{}\"}} {}",
+            escape_label(id),
+            restart_counts.get(*id).copied().unwrap_or(0)
+        );
+    }
+
+    out
+}
+
+/// Append LLM request latency and outcome counts, by provider, model and
+/// status. Percentiles come from the histogram, e.g. p95 per provider is
+/// `histogram_quantile(0.95, sum by (provider, le) (rate(ai_manager_llm_request_duration_seconds_bucket[5m])))`.
+fn render_llm_metrics(out: &mut String, requests: &[(RequestLabels, RequestLatency)]) {
+    let labels = |labels: &RequestLabels| {
+        format!(
+            "provider=\"{}\",model=\"{}\",status=\"{}\"",
+            escape_label(&labels.provider),
+            escape_label(&labels.model),
+            escape_label(&labels.status)
+        )
+    };
+
+    let _ = writeln!(
+        out,
+        "# HELP ai_manager_llm_requests_total LLM provider requests, by outcome"
+    );
+    let _ = writeln!(out, "# TYPE ai_manager_llm_requests_total counter");
+    for (request, latency) in requests {
+        let _ = writeln!(
+            out,
+            "ai_manager_llm_requests_total{{{}}} {}",
+            labels(request),
+            latency.count
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP ai_manager_llm_request_duration_seconds Time for an LLM provider to answer a request"
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE ai_manager_llm_request_duration_seconds histogram"
+    );
+    for (request, latency) in requests {
+        let labels = labels(request);
+        let mut cumulative = 0;
+        for (bound, count) in latency.bounds.iter().zip(&latency.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "ai_manager_llm_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels,
+                bound.as_secs_f64(),
+                cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "ai_manager_llm_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, latency.count
+        );
+        let _ = writeln!(
+            out,
+            "ai_manager_llm_request_duration_seconds_sum{{{}}} {}",
+            labels,
+            latency.sum.as_secs_f64()
+        );
+        let _ = writeln!(
+            out,
+            "ai_manager_llm_request_duration_seconds_count{{{}}} {}",
+            labels, latency.count
+        );
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ai_manager_shared::ServiceMessage;
+
+    struct FakeMetrics {
+        memory_mb: f64,
+        cpu_percent: f64,
+    }
+
+    impl ProcessMetrics for FakeMetrics {
+        fn refresh(&mut self) {}
+
+        fn memory_usage_mb(&self) -> f64 {
+            self.memory_mb
+        }
+
+        fn cpu_usage_percent(&self) -> f64 {
+            self.cpu_percent
+        }
+    }
+
+    fn fake_checker(memory_mb: f64, cpu_percent: f64) -> HealthChecker {
+        HealthChecker::new().with_process_metrics(Box::new(FakeMetrics {
+            memory_mb,
+            cpu_percent,
+        }))
+    }
 
     #[tokio::test]
     async fn test_health_check() {
-        let mut checker = HealthChecker::new();
+        let mut checker = fake_checker(50.0, 5.0);
 
         // Add a small delay to ensure uptime > 0
         tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
@@ -188,7 +697,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_error_recording() {
-        let mut checker = HealthChecker::new();
+        let mut checker = fake_checker(50.0, 5.0);
 
         // Record some errors
         for i in 0..15 {
@@ -201,4 +710,219 @@ mod tests {
         assert!(matches!(report.status, ServiceHealth::Unhealthy { .. }));
         assert_eq!(report.metrics.error_count, 15);
     }
+
+    #[tokio::test]
+    async fn test_high_memory_is_degraded() {
+        let mut checker = fake_checker(750.0, 5.0);
+
+        let report = checker.check_health("test-service").await.unwrap();
+
+        match report.status {
+            ServiceHealth::Degraded { reason } => {
+                assert_eq!(reason, "High memory usage: 750.0 MB");
+            }
+            other => panic!("expected Degraded, got {:?}", other),
+        }
+        assert_eq!(report.metrics.memory_usage_mb, 750.0);
+    }
+
+    #[tokio::test]
+    async fn test_custom_thresholds_and_queue_length() {
+        let mut checker = fake_checker(750.0, 5.0).with_thresholds(HealthThresholds {
+            max_memory_mb: 1024.0,
+            max_queue_length: 10,
+            ..HealthThresholds::default()
+        });
+
+        let report = checker.check_health("test-service").await.unwrap();
+        assert!(matches!(report.status, ServiceHealth::Healthy));
+
+        checker.set_message_queue_length(25);
+        let report = checker.check_health("test-service").await.unwrap();
+
+        assert_eq!(report.metrics.message_queue_length, 25);
+        match report.status {
+            ServiceHealth::Degraded { reason } => {
+                assert_eq!(reason, "Large message queue: 25");
+            }
+            other => panic!("expected Degraded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unhealthy_services() {
+        let mut statuses = HashMap::new();
+        statuses.insert("core".to_string(), ServiceStatus::Running);
+        statuses.insert(
+            "llm".to_string(),
+            ServiceStatus::Failed {
+                error: "crashed".to_string(),
+            },
+        );
+        statuses.insert(
+            "data".to_string(),
+            ServiceStatus::CircuitOpen {
+                until: chrono::Utc::now(),
+            },
+        );
+
+        assert_eq!(unhealthy_services(&statuses), vec!["data", "llm"]);
+    }
+
+    #[test]
+    fn test_system_health_aggregation() {
+        let healthy = SystemHealth::from_results([
+            ("core".to_string(), Ok(ServiceHealth::Healthy)),
+            ("llm".to_string(), Ok(ServiceHealth::Healthy)),
+        ]);
+        assert!(matches!(healthy.overall, ServiceHealth::Healthy));
+
+        let degraded = SystemHealth::from_results([
+            ("core".to_string(), Ok(ServiceHealth::Healthy)),
+            (
+                "llm".to_string(),
+                Ok(ServiceHealth::Degraded {
+                    reason: "slow".to_string(),
+                }),
+            ),
+        ]);
+        assert!(matches!(degraded.overall, ServiceHealth::Degraded { .. }));
+
+        let unhealthy = SystemHealth::from_results([
+            (
+                "llm".to_string(),
+                Ok(ServiceHealth::Degraded {
+                    reason: "slow".to_string(),
+                }),
+            ),
+            (
+                "data".to_string(),
+                Err(ai_manager_shared::SystemError::Timeout),
+            ),
+        ]);
+        assert!(matches!(
+            &unhealthy.overall,
+            ServiceHealth::Unhealthy { error } if error == "unhealthy: data"
+        ));
+        assert!(matches!(
+            unhealthy.per_service["data"],
+            ServiceHealth::Unhealthy { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_system_health_skips_silent_services() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_tx, mut llm_rx) = event_bus
+            .register_service(ai_manager_shared::LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_tx, mut data_rx) = event_bus
+            .register_service(ai_manager_shared::DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        // The UI doesn't answer health checks, so isn't asked
+        let (_tx, _ui_rx) = event_bus
+            .register_service(ai_manager_shared::UI_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let responder_bus = event_bus.clone();
+        tokio::spawn(async move {
+            if let Some(ServiceMessage::ServiceHealthCheck { .. }) = llm_rx.recv().await {
+                let response = ServiceMessage::ServiceHealthResponse {
+                    service_id: ai_manager_shared::LLM_SERVICE_ID.to_string(),
+                    status: ServiceHealth::Healthy,
+                };
+                responder_bus.route_message(response, None).await.unwrap();
+            }
+        });
+        // The data service never answers
+        tokio::spawn(async move { while data_rx.recv().await.is_some() {} });
+
+        let health = check_system_health(&event_bus, Duration::from_millis(200)).await;
+        assert_eq!(
+            health.per_service.keys().collect::<Vec<_>>(),
+            vec![
+                ai_manager_shared::DATA_SERVICE_ID,
+                ai_manager_shared::LLM_SERVICE_ID
+            ]
+        );
+        assert!(matches!(health.overall, ServiceHealth::Unhealthy { .. }));
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let mut stats = EventBusStats {
+            messages_routed: 42,
+            events_broadcast: 3,
+            routing_errors: 1,
+            ..EventBusStats::default()
+        };
+        stats.messages_by_type.insert("UserInput", 40);
+        stats.messages_by_type.insert("LLMRequest", 2);
+        stats.routing_latency.observe(Duration::from_micros(300));
+        stats.routing_latency.observe(Duration::from_secs(2));
+        let mut statuses = HashMap::new();
+        statuses.insert("core".to_string(), ServiceStatus::Running);
+        statuses.insert("llm".to_string(), ServiceStatus::Restarting);
+        let mut restart_counts = HashMap::new();
+        restart_counts.insert("llm".to_string(), 2);
+
+        let text = render_metrics(&stats, &statuses, &restart_counts, Duration::from_secs(90));
+
+        assert!(text.contains("# TYPE ai_manager_messages_routed_total counter"));
+        assert!(text.contains("ai_manager_messages_routed_total 42\n"));
+        assert!(text.contains("ai_manager_routing_errors_total 1\n"));
+        assert!(text.contains("ai_manager_uptime_seconds 90\n"));
+        assert!(text.contains("ai_manager_messages_routed_by_type_total{type=\"UserInput\"} 40\n"));
+        assert!(text.contains("ai_manager_routing_latency_seconds_bucket{le=\"0.0001\"} 0\n"));
+        assert!(text.contains("ai_manager_routing_latency_seconds_bucket{le=\"0.0005\"} 1\n"));
+        assert!(text.contains("ai_manager_routing_latency_seconds_bucket{le=\"1\"} 1\n"));
+        assert!(text.contains("ai_manager_routing_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("ai_manager_routing_latency_seconds_count 2\n"));
+        assert!(text.contains("ai_manager_service_up{service=\"core\"} 1\n"));
+        assert!(text.contains("ai_manager_service_up{service=\"llm\"} 0\n"));
+        assert!(text.contains("ai_manager_service_restarts_total{service=\"llm\"} 2\n"));
+        assert!(text.contains("ai_manager_service_restarts_total{service=\"core\"} 0\n"));
+    }
+
+    #[test]
+    fn test_render_llm_metrics() {
+        let metrics = llm_metrics::LlmMetrics::new();
+        metrics.record("openai", "gpt-4", Duration::from_millis(400), &Ok(()));
+        metrics.record::<()>(
+            "openai",
+            "gpt-4",
+            Duration::from_secs(3),
+            &Err(ai_manager_shared::SystemError::Timeout),
+        );
+
+        let mut text = String::new();
+        render_llm_metrics(&mut text, &metrics.snapshot());
+
+        assert!(text.contains(
+            "ai_manager_llm_requests_total{provider=\"openai\",model=\"gpt-4\",status=\"ok\"} 1\n"
+        ));
+        assert!(text.contains(
+            "ai_manager_llm_requests_total{provider=\"openai\",model=\"gpt-4\",status=\"provider_down\"} 1\n"
+        ));
+        assert!(text.contains(
+            "ai_manager_llm_request_duration_seconds_bucket{provider=\"openai\",model=\"gpt-4\",status=\"ok\",le=\"0.5\"} 1\n"
+        ));
+        assert!(text.contains(
+            "ai_manager_llm_request_duration_seconds_bucket{provider=\"openai\",model=\"gpt-4\",status=\"provider_down\",le=\"2.5\"} 0\n"
+        ));
+        assert!(text.contains(
+            "ai_manager_llm_request_duration_seconds_count{provider=\"openai\",model=\"gpt-4\",status=\"provider_down\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn test_sysinfo_reads_current_process() {
+        let mut metrics = SysinfoMetrics::new();
+        metrics.refresh();
+
+        assert!(metrics.memory_usage_mb() > 0.0);
+    }
 }
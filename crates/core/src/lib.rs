@@ -1,10 +1,28 @@
+pub mod api;
 pub mod config;
+pub mod core_service;
 pub mod event_bus;
 pub mod handlers;
 pub mod health;
+pub mod input_guard;
+pub mod rate_limiter;
+pub mod remote;
+pub mod replies;
+pub mod scheduler;
 pub mod service_manager;
+pub mod service_registry;
+pub mod summarizer;
 
+pub use api::*;
 pub use config::*;
+pub use core_service::*;
 pub use event_bus::*;
 pub use health::*;
+pub use input_guard::*;
+pub use rate_limiter::*;
+pub use remote::*;
+pub use replies::*;
+pub use scheduler::*;
 pub use service_manager::*;
+pub use service_registry::*;
+pub use summarizer::*;
@@ -1,21 +1,34 @@
 use ai_manager_core::{
+    api::{serve_api, ApiTokens},
     config::ConfigManager,
+    core_service::{run_llm_service, CoreService},
     event_bus::EventBus,
-    handlers::{LLMResponseHandler, SystemEventHandler, UserInputHandler},
+    health::serve_http,
+    remote::connect_remote_service,
+    scheduler::{Schedule, Scheduler},
     service_manager::{RestartPolicy, ServiceManager},
 };
-use ai_manager_shared::{Result, ServiceMessage, CORE_SERVICE_ID};
+use ai_manager_llm_service::UsageTracker;
+use ai_manager_shared::{
+    Result, ServiceMessage, SystemError, CONVERSATION_CLEANUP_INTERVAL_HOURS, CORE_SERVICE_ID,
+    DATA_SERVICE_ID, DEFAULT_CONFIG_PATH, LLM_SERVICE_ID, MAX_USAGE_RECORDS,
+};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::sync::RwLock;
 use tokio::time::Duration;
-use tracing::{debug, error, info, warn};
+use tracing::{error, info};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    init_logging();
+    init_logging(log_format_from_args(std::env::args().skip(1))?);
 
     info!("🚀 Starting AI Manager Core Service");
 
+    // Write a config template on first run
+    ConfigManager::init_default(DEFAULT_CONFIG_PATH)?;
+
     // Load configuration
     let config_manager = ConfigManager::new().map_err(|e| {
         error!("Failed to load configuration: {}", e);
@@ -28,10 +41,13 @@ async fn main() -> Result<()> {
         return Err(e);
     }
 
+    let app_config = config_manager.get_app_config()?;
+    ai_manager_shared::http::configure(&app_config.http)?;
     info!("✓ Configuration loaded and validated");
 
     // Create event bus
-    let event_bus = Arc::new(EventBus::new());
+    let event_bus =
+        Arc::new(EventBus::new().with_service_token(app_config.server.service_token.clone()));
     info!("✓ Event bus initialized");
 
     // Create service manager with restart policy
@@ -40,6 +56,8 @@ async fn main() -> Result<()> {
         restart_delay: Duration::from_secs(2),
         backoff_multiplier: 1.5,
         max_restart_delay: Duration::from_secs(60),
+        circuit_cooldown: Duration::from_secs(ai_manager_shared::SERVICE_RESTART_COOLDOWN_SECONDS),
+        stability_window: Duration::from_secs(60),
     };
 
     let mut service_manager =
@@ -47,12 +65,23 @@ async fn main() -> Result<()> {
 
     info!("✓ Service manager initialized");
 
+    // Shared by the LLM service, which records usage, and the core service
+    // and REST API, which report it
+    let usage_tracker = Arc::new(UsageTracker::new());
+
     // Start core service
     let event_bus_clone = event_bus.clone();
+    let core_usage = usage_tracker.clone();
+    let core_shutdown = service_manager.shutdown_token(CORE_SERVICE_ID);
     let core_service_task = move || {
-        let event_bus = event_bus_clone;
+        let event_bus = event_bus_clone.clone();
+        let config_manager = config_manager.clone();
+        let usage_tracker = core_usage.clone();
+        let shutdown = core_shutdown.clone();
         async move {
-            let mut core_service = CoreService::new(event_bus, config_manager);
+            let mut core_service = CoreService::new(event_bus, config_manager)
+                .with_usage_tracker(usage_tracker)
+                .with_shutdown(shutdown);
             core_service.start().await
         }
     };
@@ -68,10 +97,138 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Start the LLM service
+    let llm_bus = event_bus.clone();
+    let llm_config = app_config.llm.clone();
+    let llm_usage = usage_tracker.clone();
+    let llm_shutdown = service_manager.shutdown_token(LLM_SERVICE_ID);
+    let llm_service_task = move || {
+        let event_bus = llm_bus.clone();
+        let llm_config = llm_config.clone();
+        let usage_tracker = llm_usage.clone();
+        let shutdown = llm_shutdown.clone();
+        async move { run_llm_service(event_bus, &llm_config, usage_tracker, shutdown).await }
+    };
+
+    match service_manager
+        .start_service(LLM_SERVICE_ID.to_string(), llm_service_task)
+        .await
+    {
+        Ok(_) => info!("✓ LLM service started successfully"),
+        Err(e) => error!("Failed to start LLM service, chat is unavailable: {}", e),
+    }
+
+    // Connect to a data service running as its own process
+    if let Some(addr) = app_config.server.data_service_addr.clone() {
+        let data_bus = event_bus.clone();
+        let data_service_task = move || {
+            let event_bus = data_bus.clone();
+            let addr = addr.clone();
+            async move { connect_remote_service(event_bus, DATA_SERVICE_ID.to_string(), &addr).await }
+        };
+
+        match service_manager
+            .start_service(DATA_SERVICE_ID.to_string(), data_service_task)
+            .await
+        {
+            Ok(_) => info!("✓ Data service connection started"),
+            Err(e) => error!("Failed to connect to data service: {}", e),
+        }
+    }
+
     // Start health monitoring
     service_manager.start_health_monitoring().await;
     info!("✓ Health monitoring started");
 
+    let service_manager = Arc::new(RwLock::new(service_manager));
+
+    // Recurring work
+    let mut scheduler = Scheduler::new(event_bus.clone());
+    let cleanup_usage = usage_tracker.clone();
+    scheduler.schedule(
+        "usage-cleanup",
+        Schedule::every(Duration::from_secs(
+            CONVERSATION_CLEANUP_INTERVAL_HOURS * 60 * 60,
+        )),
+        move || {
+            let usage_tracker = cleanup_usage.clone();
+            async move {
+                usage_tracker.cleanup_old_records(MAX_USAGE_RECORDS).await;
+                Ok(())
+            }
+        },
+    )?;
+    let retention = app_config.retention.clone();
+    if retention.enabled && app_config.server.data_service_addr.is_some() {
+        let cleanup_bus = event_bus.clone();
+        scheduler.schedule(
+            "conversation-cleanup",
+            Schedule::every(Duration::from_secs(
+                retention.cleanup_interval_hours * 60 * 60,
+            )),
+            move || {
+                let event_bus = cleanup_bus.clone();
+                let cleanup = ServiceMessage::CleanupConversations {
+                    retain_days: retention.retain_days,
+                    max_messages: retention.max_messages,
+                };
+                async move {
+                    event_bus
+                        .route_message(cleanup, Some(DATA_SERVICE_ID.to_string()))
+                        .await
+                }
+            },
+        )?;
+    }
+    info!("✓ Scheduler started");
+
+    // Serve health probes and metrics
+    let http_handle = if app_config.server.enabled {
+        let addr: SocketAddr = format!(
+            "{}:{}",
+            app_config.server.host, app_config.server.health_port
+        )
+        .parse()
+        .map_err(|e| {
+            SystemError::Configuration(format!("Invalid health endpoint address: {}", e))
+        })?;
+
+        let service_manager = service_manager.clone();
+        let event_bus = event_bus.clone();
+        Some(tokio::spawn(async move {
+            if let Err(e) = serve_http(addr, service_manager, event_bus).await {
+                error!("Health endpoint stopped: {}", e);
+            }
+        }))
+    } else {
+        None
+    };
+
+    // Serve the REST API when a token is configured
+    let api_tokens = ApiTokens::from_config(&app_config.server);
+    let api_handle = match (!api_tokens.is_empty()).then_some(api_tokens) {
+        Some(tokens) => {
+            let addr: SocketAddr =
+                format!("{}:{}", app_config.server.host, app_config.server.api_port)
+                    .parse()
+                    .map_err(|e| {
+                        SystemError::Configuration(format!("Invalid REST API address: {}", e))
+                    })?;
+
+            let event_bus = event_bus.clone();
+            let usage_tracker = usage_tracker.clone();
+            Some(tokio::spawn(async move {
+                if let Err(e) = serve_api(addr, event_bus, usage_tracker, tokens).await {
+                    error!("REST API stopped: {}", e);
+                }
+            }))
+        }
+        None => {
+            info!("REST API disabled: no server.api_token or server.api_users configured");
+            None
+        }
+    };
+
     // Handle shutdown gracefully
     match tokio::signal::ctrl_c().await {
         Ok(()) => {
@@ -84,120 +241,93 @@ async fn main() -> Result<()> {
 
     // Shutdown all services
     info!("🔄 Shutting down services...");
-    service_manager.shutdown_all().await?;
+    for handle in [http_handle, api_handle].into_iter().flatten() {
+        handle.abort();
+    }
+    scheduler.shutdown().await;
+    service_manager.write().await.shutdown_all().await?;
     info!("✓ All services shut down successfully");
 
     info!("👋 AI Manager Core Service stopped");
     Ok(())
 }
 
-fn init_logging() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "ai_manager_core=debug,ai_manager_shared=info".into()),
-        )
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
-        .init();
-}
-
-#[allow(dead_code)]
-struct CoreService {
-    event_bus: Arc<EventBus>,
-    config_manager: ConfigManager,
-    user_input_handler: UserInputHandler,
-    llm_response_handler: LLMResponseHandler,
-    system_event_handler: SystemEventHandler,
+/// How log lines are written, chosen with `--log-format text|json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    /// One JSON object per line, with the fields of the enclosing spans,
+    /// for log ingestion
+    Json,
 }
 
-impl CoreService {
-    fn new(event_bus: Arc<EventBus>, config_manager: ConfigManager) -> Self {
-        let user_input_handler = UserInputHandler::new(event_bus.clone());
-        let llm_response_handler = LLMResponseHandler::new(event_bus.clone());
-        let system_event_handler = SystemEventHandler::new(event_bus.clone());
-
-        Self {
-            event_bus,
-            config_manager,
-            user_input_handler,
-            llm_response_handler,
-            system_event_handler,
-        }
-    }
-
-    async fn start(&mut self) -> Result<()> {
-        info!("Starting core service components");
-
-        // Register core service with event bus
-        let (_tx, mut rx) = self
-            .event_bus
-            .register_service(CORE_SERVICE_ID.to_string())
-            .await?;
-
-        // Start system event handler
-        self.system_event_handler.start().await?;
-        info!("✓ System event handler started");
-
-        // Create references to handlers
-        let event_bus = self.event_bus.clone();
-        let user_input_handler = UserInputHandler::new(event_bus.clone());
-        let llm_response_handler = LLMResponseHandler::new(event_bus.clone());
-
-        // Start message processing loop
-        info!("📨 Core service message loop started");
-
-        while let Some(message) = rx.recv().await {
-            debug!("Core service received message: {:?}", message);
-
-            let result = match &message {
-                ServiceMessage::UserInput { .. } => {
-                    user_input_handler.handle_user_input(message.clone()).await
-                }
-                ServiceMessage::LLMResponse { .. } => {
-                    llm_response_handler
-                        .handle_llm_response(message.clone())
-                        .await
-                }
-                ServiceMessage::ServiceHealthCheck { service_id } => {
-                    Self::handle_health_check(service_id, &event_bus).await
-                }
-                ServiceMessage::ShutdownService { service_id } => {
-                    info!("Shutdown request for service: {}", service_id);
-                    break; // Exit the loop to shutdown
-                }
-                _ => {
-                    warn!("Unhandled message type in core service: {:?}", message);
-                    Ok(())
-                }
-            };
-
-            if let Err(e) = result {
-                error!("Error processing message in core service: {}", e);
+fn log_format_from_args(mut args: impl Iterator<Item = String>) -> Result<LogFormat> {
+    let mut format = LogFormat::Text;
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--log-format") {
+            Some("") => args.next().unwrap_or_default(),
+            Some(rest) => match rest.strip_prefix('=') {
+                Some(value) => value.to_string(),
+                None => continue,
+            },
+            None => continue,
+        };
+        format = match value.as_str() {
+            "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            other => {
+                return Err(SystemError::Configuration(format!(
+                    "Unknown log format '{}', expected text or json",
+                    other
+                )))
             }
-        }
-
-        info!("📪 Core service message loop ended");
-        Ok(())
+        };
     }
+    Ok(format)
+}
 
-    async fn handle_health_check(service_id: &str, event_bus: &EventBus) -> Result<()> {
-        debug!("Processing health check for service: {}", service_id);
+fn init_logging(format: LogFormat) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "ai_manager_core=debug,ai_manager_shared=info".into());
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_thread_ids(true)
+        .with_file(true)
+        .with_line_number(true);
+
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+    }
+}
 
-        // TODO: Implement actual health check logic
-        let health_response = ServiceMessage::ServiceHealthResponse {
-            service_id: service_id.to_string(),
-            status: ai_manager_shared::ServiceHealth::Healthy,
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        event_bus.route_message(health_response, None).await
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
     }
-}
 
-impl Drop for CoreService {
-    fn drop(&mut self) {
-        info!("Core service dropping");
+    #[test]
+    fn test_log_format_from_args() {
+        assert_eq!(log_format_from_args(args(&[])).unwrap(), LogFormat::Text);
+        assert_eq!(
+            log_format_from_args(args(&["--log-format", "json"])).unwrap(),
+            LogFormat::Json
+        );
+        assert_eq!(
+            log_format_from_args(args(&["--log-format=text"])).unwrap(),
+            LogFormat::Text
+        );
+        assert!(log_format_from_args(args(&["--log-format", "xml"])).is_err());
     }
 }
@@ -0,0 +1,110 @@
+use ai_manager_shared::RateLimitConfig;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+/// Users tracked before buckets that have refilled are dropped
+const PRUNE_THRESHOLD_USERS: usize = 1024;
+
+/// Shown instead of an answer when a user is over their rate limit
+pub const RATE_LIMITED_MESSAGE: &str =
+    "You're sending messages too fast. Please wait a moment and try again.";
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Per-user token buckets: each user may send `burst` messages at once,
+/// then `requests_per_minute` on average. Shared between tasks.
+pub struct UserRateLimiter {
+    capacity: f64,
+    per_second: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl UserRateLimiter {
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        Self {
+            capacity: f64::from(burst.max(1)),
+            per_second: f64::from(requests_per_minute) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The limiter `config` describes, or `None` if rate limiting is off
+    pub fn from_config(config: &RateLimitConfig) -> Option<Self> {
+        config
+            .enabled
+            .then(|| Self::new(config.requests_per_minute, config.burst))
+    }
+
+    /// Take one request from the user's bucket; false if it is empty
+    pub fn try_acquire(&self, user_id: &str) -> bool {
+        self.try_acquire_at(user_id, Instant::now())
+    }
+
+    fn try_acquire_at(&self, user_id: &str, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() >= PRUNE_THRESHOLD_USERS {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(user_id.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.refilled_at = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Tokens in `bucket` at `now`
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = UserRateLimiter::new(60, 2);
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at("alice", start));
+        assert!(limiter.try_acquire_at("alice", start));
+        assert!(!limiter.try_acquire_at("alice", start));
+
+        // Other users have their own bucket
+        assert!(limiter.try_acquire_at("bob", start));
+
+        // One request a second comes back
+        assert!(limiter.try_acquire_at("alice", start + Duration::from_secs(1)));
+        assert!(!limiter.try_acquire_at("alice", start + Duration::from_secs(1)));
+
+        // Never more than the burst
+        let later = start + Duration::from_secs(600);
+        assert!(limiter.try_acquire_at("alice", later));
+        assert!(limiter.try_acquire_at("alice", later));
+        assert!(!limiter.try_acquire_at("alice", later));
+    }
+
+    #[test]
+    fn test_disabled_config_has_no_limiter() {
+        let config = RateLimitConfig {
+            enabled: false,
+            ..RateLimitConfig::default()
+        };
+        assert!(UserRateLimiter::from_config(&config).is_none());
+        assert!(UserRateLimiter::from_config(&RateLimitConfig::default()).is_some());
+    }
+}
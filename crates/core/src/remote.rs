@@ -0,0 +1,99 @@
+use crate::event_bus::EventBus;
+use ai_manager_shared::{transport, Result, ServiceId};
+use std::sync::Arc;
+use tracing::info;
+
+/// Stand in on `event_bus` for a service running in another process at
+/// `addr`, either a TCP `host:port` or `unix:<path>`. Messages are exchanged
+/// as length-prefixed JSON frames. Returns an error once the connection
+/// drops, so a service manager can reconnect.
+pub async fn connect_remote_service(
+    event_bus: Arc<EventBus>,
+    service_id: ServiceId,
+    addr: &str,
+) -> Result<()> {
+    let transport = transport::connect(addr).await?;
+    info!("Connected to remote service '{}' at {}", service_id, addr);
+
+    event_bus.register_transport(service_id, transport).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_manager_shared::{
+        MessageTransport, ServiceMessage, StreamTransport, CORE_SERVICE_ID, DATA_SERVICE_ID,
+    };
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_messages_bridged_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let event_bus = Arc::new(EventBus::new());
+        let (_core_tx, mut core_rx) = event_bus
+            .register_service(CORE_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let bridge_bus = event_bus.clone();
+        let bridge = tokio::spawn(async move {
+            connect_remote_service(bridge_bus, DATA_SERVICE_ID.to_string(), &addr).await
+        });
+
+        // Stand-in data service that answers a history request
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut remote = StreamTransport::new(stream);
+
+        // Wait for the bridge to register before routing to it
+        while !event_bus
+            .get_registered_services()
+            .await
+            .contains(&DATA_SERVICE_ID.to_string())
+        {
+            tokio::task::yield_now().await;
+        }
+
+        let request_id = Uuid::new_v4();
+        event_bus
+            .route_message(
+                ServiceMessage::LoadConversationHistory {
+                    user_id: "alice".to_string(),
+                    limit: 5,
+                    request_id,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            remote.recv().await.unwrap(),
+            Some(ServiceMessage::LoadConversationHistory { .. })
+        ));
+
+        remote
+            .send(ServiceMessage::ConversationHistoryResponse {
+                user_id: "alice".to_string(),
+                messages: vec![],
+                request_id,
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            core_rx.recv().await.unwrap(),
+            ServiceMessage::ConversationHistoryResponse { .. }
+        ));
+
+        // Disconnecting ends the bridge and unregisters the service
+        drop(remote);
+        assert!(bridge.await.unwrap().is_err());
+        assert!(!event_bus
+            .get_registered_services()
+            .await
+            .contains(&DATA_SERVICE_ID.to_string()));
+    }
+}
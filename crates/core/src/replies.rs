@@ -0,0 +1,121 @@
+use ai_manager_shared::{Result, ServiceMessage, SystemError, LLM_REQUEST_TIMEOUT};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Replies a single request may have queued before it reads them
+const REPLY_QUEUE_CAPACITY: usize = 64;
+
+/// Who each request was made for, and where its replies go
+type ReplySenders = HashMap<Uuid, (String, mpsc::Sender<ServiceMessage>)>;
+
+/// Requests waiting for the core's replies, by the request id their input
+/// was sent with
+#[derive(Clone, Default)]
+pub struct PendingReplies {
+    waiters: Arc<Mutex<ReplySenders>>,
+}
+
+impl PendingReplies {
+    /// Start collecting the replies to `request_id`, made for `user_id`
+    pub fn expect(&self, request_id: Uuid, user_id: &str) -> Replies {
+        let (tx, rx) = mpsc::channel(REPLY_QUEUE_CAPACITY);
+        self.lock().insert(request_id, (user_id.to_string(), tx));
+        Replies {
+            request_id,
+            rx,
+            pending: self.clone(),
+        }
+    }
+
+    /// Hand each reply the UI service receives to the request it belongs
+    /// to. Replies no request is waiting for, e.g. because it timed out,
+    /// and replies naming a user other than the request's are dropped.
+    pub async fn dispatch(self, mut ui_receiver: mpsc::Receiver<ServiceMessage>) {
+        while let Some(reply) = ui_receiver.recv().await {
+            let waiter = reply
+                .trace_id()
+                .and_then(|request_id| self.lock().get(&request_id).cloned());
+            match waiter {
+                Some((user_id, _)) if reply.user_id().is_some_and(|owner| owner != user_id) => {
+                    warn!(
+                        "Dropping {} for another user than the request's",
+                        reply.message_type()
+                    );
+                }
+                Some((_, tx)) => {
+                    let _ = tx.send(reply).await;
+                }
+                None => debug!(
+                    "Dropping {} no request is waiting for",
+                    reply.message_type()
+                ),
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ReplySenders> {
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The replies to one request. Stops collecting them when dropped.
+pub struct Replies {
+    request_id: Uuid,
+    rx: mpsc::Receiver<ServiceMessage>,
+    pending: PendingReplies,
+}
+
+impl Replies {
+    /// The next reply, failing if none comes within `LLM_REQUEST_TIMEOUT`
+    pub async fn next(&mut self) -> Result<ServiceMessage> {
+        let timeout = Duration::from_secs(LLM_REQUEST_TIMEOUT);
+        tokio::time::timeout(timeout, self.rx.recv())
+            .await
+            .map_err(|_| SystemError::Timeout)?
+            .ok_or_else(|| {
+                SystemError::ServiceCommunication("UI service channel closed".to_string())
+            })
+    }
+}
+
+impl Drop for Replies {
+    fn drop(&mut self) {
+        self.pending.lock().remove(&self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replies_for_another_user_are_dropped() {
+        let replies = PendingReplies::default();
+        let (ui_tx, ui_rx) = mpsc::channel(16);
+        tokio::spawn(replies.clone().dispatch(ui_rx));
+
+        let request_id = Uuid::new_v4();
+        let mut alice = replies.expect(request_id, "alice");
+        for user_id in ["bob", "alice"] {
+            let reply = ServiceMessage::ConversationHistoryResponse {
+                user_id: user_id.to_string(),
+                messages: vec![],
+                request_id,
+            };
+            ui_tx.send(reply).await.unwrap();
+        }
+
+        match alice.next().await.unwrap() {
+            ServiceMessage::ConversationHistoryResponse { user_id, .. } => {
+                assert_eq!(user_id, "alice")
+            }
+            other => panic!("Unexpected reply: {:?}", other),
+        }
+        drop(alice);
+        assert!(replies.lock().is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::event_bus::EventBus;
+use ai_manager_shared::{
+    Result, SystemError, SystemEvent, CORE_SERVICE_ID, SERVICE_SHUTDOWN_GRACE_SECONDS,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// When a scheduled job runs
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Every period, starting one period after the job is scheduled
+    Interval(Duration),
+    /// At the UTC times matched by a cron expression
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn every(period: Duration) -> Self {
+        Self::Interval(period)
+    }
+
+    /// Parse a cron expression with seconds, e.g. `0 30 9 * * Mon-Fri` for
+    /// 09:30 every weekday
+    pub fn cron(expression: &str) -> Result<Self> {
+        cron::Schedule::from_str(expression)
+            .map(|schedule| Self::Cron(Box::new(schedule)))
+            .map_err(|e| {
+                SystemError::Configuration(format!(
+                    "Invalid cron expression '{}': {}",
+                    expression, e
+                ))
+            })
+    }
+
+    /// How long after `now` the job next runs; `None` if it never runs again
+    pub fn next_delay(&self, now: DateTime<Utc>) -> Option<Duration> {
+        match self {
+            Self::Interval(period) => Some(*period),
+            Self::Cron(schedule) => schedule
+                .after(&now)
+                .next()
+                .map(|next| (next - now).to_std().unwrap_or_default()),
+        }
+    }
+}
+
+/// Runs recurring jobs on the tokio runtime. Failed runs are broadcast as
+/// `SystemEvent::ErrorOccurred`; the job keeps its schedule.
+pub struct Scheduler {
+    event_bus: Arc<EventBus>,
+    jobs: HashMap<String, JoinHandle<()>>,
+    shutdown: watch::Sender<bool>,
+    shutdown_grace_period: Duration,
+}
+
+impl Scheduler {
+    pub fn new(event_bus: Arc<EventBus>) -> Self {
+        let (shutdown, _) = watch::channel(false);
+
+        Self {
+            event_bus,
+            jobs: HashMap::new(),
+            shutdown,
+            shutdown_grace_period: Duration::from_secs(SERVICE_SHUTDOWN_GRACE_SECONDS),
+        }
+    }
+
+    /// How long `shutdown` lets a running job finish before aborting it
+    pub fn with_shutdown_grace_period(mut self, grace_period: Duration) -> Self {
+        self.shutdown_grace_period = grace_period;
+        self
+    }
+
+    /// Run `job` on `schedule` until cancelled, replacing any job with the
+    /// same name
+    pub fn schedule<F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        schedule: Schedule,
+        job: F,
+    ) -> Result<()>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        if matches!(schedule, Schedule::Interval(period) if period.is_zero()) {
+            return Err(SystemError::InvalidInput(format!(
+                "Job '{}' needs a non-zero interval",
+                name
+            )));
+        }
+        self.cancel(&name);
+
+        let mut shutdown = self.shutdown.subscribe();
+        let event_bus = self.event_bus.clone();
+        let job_name = name.clone();
+        let handle = tokio::spawn(async move {
+            while let Some(delay) = schedule.next_delay(Utc::now()) {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.changed() => break,
+                }
+
+                debug!("Running scheduled job '{}'", job_name);
+                if let Err(e) = job().await {
+                    error!("Scheduled job '{}' failed: {}", job_name, e);
+                    event_bus
+                        .broadcast_event(SystemEvent::ErrorOccurred {
+                            service_id: CORE_SERVICE_ID.to_string(),
+                            error: format!("Scheduled job '{}' failed: {}", job_name, e),
+                        })
+                        .await;
+                }
+            }
+            debug!("Scheduled job '{}' stopped", job_name);
+        });
+
+        info!("Scheduled job '{}'", name);
+        self.jobs.insert(name, handle);
+        Ok(())
+    }
+
+    /// Stop a job immediately; returns whether it was scheduled
+    pub fn cancel(&mut self, name: &str) -> bool {
+        match self.jobs.remove(name) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Names of the scheduled jobs, sorted
+    pub fn job_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.jobs.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Stop every job, letting runs in progress finish within the grace period
+    pub async fn shutdown(&mut self) {
+        let _ = self.shutdown.send(true);
+
+        for (name, mut handle) in self.jobs.drain() {
+            if tokio::time::timeout(self.shutdown_grace_period, &mut handle)
+                .await
+                .is_err()
+            {
+                warn!(
+                    "Scheduled job '{}' still running after {:?}, aborting",
+                    name, self.shutdown_grace_period
+                );
+                handle.abort();
+            }
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        for handle in self.jobs.values() {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting_job(
+        runs: &Arc<AtomicUsize>,
+    ) -> impl Fn() -> futures::future::Ready<Result<()>> + Send + Sync + 'static {
+        let runs = runs.clone();
+        move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            futures::future::ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let schedule = Schedule::cron("0 0 * * * *").unwrap();
+        let now = Utc::now();
+        let delay = schedule.next_delay(now).unwrap();
+        assert!(delay <= Duration::from_secs(60 * 60));
+
+        assert!(Schedule::cron("every tuesday").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interval_job_runs_until_shutdown() {
+        let mut scheduler = Scheduler::new(Arc::new(EventBus::new()));
+        let runs = Arc::new(AtomicUsize::new(0));
+        scheduler
+            .schedule(
+                "tick",
+                Schedule::every(Duration::from_millis(20)),
+                counting_job(&runs),
+            )
+            .unwrap();
+        assert_eq!(scheduler.job_names(), vec!["tick".to_string()]);
+
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        scheduler.shutdown().await;
+        let after_shutdown = runs.load(Ordering::SeqCst);
+        assert!(after_shutdown >= 2);
+        assert!(scheduler.job_names().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), after_shutdown);
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_reported() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut events = event_bus.subscribe_to_events();
+        let mut scheduler = Scheduler::new(event_bus);
+        scheduler
+            .schedule(
+                "sync",
+                Schedule::every(Duration::from_millis(10)),
+                || async { Err(SystemError::Network("unreachable".to_string())) },
+            )
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_millis(500), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match event {
+            SystemEvent::ErrorOccurred { service_id, error } => {
+                assert_eq!(service_id, CORE_SERVICE_ID);
+                assert!(error.contains("'sync'"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        assert!(scheduler.cancel("sync"));
+        assert!(!scheduler.cancel("sync"));
+        assert!(scheduler
+            .schedule("zero", Schedule::every(Duration::ZERO), || async { Ok(()) })
+            .is_err());
+    }
+}
@@ -1,36 +1,75 @@
 use crate::event_bus::EventBus;
-use ai_manager_shared::{Result, ServiceId};
-use std::collections::HashMap;
+use ai_manager_shared::{
+    Result, ServiceHealth, ServiceId, ServiceMessage, SystemError, SystemEvent,
+};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 #[derive(Debug)]
 pub struct ServiceManager {
-    services: Arc<RwLock<HashMap<ServiceId, ServiceInfo>>>,
+    services: ServiceMap,
     event_bus: Arc<EventBus>,
     restart_policy: RestartPolicy,
+    health_check_timeout: Duration,
     health_monitor_handle: Option<JoinHandle<()>>,
+    dependencies: HashMap<ServiceId, Vec<ServiceId>>,
+    dependency_timeout: Duration,
+    shutdown_grace_period: Duration,
+    shutdown: CancellationToken,
+    shutdown_tokens: HashMap<ServiceId, CancellationToken>,
+}
+
+type ServiceMap = Arc<RwLock<HashMap<ServiceId, ServiceInfo>>>;
+
+/// Recreates a service's task so it can be restarted
+#[derive(Clone)]
+struct ServiceFactory(Arc<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>);
+
+impl std::fmt::Debug for ServiceFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ServiceFactory")
+    }
 }
 
 #[derive(Debug)]
 struct ServiceInfo {
     handle: JoinHandle<()>,
+    factory: ServiceFactory,
     last_health_check: Instant,
     restart_count: u32,
     status: ServiceStatus,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ServiceStatus {
     Starting,
     Running,
     Stopping,
     Stopped,
-    Failed { error: String },
+    Failed {
+        error: String,
+    },
     Restarting,
+    /// Restarts are paused after repeated failures until `until`
+    CircuitOpen {
+        until: chrono::DateTime<chrono::Utc>,
+    },
+}
+
+/// How a service ended when it was stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// Exited on its own after the shutdown request
+    Graceful,
+    /// Didn't exit within the grace period and was aborted
+    Aborted,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +78,29 @@ pub struct RestartPolicy {
     pub restart_delay: Duration,
     pub backoff_multiplier: f64,
     pub max_restart_delay: Duration,
+    /// How long restarts pause once `max_restart_attempts` is reached
+    pub circuit_cooldown: Duration,
+    /// How long a trial run must survive for the restart count to reset
+    pub stability_window: Duration,
+}
+
+impl RestartPolicy {
+    /// Whether another restart is allowed after `restart_count` restarts
+    fn allows_restart(&self, restart_count: u32) -> bool {
+        restart_count < self.max_restart_attempts
+    }
+
+    /// Delay before the given restart, with exponential backoff
+    fn restart_delay_for(&self, restart_count: u32) -> Duration {
+        let base_delay = self.restart_delay.as_secs_f64();
+        let multiplier = self.backoff_multiplier.powi(restart_count as i32);
+        let delay_secs = base_delay * multiplier;
+
+        let max_delay_secs = self.max_restart_delay.as_secs_f64();
+        let final_delay_secs = delay_secs.min(max_delay_secs);
+
+        Duration::from_secs_f64(final_delay_secs)
+    }
 }
 
 impl Default for RestartPolicy {
@@ -48,6 +110,10 @@ impl Default for RestartPolicy {
             restart_delay: Duration::from_secs(1),
             backoff_multiplier: 2.0,
             max_restart_delay: Duration::from_secs(30),
+            circuit_cooldown: Duration::from_secs(
+                ai_manager_shared::SERVICE_RESTART_COOLDOWN_SECONDS,
+            ),
+            stability_window: Duration::from_secs(60),
         }
     }
 }
@@ -58,19 +124,58 @@ impl ServiceManager {
             services: Arc::new(RwLock::new(HashMap::new())),
             event_bus,
             restart_policy: RestartPolicy::default(),
+            health_check_timeout: Duration::from_secs(
+                ai_manager_shared::HEALTH_CHECK_TIMEOUT_SECONDS,
+            ),
             health_monitor_handle: None,
+            dependencies: HashMap::new(),
+            dependency_timeout: Duration::from_secs(30),
+            shutdown_grace_period: Duration::from_secs(
+                ai_manager_shared::SERVICE_SHUTDOWN_GRACE_SECONDS,
+            ),
+            shutdown: CancellationToken::new(),
+            shutdown_tokens: HashMap::new(),
         }
     }
 
+    /// Token cancelled when `service_id` is stopped. A service that selects
+    /// on it should stop taking messages, finish the ones it has and exit;
+    /// services without one are sent `ShutdownService` instead.
+    pub fn shutdown_token(&mut self, service_id: &str) -> CancellationToken {
+        self.shutdown_tokens
+            .entry(service_id.to_string())
+            .or_insert_with(|| self.shutdown.child_token())
+            .clone()
+    }
+
     pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
         self.restart_policy = policy;
         self
     }
 
-    /// Start a service with a provided task function
+    /// How long a stopping service gets to exit before it is aborted
+    pub fn with_shutdown_grace_period(mut self, grace_period: Duration) -> Self {
+        self.shutdown_grace_period = grace_period;
+        self
+    }
+
+    /// How long `start_service_with_deps` waits for dependencies to become healthy
+    pub fn with_dependency_timeout(mut self, timeout: Duration) -> Self {
+        self.dependency_timeout = timeout;
+        self
+    }
+
+    /// How long to wait for a service to answer a health check
+    pub fn with_health_check_timeout(mut self, timeout: Duration) -> Self {
+        self.health_check_timeout = timeout;
+        self
+    }
+
+    /// Start a service with a provided task function. The function is kept
+    /// so the service can be recreated when it fails or is restarted.
     pub async fn start_service<F, Fut>(&mut self, service_id: ServiceId, task: F) -> Result<()>
     where
-        F: FnOnce() -> Fut + Send + 'static,
+        F: Fn() -> Fut + Send + Sync + 'static,
         Fut: std::future::Future<Output = Result<()>> + Send + 'static,
     {
         info!("Starting service: {}", service_id);
@@ -78,33 +183,118 @@ impl ServiceManager {
         // Register with event bus
         let (_tx, _rx) = self.event_bus.register_service(service_id.clone()).await?;
 
-        // Start the service task
-        let service_id_clone = service_id.clone();
-        let handle = tokio::spawn(async move {
-            if let Err(e) = task().await {
-                error!("Service '{}' failed: {}", service_id_clone, e);
-            }
-        });
-
-        // Register service info
-        let service_info = ServiceInfo {
-            handle,
-            last_health_check: Instant::now(),
-            restart_count: 0,
-            status: ServiceStatus::Starting,
-        };
+        let factory = ServiceFactory(Arc::new(move || -> BoxFuture<'static, Result<()>> {
+            Box::pin(task())
+        }));
 
+        // Hold the lock while spawning so the task can't update its entry
+        // before it exists
         {
             let mut services = self.services.write().await;
-            services.insert(service_id.clone(), service_info);
+            let handle = spawn_supervised(
+                self.services.clone(),
+                self.restart_policy.clone(),
+                service_id.clone(),
+                factory.clone(),
+                StartMode::Normal,
+                None,
+            );
+            services.insert(
+                service_id.clone(),
+                ServiceInfo {
+                    handle,
+                    factory,
+                    last_health_check: Instant::now(),
+                    restart_count: 0,
+                    status: ServiceStatus::Starting,
+                },
+            );
         }
 
         info!("Service '{}' started successfully", service_id);
         Ok(())
     }
 
-    /// Stop a specific service
-    pub async fn stop_service(&mut self, service_id: &ServiceId) -> Result<()> {
+    /// Start a service once all of its dependencies report healthy
+    pub async fn start_service_with_deps<F, Fut>(
+        &mut self,
+        service_id: ServiceId,
+        deps: Vec<ServiceId>,
+        task: F,
+    ) -> Result<()>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<()>> + Send + 'static,
+    {
+        self.register_dependencies(&service_id, deps.clone())?;
+
+        if let Err(e) = self.wait_for_dependencies(&service_id, &deps).await {
+            self.dependencies.remove(&service_id);
+            return Err(e);
+        }
+
+        self.start_service(service_id, task).await
+    }
+
+    /// Record a service's dependencies, rejecting cycles
+    fn register_dependencies(
+        &mut self,
+        service_id: &ServiceId,
+        deps: Vec<ServiceId>,
+    ) -> Result<()> {
+        let previous = self.dependencies.insert(service_id.clone(), deps);
+
+        if let Some(cycle) = find_cycle(&self.dependencies, service_id) {
+            match previous {
+                Some(previous) => self.dependencies.insert(service_id.clone(), previous),
+                None => self.dependencies.remove(service_id),
+            };
+            return Err(SystemError::Configuration(format!(
+                "Service dependency cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn wait_for_dependencies(
+        &self,
+        service_id: &ServiceId,
+        deps: &[ServiceId],
+    ) -> Result<()> {
+        let deadline = Instant::now() + self.dependency_timeout;
+
+        for dep in deps {
+            loop {
+                match self
+                    .event_bus
+                    .check_service_health(dep, self.health_check_timeout)
+                    .await
+                {
+                    Ok(ServiceHealth::Healthy) | Ok(ServiceHealth::Degraded { .. }) => break,
+                    Ok(ServiceHealth::Unhealthy { error }) => {
+                        debug!("Dependency '{}' is unhealthy: {}", dep, error)
+                    }
+                    Err(e) => debug!("Dependency '{}' not ready: {}", dep, e),
+                }
+
+                if Instant::now() >= deadline {
+                    return Err(SystemError::ServiceUnavailable {
+                        service: dep.clone(),
+                    });
+                }
+                info!("Service '{}' waiting for dependency '{}'", service_id, dep);
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stop a specific service, asking it to shut down first and aborting
+    /// it only if it doesn't exit within the grace period
+    pub async fn stop_service(&mut self, service_id: &ServiceId) -> Result<ShutdownOutcome> {
         info!("Stopping service: {}", service_id);
 
         let service_info = {
@@ -112,14 +302,51 @@ impl ServiceManager {
             services.remove(service_id)
         };
 
+        let mut outcome = ShutdownOutcome::Graceful;
         if let Some(mut info) = service_info {
             info.status = ServiceStatus::Stopping;
-            info.handle.abort();
 
-            // Wait for service to stop
-            if let Err(e) = info.handle.await {
-                if !e.is_cancelled() {
-                    error!("Error stopping service '{}': {}", service_id, e);
+            let delivered = match self.shutdown_tokens.remove(service_id) {
+                Some(token) => {
+                    token.cancel();
+                    true
+                }
+                None => {
+                    let request = ServiceMessage::ShutdownService {
+                        service_id: service_id.clone(),
+                    };
+                    match self
+                        .event_bus
+                        .route_message(request, Some(service_id.clone()))
+                        .await
+                    {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("Could not ask service '{}' to shut down: {}", service_id, e);
+                            false
+                        }
+                    }
+                }
+            };
+
+            let exited = delivered
+                && tokio::time::timeout(self.shutdown_grace_period, &mut info.handle)
+                    .await
+                    .is_ok();
+
+            if !exited {
+                warn!(
+                    "Service '{}' did not exit within {:?}, aborting",
+                    service_id, self.shutdown_grace_period
+                );
+                info.handle.abort();
+                outcome = ShutdownOutcome::Aborted;
+
+                // Wait for service to stop
+                if let Err(e) = info.handle.await {
+                    if !e.is_cancelled() {
+                        error!("Error stopping service '{}': {}", service_id, e);
+                    }
                 }
             }
         }
@@ -127,46 +354,35 @@ impl ServiceManager {
         // Unregister from event bus
         self.event_bus.unregister_service(service_id).await?;
 
-        info!("Service '{}' stopped", service_id);
-        Ok(())
+        info!("Service '{}' stopped ({:?})", service_id, outcome);
+        Ok(outcome)
     }
 
-    /// Restart a service
+    /// Restart a service: stop its task, wait the backoff delay and spawn it
+    /// again from the stored factory
     pub async fn restart_service(&mut self, service_id: &ServiceId) -> Result<()> {
-        info!("Restarting service: {}", service_id);
-
-        // Update status
-        {
-            let mut services = self.services.write().await;
-            if let Some(service_info) = services.get_mut(service_id) {
-                service_info.status = ServiceStatus::Restarting;
-                service_info.restart_count += 1;
-            }
-        }
-
-        // Stop the service
-        self.stop_service(service_id).await?;
-
-        // Wait for restart delay
-        let restart_count = {
-            let services = self.services.read().await;
-            services
-                .get(service_id)
-                .map(|s| s.restart_count)
-                .unwrap_or(0)
-        };
-
-        let delay = self.calculate_restart_delay(restart_count);
-        sleep(delay).await;
+        restart_supervised(
+            &self.services,
+            &self.event_bus,
+            &self.restart_policy,
+            service_id,
+        )
+        .await
+    }
 
-        // Note: In a real implementation, we'd need to store the service
-        // factory/constructor to recreate the service here
-        warn!(
-            "Service restart not fully implemented - would restart '{}' here",
-            service_id
-        );
+    /// Get how many times a service has been restarted
+    pub async fn get_restart_count(&self, service_id: &ServiceId) -> Option<u32> {
+        let services = self.services.read().await;
+        services.get(service_id).map(|info| info.restart_count)
+    }
 
-        Ok(())
+    /// Get how many times each service has been restarted
+    pub async fn get_restart_counts(&self) -> HashMap<ServiceId, u32> {
+        let services = self.services.read().await;
+        services
+            .iter()
+            .map(|(id, info)| (id.clone(), info.restart_count))
+            .collect()
     }
 
     /// Get the status of all services
@@ -184,7 +400,8 @@ impl ServiceManager {
         services.get(service_id).map(|info| info.status.clone())
     }
 
-    /// Start health monitoring for all services
+    /// Start health monitoring for all services. Unhealthy or unresponsive
+    /// services are restarted while the restart policy allows it.
     pub async fn start_health_monitoring(&mut self) {
         if self.health_monitor_handle.is_some() {
             warn!("Health monitoring already running");
@@ -192,6 +409,9 @@ impl ServiceManager {
         }
 
         let services = self.services.clone();
+        let event_bus = self.event_bus.clone();
+        let policy = self.restart_policy.clone();
+        let timeout = self.health_check_timeout;
         let interval = Duration::from_secs(ai_manager_shared::HEALTH_CHECK_INTERVAL_SECONDS);
 
         let handle = tokio::spawn(async move {
@@ -199,26 +419,7 @@ impl ServiceManager {
 
             loop {
                 interval_timer.tick().await;
-
-                debug!("Running health checks");
-
-                let service_ids: Vec<ServiceId> = {
-                    let services_read = services.read().await;
-                    services_read.keys().cloned().collect()
-                };
-
-                for service_id in service_ids {
-                    // In a real implementation, we'd check service health here
-                    debug!("Health check for service: {}", service_id);
-
-                    // Update last health check time
-                    {
-                        let mut services_write = services.write().await;
-                        if let Some(service_info) = services_write.get_mut(&service_id) {
-                            service_info.last_health_check = Instant::now();
-                        }
-                    }
-                }
+                check_services_health(&services, &event_bus, &policy, timeout).await;
             }
         });
 
@@ -226,6 +427,17 @@ impl ServiceManager {
         info!("Health monitoring started");
     }
 
+    /// Run a single round of health checks
+    pub async fn check_services_health(&self) {
+        check_services_health(
+            &self.services,
+            &self.event_bus,
+            &self.restart_policy,
+            self.health_check_timeout,
+        )
+        .await;
+    }
+
     /// Stop health monitoring
     pub async fn stop_health_monitoring(&mut self) {
         if let Some(handle) = self.health_monitor_handle.take() {
@@ -235,47 +447,402 @@ impl ServiceManager {
     }
 
     /// Shutdown all services
-    pub async fn shutdown_all(&mut self) -> Result<()> {
+    pub async fn shutdown_all(&mut self) -> Result<Vec<(ServiceId, ShutdownOutcome)>> {
         info!("Shutting down all services");
 
         // Stop health monitoring
         self.stop_health_monitoring().await;
 
-        // Get all service IDs
+        // Stop dependents before the services they depend on
         let service_ids: Vec<ServiceId> = {
             let services = self.services.read().await;
-            services.keys().cloned().collect()
+            let running: Vec<ServiceId> = services.keys().cloned().collect();
+            shutdown_order(&self.dependencies, &running)
         };
 
         // Stop all services
+        let mut outcomes = Vec::new();
         for service_id in service_ids {
-            if let Err(e) = self.stop_service(&service_id).await {
-                error!("Error stopping service '{}': {}", service_id, e);
+            match self.stop_service(&service_id).await {
+                Ok(outcome) => outcomes.push((service_id, outcome)),
+                Err(e) => error!("Error stopping service '{}': {}", service_id, e),
             }
         }
 
-        info!("All services shut down");
-        Ok(())
+        // Anything still holding a token stops too
+        self.shutdown.cancel();
+
+        let aborted = outcomes
+            .iter()
+            .filter(|(_, outcome)| *outcome == ShutdownOutcome::Aborted)
+            .count();
+        info!(
+            "All services shut down ({} graceful, {} aborted)",
+            outcomes.len() - aborted,
+            aborted
+        );
+        Ok(outcomes)
     }
 
-    /// Calculate restart delay with exponential backoff
-    fn calculate_restart_delay(&self, restart_count: u32) -> Duration {
-        let base_delay = self.restart_policy.restart_delay.as_secs_f64();
-        let multiplier = self
-            .restart_policy
-            .backoff_multiplier
-            .powi(restart_count as i32);
-        let delay_secs = base_delay * multiplier;
+    /// Check if a service should be restarted
+    pub fn should_restart_service(&self, _service_id: &ServiceId, restart_count: u32) -> bool {
+        self.restart_policy.allows_restart(restart_count)
+    }
+}
 
-        let max_delay_secs = self.restart_policy.max_restart_delay.as_secs_f64();
-        let final_delay_secs = delay_secs.min(max_delay_secs);
+/// Follow dependencies from `start`, returning the path of the first cycle found
+fn find_cycle(
+    graph: &HashMap<ServiceId, Vec<ServiceId>>,
+    start: &ServiceId,
+) -> Option<Vec<ServiceId>> {
+    fn visit(
+        graph: &HashMap<ServiceId, Vec<ServiceId>>,
+        node: &ServiceId,
+        path: &mut Vec<ServiceId>,
+        done: &mut HashSet<ServiceId>,
+    ) -> Option<Vec<ServiceId>> {
+        if let Some(pos) = path.iter().position(|id| id == node) {
+            let mut cycle = path[pos..].to_vec();
+            cycle.push(node.clone());
+            return Some(cycle);
+        }
+        if done.contains(node) {
+            return None;
+        }
 
-        Duration::from_secs_f64(final_delay_secs)
+        path.push(node.clone());
+        for dep in graph.get(node).into_iter().flatten() {
+            if let Some(cycle) = visit(graph, dep, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(node.clone());
+        None
     }
 
-    /// Check if a service should be restarted
-    pub fn should_restart_service(&self, _service_id: &ServiceId, restart_count: u32) -> bool {
-        restart_count < self.restart_policy.max_restart_attempts
+    visit(graph, start, &mut Vec::new(), &mut HashSet::new())
+}
+
+/// Order services so that every service comes before its dependencies
+fn shutdown_order(
+    graph: &HashMap<ServiceId, Vec<ServiceId>>,
+    services: &[ServiceId],
+) -> Vec<ServiceId> {
+    fn visit(
+        graph: &HashMap<ServiceId, Vec<ServiceId>>,
+        node: &ServiceId,
+        visited: &mut HashSet<ServiceId>,
+        order: &mut Vec<ServiceId>,
+    ) {
+        if !visited.insert(node.clone()) {
+            return;
+        }
+        for dep in graph.get(node).into_iter().flatten() {
+            visit(graph, dep, visited, order);
+        }
+        order.push(node.clone());
+    }
+
+    // Dependencies first, then reverse so dependents stop first
+    let mut sorted: Vec<&ServiceId> = services.iter().collect();
+    sorted.sort();
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for service_id in sorted {
+        visit(graph, service_id, &mut visited, &mut order);
+    }
+    order.reverse();
+    order.retain(|id| services.contains(id));
+    order
+}
+
+/// How a supervised task begins
+#[derive(Debug, Clone, Copy)]
+enum StartMode {
+    Normal,
+    /// Half-open circuit: wait out the cooldown, then make a single trial run
+    Trial {
+        after: Duration,
+    },
+}
+
+/// Spawn a service task that re-runs the factory when it fails. Once the
+/// restart policy's attempt limit is reached the circuit opens for the
+/// cooldown, followed by a trial run that closes it again if the service
+/// stays up for the stability window. `started` is signalled when the
+/// first run begins.
+fn spawn_supervised(
+    services: ServiceMap,
+    policy: RestartPolicy,
+    service_id: ServiceId,
+    factory: ServiceFactory,
+    mode: StartMode,
+    mut started: Option<oneshot::Sender<()>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut half_open = false;
+        if let StartMode::Trial { after } = mode {
+            sleep(after).await;
+            half_open = true;
+        }
+
+        loop {
+            set_status(&services, &service_id, ServiceStatus::Running).await;
+
+            let run = {
+                let started = started.take();
+                let run = (factory.0)();
+                async move {
+                    if let Some(started) = started {
+                        let _ = started.send(());
+                    }
+                    run.await
+                }
+            };
+            tokio::pin!(run);
+            let result = if half_open {
+                tokio::select! {
+                    result = &mut run => result,
+                    _ = sleep(policy.stability_window) => {
+                        info!("Service '{}' is stable again, closing circuit", service_id);
+                        if let Some(service_info) = services.write().await.get_mut(&service_id) {
+                            service_info.restart_count = 0;
+                        }
+                        half_open = false;
+                        run.await
+                    }
+                }
+            } else {
+                run.await
+            };
+
+            let error = match result {
+                Ok(()) => {
+                    info!("Service '{}' exited", service_id);
+                    set_status(&services, &service_id, ServiceStatus::Stopped).await;
+                    return;
+                }
+                Err(e) => e,
+            };
+            error!("Service '{}' failed: {}", service_id, error);
+
+            let restart_count = {
+                let mut services = services.write().await;
+                let Some(service_info) = services.get_mut(&service_id) else {
+                    return;
+                };
+                if half_open || !policy.allows_restart(service_info.restart_count) {
+                    service_info.status = ServiceStatus::CircuitOpen {
+                        until: circuit_open_until(&policy),
+                    };
+                    None
+                } else {
+                    service_info.restart_count += 1;
+                    service_info.status = ServiceStatus::Restarting;
+                    Some(service_info.restart_count)
+                }
+            };
+
+            match restart_count {
+                Some(restart_count) => {
+                    let delay = policy.restart_delay_for(restart_count);
+                    warn!(
+                        "Restarting service '{}' in {:?} (attempt {}/{})",
+                        service_id, delay, restart_count, policy.max_restart_attempts
+                    );
+                    sleep(delay).await;
+                }
+                None => {
+                    error!(
+                        "Service '{}' keeps failing, opening circuit for {:?}",
+                        service_id, policy.circuit_cooldown
+                    );
+                    sleep(policy.circuit_cooldown).await;
+                    half_open = true;
+                }
+            }
+        }
+    })
+}
+
+fn circuit_open_until(policy: &RestartPolicy) -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now()
+        + chrono::Duration::from_std(policy.circuit_cooldown).unwrap_or(chrono::Duration::zero())
+}
+
+/// Stop a service's task, wait the backoff delay and spawn it again from
+/// its stored factory
+async fn restart_supervised(
+    services: &ServiceMap,
+    event_bus: &EventBus,
+    policy: &RestartPolicy,
+    service_id: &ServiceId,
+) -> Result<()> {
+    info!("Restarting service: {}", service_id);
+
+    let (factory, restart_count) = {
+        let mut services = services.write().await;
+        let service_info =
+            services
+                .get_mut(service_id)
+                .ok_or_else(|| SystemError::ServiceUnavailable {
+                    service: service_id.clone(),
+                })?;
+        service_info.status = ServiceStatus::Restarting;
+        service_info.restart_count += 1;
+        service_info.handle.abort();
+        (service_info.factory.clone(), service_info.restart_count)
+    };
+
+    sleep(policy.restart_delay_for(restart_count)).await;
+
+    let (started_tx, started_rx) = oneshot::channel();
+    {
+        let mut services_write = services.write().await;
+        let handle = spawn_supervised(
+            services.clone(),
+            policy.clone(),
+            service_id.clone(),
+            factory,
+            StartMode::Normal,
+            Some(started_tx),
+        );
+        match services_write.get_mut(service_id) {
+            Some(service_info) => service_info.handle = handle,
+            None => {
+                // Stopped while we were waiting
+                handle.abort();
+                return Ok(());
+            }
+        }
+    }
+
+    // Wait for the new run to begin, so a restart right after this one
+    // can't abort it before it has run at all
+    let _ = started_rx.await;
+
+    event_bus
+        .broadcast_event(SystemEvent::ServiceRestarted {
+            service_id: service_id.clone(),
+        })
+        .await;
+
+    info!(
+        "Service '{}' restarted (restart #{})",
+        service_id, restart_count
+    );
+    Ok(())
+}
+
+/// Ask every running service for its health and restart the ones that are
+/// unhealthy or don't answer in time
+async fn check_services_health(
+    services: &ServiceMap,
+    event_bus: &EventBus,
+    policy: &RestartPolicy,
+    timeout: Duration,
+) {
+    debug!("Running health checks");
+
+    let service_ids: Vec<ServiceId> = {
+        let services_read = services.read().await;
+        services_read
+            .iter()
+            .filter(|(_, info)| matches!(info.status, ServiceStatus::Running))
+            .map(|(id, _)| id.clone())
+            .collect()
+    };
+
+    let mut results: HashMap<ServiceId, Result<ServiceHealth>> = event_bus
+        .check_all_services_health(timeout)
+        .await
+        .into_iter()
+        .collect();
+
+    for service_id in service_ids {
+        debug!("Health check for service: {}", service_id);
+
+        let result = results.remove(&service_id).unwrap_or_else(|| {
+            Err(SystemError::ServiceUnavailable {
+                service: service_id.clone(),
+            })
+        });
+        let error = match result {
+            Ok(ServiceHealth::Healthy) => None,
+            Ok(ServiceHealth::Degraded { reason }) => {
+                warn!("Service '{}' is degraded: {}", service_id, reason);
+                None
+            }
+            Ok(ServiceHealth::Unhealthy { error }) => Some(error),
+            Err(e) => Some(format!("No health response: {}", e)),
+        };
+
+        let restart_count = {
+            let mut services_write = services.write().await;
+            let Some(service_info) = services_write.get_mut(&service_id) else {
+                continue;
+            };
+            service_info.last_health_check = Instant::now();
+
+            let Some(error) = &error else {
+                continue;
+            };
+            service_info.status = ServiceStatus::Failed {
+                error: error.clone(),
+            };
+            service_info.restart_count
+        };
+
+        let error = error.unwrap_or_default();
+        error!("Service '{}' is unhealthy: {}", service_id, error);
+        event_bus
+            .broadcast_event(SystemEvent::ErrorOccurred {
+                service_id: service_id.clone(),
+                error,
+            })
+            .await;
+
+        if !policy.allows_restart(restart_count) {
+            error!(
+                "Service '{}' exceeded {} restart attempts, opening circuit for {:?}",
+                service_id, policy.max_restart_attempts, policy.circuit_cooldown
+            );
+            open_circuit(services, policy, &service_id).await;
+            continue;
+        }
+
+        if let Err(e) = restart_supervised(services, event_bus, policy, &service_id).await {
+            error!("Failed to restart service '{}': {}", service_id, e);
+        }
+    }
+}
+
+/// Stop a service and schedule a single trial run after the cooldown
+async fn open_circuit(services: &ServiceMap, policy: &RestartPolicy, service_id: &ServiceId) {
+    let mut services_write = services.write().await;
+    let Some(service_info) = services_write.get_mut(service_id) else {
+        return;
+    };
+    service_info.handle.abort();
+    service_info.status = ServiceStatus::CircuitOpen {
+        until: circuit_open_until(policy),
+    };
+    service_info.handle = spawn_supervised(
+        services.clone(),
+        policy.clone(),
+        service_id.clone(),
+        service_info.factory.clone(),
+        StartMode::Trial {
+            after: policy.circuit_cooldown,
+        },
+        None,
+    );
+}
+
+async fn set_status(services: &ServiceMap, service_id: &ServiceId, status: ServiceStatus) {
+    if let Some(service_info) = services.write().await.get_mut(service_id) {
+        service_info.status = status;
     }
 }
 
@@ -324,4 +891,459 @@ mod tests {
             .await;
         assert!(status.is_none());
     }
+
+    #[tokio::test]
+    async fn test_failing_service_is_restarted_up_to_limit() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus).with_restart_policy(RestartPolicy {
+            max_restart_attempts: 3,
+            restart_delay: Duration::from_millis(5),
+            backoff_multiplier: 1.0,
+            max_restart_delay: Duration::from_millis(5),
+            ..RestartPolicy::default()
+        });
+
+        let runs = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let runs_clone = runs.clone();
+        manager
+            .start_service("flaky-service".to_string(), move || {
+                let runs = runs_clone.clone();
+                async move {
+                    runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Err(SystemError::Unknown("boom".to_string()))
+                }
+            })
+            .await
+            .unwrap();
+
+        sleep(Duration::from_millis(200)).await;
+
+        let service_id = "flaky-service".to_string();
+        // Initial run plus one per allowed restart
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 4);
+        assert_eq!(manager.get_restart_count(&service_id).await, Some(3));
+        assert!(matches!(
+            manager.get_service_status(&service_id).await,
+            Some(ServiceStatus::CircuitOpen { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_manual_restart_respawns_task() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus).with_restart_policy(RestartPolicy {
+            max_restart_attempts: 3,
+            restart_delay: Duration::from_millis(5),
+            backoff_multiplier: 1.0,
+            max_restart_delay: Duration::from_millis(5),
+            ..RestartPolicy::default()
+        });
+
+        let runs = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let runs_clone = runs.clone();
+        let service_id = "long-running".to_string();
+        manager
+            .start_service(service_id.clone(), move || {
+                let runs = runs_clone.clone();
+                async move {
+                    runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(20)).await;
+
+        manager.restart_service(&service_id).await.unwrap();
+        manager.restart_service(&service_id).await.unwrap();
+        sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(manager.get_restart_count(&service_id).await, Some(2));
+        assert!(matches!(
+            manager.get_service_status(&service_id).await,
+            Some(ServiceStatus::Running)
+        ));
+
+        // Restarting an unknown service is an error
+        assert!(manager
+            .restart_service(&"missing".to_string())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_service_is_restarted() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut events = event_bus.subscribe_to_events();
+        let mut manager = ServiceManager::new(event_bus.clone())
+            .with_restart_policy(RestartPolicy {
+                max_restart_attempts: 1,
+                restart_delay: Duration::from_millis(5),
+                backoff_multiplier: 1.0,
+                max_restart_delay: Duration::from_millis(5),
+                ..RestartPolicy::default()
+            })
+            .with_health_check_timeout(Duration::from_millis(200));
+
+        // A service that always reports itself unhealthy
+        let service_id = "sick-service".to_string();
+        let bus = event_bus.clone();
+        let id = service_id.clone();
+        manager
+            .start_service(service_id.clone(), move || {
+                let bus = bus.clone();
+                let id = id.clone();
+                async move {
+                    let (_tx, mut rx) = bus.register_service(id.clone()).await?;
+                    while let Some(message) = rx.recv().await {
+                        if let ai_manager_shared::ServiceMessage::ServiceHealthCheck { .. } =
+                            message
+                        {
+                            let response =
+                                ai_manager_shared::ServiceMessage::ServiceHealthResponse {
+                                    service_id: id.clone(),
+                                    status: ServiceHealth::Unhealthy {
+                                        error: "database gone".to_string(),
+                                    },
+                                };
+                            bus.route_message(response, None).await?;
+                        }
+                    }
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(20)).await;
+
+        manager.check_services_health().await;
+        assert_eq!(manager.get_restart_count(&service_id).await, Some(1));
+
+        let mut saw_error = false;
+        let mut saw_restart = false;
+        while let Ok(event) = events.try_recv() {
+            match event {
+                SystemEvent::ErrorOccurred { error, .. } => {
+                    saw_error = error == "database gone";
+                }
+                SystemEvent::ServiceRestarted { .. } => saw_restart = true,
+                _ => {}
+            }
+        }
+        assert!(saw_error);
+        assert!(saw_restart);
+
+        // The restart limit is reached, so the next failure opens the circuit
+        sleep(Duration::from_millis(20)).await;
+        manager.check_services_health().await;
+        assert_eq!(manager.get_restart_count(&service_id).await, Some(1));
+        assert!(matches!(
+            manager.get_service_status(&service_id).await,
+            Some(ServiceStatus::CircuitOpen { .. })
+        ));
+    }
+
+    fn circuit_policy() -> RestartPolicy {
+        RestartPolicy {
+            max_restart_attempts: 1,
+            restart_delay: Duration::from_millis(5),
+            backoff_multiplier: 1.0,
+            max_restart_delay: Duration::from_millis(5),
+            circuit_cooldown: Duration::from_millis(100),
+            stability_window: Duration::from_millis(30),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_circuit_reopens_when_trial_fails() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus).with_restart_policy(circuit_policy());
+
+        let runs = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let runs_clone = runs.clone();
+        let service_id = "broken-service".to_string();
+        manager
+            .start_service(service_id.clone(), move || {
+                let runs = runs_clone.clone();
+                async move {
+                    runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Err(SystemError::Unknown("boom".to_string()))
+                }
+            })
+            .await
+            .unwrap();
+
+        // Initial run and one restart, then the circuit opens
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert!(matches!(
+            manager.get_service_status(&service_id).await,
+            Some(ServiceStatus::CircuitOpen { .. })
+        ));
+
+        // A single trial after the cooldown, which fails and re-opens the circuit
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(matches!(
+            manager.get_service_status(&service_id).await,
+            Some(ServiceStatus::CircuitOpen { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_closes_after_stable_trial() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus).with_restart_policy(circuit_policy());
+
+        // Fails twice, then stays up
+        let runs = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let runs_clone = runs.clone();
+        let service_id = "recovering-service".to_string();
+        manager
+            .start_service(service_id.clone(), move || {
+                let runs = runs_clone.clone();
+                async move {
+                    if runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+                        return Err(SystemError::Unknown("boom".to_string()));
+                    }
+                    sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(manager.get_restart_count(&service_id).await, Some(0));
+        assert!(matches!(
+            manager.get_service_status(&service_id).await,
+            Some(ServiceStatus::Running)
+        ));
+    }
+
+    /// A service task that answers health checks as healthy
+    fn healthy_service(
+        bus: Arc<EventBus>,
+        id: &str,
+    ) -> impl Fn() -> futures::future::BoxFuture<'static, Result<()>> + Send + Sync + 'static {
+        let id = id.to_string();
+        move || {
+            let bus = bus.clone();
+            let id = id.clone();
+            Box::pin(async move {
+                let (_tx, mut rx) = bus.register_service(id.clone()).await?;
+                while let Some(message) = rx.recv().await {
+                    match message {
+                        ServiceMessage::ServiceHealthCheck { .. } => {
+                            let response = ServiceMessage::ServiceHealthResponse {
+                                service_id: id.clone(),
+                                status: ServiceHealth::Healthy,
+                            };
+                            bus.route_message(response, None).await?;
+                        }
+                        ServiceMessage::ShutdownService { .. } => break,
+                        _ => {}
+                    }
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_waits_for_dependencies() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus.clone())
+            .with_dependency_timeout(Duration::from_millis(300))
+            .with_health_check_timeout(Duration::from_millis(50));
+
+        // Missing dependency times out
+        let result = manager
+            .start_service_with_deps(
+                "core".to_string(),
+                vec!["llm".to_string()],
+                healthy_service(event_bus.clone(), "core"),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(SystemError::ServiceUnavailable { service }) if service == "llm"
+        ));
+        assert!(manager
+            .get_service_status(&"core".to_string())
+            .await
+            .is_none());
+
+        manager
+            .start_service(
+                "data".to_string(),
+                healthy_service(event_bus.clone(), "data"),
+            )
+            .await
+            .unwrap();
+        let result = manager
+            .start_service_with_deps(
+                "core".to_string(),
+                vec!["data".to_string()],
+                healthy_service(event_bus.clone(), "core"),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dependency_cycle_is_rejected() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus);
+
+        manager
+            .register_dependencies(&"a".to_string(), vec!["b".to_string()])
+            .unwrap();
+        manager
+            .register_dependencies(&"b".to_string(), vec!["c".to_string()])
+            .unwrap();
+
+        let result = manager.register_dependencies(&"c".to_string(), vec!["a".to_string()]);
+        match result {
+            Err(SystemError::Configuration(message)) => {
+                assert!(message.contains("c -> a -> b -> c"));
+            }
+            other => panic!("expected configuration error, got {:?}", other),
+        }
+        // The rejected edge isn't kept
+        assert!(!manager.dependencies.contains_key("c"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_dependents_first() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus.clone());
+
+        manager
+            .start_service(
+                "data".to_string(),
+                healthy_service(event_bus.clone(), "data"),
+            )
+            .await
+            .unwrap();
+        manager
+            .start_service("llm".to_string(), healthy_service(event_bus.clone(), "llm"))
+            .await
+            .unwrap();
+        manager
+            .start_service_with_deps(
+                "core".to_string(),
+                vec!["data".to_string(), "llm".to_string()],
+                healthy_service(event_bus.clone(), "core"),
+            )
+            .await
+            .unwrap();
+
+        let mut events = event_bus.subscribe_to_events();
+        manager.shutdown_all().await.unwrap();
+
+        let mut stopped = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let SystemEvent::ServiceStopped { service_id } = event {
+                stopped.push(service_id);
+            }
+        }
+        assert_eq!(stopped.first().map(String::as_str), Some("core"));
+        assert_eq!(stopped.len(), 3);
+    }
+
+    #[test]
+    fn test_shutdown_order() {
+        let mut graph = HashMap::new();
+        graph.insert(
+            "core".to_string(),
+            vec!["data".to_string(), "llm".to_string()],
+        );
+        graph.insert("llm".to_string(), vec!["data".to_string()]);
+
+        let services: Vec<ServiceId> = ["data", "llm", "core", "ui"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let order = shutdown_order(&graph, &services);
+
+        let position = |id: &str| order.iter().position(|s| s == id).unwrap();
+        assert!(position("core") < position("llm"));
+        assert!(position("llm") < position("data"));
+        assert_eq!(order.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stop_service_reports_graceful_and_aborted() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager = ServiceManager::new(event_bus.clone())
+            .with_shutdown_grace_period(Duration::from_millis(100));
+
+        manager
+            .start_service(
+                "data".to_string(),
+                healthy_service(event_bus.clone(), "data"),
+            )
+            .await
+            .unwrap();
+
+        // Registers for messages but never reads them
+        let bus = event_bus.clone();
+        manager
+            .start_service("stuck".to_string(), move || {
+                let bus = bus.clone();
+                async move {
+                    let (_tx, _rx) = bus.register_service("stuck".to_string()).await?;
+                    sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(20)).await;
+
+        let outcome = manager.stop_service(&"data".to_string()).await.unwrap();
+        assert_eq!(outcome, ShutdownOutcome::Graceful);
+
+        let outcomes = manager.shutdown_all().await.unwrap();
+        assert_eq!(
+            outcomes,
+            vec![("stuck".to_string(), ShutdownOutcome::Aborted)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_token_lets_service_finish_work() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut manager =
+            ServiceManager::new(event_bus).with_shutdown_grace_period(Duration::from_millis(500));
+
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let shutdown = manager.shutdown_token("worker");
+        let worker_finished = finished.clone();
+        manager
+            .start_service("worker".to_string(), move || {
+                let shutdown = shutdown.clone();
+                let finished = worker_finished.clone();
+                async move {
+                    shutdown.cancelled().await;
+                    // Work in flight when the shutdown came
+                    sleep(Duration::from_millis(50)).await;
+                    finished.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+
+        let outcomes = manager.shutdown_all().await.unwrap();
+        assert_eq!(
+            outcomes,
+            vec![("worker".to_string(), ShutdownOutcome::Graceful)]
+        );
+        assert!(finished.load(std::sync::atomic::Ordering::SeqCst));
+    }
 }
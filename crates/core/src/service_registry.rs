@@ -0,0 +1,239 @@
+use ai_manager_shared::{
+    ServiceId, CORE_SERVICE_ID, DATA_SERVICE_ID, EXTERNAL_SERVICE_ID, LLM_SERVICE_ID, UI_SERVICE_ID,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// Message types handled by each of the standard services. Messages are
+/// routed to these services when no other registered service handles them.
+const STANDARD_CAPABILITIES: &[(&str, &[&str])] = &[
+    (
+        LLM_SERVICE_ID,
+        &["LLMRequest", "CancelLLMRequest", "ServiceHealthCheck"],
+    ),
+    (
+        DATA_SERVICE_ID,
+        &[
+            "StoreConversation",
+            "StoreConversationSummary",
+            "LoadUserProfile",
+            "ClearConversation",
+            "CleanupConversations",
+            "UpdateUserPreferences",
+            "LoadConversationHistory",
+            "TruncateConversation",
+            "ExportConversation",
+            "StoreSenderRule",
+            "LoadSenderRules",
+            "StoreRepliedThread",
+            "LoadRepliedThreads",
+            "ServiceHealthCheck",
+        ],
+    ),
+    (
+        EXTERNAL_SERVICE_ID,
+        &[
+            "CalendarSync",
+            "EmailProcess",
+            "EmailAction",
+            "SenderRulesLoaded",
+            "RepliedThreadsLoaded",
+            "ServiceHealthCheck",
+        ],
+    ),
+    (
+        UI_SERVICE_ID,
+        &[
+            "SystemResponse",
+            "SystemError",
+            "UserProfileResponse",
+            "CalendarEventsResponse",
+            "CalendarEventCreated",
+            "EmailProcessResult",
+        ],
+    ),
+    (
+        CORE_SERVICE_ID,
+        &[
+            "UserInput",
+            "RegenerateResponse",
+            "LLMResponse",
+            "LLMResponseChunk",
+            "ConversationHistoryResponse",
+            "ConversationExport",
+            "ServiceHealthResponse",
+            "ServiceHealthCheck",
+        ],
+    ),
+];
+
+/// Message types the standard service `service_id` handles; empty for any
+/// other id
+pub fn standard_capabilities(service_id: &str) -> &'static [&'static str] {
+    STANDARD_CAPABILITIES
+        .iter()
+        .find(|(id, _)| *id == service_id)
+        .map(|(_, capabilities)| *capabilities)
+        .unwrap_or(&[])
+}
+
+/// The standard service that handles `message_type`, if any
+pub fn standard_service_for(message_type: &str) -> Option<&'static str> {
+    STANDARD_CAPABILITIES
+        .iter()
+        .find(|(_, capabilities)| capabilities.contains(&message_type))
+        .map(|(id, _)| *id)
+}
+
+/// What a service tells the bus about itself when registering
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceDescriptor {
+    pub service_id: ServiceId,
+    /// `ServiceMessage` types, as named by `message_type()`, it handles
+    pub capabilities: BTreeSet<String>,
+    pub version: Option<String>,
+}
+
+impl ServiceDescriptor {
+    /// A service handling what the standard service of the same id does
+    pub fn new(service_id: impl Into<ServiceId>) -> Self {
+        let service_id = service_id.into();
+        let capabilities = standard_capabilities(&service_id)
+            .iter()
+            .map(|capability| capability.to_string())
+            .collect();
+
+        Self {
+            service_id,
+            capabilities,
+            version: None,
+        }
+    }
+
+    /// Handle exactly `capabilities` instead
+    pub fn with_capabilities<I, S>(mut self, capabilities: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.capabilities = capabilities.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+}
+
+/// A registered service
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServiceInfo {
+    pub service_id: ServiceId,
+    pub capabilities: BTreeSet<String>,
+    pub version: Option<String>,
+    pub registered_at: DateTime<Utc>,
+}
+
+impl ServiceInfo {
+    pub fn handles(&self, message_type: &str) -> bool {
+        self.capabilities.contains(message_type)
+    }
+}
+
+/// Registered services and what they handle
+#[derive(Debug, Default)]
+pub struct ServiceRegistry {
+    services: HashMap<ServiceId, ServiceInfo>,
+}
+
+impl ServiceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `descriptor`, replacing any earlier registration of its id
+    pub fn register(&mut self, descriptor: ServiceDescriptor) -> &ServiceInfo {
+        let info = ServiceInfo {
+            service_id: descriptor.service_id.clone(),
+            capabilities: descriptor.capabilities,
+            version: descriptor.version,
+            registered_at: Utc::now(),
+        };
+        self.services.insert(descriptor.service_id.clone(), info);
+        &self.services[&descriptor.service_id]
+    }
+
+    pub fn unregister(&mut self, service_id: &str) -> Option<ServiceInfo> {
+        self.services.remove(service_id)
+    }
+
+    pub fn get(&self, service_id: &str) -> Option<&ServiceInfo> {
+        self.services.get(service_id)
+    }
+
+    /// Services handling `message_type`, earliest registered first
+    pub fn services_handling(&self, message_type: &str) -> Vec<&ServiceInfo> {
+        let mut services: Vec<&ServiceInfo> = self
+            .services
+            .values()
+            .filter(|info| info.handles(message_type))
+            .collect();
+        services.sort_by(|a, b| {
+            a.registered_at
+                .cmp(&b.registered_at)
+                .then(a.service_id.cmp(&b.service_id))
+        });
+        services
+    }
+
+    /// Every registered service, by id
+    pub fn services(&self) -> Vec<&ServiceInfo> {
+        let mut services: Vec<&ServiceInfo> = self.services.values().collect();
+        services.sort_by(|a, b| a.service_id.cmp(&b.service_id));
+        services
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_services_described() {
+        let mut registry = ServiceRegistry::new();
+        registry.register(ServiceDescriptor::new(LLM_SERVICE_ID));
+        registry.register(ServiceDescriptor::new("plugin"));
+
+        assert!(registry.get(LLM_SERVICE_ID).unwrap().handles("LLMRequest"));
+        assert!(registry.get("plugin").unwrap().capabilities.is_empty());
+        assert!(registry.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_services_handling() {
+        let mut registry = ServiceRegistry::new();
+        registry.register(ServiceDescriptor::new(LLM_SERVICE_ID));
+        registry.register(
+            ServiceDescriptor::new("llm-local")
+                .with_capabilities(["LLMRequest"])
+                .with_version("0.2.0"),
+        );
+        registry.register(ServiceDescriptor::new(DATA_SERVICE_ID));
+
+        let handling: Vec<&str> = registry
+            .services_handling("LLMRequest")
+            .iter()
+            .map(|info| info.service_id.as_str())
+            .collect();
+        assert_eq!(handling, vec![LLM_SERVICE_ID, "llm-local"]);
+
+        registry.unregister(LLM_SERVICE_ID);
+        assert_eq!(registry.services_handling("LLMRequest").len(), 1);
+        assert_eq!(
+            registry.get("llm-local").unwrap().version.as_deref(),
+            Some("0.2.0")
+        );
+    }
+}
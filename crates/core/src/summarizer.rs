@@ -0,0 +1,333 @@
+use crate::event_bus::EventBus;
+use crate::handlers::format_turn;
+use ai_manager_llm_service::PromptManager;
+use ai_manager_shared::{
+    LLMConfig, Message, Result, ServiceMessage, SummarizationConfig, SystemError,
+    CONTEXT_LOAD_TIMEOUT_SECONDS, DATA_SERVICE_ID, LLM_REQUEST_TIMEOUT, LLM_SERVICE_ID,
+    MAX_MESSAGE_HISTORY,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tracing::{debug, info, warn, Instrument};
+use uuid::Uuid;
+
+/// Condenses the older turns of long conversations into a single summary
+/// message, so context stays within the prompt budget
+pub struct ConversationSummarizer {
+    event_bus: Arc<EventBus>,
+    config: SummarizationConfig,
+    provider: String,
+    prompts: PromptManager,
+    // Users whose conversation is being summarized right now
+    in_progress: Mutex<HashSet<String>>,
+}
+
+impl ConversationSummarizer {
+    pub fn new(event_bus: Arc<EventBus>, llm_config: &LLMConfig) -> Self {
+        let config = llm_config.summarization.clone();
+        let provider = config
+            .provider
+            .clone()
+            .unwrap_or_else(|| llm_config.default_provider.clone());
+
+        Self {
+            event_bus,
+            config,
+            provider,
+            prompts: PromptManager::new(),
+            in_progress: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Summarize `user_id`'s conversation in the background if it has grown
+    /// past the threshold. Does nothing if a summary for them is underway.
+    pub fn spawn(self: &Arc<Self>, user_id: String) {
+        if !self.config.enabled || !self.lock_in_progress().insert(user_id.clone()) {
+            return;
+        }
+
+        let summarizer = self.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = summarizer.summarize_if_needed(&user_id).await {
+                    warn!("Failed to summarize conversation for '{}': {}", user_id, e);
+                }
+                summarizer.lock_in_progress().remove(&user_id);
+            }
+            // Log under the request whose reply triggered it
+            .in_current_span(),
+        );
+    }
+
+    fn lock_in_progress(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.in_progress
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace all but the newest turns of `user_id`'s conversation with a
+    /// summary if it has more messages than the threshold. Returns whether
+    /// it did.
+    pub async fn summarize_if_needed(&self, user_id: &str) -> Result<bool> {
+        let mut turns = self.load_history(user_id).await?;
+        let previous = match turns.first() {
+            Some(first) if first.is_summary() => Some(turns.remove(0)),
+            _ => None,
+        };
+
+        if turns.len() <= self.config.threshold {
+            return Ok(false);
+        }
+
+        let older = &turns[..turns.len().saturating_sub(self.config.keep_recent)];
+        let Some(last) = older.last() else {
+            return Ok(false);
+        };
+        debug!(
+            "Summarizing {} of {} messages for '{}'",
+            older.len(),
+            turns.len(),
+            user_id
+        );
+
+        // Fold the previous summary in so nothing it covered is lost
+        let transcript: Vec<String> = previous.iter().chain(older).map(format_turn).collect();
+        let prompt = self
+            .prompts
+            .render_template(
+                "summarize",
+                &HashMap::from([("content".to_string(), transcript.join("\n"))]),
+            )
+            .map_err(|missing| {
+                SystemError::Configuration(format!(
+                    "Cannot render summarize prompt: {}",
+                    missing.join(", ")
+                ))
+            })?;
+
+        let summary = self.request_summary(prompt, user_id).await?;
+        self.event_bus
+            .route_message(
+                ServiceMessage::StoreConversationSummary {
+                    user_id: user_id.to_string(),
+                    summary: Message::summary(summary),
+                    summarized_until: last.timestamp,
+                },
+                Some(DATA_SERVICE_ID.to_string()),
+            )
+            .await?;
+
+        info!(
+            "Summarized {} older messages for '{}'",
+            older.len(),
+            user_id
+        );
+        Ok(true)
+    }
+
+    async fn load_history(&self, user_id: &str) -> Result<Vec<Message>> {
+        let request = ServiceMessage::LoadConversationHistory {
+            user_id: user_id.to_string(),
+            limit: MAX_MESSAGE_HISTORY,
+            request_id: Uuid::new_v4(),
+        };
+
+        match self
+            .event_bus
+            .send_and_await_response(
+                request,
+                Some(DATA_SERVICE_ID.to_string()),
+                Duration::from_secs(CONTEXT_LOAD_TIMEOUT_SECONDS),
+            )
+            .await?
+        {
+            ServiceMessage::ConversationHistoryResponse { messages, .. } => Ok(messages),
+            other => Err(SystemError::ServiceCommunication(format!(
+                "Unexpected reply to history request: {}",
+                other.message_type()
+            ))),
+        }
+    }
+
+    async fn request_summary(&self, prompt: String, user_id: &str) -> Result<String> {
+        let request = ServiceMessage::LLMRequest {
+            prompt,
+            context: vec![],
+            provider: self.provider.clone(),
+            request_id: Uuid::new_v4(),
+            user_id: user_id.to_string(),
+            model: self.config.model.clone(),
+            temperature: None,
+            stream: false,
+        };
+
+        match self
+            .event_bus
+            .send_and_await_response(
+                request,
+                Some(LLM_SERVICE_ID.to_string()),
+                Duration::from_secs(LLM_REQUEST_TIMEOUT),
+            )
+            .await?
+        {
+            ServiceMessage::LLMResponse { content, .. } => Ok(content),
+            ServiceMessage::SystemError { code, .. } => Err(SystemError::ServiceCommunication(
+                format!("LLM service could not summarize: {:?}", code),
+            )),
+            other => Err(SystemError::ServiceCommunication(format!(
+                "Unexpected reply to summary request: {}",
+                other.message_type()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_manager_shared::{MessageRole, TokenUsage};
+    use chrono::Utc;
+
+    fn llm_config(threshold: usize, keep_recent: usize) -> LLMConfig {
+        LLMConfig {
+            default_provider: "openai".to_string(),
+            providers: HashMap::new(),
+            summarization: SummarizationConfig {
+                threshold,
+                keep_recent,
+                model: Some("gpt-4o-mini".to_string()),
+                ..SummarizationConfig::default()
+            },
+        }
+    }
+
+    fn turns(count: usize) -> Vec<Message> {
+        let start = Utc::now();
+        (0..count)
+            .map(|i| Message {
+                id: Uuid::new_v4(),
+                content: format!("turn {}", i),
+                timestamp: start + chrono::Duration::seconds(i as i64),
+                role: MessageRole::User,
+                metadata: None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_older_turns_summarized() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_data_tx, mut data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+        let (_llm_tx, mut llm_rx) = event_bus
+            .register_service(LLM_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let summarizer = ConversationSummarizer::new(event_bus.clone(), &llm_config(3, 2));
+        let job = tokio::spawn(async move { summarizer.summarize_if_needed("alice").await });
+
+        // Play the data service, then the LLM service
+        let history = turns(5);
+        let summarized_until = history[2].timestamp;
+        let request_id = match data_rx.recv().await.unwrap() {
+            ServiceMessage::LoadConversationHistory { request_id, .. } => request_id,
+            other => panic!("expected a history request, got {:?}", other),
+        };
+        event_bus
+            .route_message(
+                ServiceMessage::ConversationHistoryResponse {
+                    user_id: "alice".to_string(),
+                    messages: history,
+                    request_id,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let request_id = match llm_rx.recv().await.unwrap() {
+            ServiceMessage::LLMRequest {
+                prompt,
+                model,
+                request_id,
+                ..
+            } => {
+                assert!(prompt.contains("User: turn 2"));
+                assert!(!prompt.contains("turn 3"));
+                assert_eq!(model.as_deref(), Some("gpt-4o-mini"));
+                request_id
+            }
+            other => panic!("expected an LLM request, got {:?}", other),
+        };
+        event_bus
+            .route_message(
+                ServiceMessage::LLMResponse {
+                    content: "Alice said hello a few times.".to_string(),
+                    model: None,
+                    usage: TokenUsage {
+                        prompt_tokens: 10,
+                        completion_tokens: 5,
+                        total_tokens: 15,
+                    },
+                    request_id,
+                    user_id: "alice".to_string(),
+                    streamed: false,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(job.await.unwrap().unwrap());
+        match data_rx.recv().await.unwrap() {
+            ServiceMessage::StoreConversationSummary {
+                user_id,
+                summary,
+                summarized_until: until,
+            } => {
+                assert_eq!(user_id, "alice");
+                assert!(summary.is_summary());
+                assert_eq!(summary.content, "Alice said hello a few times.");
+                assert_eq!(until, summarized_until);
+            }
+            other => panic!("expected a summary to store, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_short_conversation_left_alone() {
+        let event_bus = Arc::new(EventBus::new());
+        let (_data_tx, mut data_rx) = event_bus
+            .register_service(DATA_SERVICE_ID.to_string())
+            .await
+            .unwrap();
+
+        let summarizer = ConversationSummarizer::new(event_bus.clone(), &llm_config(3, 2));
+        let job = tokio::spawn(async move { summarizer.summarize_if_needed("alice").await });
+
+        let request_id = match data_rx.recv().await.unwrap() {
+            ServiceMessage::LoadConversationHistory { request_id, .. } => request_id,
+            other => panic!("expected a history request, got {:?}", other),
+        };
+        // A summary plus three turns is still within the threshold
+        let mut history = vec![Message::summary("Earlier chat.".to_string())];
+        history.extend(turns(3));
+        event_bus
+            .route_message(
+                ServiceMessage::ConversationHistoryResponse {
+                    user_id: "alice".to_string(),
+                    messages: history,
+                    request_id,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(!job.await.unwrap().unwrap());
+    }
+}
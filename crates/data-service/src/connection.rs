@@ -1,13 +1,18 @@
-use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::{
+    errors::SystemError,
+    types::{DatabaseConfig, DatabaseType},
+    DATABASE_CONNECT_TIMEOUT_SECONDS, DEFAULT_DB_MAX_CONNECTIONS, DEFAULT_QUERY_TIMEOUT_SECONDS,
+    SLOW_QUERY_THRESHOLD_MS,
+};
 use async_trait::async_trait;
-use sqlx::{Column, Pool, Postgres, Row, Sqlite};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Column, ConnectOptions, Pool, Postgres, Row, Sqlite};
+use std::future::Future;
+use std::str::FromStr;
 use std::sync::Arc;
-
-#[derive(Debug, Clone)]
-pub enum DatabaseType {
-    SQLite,
-    PostgreSQL,
-}
+use std::time::{Duration, Instant};
+use tracing::warn;
 
 #[async_trait]
 pub trait DatabaseConnection: Send + Sync {
@@ -19,30 +24,58 @@ pub trait DatabaseConnection: Send + Sync {
     ) -> Result<(), SystemError>;
     async fn fetch_one_json(&self, query: &str) -> Result<Option<serde_json::Value>, SystemError>;
     async fn fetch_all_json(&self, query: &str) -> Result<Vec<serde_json::Value>, SystemError>;
+    /// Run `queries` in order in one transaction, rolling back if any fails;
+    /// returns the number of rows each query changed
+    async fn execute_in_transaction(&self, queries: &[String]) -> Result<Vec<u64>, SystemError>;
+    /// Run `guard` and then `queries` in one transaction, unless `guard`
+    /// changes no rows; then roll back and return `false`
+    async fn execute_guarded_transaction(
+        &self,
+        guard: &str,
+        queries: &[String],
+    ) -> Result<bool, SystemError>;
     async fn health_check(&self) -> Result<(), SystemError>;
 }
 
 pub struct SqliteConnection {
     pool: Pool<Sqlite>,
+    policy: QueryPolicy,
 }
 
 impl SqliteConnection {
-    pub async fn new(database_url: &str) -> Result<Self, SystemError> {
-        let pool = sqlx::SqlitePool::connect(database_url)
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, SystemError> {
+        let mut options = SqliteConnectOptions::from_str(&config.connection_string)
+            .map_err(|e| SystemError::Configuration(format!("Invalid SQLite URL: {}", e)))?;
+        if !config.enable_logging {
+            options = options.disable_statement_logging();
+        }
+
+        let (max_connections, acquire_timeout) = pool_settings(config);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .acquire_timeout(acquire_timeout)
+            .connect_with(options)
             .await
             .map_err(|e| SystemError::Database(format!("Failed to connect to SQLite: {}", e)))?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            policy: QueryPolicy::from_config(config),
+        })
     }
 }
 
 #[async_trait]
 impl DatabaseConnection for SqliteConnection {
     async fn execute(&self, query: &str) -> Result<(), SystemError> {
-        sqlx::query(query)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| SystemError::Database(format!("SQLite execute error: {}", e)))?;
+        self.policy
+            .run(query, async {
+                sqlx::query(query)
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| SystemError::Database(format!("SQLite execute error: {}", e)))
+            })
+            .await?;
         Ok(())
     }
 
@@ -56,10 +89,15 @@ impl DatabaseConnection for SqliteConnection {
     }
 
     async fn fetch_one_json(&self, query: &str) -> Result<Option<serde_json::Value>, SystemError> {
-        let row = sqlx::query(query)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| SystemError::Database(format!("SQLite fetch error: {}", e)))?;
+        let row = self
+            .policy
+            .run(query, async {
+                sqlx::query(query)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .map_err(|e| SystemError::Database(format!("SQLite fetch error: {}", e)))
+            })
+            .await?;
 
         if let Some(row) = row {
             let mut json = serde_json::Map::new();
@@ -91,10 +129,15 @@ impl DatabaseConnection for SqliteConnection {
     }
 
     async fn fetch_all_json(&self, query: &str) -> Result<Vec<serde_json::Value>, SystemError> {
-        let rows = sqlx::query(query)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| SystemError::Database(format!("SQLite fetch error: {}", e)))?;
+        let rows = self
+            .policy
+            .run(query, async {
+                sqlx::query(query)
+                    .fetch_all(&self.pool)
+                    .await
+                    .map_err(|e| SystemError::Database(format!("SQLite fetch error: {}", e)))
+            })
+            .await?;
 
         let mut results = Vec::new();
         for row in rows {
@@ -125,6 +168,61 @@ impl DatabaseConnection for SqliteConnection {
         Ok(results)
     }
 
+    async fn execute_in_transaction(&self, queries: &[String]) -> Result<Vec<u64>, SystemError> {
+        let description = transaction_description(queries);
+        self.policy
+            .run(&description, async {
+                let mut tx = self.pool.begin().await.map_err(|e| {
+                    SystemError::Database(format!("SQLite transaction error: {}", e))
+                })?;
+                let mut changed = Vec::with_capacity(queries.len());
+                for query in queries {
+                    let result = sqlx::query(query).execute(&mut *tx).await.map_err(|e| {
+                        SystemError::Database(format!("SQLite execute error: {}", e))
+                    })?;
+                    changed.push(result.rows_affected());
+                }
+                tx.commit()
+                    .await
+                    .map_err(|e| SystemError::Database(format!("SQLite commit error: {}", e)))?;
+                Ok(changed)
+            })
+            .await
+    }
+
+    async fn execute_guarded_transaction(
+        &self,
+        guard: &str,
+        queries: &[String],
+    ) -> Result<bool, SystemError> {
+        self.policy
+            .run(guard, async {
+                let mut tx = self.pool.begin().await.map_err(|e| {
+                    SystemError::Database(format!("SQLite transaction error: {}", e))
+                })?;
+                let guarded = sqlx::query(guard)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| SystemError::Database(format!("SQLite execute error: {}", e)))?;
+                if guarded.rows_affected() == 0 {
+                    tx.rollback().await.map_err(|e| {
+                        SystemError::Database(format!("SQLite rollback error: {}", e))
+                    })?;
+                    return Ok(false);
+                }
+                for query in queries {
+                    sqlx::query(query).execute(&mut *tx).await.map_err(|e| {
+                        SystemError::Database(format!("SQLite execute error: {}", e))
+                    })?;
+                }
+                tx.commit()
+                    .await
+                    .map_err(|e| SystemError::Database(format!("SQLite commit error: {}", e)))?;
+                Ok(true)
+            })
+            .await
+    }
+
     async fn health_check(&self) -> Result<(), SystemError> {
         sqlx::query("SELECT 1")
             .fetch_one(&self.pool)
@@ -136,25 +234,45 @@ impl DatabaseConnection for SqliteConnection {
 
 pub struct PostgresConnection {
     pool: Pool<Postgres>,
+    policy: QueryPolicy,
 }
 
 impl PostgresConnection {
-    pub async fn new(database_url: &str) -> Result<Self, SystemError> {
-        let pool = sqlx::PgPool::connect(database_url).await.map_err(|e| {
-            SystemError::Database(format!("Failed to connect to PostgreSQL: {}", e))
-        })?;
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, SystemError> {
+        let mut options = PgConnectOptions::from_str(&config.connection_string)
+            .map_err(|e| SystemError::Configuration(format!("Invalid PostgreSQL URL: {}", e)))?;
+        if !config.enable_logging {
+            options = options.disable_statement_logging();
+        }
 
-        Ok(Self { pool })
+        let (max_connections, acquire_timeout) = pool_settings(config);
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .acquire_timeout(acquire_timeout)
+            .connect_with(options)
+            .await
+            .map_err(|e| {
+                SystemError::Database(format!("Failed to connect to PostgreSQL: {}", e))
+            })?;
+
+        Ok(Self {
+            pool,
+            policy: QueryPolicy::from_config(config),
+        })
     }
 }
 
 #[async_trait]
 impl DatabaseConnection for PostgresConnection {
     async fn execute(&self, query: &str) -> Result<(), SystemError> {
-        sqlx::query(query)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| SystemError::Database(format!("PostgreSQL execute error: {}", e)))?;
+        self.policy
+            .run(query, async {
+                sqlx::query(query)
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| SystemError::Database(format!("PostgreSQL execute error: {}", e)))
+            })
+            .await?;
         Ok(())
     }
 
@@ -168,10 +286,15 @@ impl DatabaseConnection for PostgresConnection {
     }
 
     async fn fetch_one_json(&self, query: &str) -> Result<Option<serde_json::Value>, SystemError> {
-        let row = sqlx::query(query)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| SystemError::Database(format!("PostgreSQL fetch error: {}", e)))?;
+        let row = self
+            .policy
+            .run(query, async {
+                sqlx::query(query)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .map_err(|e| SystemError::Database(format!("PostgreSQL fetch error: {}", e)))
+            })
+            .await?;
 
         if let Some(row) = row {
             let mut json = serde_json::Map::new();
@@ -203,10 +326,14 @@ impl DatabaseConnection for PostgresConnection {
     }
 
     async fn fetch_all_json(&self, query: &str) -> Result<Vec<serde_json::Value>, SystemError> {
-        let rows = sqlx::query(query)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| SystemError::Database(format!("PostgreSQL fetch error: {}", e)))?;
+        let rows =
+            self.policy
+                .run(query, async {
+                    sqlx::query(query).fetch_all(&self.pool).await.map_err(|e| {
+                        SystemError::Database(format!("PostgreSQL fetch error: {}", e))
+                    })
+                })
+                .await?;
 
         let mut results = Vec::new();
         for row in rows {
@@ -237,6 +364,60 @@ impl DatabaseConnection for PostgresConnection {
         Ok(results)
     }
 
+    async fn execute_in_transaction(&self, queries: &[String]) -> Result<Vec<u64>, SystemError> {
+        let description = transaction_description(queries);
+        self.policy
+            .run(&description, async {
+                let mut tx = self.pool.begin().await.map_err(|e| {
+                    SystemError::Database(format!("PostgreSQL transaction error: {}", e))
+                })?;
+                let mut changed = Vec::with_capacity(queries.len());
+                for query in queries {
+                    let result = sqlx::query(query).execute(&mut *tx).await.map_err(|e| {
+                        SystemError::Database(format!("PostgreSQL execute error: {}", e))
+                    })?;
+                    changed.push(result.rows_affected());
+                }
+                tx.commit().await.map_err(|e| {
+                    SystemError::Database(format!("PostgreSQL commit error: {}", e))
+                })?;
+                Ok(changed)
+            })
+            .await
+    }
+
+    async fn execute_guarded_transaction(
+        &self,
+        guard: &str,
+        queries: &[String],
+    ) -> Result<bool, SystemError> {
+        self.policy
+            .run(guard, async {
+                let mut tx = self.pool.begin().await.map_err(|e| {
+                    SystemError::Database(format!("PostgreSQL transaction error: {}", e))
+                })?;
+                let guarded = sqlx::query(guard).execute(&mut *tx).await.map_err(|e| {
+                    SystemError::Database(format!("PostgreSQL execute error: {}", e))
+                })?;
+                if guarded.rows_affected() == 0 {
+                    tx.rollback().await.map_err(|e| {
+                        SystemError::Database(format!("PostgreSQL rollback error: {}", e))
+                    })?;
+                    return Ok(false);
+                }
+                for query in queries {
+                    sqlx::query(query).execute(&mut *tx).await.map_err(|e| {
+                        SystemError::Database(format!("PostgreSQL execute error: {}", e))
+                    })?;
+                }
+                tx.commit().await.map_err(|e| {
+                    SystemError::Database(format!("PostgreSQL commit error: {}", e))
+                })?;
+                Ok(true)
+            })
+            .await
+    }
+
     async fn health_check(&self) -> Result<(), SystemError> {
         sqlx::query("SELECT 1")
             .fetch_one(&self.pool)
@@ -246,19 +427,102 @@ impl DatabaseConnection for PostgresConnection {
     }
 }
 
+/// Longest query text included in log lines
+const MAX_LOGGED_QUERY_CHARS: usize = 200;
+
+/// Time limit and slow-query logging applied to every query
+#[derive(Debug, Clone, Copy)]
+struct QueryPolicy {
+    timeout: Duration,
+    slow_threshold: Duration,
+}
+
+impl QueryPolicy {
+    fn from_config(config: &DatabaseConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(
+                config
+                    .query_timeout_secs
+                    .unwrap_or(DEFAULT_QUERY_TIMEOUT_SECONDS),
+            ),
+            slow_threshold: Duration::from_millis(
+                config.slow_query_ms.unwrap_or(SLOW_QUERY_THRESHOLD_MS),
+            ),
+        }
+    }
+
+    /// Run `future`, the execution of `query`, failing with
+    /// `SystemError::Timeout` if it takes longer than the timeout
+    async fn run<T>(
+        &self,
+        query: &str,
+        future: impl Future<Output = Result<T, SystemError>>,
+    ) -> Result<T, SystemError> {
+        let started = Instant::now();
+        let Ok(result) = tokio::time::timeout(self.timeout, future).await else {
+            warn!(
+                "Query timed out after {:?}: {}",
+                self.timeout,
+                truncate_query(query)
+            );
+            return Err(SystemError::Timeout);
+        };
+
+        let elapsed = started.elapsed();
+        if elapsed >= self.slow_threshold {
+            warn!("Slow query took {:?}: {}", elapsed, truncate_query(query));
+        }
+        result
+    }
+}
+
+/// `query` on one line, cut to `MAX_LOGGED_QUERY_CHARS` characters
+fn truncate_query(query: &str) -> String {
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    match query.char_indices().nth(MAX_LOGGED_QUERY_CHARS) {
+        Some((end, _)) => format!("{}...", &query[..end]),
+        None => query,
+    }
+}
+
+/// How a transaction shows up in query logs
+fn transaction_description(queries: &[String]) -> String {
+    match queries.first() {
+        Some(first) => format!("transaction of {} statements: {}", queries.len(), first),
+        None => "empty transaction".to_string(),
+    }
+}
+
+/// Pool size and how long to wait for a free connection
+fn pool_settings(config: &DatabaseConfig) -> (u32, Duration) {
+    let max_connections = config
+        .max_connections
+        .unwrap_or(DEFAULT_DB_MAX_CONNECTIONS)
+        .max(1);
+    let acquire_timeout = Duration::from_secs(
+        config
+            .connect_timeout_secs
+            .unwrap_or(DATABASE_CONNECT_TIMEOUT_SECONDS),
+    );
+    (max_connections, acquire_timeout)
+}
+
 pub async fn create_connection(
-    db_type: DatabaseType,
-    database_url: &str,
+    config: &DatabaseConfig,
 ) -> Result<Arc<dyn DatabaseConnection>, SystemError> {
-    match db_type {
+    match &config.database_type {
         DatabaseType::SQLite => {
-            let conn = SqliteConnection::new(database_url).await?;
+            let conn = SqliteConnection::new(config).await?;
             Ok(Arc::new(conn))
         }
         DatabaseType::PostgreSQL => {
-            let conn = PostgresConnection::new(database_url).await?;
+            let conn = PostgresConnection::new(config).await?;
             Ok(Arc::new(conn))
         }
+        DatabaseType::External { provider } => Err(SystemError::Configuration(format!(
+            "Unsupported database provider: {}",
+            provider
+        ))),
     }
 }
 
@@ -268,7 +532,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_sqlite_connection() {
-        let conn = create_connection(DatabaseType::SQLite, ":memory:").await;
+        let conn = create_connection(&DatabaseConfig::sqlite(":memory:")).await;
         assert!(conn.is_ok());
 
         if let Ok(conn) = conn {
@@ -279,7 +543,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_sqlite_basic_operations() {
-        let conn = create_connection(DatabaseType::SQLite, ":memory:")
+        let conn = create_connection(&DatabaseConfig::sqlite(":memory:"))
             .await
             .expect("Failed to create connection");
 
@@ -301,4 +565,143 @@ mod tests {
 
         assert!(!result.is_empty());
     }
+
+    #[test]
+    fn test_pool_settings() {
+        let mut config = DatabaseConfig::sqlite(":memory:");
+        assert_eq!(
+            pool_settings(&config),
+            (
+                DEFAULT_DB_MAX_CONNECTIONS,
+                Duration::from_secs(DATABASE_CONNECT_TIMEOUT_SECONDS)
+            )
+        );
+
+        config.max_connections = Some(0);
+        config.connect_timeout_secs = Some(2);
+        assert_eq!(pool_settings(&config), (1, Duration::from_secs(2)));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_provider_rejected() {
+        let mut config = DatabaseConfig::sqlite(":memory:");
+        config.database_type = DatabaseType::External {
+            provider: "dynamo".to_string(),
+        };
+        assert!(matches!(
+            create_connection(&config).await,
+            Err(SystemError::Configuration(_))
+        ));
+    }
+
+    #[test]
+    fn test_truncate_query() {
+        assert_eq!(
+            truncate_query("SELECT *\n    FROM conversations"),
+            "SELECT * FROM conversations"
+        );
+
+        let long = format!("SELECT '{}'", "é".repeat(300));
+        let truncated = truncate_query(&long);
+        assert!(truncated.ends_with("..."));
+        assert_eq!(truncated.chars().count(), MAX_LOGGED_QUERY_CHARS + 3);
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        let policy = QueryPolicy {
+            timeout: Duration::from_millis(20),
+            slow_threshold: Duration::from_millis(5),
+        };
+
+        let result = policy
+            .run("SELECT 1", async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(result, Err(SystemError::Timeout)));
+
+        let result = policy.run("SELECT 1", async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back_on_failure() {
+        let conn = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .unwrap();
+        conn.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)")
+            .await
+            .unwrap();
+
+        let result = conn
+            .execute_in_transaction(&[
+                "INSERT INTO test (name) VALUES ('kept?')".to_string(),
+                "INSERT INTO missing (name) VALUES ('boom')".to_string(),
+            ])
+            .await;
+        assert!(result.is_err());
+        assert!(conn
+            .fetch_all_json("SELECT * FROM test")
+            .await
+            .unwrap()
+            .is_empty());
+
+        let changed = conn
+            .execute_in_transaction(&[
+                "INSERT INTO test (name) VALUES ('a'), ('b')".to_string(),
+                "INSERT INTO test (name) VALUES ('c')".to_string(),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(changed, vec![2, 1]);
+        assert_eq!(
+            conn.fetch_all_json("SELECT * FROM test")
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guarded_transaction_skipped_when_guard_changes_nothing() {
+        let conn = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .unwrap();
+        conn.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, revision INTEGER)")
+            .await
+            .unwrap();
+        conn.execute("INSERT INTO test (revision) VALUES (1)")
+            .await
+            .unwrap();
+        let insert = ["INSERT INTO test (revision) VALUES (0)".to_string()];
+
+        let applied = conn
+            .execute_guarded_transaction("UPDATE test SET revision = 2 WHERE revision = 5", &insert)
+            .await
+            .unwrap();
+        assert!(!applied);
+        assert_eq!(
+            conn.fetch_all_json("SELECT * FROM test")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let applied = conn
+            .execute_guarded_transaction("UPDATE test SET revision = 2 WHERE revision = 1", &insert)
+            .await
+            .unwrap();
+        assert!(applied);
+        assert_eq!(
+            conn.fetch_all_json("SELECT * FROM test")
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
 }
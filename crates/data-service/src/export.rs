@@ -0,0 +1,304 @@
+use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::messages::{ExportFormat, Message, MessageRole, TokenUsage};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Characters escaped wherever they appear in message text
+const INLINE_SPECIAL: &[char] = &['\\', '`', '*', '_', '[', ']', '<', '>', '|', '~'];
+
+/// Characters that start a block (heading, quote, list, rule) at the start
+/// of a line
+const LINE_START_SPECIAL: &[char] = &['#', '-', '+', '=', '>'];
+
+/// A stored conversation to render
+pub struct ConversationExport<'a> {
+    pub user_id: &'a str,
+    pub conversation_id: i64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub messages: &'a [Message],
+}
+
+/// Render a conversation as Markdown: a YAML front-matter block with the
+/// models and token usage of the replies, then one section per message.
+/// Message text is escaped so it shows as written, except for code, which
+/// is kept as is.
+pub fn conversation_markdown(conversation: &ConversationExport<'_>) -> String {
+    let mut models = BTreeSet::new();
+    let mut usage = TokenUsage {
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        total_tokens: 0,
+    };
+    for metadata in conversation
+        .messages
+        .iter()
+        .filter_map(|m| m.metadata.as_ref())
+    {
+        if let Some(model) = metadata.get("model").and_then(|v| v.as_str()) {
+            models.insert(model.to_string());
+        }
+        if let Some(reply_usage) = metadata
+            .get("token_usage")
+            .and_then(|v| serde_json::from_value::<TokenUsage>(v.clone()).ok())
+        {
+            usage.prompt_tokens += reply_usage.prompt_tokens;
+            usage.completion_tokens += reply_usage.completion_tokens;
+            usage.total_tokens += reply_usage.total_tokens;
+        }
+    }
+
+    let mut out = String::from("---\n");
+    out.push_str(&format!("user_id: {}\n", yaml_string(conversation.user_id)));
+    out.push_str(&format!(
+        "conversation_id: {}\n",
+        conversation.conversation_id
+    ));
+    if let Some(created_at) = conversation.created_at {
+        out.push_str(&format!("created_at: {}\n", created_at.to_rfc3339()));
+    }
+    if let Some(updated_at) = conversation.updated_at {
+        out.push_str(&format!("updated_at: {}\n", updated_at.to_rfc3339()));
+    }
+    out.push_str(&format!("messages: {}\n", conversation.messages.len()));
+    if !models.is_empty() {
+        out.push_str("models:\n");
+        for model in &models {
+            out.push_str(&format!("  - {}\n", yaml_string(model)));
+        }
+    }
+    out.push_str("token_usage:\n");
+    out.push_str(&format!("  prompt_tokens: {}\n", usage.prompt_tokens));
+    out.push_str(&format!(
+        "  completion_tokens: {}\n",
+        usage.completion_tokens
+    ));
+    out.push_str(&format!("  total_tokens: {}\n", usage.total_tokens));
+    out.push_str("---\n\n");
+    out.push_str(&format!(
+        "# Conversation {}\n",
+        conversation.conversation_id
+    ));
+
+    for message in conversation.messages {
+        let role = match message.role {
+            _ if message.is_summary() => "Summary of earlier messages",
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+            MessageRole::System => "System",
+        };
+        out.push_str(&format!(
+            "\n## {} · {}\n\n",
+            role,
+            message.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+        out.push_str(&escape_markdown(&message.content));
+        out.push('\n');
+    }
+    out
+}
+
+/// Escape Markdown syntax in `text` so it renders literally. Fenced code
+/// blocks and inline code spans are left untouched; a fence left open is
+/// closed so it can't swallow the rest of the document.
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut fence: Option<(char, usize)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some((marker, length)) => {
+                out.push_str(line);
+                if fence_length(trimmed, marker).is_some_and(|closing| closing >= length)
+                    && trimmed.trim_start_matches(marker).trim().is_empty()
+                {
+                    fence = None;
+                }
+            }
+            None => match ['`', '~']
+                .into_iter()
+                .find_map(|marker| fence_length(trimmed, marker).map(|n| (marker, n)))
+            {
+                Some(opening) => {
+                    fence = Some(opening);
+                    out.push_str(line);
+                }
+                None => out.push_str(&escape_line(line)),
+            },
+        }
+        out.push('\n');
+    }
+
+    if let Some((marker, length)) = fence {
+        out.push_str(&marker.to_string().repeat(length));
+        out.push('\n');
+    }
+    out.truncate(out.trim_end_matches('\n').len());
+    out
+}
+
+/// Length of the code fence `line` starts with, if it starts with three or
+/// more `marker`s
+fn fence_length(line: &str, marker: char) -> Option<usize> {
+    let length = line.chars().take_while(|c| *c == marker).count();
+    (length >= 3).then_some(length)
+}
+
+/// Escape one line outside a code block, keeping inline code spans
+fn escape_line(line: &str) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let (leading, rest) = line.split_at(indent);
+    let mut out = String::from(leading);
+
+    // Block markers only mean something at the start of a line
+    let mut chars = rest.char_indices().peekable();
+    if let Some(&(_, first)) = chars.peek() {
+        if LINE_START_SPECIAL.contains(&first) {
+            out.push('\\');
+            out.push(first);
+            chars.next();
+        } else if first.is_ascii_digit() {
+            let digits = rest.chars().take_while(char::is_ascii_digit).count();
+            if matches!(rest[digits..].chars().next(), Some('.' | ')')) {
+                out.push_str(&rest[..digits]);
+                out.push('\\');
+                for _ in 0..digits {
+                    chars.next();
+                }
+            }
+        }
+    }
+
+    while let Some((i, c)) = chars.next() {
+        if c == '`' {
+            let ticks = rest[i..].chars().take_while(|c| *c == '`').count();
+            let delimiter = "`".repeat(ticks);
+            if let Some(end) = rest[i + ticks..].find(&delimiter) {
+                let span_end = i + ticks + end + ticks;
+                out.push_str(&rest[i..span_end]);
+                while chars.peek().is_some_and(|(j, _)| *j < span_end) {
+                    chars.next();
+                }
+                continue;
+            }
+        }
+        if INLINE_SPECIAL.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Write `content` to a new file in `dir`, named after the user and the
+/// current time, returning its path
+pub async fn write_export(
+    dir: &Path,
+    user_id: &str,
+    format: ExportFormat,
+    content: &str,
+) -> Result<PathBuf, SystemError> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    // User ids are free-form; keep them out of the path structure
+    let user: String = user_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!(
+        "{}-{}.{}",
+        user,
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        format.extension()
+    ));
+    tokio::fs::write(&path, content).await?;
+    Ok(path)
+}
+
+/// `value` as a double-quoted YAML string
+fn yaml_string(value: &str) -> String {
+    // JSON strings are valid YAML
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(
+            escape_markdown("# not a heading\n*stars* and [link](x) <b>"),
+            "\\# not a heading\n\\*stars\\* and \\[link\\](x) \\<b\\>"
+        );
+        assert_eq!(
+            escape_markdown("1. first\n- item\n> quote"),
+            "1\\. first\n\\- item\n\\> quote"
+        );
+        // Inline code is kept
+        assert_eq!(escape_markdown("run `a * b` now_"), "run `a * b` now\\_");
+    }
+
+    #[test]
+    fn test_code_blocks_preserved() {
+        let text = "Try:\n```rust\nlet x = a * b; // *not* emphasis\n```\n_done_";
+        assert_eq!(
+            escape_markdown(text),
+            "Try:\n```rust\nlet x = a * b; // *not* emphasis\n```\n\\_done\\_"
+        );
+
+        // An unclosed fence is closed
+        assert_eq!(escape_markdown("```\n# code"), "```\n# code\n```");
+    }
+
+    #[test]
+    fn test_conversation_markdown() {
+        let timestamp = "2024-05-01T09:00:00Z".parse().unwrap();
+        let messages = vec![
+            Message {
+                id: Uuid::new_v4(),
+                content: "What's 2*3?".to_string(),
+                timestamp,
+                role: MessageRole::User,
+                metadata: None,
+            },
+            Message {
+                id: Uuid::new_v4(),
+                content: "6".to_string(),
+                timestamp,
+                role: MessageRole::Assistant,
+                metadata: Some(serde_json::json!({
+                    "model": "gpt-4",
+                    "token_usage": {
+                        "prompt_tokens": 7,
+                        "completion_tokens": 1,
+                        "total_tokens": 8,
+                    },
+                })),
+            },
+        ];
+
+        let markdown = conversation_markdown(&ConversationExport {
+            user_id: "alice",
+            conversation_id: 3,
+            created_at: None,
+            updated_at: None,
+            messages: &messages,
+        });
+
+        assert!(markdown.starts_with("---\nuser_id: \"alice\"\nconversation_id: 3\n"));
+        assert!(markdown.contains("models:\n  - \"gpt-4\"\n"));
+        assert!(markdown.contains("  total_tokens: 8\n---\n"));
+        assert!(markdown.contains("## User · 2024-05-01 09:00:00 UTC\n\nWhat's 2\\*3?\n"));
+        assert!(markdown.ends_with("## Assistant · 2024-05-01 09:00:00 UTC\n\n6\n"));
+    }
+}
@@ -1,17 +1,27 @@
 pub mod connection;
-mod migrations;
+pub mod export;
+pub mod migrations;
 mod models;
 pub mod repository;
+pub mod transport;
 
-use ai_manager_shared::{errors::SystemError, messages::ServiceMessage};
+use ai_manager_shared::{
+    errors::SystemError,
+    messages::{ExportFormat, SenderRule, ServiceMessage},
+    types::{DatabaseConfig, ExportConfig},
+    DATA_SERVICE_ID,
+};
 use async_trait::async_trait;
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn, Instrument};
 
-pub use connection::{DatabaseConnection, DatabaseType};
+pub use connection::DatabaseConnection;
 pub use models::*;
-pub use repository::{ConversationRepository, UserProfileRepository};
+pub use repository::{
+    ConversationRepository, RepliedThreadRepository, SenderRuleRepository, UserProfileRepository,
+};
 
 #[async_trait]
 pub trait Service {
@@ -25,55 +35,322 @@ pub struct DataService {
     connection: Arc<dyn DatabaseConnection>,
     conversation_repo: ConversationRepository,
     profile_repo: UserProfileRepository,
+    sender_rule_repo: SenderRuleRepository,
+    replied_thread_repo: RepliedThreadRepository,
+    export: ExportConfig,
+    shutdown: CancellationToken,
     tx: Option<mpsc::Sender<ServiceMessage>>,
 }
 
 impl DataService {
     pub async fn new(
-        db_type: DatabaseType,
-        database_url: &str,
+        config: &DatabaseConfig,
         tx: mpsc::Sender<ServiceMessage>,
     ) -> Result<Self, SystemError> {
-        let connection = connection::create_connection(db_type, database_url).await?;
+        let connection = connection::create_connection(config).await?;
 
-        // Run migrations
-        migrations::run_migrations(&*connection).await?;
+        // Run migrations, preferring on-disk files over the embedded defaults
+        migrations::run_migrations_from_dir(&*connection, config.migrations_path()).await?;
 
         let conversation_repo = ConversationRepository::new(connection.clone());
         let profile_repo = UserProfileRepository::new(connection.clone());
+        let sender_rule_repo = SenderRuleRepository::new(connection.clone());
+        let replied_thread_repo = RepliedThreadRepository::new(connection.clone());
 
         Ok(Self {
             connection,
             conversation_repo,
             profile_repo,
+            sender_rule_repo,
+            replied_thread_repo,
+            export: ExportConfig::default(),
+            shutdown: CancellationToken::new(),
             tx: Some(tx),
         })
     }
 
-    async fn handle_store_conversation(
+    /// Stop taking messages and exit once those queued are handled when
+    /// `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    async fn process(&mut self, message: ServiceMessage) {
+        let span = message.span();
+        if let Err(e) = self.handle_message(message).instrument(span.clone()).await {
+            span.in_scope(|| error!("Error handling message: {}", e));
+        }
+    }
+
+    /// Where large exports are written
+    pub fn with_export_config(mut self, export: ExportConfig) -> Self {
+        self.export = export;
+        self
+    }
+
+    async fn handle_append_to_conversation(
         &mut self,
         user_id: String,
         messages: Vec<ai_manager_shared::messages::Message>,
     ) -> Result<(), SystemError> {
         self.conversation_repo
-            .store_conversation(&user_id, &messages)
+            .append_to_conversation(&user_id, &messages)
+            .await?;
+        info!(
+            "Appended {} message(s) to conversation for user: {}",
+            messages.len(),
+            user_id
+        );
+        Ok(())
+    }
+
+    async fn handle_store_conversation_summary(
+        &mut self,
+        user_id: String,
+        summary: ai_manager_shared::messages::Message,
+        summarized_until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), SystemError> {
+        self.conversation_repo
+            .store_summary(&user_id, &summary, summarized_until)
+            .await?;
+        info!("Summarized older conversation for user: {}", user_id);
+        Ok(())
+    }
+
+    async fn handle_clear_conversation(&mut self, user_id: String) -> Result<(), SystemError> {
+        self.conversation_repo
+            .delete_conversations(&user_id)
+            .await?;
+        info!("Cleared conversation history for user: {}", user_id);
+        Ok(())
+    }
+
+    async fn handle_cleanup_conversations(
+        &mut self,
+        retain_days: u32,
+        max_messages: usize,
+    ) -> Result<(), SystemError> {
+        let deleted = self
+            .conversation_repo
+            .cleanup_old_conversations(retain_days)
+            .await?;
+        let trimmed = self
+            .conversation_repo
+            .trim_conversations(max_messages)
             .await?;
-        info!("Stored conversation for user: {}", user_id);
+        info!(
+            "Conversation cleanup: deleted {} idle for over {} days, trimmed {}",
+            deleted, retain_days, trimmed
+        );
         Ok(())
     }
 
-    async fn handle_load_user_profile(&mut self, user_id: String) -> Result<(), SystemError> {
-        let profile = self.profile_repo.get_profile(&user_id).await?;
+    async fn handle_load_conversation_history(
+        &mut self,
+        user_id: String,
+        limit: usize,
+        request_id: uuid::Uuid,
+    ) -> Result<(), SystemError> {
+        // Each conversation row holds several messages, so `limit` rows is
+        // always enough to cover the last `limit` messages
+        let mut messages = match self
+            .conversation_repo
+            .get_conversation_history(&user_id, Some(limit as i32))
+            .await
+        {
+            Ok(messages) => messages,
+            Err(e) => {
+                if let Some(tx) = &self.tx {
+                    tx.send(ServiceMessage::error_reply(&e, Some(request_id)))
+                        .await
+                        .map_err(|e| {
+                            SystemError::ServiceCommunication(format!(
+                                "Failed to send history error: {}",
+                                e
+                            ))
+                        })?;
+                }
+                return Err(e);
+            }
+        };
+
+        // Keep the summary, if any, ahead of the last `limit` turns
+        let summaries = messages.iter().take_while(|m| m.is_summary()).count();
+        let excess = (messages.len() - summaries).saturating_sub(limit);
+        messages.drain(summaries..summaries + excess);
 
         if let Some(tx) = &self.tx {
-            let response = ServiceMessage::UserProfileResponse { profile };
+            let response = ServiceMessage::ConversationHistoryResponse {
+                user_id,
+                messages,
+                request_id,
+            };
             tx.send(response).await.map_err(|e| {
-                SystemError::ServiceCommunication(format!("Failed to send profile response: {}", e))
+                SystemError::ServiceCommunication(format!("Failed to send history response: {}", e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop the messages after `after_message_id` and reply with what
+    /// remains. Failures, e.g. an unknown message, are reported to the
+    /// requester rather than leaving it waiting.
+    async fn handle_truncate_conversation(
+        &mut self,
+        user_id: String,
+        after_message_id: uuid::Uuid,
+        limit: usize,
+        request_id: uuid::Uuid,
+    ) -> Result<(), SystemError> {
+        match self
+            .conversation_repo
+            .truncate_after(&user_id, after_message_id)
+            .await
+        {
+            Ok(dropped) => {
+                info!(
+                    "Dropped {} messages after {} for user: {}",
+                    dropped, after_message_id, user_id
+                );
+                self.handle_load_conversation_history(user_id, limit, request_id)
+                    .await
+            }
+            Err(e) => {
+                if let Some(tx) = &self.tx {
+                    tx.send(ServiceMessage::error_reply(&e, Some(request_id)))
+                        .await
+                        .map_err(|e| {
+                            SystemError::ServiceCommunication(format!(
+                                "Failed to send truncate error: {}",
+                                e
+                            ))
+                        })?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn handle_export_conversation(
+        &mut self,
+        user_id: String,
+        conversation_id: Option<i64>,
+        format: ExportFormat,
+        spill_to_file: bool,
+        request_id: uuid::Uuid,
+    ) -> Result<(), SystemError> {
+        let exported = match self
+            .conversation_repo
+            .export(&user_id, conversation_id, format)
+            .await
+        {
+            Ok(content) if spill_to_file && content.len() > self.export.inline_max_bytes => {
+                export::write_export(&self.export.dir, &user_id, format, &content)
+                    .await
+                    .map(|path| (String::new(), Some(path.display().to_string())))
+            }
+            Ok(content) => Ok((content, None)),
+            Err(e) => Err(e),
+        };
+
+        let response = match exported {
+            Ok((content, path)) => ServiceMessage::ConversationExport {
+                user_id,
+                content,
+                path,
+                request_id,
+            },
+            Err(e) => {
+                warn!("Export for user {} failed: {}", user_id, e);
+                ServiceMessage::error_reply(&e, Some(request_id))
+            }
+        };
+
+        if let Some(tx) = &self.tx {
+            tx.send(response).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!("Failed to send export: {}", e))
+            })?;
+        }
+        Ok(())
+    }
+
+    async fn handle_update_user_preferences(
+        &mut self,
+        user_id: String,
+        preferences: serde_json::Value,
+    ) -> Result<(), SystemError> {
+        self.profile_repo
+            .merge_preferences(&user_id, &preferences)
+            .await?;
+        info!("Updated preferences for user: {}", user_id);
+        Ok(())
+    }
+
+    async fn handle_store_sender_rule(
+        &mut self,
+        address: String,
+        rule: Option<SenderRule>,
+    ) -> Result<(), SystemError> {
+        self.sender_rule_repo.set_rule(&address, rule).await?;
+        info!("Updated sender rule for {}: {:?}", address, rule);
+        Ok(())
+    }
+
+    async fn handle_load_sender_rules(&mut self) -> Result<(), SystemError> {
+        let rules = self.sender_rule_repo.rules().await?;
+
+        if let Some(tx) = &self.tx {
+            let response = ServiceMessage::SenderRulesLoaded { rules };
+            tx.send(response).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!("Failed to send sender rules: {}", e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_store_replied_thread(&mut self, thread_key: String) -> Result<(), SystemError> {
+        self.replied_thread_repo.record(&thread_key).await?;
+        info!("Recorded auto-reply to thread {:?}", thread_key);
+        Ok(())
+    }
+
+    async fn handle_load_replied_threads(&mut self) -> Result<(), SystemError> {
+        let thread_keys = self.replied_thread_repo.thread_keys().await?;
+
+        if let Some(tx) = &self.tx {
+            let response = ServiceMessage::RepliedThreadsLoaded { thread_keys };
+            tx.send(response).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!("Failed to send replied threads: {}", e))
             })?;
         }
 
         Ok(())
     }
+
+    async fn handle_load_user_profile(
+        &mut self,
+        user_id: String,
+        request_id: uuid::Uuid,
+    ) -> Result<(), SystemError> {
+        let loaded = self.profile_repo.get_or_create_profile(&user_id).await;
+
+        // Reply without a profile on failure, so the requester isn't left
+        // waiting for one
+        if let Some(tx) = &self.tx {
+            let response = ServiceMessage::UserProfileResponse {
+                profile: loaded.as_ref().ok().cloned(),
+                request_id,
+            };
+            tx.send(response).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!("Failed to send profile response: {}", e))
+            })?;
+        }
+
+        loaded.map(|_| ())
+    }
 }
 
 #[async_trait]
@@ -81,29 +358,106 @@ impl Service for DataService {
     async fn start(&mut self, mut rx: mpsc::Receiver<ServiceMessage>) -> Result<(), SystemError> {
         info!("Data Service starting...");
 
-        while let Some(message) = rx.recv().await {
-            if let Err(e) = self.handle_message(message).await {
-                error!("Error handling message: {}", e);
-            }
+        loop {
+            let message = tokio::select! {
+                message = rx.recv() => message,
+                _ = self.shutdown.cancelled() => break,
+            };
+            let Some(message) = message else {
+                warn!("Data Service message receiver closed");
+                return Ok(());
+            };
+            self.process(message).await;
         }
 
-        warn!("Data Service message receiver closed");
-        Ok(())
+        // Take no more messages, but store the ones already queued
+        rx.close();
+        while let Some(message) = rx.recv().await {
+            self.process(message).await;
+        }
+        self.shutdown().await
     }
 
     async fn handle_message(&mut self, msg: ServiceMessage) -> Result<(), SystemError> {
         match msg {
             ServiceMessage::StoreConversation { user_id, messages } => {
-                self.handle_store_conversation(user_id, messages).await
+                self.handle_append_to_conversation(user_id, messages).await
+            }
+            ServiceMessage::StoreConversationSummary {
+                user_id,
+                summary,
+                summarized_until,
+            } => {
+                self.handle_store_conversation_summary(user_id, summary, summarized_until)
+                    .await
+            }
+            ServiceMessage::LoadUserProfile {
+                user_id,
+                request_id,
+            } => self.handle_load_user_profile(user_id, request_id).await,
+            ServiceMessage::ClearConversation { user_id } => {
+                self.handle_clear_conversation(user_id).await
+            }
+            ServiceMessage::CleanupConversations {
+                retain_days,
+                max_messages,
+            } => {
+                self.handle_cleanup_conversations(retain_days, max_messages)
+                    .await
+            }
+            ServiceMessage::UpdateUserPreferences {
+                user_id,
+                preferences,
+            } => {
+                self.handle_update_user_preferences(user_id, preferences)
+                    .await
+            }
+            ServiceMessage::LoadConversationHistory {
+                user_id,
+                limit,
+                request_id,
+            } => {
+                self.handle_load_conversation_history(user_id, limit, request_id)
+                    .await
             }
-            ServiceMessage::LoadUserProfile { user_id } => {
-                self.handle_load_user_profile(user_id).await
+            ServiceMessage::TruncateConversation {
+                user_id,
+                after_message_id,
+                limit,
+                request_id,
+            } => {
+                self.handle_truncate_conversation(user_id, after_message_id, limit, request_id)
+                    .await
             }
+            ServiceMessage::ExportConversation {
+                user_id,
+                conversation_id,
+                format,
+                spill_to_file,
+                request_id,
+            } => {
+                self.handle_export_conversation(
+                    user_id,
+                    conversation_id,
+                    format,
+                    spill_to_file,
+                    request_id,
+                )
+                .await
+            }
+            ServiceMessage::StoreSenderRule { address, rule } => {
+                self.handle_store_sender_rule(address, rule).await
+            }
+            ServiceMessage::LoadSenderRules => self.handle_load_sender_rules().await,
+            ServiceMessage::StoreRepliedThread { thread_key } => {
+                self.handle_store_replied_thread(thread_key).await
+            }
+            ServiceMessage::LoadRepliedThreads => self.handle_load_replied_threads().await,
             ServiceMessage::ServiceHealthCheck { service_id: _ } => {
                 if let Some(tx) = &self.tx {
                     let health = self.health_check().await;
                     let response = ServiceMessage::ServiceHealthResponse {
-                        service_id: "data-service".to_string(),
+                        service_id: DATA_SERVICE_ID.to_string(),
                         status: health,
                     };
                     tx.send(response).await.map_err(|e| {
@@ -145,8 +499,294 @@ mod tests {
     #[tokio::test]
     async fn test_data_service_creation() {
         let (tx, _rx) = mpsc::channel(100);
-        let result = DataService::new(DatabaseType::SQLite, ":memory:", tx).await;
+        let result = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx).await;
 
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_load_conversation_history_returns_latest_messages() {
+        use ai_manager_shared::messages::{Message, MessageRole};
+
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut service = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx)
+            .await
+            .unwrap();
+
+        let start = chrono::Utc::now();
+        let messages: Vec<Message> = ["first", "second", "third"]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| Message {
+                id: uuid::Uuid::new_v4(),
+                content: content.to_string(),
+                timestamp: start + chrono::Duration::seconds(i as i64),
+                role: MessageRole::User,
+                metadata: None,
+            })
+            .collect();
+        // Stored one at a time, as the core does; each store appends
+        for message in messages {
+            service
+                .handle_message(ServiceMessage::StoreConversation {
+                    user_id: "alice".to_string(),
+                    messages: vec![message],
+                })
+                .await
+                .unwrap();
+        }
+
+        let request_id = uuid::Uuid::new_v4();
+        service
+            .handle_message(ServiceMessage::LoadConversationHistory {
+                user_id: "alice".to_string(),
+                limit: 2,
+                request_id,
+            })
+            .await
+            .unwrap();
+
+        match rx.recv().await.unwrap() {
+            ServiceMessage::ConversationHistoryResponse {
+                messages,
+                request_id: response_id,
+                ..
+            } => {
+                assert_eq!(response_id, request_id);
+                let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+                assert_eq!(contents, vec!["second", "third"]);
+            }
+            other => panic!("expected history response, got {:?}", other),
+        }
+
+        // Summarize the first two; the summary leads the history
+        service
+            .handle_message(ServiceMessage::StoreConversationSummary {
+                user_id: "alice".to_string(),
+                summary: Message::summary("first and second".to_string()),
+                summarized_until: start + chrono::Duration::seconds(1),
+            })
+            .await
+            .unwrap();
+        service
+            .handle_message(ServiceMessage::LoadConversationHistory {
+                user_id: "alice".to_string(),
+                limit: 2,
+                request_id,
+            })
+            .await
+            .unwrap();
+
+        match rx.recv().await.unwrap() {
+            ServiceMessage::ConversationHistoryResponse { messages, .. } => {
+                let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+                assert_eq!(contents, vec!["first and second", "third"]);
+                assert!(messages[0].is_summary());
+            }
+            other => panic!("expected history response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_export_written_to_file() {
+        use ai_manager_shared::messages::{Message, MessageRole};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut service = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx)
+            .await
+            .unwrap()
+            .with_export_config(ExportConfig {
+                dir: dir.path().to_path_buf(),
+                inline_max_bytes: 200,
+            });
+
+        let message = |content: String| Message {
+            id: uuid::Uuid::new_v4(),
+            content,
+            timestamp: chrono::Utc::now(),
+            role: MessageRole::User,
+            metadata: None,
+        };
+        service
+            .handle_message(ServiceMessage::StoreConversation {
+                user_id: "alice".to_string(),
+                messages: vec![message("short".to_string())],
+            })
+            .await
+            .unwrap();
+
+        let export = |spill_to_file| ServiceMessage::ExportConversation {
+            user_id: "alice".to_string(),
+            conversation_id: None,
+            format: ExportFormat::Jsonl,
+            spill_to_file,
+            request_id: uuid::Uuid::new_v4(),
+        };
+        service.handle_message(export(true)).await.unwrap();
+        match rx.recv().await.unwrap() {
+            ServiceMessage::ConversationExport { content, path, .. } => {
+                assert!(content.contains("\"short\""));
+                assert!(path.is_none());
+            }
+            other => panic!("expected an export, got {:?}", other),
+        }
+
+        service
+            .handle_message(ServiceMessage::StoreConversation {
+                user_id: "alice".to_string(),
+                messages: vec![message("long ".repeat(100))],
+            })
+            .await
+            .unwrap();
+        service.handle_message(export(true)).await.unwrap();
+        match rx.recv().await.unwrap() {
+            ServiceMessage::ConversationExport { content, path, .. } => {
+                assert!(content.is_empty());
+                let path = path.unwrap();
+                assert!(path.ends_with(".jsonl"));
+                let written = std::fs::read_to_string(path).unwrap();
+                assert_eq!(written.lines().count(), 2);
+            }
+            other => panic!("expected an export, got {:?}", other),
+        }
+
+        // Without spilling, the export is always inline
+        service.handle_message(export(false)).await.unwrap();
+        match rx.recv().await.unwrap() {
+            ServiceMessage::ConversationExport { content, path, .. } => {
+                assert!(content.contains("long long"));
+                assert!(path.is_none());
+            }
+            other => panic!("expected an export, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_messages_handled_on_shutdown() {
+        use ai_manager_shared::messages::{Message, MessageRole};
+
+        let shutdown = CancellationToken::new();
+        let (tx, mut replies) = mpsc::channel(100);
+        let mut service = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx)
+            .await
+            .unwrap()
+            .with_shutdown(shutdown.clone());
+
+        let (inbound_tx, inbound_rx) = mpsc::channel(100);
+        inbound_tx
+            .send(ServiceMessage::StoreConversation {
+                user_id: "alice".to_string(),
+                messages: vec![Message {
+                    id: uuid::Uuid::new_v4(),
+                    content: "hello".to_string(),
+                    timestamp: chrono::Utc::now(),
+                    role: MessageRole::User,
+                    metadata: None,
+                }],
+            })
+            .await
+            .unwrap();
+        inbound_tx
+            .send(ServiceMessage::LoadConversationHistory {
+                user_id: "alice".to_string(),
+                limit: 10,
+                request_id: uuid::Uuid::new_v4(),
+            })
+            .await
+            .unwrap();
+
+        shutdown.cancel();
+        service.start(inbound_rx).await.unwrap();
+
+        // Both were handled before it exited
+        match replies.recv().await.unwrap() {
+            ServiceMessage::ConversationHistoryResponse { messages, .. } => {
+                assert_eq!(messages.len(), 1)
+            }
+            other => panic!("expected history response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_profile_load_still_replies() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut service = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx)
+            .await
+            .unwrap();
+        service
+            .connection
+            .execute("DROP TABLE user_profiles")
+            .await
+            .unwrap();
+
+        let request_id = uuid::Uuid::new_v4();
+        let result = service
+            .handle_message(ServiceMessage::LoadUserProfile {
+                user_id: "alice".to_string(),
+                request_id,
+            })
+            .await;
+
+        assert!(result.is_err());
+        match rx.recv().await.unwrap() {
+            ServiceMessage::UserProfileResponse {
+                profile,
+                request_id: response_id,
+            } => {
+                assert!(profile.is_none());
+                assert_eq!(response_id, request_id);
+            }
+            other => panic!("expected profile response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_history_load_still_replies() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut service = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx)
+            .await
+            .unwrap();
+        service
+            .connection
+            .execute("DROP TABLE conversations")
+            .await
+            .unwrap();
+
+        let request_id = uuid::Uuid::new_v4();
+        let result = service
+            .handle_message(ServiceMessage::LoadConversationHistory {
+                user_id: "alice".to_string(),
+                limit: 10,
+                request_id,
+            })
+            .await;
+
+        assert!(result.is_err());
+        let reply = rx.recv().await.unwrap();
+        assert!(matches!(reply, ServiceMessage::SystemError { .. }));
+        assert_eq!(reply.in_reply_to(), Some(request_id));
+    }
+
+    #[tokio::test]
+    async fn test_health_response_uses_registered_service_id() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut service = DataService::new(&DatabaseConfig::sqlite(":memory:"), tx)
+            .await
+            .unwrap();
+
+        service
+            .handle_message(ServiceMessage::ServiceHealthCheck {
+                service_id: DATA_SERVICE_ID.to_string(),
+            })
+            .await
+            .unwrap();
+
+        match rx.recv().await.unwrap() {
+            ServiceMessage::ServiceHealthResponse { service_id, .. } => {
+                assert_eq!(service_id, DATA_SERVICE_ID)
+            }
+            other => panic!("expected health response, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,168 @@
+use ai_manager_data_service::{transport, DataService, Service};
+use ai_manager_shared::{
+    auth, errors::SystemError, messages::ServiceMessage, transport::UNIX_ADDR_PREFIX, AppConfig,
+    MessageTransport, StreamTransport, CORE_SERVICE_ID, DATA_SERVICE_ID, DEFAULT_CONFIG_PATH,
+    DEFAULT_DATA_SERVICE_ADDR, MESSAGE_QUEUE_CAPACITY, USER_CONFIG_PATH,
+};
+use config::{Config, Environment, File};
+use std::path::Path;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// Run the data service as its own process. The core connects to
+/// `server.data_service_addr`, a TCP `host:port` or `unix:<path>`, and
+/// exchanges length-prefixed JSON `ServiceMessage` frames with it. With
+/// `server.service_token` set, the core must authenticate first.
+#[tokio::main]
+async fn main() -> Result<(), SystemError> {
+    init_logging();
+
+    let app_config = load_config()?;
+
+    let (reply_tx, replies) = mpsc::channel(MESSAGE_QUEUE_CAPACITY);
+    let shutdown = CancellationToken::new();
+    let mut service = DataService::new(&app_config.database, reply_tx)
+        .await?
+        .with_export_config(app_config.export.clone())
+        .with_shutdown(shutdown.clone());
+    info!("✓ Database ready");
+
+    let (inbound_tx, inbound_rx) = mpsc::channel(MESSAGE_QUEUE_CAPACITY);
+    let service_handle = tokio::spawn(async move {
+        if let Err(e) = service.start(inbound_rx).await {
+            error!("Data service stopped: {}", e);
+        }
+    });
+
+    let addr = app_config
+        .server
+        .data_service_addr
+        .unwrap_or_else(|| DEFAULT_DATA_SERVICE_ADDR.to_string());
+    let listener = Listener::bind(&addr).await?;
+    info!("Data service listening on {}", addr);
+    let service_token = app_config.server.service_token;
+    if service_token.is_none() {
+        warn!("No server.service_token configured; accepting unauthenticated connections");
+    }
+
+    tokio::select! {
+        result = accept_connections(listener, service_token.as_deref(), inbound_tx, replies) => result?,
+        _ = tokio::signal::ctrl_c() => info!("📴 Shutdown signal received"),
+    }
+
+    // Let the service store what it has already received
+    shutdown.cancel();
+    if let Err(e) = service_handle.await {
+        error!("Data service did not shut down cleanly: {}", e);
+    }
+    Ok(())
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    async fn bind(addr: &str) -> Result<Self, SystemError> {
+        #[cfg(unix)]
+        if let Some(path) = addr.strip_prefix(UNIX_ADDR_PREFIX) {
+            // A socket file left behind by a previous run would block the bind
+            let _ = std::fs::remove_file(path);
+            return Ok(Self::Unix(tokio::net::UnixListener::bind(path)?));
+        }
+
+        Ok(Self::Tcp(TcpListener::bind(addr).await?))
+    }
+
+    /// Wait for the next connection, returning it with a description of the peer
+    async fn accept(&self) -> Result<(Box<dyn MessageTransport>, String), SystemError> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                Ok((Box::new(StreamTransport::new(stream)), peer.to_string()))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((
+                    Box::new(StreamTransport::new(stream)),
+                    "unix socket".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Serve one core at a time, waiting for it to reconnect if it goes away.
+/// With a `service_token`, peers that can't prove they are the core holding
+/// it are disconnected before any message is read.
+async fn accept_connections(
+    listener: Listener,
+    service_token: Option<&str>,
+    inbound: mpsc::Sender<ServiceMessage>,
+    mut replies: mpsc::Receiver<ServiceMessage>,
+) -> Result<(), SystemError> {
+    loop {
+        let (mut connection, peer) = listener.accept().await?;
+        if let Some(token) = service_token {
+            match auth::authenticate(connection.as_mut(), DATA_SERVICE_ID, token).await {
+                Ok(service_id) if service_id == CORE_SERVICE_ID => {}
+                Ok(service_id) => {
+                    warn!(
+                        "Rejected {}: authenticated as '{}', not the core",
+                        peer, service_id
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Rejected {}: {}", peer, e);
+                    continue;
+                }
+            }
+        }
+        info!("Core connected from {}", peer);
+
+        if let Err(e) =
+            transport::serve_connection(connection.as_mut(), &inbound, &mut replies).await
+        {
+            warn!("Connection to {} failed: {}", peer, e);
+        }
+        info!("Core at {} disconnected", peer);
+    }
+}
+
+/// Layer the default and user config files and `AI_MANAGER_*` environment
+/// variables, like the core does
+fn load_config() -> Result<AppConfig, SystemError> {
+    let mut builder = Config::builder();
+    for path in [DEFAULT_CONFIG_PATH, USER_CONFIG_PATH] {
+        if Path::new(path).exists() {
+            builder = builder.add_source(File::from(Path::new(path)));
+        }
+    }
+
+    builder
+        .add_source(
+            Environment::with_prefix("AI_MANAGER")
+                .prefix_separator("_")
+                .separator("__"),
+        )
+        .build()
+        .and_then(Config::try_deserialize)
+        .map_err(|e| SystemError::Configuration(format!("Failed to load config: {}", e)))
+}
+
+fn init_logging() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "ai_manager_data_service=info".into()),
+        )
+        .with_target(false)
+        .init();
+}
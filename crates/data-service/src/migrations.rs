@@ -1,5 +1,8 @@
 use crate::connection::DatabaseConnection;
 use ai_manager_shared::errors::SystemError;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
 
 const MIGRATIONS: &[&str] = &[
     // Migration 001: Create conversations table
@@ -33,9 +36,289 @@ const MIGRATIONS: &[&str] = &[
     r#"
     CREATE INDEX IF NOT EXISTS idx_user_profiles_email ON user_profiles(email);
     "#,
+    // Migration 006: Create message_embeddings table for semantic search
+    r#"
+    CREATE TABLE IF NOT EXISTS message_embeddings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        content TEXT NOT NULL,
+        embedding TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    "#,
+    // Migration 007: Create discarded_messages table for regenerated replies
+    r#"
+    CREATE TABLE IF NOT EXISTS discarded_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        messages TEXT NOT NULL,
+        discarded_at TEXT NOT NULL
+    );
+    "#,
+    // Migration 008: Create sender_rules table for the email allow/blocklist
+    r#"
+    CREATE TABLE IF NOT EXISTS sender_rules (
+        address TEXT PRIMARY KEY,
+        rule TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    "#,
+    // Migration 009: Create replied_threads table
+    r#"
+    CREATE TABLE IF NOT EXISTS replied_threads (
+        thread_key TEXT PRIMARY KEY,
+        replied_at TEXT NOT NULL
+    );
+    "#,
+];
+
+// Down migrations, index-aligned with MIGRATIONS
+const DOWN_MIGRATIONS: &[&str] = &[
+    // Migration 001: Drop conversations table
+    r#"
+    DROP TABLE IF EXISTS conversations;
+    "#,
+    // Migration 002: Drop user_profiles table
+    r#"
+    DROP TABLE IF EXISTS user_profiles;
+    "#,
+    // Migration 003: Drop indexes
+    r#"
+    DROP INDEX IF EXISTS idx_conversations_user_id;
+    "#,
+    r#"
+    DROP INDEX IF EXISTS idx_conversations_created_at;
+    "#,
+    r#"
+    DROP INDEX IF EXISTS idx_user_profiles_email;
+    "#,
+    // Migration 006: Drop message_embeddings table
+    r#"
+    DROP TABLE IF EXISTS message_embeddings;
+    "#,
+    // Migration 007: Drop discarded_messages table
+    r#"
+    DROP TABLE IF EXISTS discarded_messages;
+    "#,
+    // Migration 008: Drop sender_rules table
+    r#"
+    DROP TABLE IF EXISTS sender_rules;
+    "#,
+    // Migration 009: Drop replied_threads table
+    r#"
+    DROP TABLE IF EXISTS replied_threads;
+    "#,
 ];
 
+fn migration_name(index: usize) -> String {
+    format!("migration_{:03}", index + 1)
+}
+
+/// Parse the zero-based index of an embedded migration out of a name like
+/// `migration_003`. File migrations are numbered separately, so their names
+/// have none.
+fn embedded_migration_index(name: &str) -> Option<usize> {
+    name.strip_prefix("migration_")?
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)
+}
+
+/// A migration to apply, with the SQL that reverts it if it can be rolled
+/// back
+struct Migration<'a> {
+    name: String,
+    up: &'a str,
+    down: Option<&'a str>,
+}
+
 pub async fn run_migrations(connection: &dyn DatabaseConnection) -> Result<(), SystemError> {
+    let migrations: Vec<Migration> = MIGRATIONS
+        .iter()
+        .enumerate()
+        .map(|(index, sql)| Migration {
+            name: migration_name(index),
+            up: sql,
+            down: DOWN_MIGRATIONS.get(index).copied(),
+        })
+        .collect();
+
+    apply_migrations(connection, &migrations).await
+}
+
+/// A migration loaded from an `NNN_name.up.sql` file, and the matching
+/// `NNN_name.down.sql` if there is one
+#[derive(Debug, Clone)]
+struct MigrationFile {
+    version: u32,
+    file_name: String,
+    sql: String,
+    down_sql: Option<String>,
+}
+
+/// Apply the embedded migrations, then those from `NNN_name.up.sql` files
+/// in `path`.
+///
+/// Files are applied in order of their numeric prefix and recorded in the
+/// `migrations` table by file name, along with the SQL of their
+/// `NNN_name.down.sql` counterpart, if any, to roll them back with. Only the
+/// embedded migrations are applied when the directory does not exist.
+pub async fn run_migrations_from_dir<P: AsRef<Path>>(
+    connection: &dyn DatabaseConnection,
+    path: P,
+) -> Result<(), SystemError> {
+    let path = path.as_ref();
+
+    if !path.is_dir() {
+        tracing::info!(
+            "Migrations directory {} not found, using embedded migrations only",
+            path.display()
+        );
+        return run_migrations(connection).await;
+    }
+
+    // Reject a broken directory before changing anything
+    let migration_files = load_migration_files(path).await?;
+    run_migrations(connection).await?;
+
+    let migrations: Vec<Migration> = migration_files
+        .iter()
+        .map(|m| Migration {
+            name: m.file_name.clone(),
+            up: &m.sql,
+            down: m.down_sql.as_deref(),
+        })
+        .collect();
+
+    apply_migrations(connection, &migrations).await
+}
+
+/// Apply the given migrations in order, skipping those already recorded and
+/// verifying the checksum of each applied migration.
+async fn apply_migrations(
+    connection: &dyn DatabaseConnection,
+    migrations: &[Migration<'_>],
+) -> Result<(), SystemError> {
+    ensure_migrations_table(connection).await?;
+
+    // Get applied migrations with their recorded checksums
+    let applied_migrations = applied_migrations(connection).await?;
+
+    for migration in migrations {
+        let migration_name = &migration.name;
+        let checksum = migration_checksum(migration.up);
+
+        match applied_migrations.get(migration_name) {
+            Some(Some(recorded)) if *recorded != checksum => {
+                return Err(SystemError::Database(format!(
+                    "Migration {} was modified after being applied (recorded checksum {}, current checksum {})",
+                    migration_name, recorded, checksum
+                )));
+            }
+            Some(Some(_)) => {}
+            Some(None) => {
+                // Applied before checksums were tracked; record the current one
+                let update_sql = format!(
+                    "UPDATE migrations SET checksum = '{}' WHERE migration_name = '{}'",
+                    checksum,
+                    migration_name.replace('\'', "''")
+                );
+                connection.execute(&update_sql).await?;
+            }
+            None => {
+                connection.execute(migration.up).await?;
+                record_migration(connection, migration, &checksum).await?;
+
+                tracing::info!("Applied migration: {}", migration_name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// SHA-256 checksum of a migration's SQL, hex encoded
+fn migration_checksum(sql: &str) -> String {
+    format!("{:x}", Sha256::digest(sql.as_bytes()))
+}
+
+async fn load_migration_files(path: &Path) -> Result<Vec<MigrationFile>, SystemError> {
+    let mut entries = tokio::fs::read_dir(path).await.map_err(|e| {
+        SystemError::Database(format!(
+            "Failed to read migrations directory {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let mut migrations: Vec<MigrationFile> = Vec::new();
+    let mut down_migrations: HashMap<String, String> = HashMap::new();
+
+    while let Some(entry) = entries.next_entry().await.map_err(|e| {
+        SystemError::Database(format!("Failed to read migrations directory entry: {}", e))
+    })? {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        if let Some(stem) = file_name.strip_suffix(".down.sql") {
+            let sql = read_migration_file(&entry.path(), &file_name).await?;
+            down_migrations.insert(stem.to_string(), sql);
+            continue;
+        }
+        let Some(stem) = file_name.strip_suffix(".up.sql") else {
+            continue;
+        };
+
+        let version = stem
+            .split('_')
+            .next()
+            .and_then(|prefix| prefix.parse::<u32>().ok())
+            .ok_or_else(|| {
+                SystemError::Database(format!(
+                    "Migration file {} must start with a numeric version prefix",
+                    file_name
+                ))
+            })?;
+
+        if let Some(existing) = migrations.iter().find(|m| m.version == version) {
+            return Err(SystemError::Database(format!(
+                "Duplicate migration version {:03}: {} and {}",
+                version, existing.file_name, file_name
+            )));
+        }
+
+        let sql = read_migration_file(&entry.path(), &file_name).await?;
+
+        migrations.push(MigrationFile {
+            version,
+            file_name,
+            sql,
+            down_sql: None,
+        });
+    }
+
+    for migration in &mut migrations {
+        let stem = migration.file_name.trim_end_matches(".up.sql");
+        migration.down_sql = down_migrations.remove(stem);
+    }
+    if let Some(stem) = down_migrations.keys().next() {
+        return Err(SystemError::Database(format!(
+            "Down migration {}.down.sql has no matching {}.up.sql",
+            stem, stem
+        )));
+    }
+
+    migrations.sort_by_key(|m| m.version);
+    Ok(migrations)
+}
+
+async fn read_migration_file(path: &Path, file_name: &str) -> Result<String, SystemError> {
+    tokio::fs::read_to_string(path).await.map_err(|e| {
+        SystemError::Database(format!("Failed to read migration {}: {}", file_name, e))
+    })
+}
+
+async fn ensure_migrations_table(connection: &dyn DatabaseConnection) -> Result<(), SystemError> {
     // Create migrations table to track applied migrations
     connection
         .execute(
@@ -43,45 +326,140 @@ pub async fn run_migrations(connection: &dyn DatabaseConnection) -> Result<(), S
         CREATE TABLE IF NOT EXISTS migrations (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             migration_name TEXT NOT NULL,
-            applied_at TEXT NOT NULL
+            applied_at TEXT NOT NULL,
+            checksum TEXT,
+            down_sql TEXT
         );
         "#,
         )
         .await?;
 
-    // Get list of applied migrations
-    let applied_migrations = match connection
-        .fetch_all_json("SELECT migration_name FROM migrations")
-        .await
-    {
-        Ok(rows) => rows
-            .into_iter()
-            .filter_map(|row| {
-                row.get("migration_name")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-            })
-            .collect::<Vec<_>>(),
-        Err(_) => Vec::new(),
+    // Tables created before checksums and down migrations were tracked lack
+    // the columns
+    for column in ["checksum", "down_sql"] {
+        let has_column = connection
+            .fetch_one_json(&format!("SELECT {} FROM migrations LIMIT 1", column))
+            .await
+            .is_ok();
+        if !has_column {
+            connection
+                .execute(&format!(
+                    "ALTER TABLE migrations ADD COLUMN {} TEXT",
+                    column
+                ))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Applied migration names mapped to their recorded checksum, if any
+async fn applied_migrations(
+    connection: &dyn DatabaseConnection,
+) -> Result<HashMap<String, Option<String>>, SystemError> {
+    let rows = connection
+        .fetch_all_json("SELECT migration_name, checksum FROM migrations")
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let name = row.get("migration_name")?.as_str()?.to_string();
+            let checksum = row
+                .get("checksum")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            Some((name, checksum))
+        })
+        .collect())
+}
+
+async fn record_migration(
+    connection: &dyn DatabaseConnection,
+    migration: &Migration<'_>,
+    checksum: &str,
+) -> Result<(), SystemError> {
+    // Record migration as applied
+    let down_sql = migration.down.map_or("NULL".to_string(), |sql| {
+        format!("'{}'", sql.replace('\'', "''"))
+    });
+    let insert_sql = format!(
+        "INSERT INTO migrations (migration_name, applied_at, checksum, down_sql) VALUES ('{}', '{}', '{}', {})",
+        migration.name.replace('\'', "''"),
+        chrono::Utc::now().to_rfc3339(),
+        checksum,
+        down_sql
+    );
+    connection.execute(&insert_sql).await
+}
+
+/// Roll back the most recently applied migration with the down SQL recorded
+/// for it, or the embedded one for migrations applied before down SQL was
+/// recorded. File migrations without a `.down.sql` can't be rolled back.
+///
+/// Returns the name of the migration that was rolled back, or `None` if no
+/// migrations have been applied.
+pub async fn rollback_last_migration(
+    connection: &dyn DatabaseConnection,
+) -> Result<Option<String>, SystemError> {
+    let last = connection
+        .fetch_one_json("SELECT migration_name, down_sql FROM migrations ORDER BY id DESC LIMIT 1")
+        .await?;
+
+    let Some(row) = last else {
+        return Ok(None);
     };
 
-    // Apply migrations that haven't been applied yet
-    for (index, migration_sql) in MIGRATIONS.iter().enumerate() {
-        let migration_name = format!("migration_{:03}", index + 1);
+    let migration_name = row
+        .get("migration_name")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| SystemError::Database("Missing migration_name field".to_string()))?
+        .to_string();
+
+    // NULL reads back as an empty string
+    let recorded = row
+        .get("down_sql")
+        .and_then(|v| v.as_str())
+        .filter(|sql| !sql.trim().is_empty());
+    let embedded = embedded_migration_index(&migration_name)
+        .and_then(|index| DOWN_MIGRATIONS.get(index).copied());
+    let down_sql = recorded.or(embedded).ok_or_else(|| {
+        SystemError::Database(format!("No down migration defined for {}", migration_name))
+    })?;
 
-        if !applied_migrations.contains(&migration_name) {
-            connection.execute(migration_sql).await?;
+    connection.execute(down_sql).await?;
 
-            // Record migration as applied
-            let insert_sql = format!(
-                "INSERT INTO migrations (migration_name, applied_at) VALUES ('{}', '{}')",
-                migration_name,
-                chrono::Utc::now().to_rfc3339()
-            );
-            connection.execute(&insert_sql).await?;
+    let delete_sql = format!(
+        "DELETE FROM migrations WHERE migration_name = '{}'",
+        migration_name.replace('\'', "''")
+    );
+    connection.execute(&delete_sql).await?;
+
+    tracing::info!("Rolled back migration: {}", migration_name);
+    Ok(Some(migration_name))
+}
 
-            tracing::info!("Applied migration: {}", migration_name);
+/// Roll back applied migrations, most recent first, until none of the
+/// embedded migrations past the first `target_index` remain. File
+/// migrations applied after those are rolled back along with them, as they
+/// may depend on them.
+///
+/// A `target_index` of 0 rolls back every embedded migration.
+pub async fn rollback_to(
+    connection: &dyn DatabaseConnection,
+    target_index: usize,
+) -> Result<(), SystemError> {
+    loop {
+        let applied = applied_migrations(connection).await?;
+        let remaining = applied
+            .keys()
+            .filter_map(|name| embedded_migration_index(name))
+            .any(|index| index >= target_index);
+        if !remaining {
+            break;
         }
+        rollback_last_migration(connection).await?;
     }
 
     Ok(())
@@ -90,11 +468,12 @@ pub async fn run_migrations(connection: &dyn DatabaseConnection) -> Result<(), S
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::connection::{create_connection, DatabaseType};
+    use crate::connection::create_connection;
+    use ai_manager_shared::types::DatabaseConfig;
 
     #[tokio::test]
     async fn test_migrations() {
-        let connection = create_connection(DatabaseType::SQLite, ":memory:")
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
             .await
             .expect("Failed to create connection");
 
@@ -124,7 +503,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_migration_idempotency() {
-        let connection = create_connection(DatabaseType::SQLite, ":memory:")
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
             .await
             .expect("Failed to create connection");
 
@@ -144,4 +523,310 @@ mod tests {
         // Should have exactly as many migrations as we defined
         assert_eq!(migration_count.len(), 1);
     }
+
+    async fn table_names(connection: &dyn DatabaseConnection) -> Vec<String> {
+        connection
+            .fetch_all_json("SELECT name FROM sqlite_master WHERE type='table' ORDER BY name")
+            .await
+            .expect("Failed to query tables")
+            .into_iter()
+            .filter_map(|row| {
+                row.get("name")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_rollback_last_migration() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        run_migrations(&*connection).await.unwrap();
+
+        let rolled_back = rollback_last_migration(&*connection).await.unwrap();
+        assert_eq!(rolled_back, Some("migration_009".to_string()));
+
+        let applied = connection
+            .fetch_all_json("SELECT migration_name FROM migrations")
+            .await
+            .unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len() - 1);
+
+        // Re-running migrations re-applies the rolled back one
+        run_migrations(&*connection).await.unwrap();
+        let applied = connection
+            .fetch_all_json("SELECT migration_name FROM migrations")
+            .await
+            .unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_to() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        run_migrations(&*connection).await.unwrap();
+
+        rollback_to(&*connection, 1).await.unwrap();
+        let tables = table_names(&*connection).await;
+        assert!(tables.contains(&"conversations".to_string()));
+        assert!(!tables.contains(&"user_profiles".to_string()));
+
+        rollback_to(&*connection, 0).await.unwrap();
+        let tables = table_names(&*connection).await;
+        assert!(!tables.contains(&"conversations".to_string()));
+
+        // Nothing left to roll back
+        let rolled_back = rollback_last_migration(&*connection).await.unwrap();
+        assert!(rolled_back.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_from_dir() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("002_create_notes.up.sql"),
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, item_id INTEGER);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("001_create_items.up.sql"),
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT);",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a migration").unwrap();
+
+        run_migrations_from_dir(&*connection, dir.path())
+            .await
+            .unwrap();
+        // Second run must be a no-op
+        run_migrations_from_dir(&*connection, dir.path())
+            .await
+            .unwrap();
+
+        let tables = table_names(&*connection).await;
+        assert!(tables.contains(&"conversations".to_string()));
+        assert!(tables.contains(&"items".to_string()));
+        assert!(tables.contains(&"notes".to_string()));
+
+        let applied = applied_migrations(&*connection).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len() + 2);
+        assert!(applied.contains_key("001_create_items.up.sql"));
+        assert!(applied.contains_key("002_create_notes.up.sql"));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_file_migrations() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("001_create_items.up.sql"),
+            "CREATE TABLE items (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("002_create_notes.up.sql"),
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("002_create_notes.down.sql"),
+            "DROP TABLE notes;",
+        )
+        .unwrap();
+
+        run_migrations_from_dir(&*connection, dir.path())
+            .await
+            .unwrap();
+
+        let rolled_back = rollback_last_migration(&*connection).await.unwrap();
+        assert_eq!(rolled_back, Some("002_create_notes.up.sql".to_string()));
+        let tables = table_names(&*connection).await;
+        assert!(!tables.contains(&"notes".to_string()));
+
+        // 001 has no down migration
+        let result = rollback_last_migration(&*connection).await;
+        match result {
+            Err(SystemError::Database(message)) => {
+                assert!(message.contains("No down migration defined for 001_create_items.up.sql"));
+            }
+            other => panic!("Expected missing down migration error, got {:?}", other),
+        }
+        assert!(table_names(&*connection)
+            .await
+            .contains(&"items".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_to_with_file_migrations() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, sql) in [
+            (
+                "001_create_items.up.sql",
+                "CREATE TABLE items (id INTEGER PRIMARY KEY);",
+            ),
+            ("001_create_items.down.sql", "DROP TABLE items;"),
+            (
+                "002_create_notes.up.sql",
+                "CREATE TABLE notes (id INTEGER PRIMARY KEY);",
+            ),
+            ("002_create_notes.down.sql", "DROP TABLE notes;"),
+        ] {
+            std::fs::write(dir.path().join(name), sql).unwrap();
+        }
+
+        run_migrations_from_dir(&*connection, dir.path())
+            .await
+            .unwrap();
+
+        // Every embedded migration is kept, so the file migrations are too
+        rollback_to(&*connection, MIGRATIONS.len()).await.unwrap();
+        let applied = applied_migrations(&*connection).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len() + 2);
+
+        // The file migrations were applied after the last embedded one, so
+        // they are undone before it
+        rollback_to(&*connection, MIGRATIONS.len() - 1)
+            .await
+            .unwrap();
+        let applied = applied_migrations(&*connection).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len() - 1);
+        assert!(!applied.contains_key("001_create_items.up.sql"));
+        assert!(!applied.contains_key(&migration_name(MIGRATIONS.len() - 1)));
+        let tables = table_names(&*connection).await;
+        assert!(!tables.contains(&"items".to_string()));
+        assert!(!tables.contains(&"notes".to_string()));
+        assert!(tables.contains(&"conversations".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_down_migration_without_up_rejected() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("001_create_items.down.sql"),
+            "DROP TABLE items;",
+        )
+        .unwrap();
+
+        let result = run_migrations_from_dir(&*connection, dir.path()).await;
+        assert!(matches!(result, Err(SystemError::Database(_))));
+    }
+
+    #[tokio::test]
+    async fn test_modified_migration_is_detected() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        let dir = tempfile::tempdir().unwrap();
+        let migration_path = dir.path().join("001_create_items.up.sql");
+        std::fs::write(
+            &migration_path,
+            "CREATE TABLE items (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+
+        run_migrations_from_dir(&*connection, dir.path())
+            .await
+            .unwrap();
+
+        // Edit the already-applied migration
+        std::fs::write(
+            &migration_path,
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT);",
+        )
+        .unwrap();
+
+        let result = run_migrations_from_dir(&*connection, dir.path()).await;
+        match result {
+            Err(SystemError::Database(message)) => {
+                assert!(message.contains(
+                    "Migration 001_create_items.up.sql was modified after being applied"
+                ));
+            }
+            other => panic!("Expected checksum mismatch error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_from_dir_rejects_duplicate_versions() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("001_create_items.up.sql"),
+            "CREATE TABLE items (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("001_create_other.up.sql"),
+            "CREATE TABLE other (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+
+        let result = run_migrations_from_dir(&*connection, dir.path()).await;
+        match result {
+            Err(SystemError::Database(message)) => {
+                assert!(message.contains("Duplicate migration version 001"));
+            }
+            other => panic!("Expected duplicate version error, got {:?}", other),
+        }
+        assert!(table_names(&*connection).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_migrations_table_is_an_error() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        connection
+            .execute("CREATE TABLE migrations (id INTEGER PRIMARY KEY, applied_at TEXT)")
+            .await
+            .unwrap();
+
+        // Not knowing what was applied must not mean applying everything
+        let result = run_migrations(&*connection).await;
+        assert!(matches!(result, Err(SystemError::Database(_))));
+        assert!(!table_names(&*connection)
+            .await
+            .contains(&"conversations".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_from_missing_dir_uses_embedded() {
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
+            .await
+            .expect("Failed to create connection");
+
+        run_migrations_from_dir(&*connection, "does/not/exist")
+            .await
+            .unwrap();
+
+        let tables = table_names(&*connection).await;
+        assert!(tables.contains(&"conversations".to_string()));
+        assert!(tables.contains(&"user_profiles".to_string()));
+    }
 }
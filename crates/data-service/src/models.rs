@@ -31,6 +31,14 @@ pub struct ConversationMessage {
     pub metadata: Option<String>, // JSON serialized metadata
 }
 
+/// A stored message ranked against a query embedding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticMatch {
+    pub message_id: Uuid,
+    pub content: String,
+    pub score: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageRole {
     User,
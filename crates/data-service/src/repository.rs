@@ -1,64 +1,154 @@
 use crate::connection::DatabaseConnection;
-use crate::models::UserProfile;
+use crate::export::{conversation_markdown, ConversationExport};
+use crate::models::{SemanticMatch, UserProfile};
 use ai_manager_shared::errors::SystemError;
-use chrono::Utc;
+use ai_manager_shared::{
+    Clock, ExportFormat, SenderRule, SystemClock, DISCARDED_BRANCH_RETAIN_HOURS,
+    MAX_REPLIED_THREADS,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::info;
 
+/// Rows per multi-row INSERT in bulk imports
+const BULK_INSERT_ROWS: usize = 500;
+
+/// Messages per conversation written by a JSON-lines import
+const IMPORT_CHUNK_MESSAGES: usize = 1000;
+
+/// Messages appended by one `json_insert` call, as SQLite caps the
+/// arguments a function takes
+const APPEND_CHUNK_MESSAGES: usize = 50;
+
+/// Conversations read at a time when trimming them all
+const TRIM_PAGE_ROWS: usize = 100;
+
+/// Times a conversation rewrite is retried when another writer changed the
+/// conversation since it was read
+const CONVERSATION_WRITE_ATTEMPTS: usize = 5;
+
+/// A user's current conversation as read for a rewrite
+struct CurrentConversation {
+    id: i64,
+    /// The messages as stored, which a rewrite checks are unchanged
+    stored: String,
+    messages: Vec<ai_manager_shared::messages::Message>,
+}
+
+/// Conversations are written without holding any lock in this process, so
+/// several repositories or data service processes can share a database. A
+/// user's current conversation is their most recently updated one. Appends
+/// are a single transaction that starts by writing, so SQLite runs them one
+/// at a time, and rewrites only apply if the stored messages haven't
+/// changed since they were read.
 pub struct ConversationRepository {
     connection: Arc<dyn DatabaseConnection>,
+    clock: Arc<dyn Clock>,
 }
 
 impl ConversationRepository {
     pub fn new(connection: Arc<dyn DatabaseConnection>) -> Self {
-        Self { connection }
+        Self {
+            connection,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Timestamp writes and measure retention with `clock` instead of the
+    /// system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    pub async fn store_conversation(
+    /// Append `messages` to the user's current conversation, starting one
+    /// if they have none
+    pub async fn append_to_conversation(
         &self,
         user_id: &str,
         messages: &[ai_manager_shared::messages::Message],
     ) -> Result<(), SystemError> {
+        let now = self.clock.now().to_rfc3339();
+        let user = user_id.replace('\'', "''");
+        let mut appended = Vec::with_capacity(messages.len());
+        for message in messages {
+            let message_json = serde_json::to_string(message).map_err(|e| {
+                SystemError::Database(format!("Failed to serialize messages: {}", e))
+            })?;
+            // Escape single quotes
+            appended.push(format!(
+                "'$[#]', json('{}')",
+                message_json.replace('\'', "''")
+            ));
+        }
         let messages_json = serde_json::to_string(messages)
             .map_err(|e| SystemError::Database(format!("Failed to serialize messages: {}", e)))?;
 
-        let now = Utc::now().to_rfc3339();
-
-        // Check if conversation exists for this user
-        let existing_query = format!(
-            "SELECT id FROM conversations WHERE user_id = '{}' ORDER BY updated_at DESC LIMIT 1",
-            user_id
-        );
+        let mut queries: Vec<String> = appended
+            .chunks(APPEND_CHUNK_MESSAGES)
+            .map(|chunk| {
+                format!(
+                    "UPDATE conversations SET messages = json_insert(messages, {}), updated_at = '{}' WHERE id = ({})",
+                    chunk.join(", "),
+                    now,
+                    current_conversation_id(&user)
+                )
+            })
+            .collect();
+        queries.push(format!(
+            "INSERT INTO conversations (user_id, messages, created_at, updated_at) SELECT '{}', '{}', '{}', '{}' WHERE NOT EXISTS (SELECT 1 FROM conversations WHERE user_id = '{}')",
+            user,
+            messages_json.replace('\'', "''"), // Escape single quotes
+            now,
+            now,
+            user
+        ));
+        self.connection.execute_in_transaction(&queries).await?;
+        Ok(())
+    }
 
-        let existing = self.connection.fetch_one_json(&existing_query).await?;
+    /// Replace the user's messages up to and including `summarized_until`,
+    /// along with any earlier summary, with `summary`
+    pub async fn store_summary(
+        &self,
+        user_id: &str,
+        summary: &ai_manager_shared::messages::Message,
+        summarized_until: DateTime<Utc>,
+    ) -> Result<(), SystemError> {
+        self.append_to_conversation(user_id, &[]).await?;
 
-        if let Some(row) = existing {
-            // Update existing conversation
-            let conversation_id = row.get("id").and_then(|v| v.as_str()).ok_or_else(|| {
-                SystemError::Database("Failed to get conversation ID".to_string())
+        for _ in 0..CONVERSATION_WRITE_ATTEMPTS {
+            let conversation = self.current_conversation(user_id).await?.ok_or_else(|| {
+                SystemError::Database(format!("No current conversation for {}", user_id))
             })?;
 
-            let update_query = format!(
-                "UPDATE conversations SET messages = '{}', updated_at = '{}' WHERE id = {}",
-                messages_json.replace('\'', "''"), // Escape single quotes
-                now,
-                conversation_id
-            );
-            self.connection.execute(&update_query).await?;
-        } else {
-            // Create new conversation
-            let insert_query = format!(
-                "INSERT INTO conversations (user_id, messages, created_at, updated_at) VALUES ('{}', '{}', '{}', '{}')",
-                user_id,
-                messages_json.replace('\'', "''"), // Escape single quotes
-                now,
-                now
+            let mut messages = vec![summary.clone()];
+            messages.extend(
+                conversation.messages.into_iter().filter(|message| {
+                    !message.is_summary() && message.timestamp > summarized_until
+                }),
             );
-            self.connection.execute(&insert_query).await?;
+            let rewrite = rewrite_query(
+                conversation.id,
+                &conversation.stored,
+                &messages,
+                Some(self.clock.now()),
+            )?;
+            if self
+                .connection
+                .execute_guarded_transaction(&rewrite, &[])
+                .await?
+            {
+                return Ok(());
+            }
         }
-
-        Ok(())
+        Err(rewrite_conflict(user_id))
     }
 
+    /// The user's messages oldest first, preceded by the latest summary of
+    /// older ones if there is one. `limit` caps the conversation rows read.
     pub async fn get_conversation_history(
         &self,
         user_id: &str,
@@ -67,7 +157,8 @@ impl ConversationRepository {
         let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
         let query = format!(
             "SELECT messages FROM conversations WHERE user_id = '{}' ORDER BY updated_at DESC{}",
-            user_id, limit_clause
+            user_id.replace('\'', "''"),
+            limit_clause
         );
 
         let rows = self.connection.fetch_all_json(&query).await?;
@@ -76,32 +167,598 @@ impl ConversationRepository {
 
         for row in rows {
             if let Some(messages_str) = row.get("messages").and_then(|v| v.as_str()) {
-                let messages: Vec<ai_manager_shared::messages::Message> =
-                    serde_json::from_str(messages_str).map_err(|e| {
-                        SystemError::Database(format!("Failed to deserialize messages: {}", e))
-                    })?;
-                all_messages.extend(messages);
+                all_messages.extend(parse_messages(messages_str)?);
             }
         }
 
-        Ok(all_messages)
+        let (summaries, mut turns): (Vec<_>, Vec<_>) = all_messages
+            .into_iter()
+            .partition(|message| message.is_summary());
+        turns.sort_by_key(|message| message.timestamp);
+
+        let mut history: Vec<_> = summaries
+            .into_iter()
+            .max_by_key(|summary| summary.timestamp)
+            .into_iter()
+            .collect();
+        history.extend(turns);
+        Ok(history)
+    }
+
+    /// One of the user's conversations as Markdown, see
+    /// [`conversation_markdown`]. `None` exports the most recently updated one.
+    pub async fn export_markdown(
+        &self,
+        user_id: &str,
+        conversation_id: Option<i64>,
+    ) -> Result<String, SystemError> {
+        let filter = conversation_id
+            .map(|id| format!(" AND id = {}", id))
+            .unwrap_or_default();
+        let query = format!(
+            "SELECT id, messages, created_at, updated_at FROM conversations WHERE user_id = '{}'{} ORDER BY updated_at DESC LIMIT 1",
+            user_id.replace('\'', "''"),
+            filter
+        );
+
+        let Some(row) = self.connection.fetch_one_json(&query).await? else {
+            return Err(SystemError::InvalidInput(match conversation_id {
+                Some(id) => format!("Conversation {} not found", id),
+                None => "No conversation to export".to_string(),
+            }));
+        };
+
+        let id = conversation_id_of(&row)?;
+        let messages = match row.get("messages").and_then(|v| v.as_str()) {
+            Some(messages_str) => parse_messages(messages_str)?,
+            None => Vec::new(),
+        };
+        let time = |column: &str| {
+            row.get(column)
+                .and_then(|v| v.as_str())
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|v| v.with_timezone(&Utc))
+        };
+
+        Ok(conversation_markdown(&ConversationExport {
+            user_id,
+            conversation_id: id,
+            created_at: time("created_at"),
+            updated_at: time("updated_at"),
+            messages: &messages,
+        }))
+    }
+
+    /// The user's conversations in `format`, see
+    /// [`ServiceMessage::ExportConversation`](ai_manager_shared::ServiceMessage::ExportConversation)
+    pub async fn export(
+        &self,
+        user_id: &str,
+        conversation_id: Option<i64>,
+        format: ExportFormat,
+    ) -> Result<String, SystemError> {
+        if format == ExportFormat::Markdown {
+            return self.export_markdown(user_id, conversation_id).await;
+        }
+
+        let messages = self.stored_messages(user_id, conversation_id).await?;
+        let serialized = match format {
+            ExportFormat::Jsonl => messages
+                .iter()
+                .map(|message| serde_json::to_string(message).map(|line| line + "\n"))
+                .collect(),
+            _ => serde_json::to_string_pretty(&messages),
+        };
+        serialized
+            .map_err(|e| SystemError::Serialization(format!("Failed to serialize messages: {}", e)))
+    }
+
+    /// Messages of the user's conversations, or of just `conversation_id`,
+    /// oldest conversation first
+    async fn stored_messages(
+        &self,
+        user_id: &str,
+        conversation_id: Option<i64>,
+    ) -> Result<Vec<ai_manager_shared::messages::Message>, SystemError> {
+        let filter = conversation_id
+            .map(|id| format!(" AND id = {}", id))
+            .unwrap_or_default();
+        let query = format!(
+            "SELECT messages FROM conversations WHERE user_id = '{}'{} ORDER BY created_at, id",
+            user_id.replace('\'', "''"),
+            filter
+        );
+        let rows = self.connection.fetch_all_json(&query).await?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            if let Some(messages_str) = row.get("messages").and_then(|v| v.as_str()) {
+                messages.extend(parse_messages(messages_str)?);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// The conversation new messages of the user are appended to
+    async fn current_conversation(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<CurrentConversation>, SystemError> {
+        let query = format!(
+            "SELECT id, messages FROM conversations WHERE id = ({})",
+            current_conversation_id(&user_id.replace('\'', "''"))
+        );
+
+        let Some(row) = self.connection.fetch_one_json(&query).await? else {
+            return Ok(None);
+        };
+
+        let id = conversation_id_of(&row)?;
+        let stored = row
+            .get("messages")
+            .and_then(|v| v.as_str())
+            .unwrap_or("[]")
+            .to_string();
+        let messages = parse_messages(&stored)?;
+
+        Ok(Some(CurrentConversation {
+            id,
+            stored,
+            messages,
+        }))
+    }
+
+    /// Drop the user's messages after `message_id` in their current
+    /// conversation. The dropped messages stay recoverable through
+    /// `discarded_branches` for `DISCARDED_BRANCH_RETAIN_HOURS`. Returns how
+    /// many were dropped.
+    pub async fn truncate_after(
+        &self,
+        user_id: &str,
+        message_id: uuid::Uuid,
+    ) -> Result<usize, SystemError> {
+        let not_found = || {
+            SystemError::InvalidInput(format!(
+                "Message {} is not in the current conversation",
+                message_id
+            ))
+        };
+
+        for _ in 0..CONVERSATION_WRITE_ATTEMPTS {
+            let conversation = self
+                .current_conversation(user_id)
+                .await?
+                .ok_or_else(not_found)?;
+            let mut messages = conversation.messages;
+            let position = messages
+                .iter()
+                .position(|message| message.id == message_id)
+                .ok_or_else(not_found)?;
+
+            let discarded = messages.split_off(position + 1);
+            if discarded.is_empty() {
+                return Ok(0);
+            }
+            let discarded_json = serde_json::to_string(&discarded).map_err(|e| {
+                SystemError::Database(format!("Failed to serialize messages: {}", e))
+            })?;
+
+            let rewrite = rewrite_query(
+                conversation.id,
+                &conversation.stored,
+                &messages,
+                Some(self.clock.now()),
+            )?;
+            let discard = format!(
+                "INSERT INTO discarded_messages (user_id, messages, discarded_at) VALUES ('{}', '{}', '{}')",
+                user_id.replace('\'', "''"),
+                discarded_json.replace('\'', "''"), // Escape single quotes
+                self.clock.now().to_rfc3339()
+            );
+            if self
+                .connection
+                .execute_guarded_transaction(&rewrite, &[discard])
+                .await?
+            {
+                return Ok(discarded.len());
+            }
+        }
+        Err(rewrite_conflict(user_id))
+    }
+
+    /// Messages dropped by `truncate_after` within the last
+    /// `DISCARDED_BRANCH_RETAIN_HOURS`, most recently dropped first
+    pub async fn discarded_branches(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<Vec<ai_manager_shared::messages::Message>>, SystemError> {
+        let cutoff = (self.clock.now()
+            - chrono::Duration::hours(DISCARDED_BRANCH_RETAIN_HOURS.into()))
+        .to_rfc3339();
+        let query = format!(
+            "SELECT messages FROM discarded_messages WHERE user_id = '{}' AND discarded_at >= '{}' ORDER BY discarded_at DESC, id DESC",
+            user_id.replace('\'', "''"),
+            cutoff
+        );
+
+        let mut branches = Vec::new();
+        for row in self.connection.fetch_all_json(&query).await? {
+            if let Some(messages_str) = row.get("messages").and_then(|v| v.as_str()) {
+                branches.push(parse_messages(messages_str)?);
+            }
+        }
+        Ok(branches)
+    }
+
+    /// Delete all of the user's conversations, embeddings and discarded
+    /// messages, or none of them if any delete fails
+    pub async fn delete_conversations(&self, user_id: &str) -> Result<(), SystemError> {
+        let user = user_id.replace('\'', "''");
+        let queries: Vec<String> = ["conversations", "message_embeddings", "discarded_messages"]
+            .iter()
+            .map(|table| format!("DELETE FROM {} WHERE user_id = '{}'", table, user))
+            .collect();
+        self.connection.execute_in_transaction(&queries).await?;
+        Ok(())
+    }
+
+    /// Store each user's messages as a new conversation, all in one
+    /// transaction, for imports and restores. Conversations keep the times of
+    /// their first and last message. Returns the number of messages written.
+    pub async fn store_conversations_bulk(
+        &self,
+        conversations: Vec<(String, Vec<ai_manager_shared::messages::Message>)>,
+    ) -> Result<usize, SystemError> {
+        let mut written = 0;
+        let mut rows = Vec::with_capacity(conversations.len());
+        for (user_id, messages) in &conversations {
+            let (Some(first), Some(last)) = (
+                messages.iter().map(|m| m.timestamp).min(),
+                messages.iter().map(|m| m.timestamp).max(),
+            ) else {
+                continue;
+            };
+            let messages_json = serde_json::to_string(messages).map_err(|e| {
+                SystemError::Database(format!("Failed to serialize messages: {}", e))
+            })?;
+
+            rows.push(format!(
+                "('{}', '{}', '{}', '{}')",
+                user_id.replace('\'', "''"),
+                messages_json.replace('\'', "''"), // Escape single quotes
+                first.to_rfc3339(),
+                last.to_rfc3339()
+            ));
+            written += messages.len();
+        }
+
+        let queries: Vec<String> = rows
+            .chunks(BULK_INSERT_ROWS)
+            .map(|chunk| {
+                format!(
+                    "INSERT INTO conversations (user_id, messages, created_at, updated_at) VALUES {}",
+                    chunk.join(", ")
+                )
+            })
+            .collect();
+        if !queries.is_empty() {
+            self.connection.execute_in_transaction(&queries).await?;
+        }
+        Ok(written)
+    }
+
+    /// Write the user's stored messages to `writer` as JSON lines, one
+    /// conversation at a time, oldest first. Returns the number written.
+    pub async fn export_jsonl<W>(&self, user_id: &str, writer: &mut W) -> Result<usize, SystemError>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let mut exported = 0;
+        for message in self.stored_messages(user_id, None).await? {
+            let mut line = serde_json::to_vec(&message).map_err(|e| {
+                SystemError::Serialization(format!("Failed to serialize message: {}", e))
+            })?;
+            line.push(b'\n');
+            writer.write_all(&line).await?;
+            exported += 1;
+        }
+        writer.flush().await?;
+        Ok(exported)
+    }
+
+    /// Read messages written by `export_jsonl` and store them as the user's,
+    /// in conversations of up to `IMPORT_CHUNK_MESSAGES` messages. Blank
+    /// lines are skipped. Returns the number imported.
+    pub async fn import_jsonl<R>(&self, user_id: &str, reader: R) -> Result<usize, SystemError>
+    where
+        R: AsyncBufRead + Unpin + Send,
+    {
+        let mut lines = reader.lines();
+        let mut chunk = Vec::with_capacity(IMPORT_CHUNK_MESSAGES);
+        let mut imported = 0;
+        let mut line_number = 0;
+
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message = serde_json::from_str(&line).map_err(|e| {
+                SystemError::Serialization(format!(
+                    "Invalid message on line {}: {}",
+                    line_number, e
+                ))
+            })?;
+            chunk.push(message);
+
+            if chunk.len() == IMPORT_CHUNK_MESSAGES {
+                imported += self
+                    .store_conversations_bulk(vec![(
+                        user_id.to_string(),
+                        std::mem::take(&mut chunk),
+                    )])
+                    .await?;
+            }
+        }
+        imported += self
+            .store_conversations_bulk(vec![(user_id.to_string(), chunk)])
+            .await?;
+        Ok(imported)
+    }
+
+    /// Delete conversations and embeddings not updated for `retain_days`
+    /// days, and messages discarded over `DISCARDED_BRANCH_RETAIN_HOURS`
+    /// ago; returns the number of conversations deleted
+    pub async fn cleanup_old_conversations(&self, retain_days: u32) -> Result<usize, SystemError> {
+        let cutoff = (self.clock.now() - chrono::Duration::days(retain_days.into())).to_rfc3339();
+
+        let discarded_cutoff = (self.clock.now()
+            - chrono::Duration::hours(DISCARDED_BRANCH_RETAIN_HOURS.into()))
+        .to_rfc3339();
+        let queries = vec![
+            format!("DELETE FROM conversations WHERE updated_at < '{}'", cutoff),
+            format!(
+                "DELETE FROM message_embeddings WHERE created_at < '{}'",
+                cutoff
+            ),
+            format!(
+                "DELETE FROM discarded_messages WHERE discarded_at < '{}'",
+                discarded_cutoff
+            ),
+        ];
+        let changed = self.connection.execute_in_transaction(&queries).await?;
+        Ok(changed[0] as usize)
+    }
+
+    /// Trim every conversation to its last `max_messages` messages, keeping
+    /// its summary; returns the number of conversations trimmed
+    pub async fn trim_conversations(&self, max_messages: usize) -> Result<usize, SystemError> {
+        let mut trimmed = 0;
+        let mut after = 0;
+        loop {
+            let rows = self
+                .connection
+                .fetch_all_json(&format!(
+                    "SELECT id, messages FROM conversations WHERE id > {} ORDER BY id LIMIT {}",
+                    after, TRIM_PAGE_ROWS
+                ))
+                .await?;
+            for row in &rows {
+                let id = conversation_id_of(row)?;
+                after = after.max(id);
+                if self.trim_conversation(id, row, max_messages).await? {
+                    trimmed += 1;
+                }
+            }
+            if rows.len() < TRIM_PAGE_ROWS {
+                return Ok(trimmed);
+            }
+        }
+    }
+
+    /// Trim the conversation `row` to its last `max_messages` messages;
+    /// returns whether it was trimmed
+    async fn trim_conversation(
+        &self,
+        id: i64,
+        row: &serde_json::Value,
+        max_messages: usize,
+    ) -> Result<bool, SystemError> {
+        let Some(messages_str) = row.get("messages").and_then(|v| v.as_str()) else {
+            return Ok(false);
+        };
+
+        let (mut messages, turns): (Vec<_>, Vec<_>) = parse_messages(messages_str)?
+            .into_iter()
+            .partition(|message| message.is_summary());
+        if turns.len() <= max_messages {
+            return Ok(false);
+        }
+        messages.extend(turns.into_iter().rev().take(max_messages).rev());
+
+        // Trimming isn't activity, so updated_at stays as it was. A
+        // conversation written to since it was read is trimmed next time.
+        let rewrite = rewrite_query(id, messages_str, &messages, None)?;
+        self.connection
+            .execute_guarded_transaction(&rewrite, &[])
+            .await
+    }
+
+    /// Store the embedding of a message for later semantic search
+    pub async fn store_embedding(
+        &self,
+        user_id: &str,
+        message_id: uuid::Uuid,
+        content: &str,
+        embedding: &[f32],
+    ) -> Result<(), SystemError> {
+        let embedding_json = serde_json::to_string(embedding)
+            .map_err(|e| SystemError::Database(format!("Failed to serialize embedding: {}", e)))?;
+
+        let query = format!(
+            "INSERT INTO message_embeddings (user_id, message_id, content, embedding, created_at) VALUES ('{}', '{}', '{}', '{}', '{}')",
+            user_id.replace('\'', "''"),
+            message_id,
+            content.replace('\'', "''"),
+            embedding_json,
+            self.clock.now().to_rfc3339()
+        );
+        self.connection.execute(&query).await
+    }
+
+    /// The `k` stored messages of a user most similar to `query`, an embedding
+    /// from the same model the messages were embedded with, best match first
+    pub async fn semantic_search(
+        &self,
+        user_id: &str,
+        query: &[f32],
+        k: usize,
+    ) -> Result<Vec<SemanticMatch>, SystemError> {
+        let sql = format!(
+            "SELECT message_id, content, embedding FROM message_embeddings WHERE user_id = '{}'",
+            user_id.replace('\'', "''")
+        );
+        let rows = self.connection.fetch_all_json(&sql).await?;
+
+        let mut matches = Vec::with_capacity(rows.len());
+        for row in rows {
+            let embedding: Vec<f32> = row
+                .get("embedding")
+                .and_then(|v| v.as_str())
+                .map(serde_json::from_str)
+                .transpose()
+                .map_err(|e| {
+                    SystemError::Database(format!("Failed to deserialize embedding: {}", e))
+                })?
+                .unwrap_or_default();
+            let message_id = row
+                .get("message_id")
+                .and_then(|v| v.as_str())
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| SystemError::Database("Invalid message_id field".to_string()))?;
+            let content = row
+                .get("content")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+
+            matches.push(SemanticMatch {
+                message_id,
+                content,
+                score: cosine_similarity(query, &embedding),
+            });
+        }
+
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(k);
+        Ok(matches)
+    }
+}
+
+fn parse_messages(
+    messages_str: &str,
+) -> Result<Vec<ai_manager_shared::messages::Message>, SystemError> {
+    serde_json::from_str(messages_str)
+        .map_err(|e| SystemError::Database(format!("Failed to deserialize messages: {}", e)))
+}
+
+/// The `id` of a conversation row, which the JSON row conversion reports
+/// as a number
+fn conversation_id_of(row: &serde_json::Value) -> Result<i64, SystemError> {
+    match row.get("id") {
+        Some(serde_json::Value::Number(id)) => id.as_i64(),
+        Some(serde_json::Value::String(id)) => id.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| SystemError::Database("Failed to get conversation ID".to_string()))
+}
+
+/// Subquery for the id of the current conversation of `user`, a user id
+/// with its quotes already escaped
+fn current_conversation_id(user: &str) -> String {
+    format!(
+        "SELECT id FROM conversations WHERE user_id = '{}' ORDER BY updated_at DESC, id DESC LIMIT 1",
+        user
+    )
+}
+
+/// Query replacing the messages of conversation `id`, which changes no rows
+/// unless they are still `stored`. `updated_at` is left as it was when
+/// `None`.
+fn rewrite_query(
+    id: i64,
+    stored: &str,
+    messages: &[ai_manager_shared::messages::Message],
+    updated_at: Option<DateTime<Utc>>,
+) -> Result<String, SystemError> {
+    let messages_json = serde_json::to_string(messages)
+        .map_err(|e| SystemError::Database(format!("Failed to serialize messages: {}", e)))?;
+    let updated_at = updated_at
+        .map(|now| format!(", updated_at = '{}'", now.to_rfc3339()))
+        .unwrap_or_default();
+
+    Ok(format!(
+        "UPDATE conversations SET messages = '{}'{} WHERE id = {} AND messages = '{}'",
+        messages_json.replace('\'', "''"), // Escape single quotes
+        updated_at,
+        id,
+        stored.replace('\'', "''")
+    ))
+}
+
+fn rewrite_conflict(user_id: &str) -> SystemError {
+    SystemError::Database(format!(
+        "Conversation of {} kept changing while being rewritten",
+        user_id
+    ))
+}
+
+/// Cosine similarity of two vectors; 0.0 if their lengths differ or either
+/// is all zeros
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
     }
 }
 
 pub struct UserProfileRepository {
     connection: Arc<dyn DatabaseConnection>,
+    clock: Arc<dyn Clock>,
 }
 
 impl UserProfileRepository {
     pub fn new(connection: Arc<dyn DatabaseConnection>) -> Self {
-        Self { connection }
+        Self {
+            connection,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Timestamp new and updated profiles with `clock` instead of the
+    /// system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
     pub async fn get_profile(
         &self,
         user_id: &str,
     ) -> Result<Option<ai_manager_shared::messages::UserProfile>, SystemError> {
-        let query = format!("SELECT * FROM user_profiles WHERE id = '{}'", user_id);
+        let query = format!(
+            "SELECT * FROM user_profiles WHERE id = '{}'",
+            user_id.replace('\'', "''")
+        );
 
         let row = self.connection.fetch_one_json(&query).await?;
 
@@ -151,6 +808,32 @@ impl UserProfileRepository {
         }
     }
 
+    /// The user's profile, creating an empty one on first contact. Safe to
+    /// call concurrently for the same user.
+    pub async fn get_or_create_profile(
+        &self,
+        user_id: &str,
+    ) -> Result<ai_manager_shared::messages::UserProfile, SystemError> {
+        if let Some(profile) = self.get_profile(user_id).await? {
+            return Ok(profile);
+        }
+
+        let now = self.clock.now().to_rfc3339();
+        let query = format!(
+            "INSERT INTO user_profiles (id, name, preferences, created_at, updated_at) \
+             VALUES ('{}', NULL, '{{}}', '{}', '{}') ON CONFLICT (id) DO NOTHING",
+            user_id.replace('\'', "''"),
+            now,
+            now
+        );
+        self.connection.execute(&query).await?;
+        info!("Created profile for new user: {}", user_id);
+
+        self.get_profile(user_id).await?.ok_or_else(|| {
+            SystemError::Database(format!("Profile for {} missing after creation", user_id))
+        })
+    }
+
     pub async fn create_profile(
         &self,
         profile: &ai_manager_shared::messages::UserProfile,
@@ -161,8 +844,8 @@ impl UserProfileRepository {
 
         let query = format!(
             "INSERT INTO user_profiles (id, name, preferences, created_at, updated_at) VALUES ('{}', '{}', '{}', '{}', '{}')",
-            profile.id,
-            profile.name.as_deref().unwrap_or(""),
+            profile.id.replace('\'', "''"),
+            profile.name.as_deref().unwrap_or("").replace('\'', "''"),
             preferences_json.replace('\'', "''"), // Escape single quotes
             profile.created_at.to_rfc3339(),
             profile.updated_at.to_rfc3339()
@@ -182,34 +865,165 @@ impl UserProfileRepository {
 
         let query = format!(
             "UPDATE user_profiles SET name = '{}', preferences = '{}', updated_at = '{}' WHERE id = '{}'",
-            profile.name.as_deref().unwrap_or(""),
+            profile.name.as_deref().unwrap_or("").replace('\'', "''"),
             preferences_json.replace('\'', "''"), // Escape single quotes
             profile.updated_at.to_rfc3339(),
-            profile.id
+            profile.id.replace('\'', "''")
         );
 
         self.connection.execute(&query).await?;
         Ok(())
     }
 
+    /// Merge `updates` into the user's preferences, creating their profile
+    /// if they have none. Keys set to `null` are removed.
+    pub async fn merge_preferences(
+        &self,
+        user_id: &str,
+        updates: &serde_json::Value,
+    ) -> Result<(), SystemError> {
+        let updates = updates.as_object().ok_or_else(|| {
+            SystemError::InvalidInput("Preferences must be a JSON object".to_string())
+        })?;
+
+        let mut profile = self.get_or_create_profile(user_id).await?;
+
+        if !profile.preferences.is_object() {
+            profile.preferences = serde_json::json!({});
+        }
+        if let Some(preferences) = profile.preferences.as_object_mut() {
+            for (key, value) in updates {
+                if value.is_null() {
+                    preferences.remove(key);
+                } else {
+                    preferences.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        profile.updated_at = self.clock.now();
+
+        self.update_profile(&profile).await
+    }
+
     pub async fn delete_profile(&self, user_id: &str) -> Result<(), SystemError> {
-        let query = format!("DELETE FROM user_profiles WHERE id = '{}'", user_id);
+        let query = format!(
+            "DELETE FROM user_profiles WHERE id = '{}'",
+            user_id.replace('\'', "''")
+        );
         self.connection.execute(&query).await?;
         Ok(())
     }
 }
 
+/// Email senders that are always or never treated as spam, by lowercase
+/// address
+pub struct SenderRuleRepository {
+    connection: Arc<dyn DatabaseConnection>,
+}
+
+impl SenderRuleRepository {
+    pub fn new(connection: Arc<dyn DatabaseConnection>) -> Self {
+        Self { connection }
+    }
+
+    /// Set the rule for `address`, or drop it if `rule` is `None`
+    pub async fn set_rule(
+        &self,
+        address: &str,
+        rule: Option<SenderRule>,
+    ) -> Result<(), SystemError> {
+        let address = address.trim().to_lowercase().replace('\'', "''");
+        let mut queries = vec![format!(
+            "DELETE FROM sender_rules WHERE address = '{}'",
+            address
+        )];
+        if let Some(rule) = rule {
+            queries.push(format!(
+                "INSERT INTO sender_rules (address, rule, updated_at) VALUES ('{}', '{}', '{}')",
+                address,
+                rule.as_str(),
+                Utc::now().to_rfc3339()
+            ));
+        }
+        self.connection.execute_in_transaction(&queries).await?;
+        Ok(())
+    }
+
+    pub async fn rules(&self) -> Result<HashMap<String, SenderRule>, SystemError> {
+        let rows = self
+            .connection
+            .fetch_all_json("SELECT address, rule FROM sender_rules")
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let address = row.get("address")?.as_str()?;
+                let rule = SenderRule::from_name(row.get("rule")?.as_str()?)?;
+                Some((address.to_string(), rule))
+            })
+            .collect())
+    }
+}
+
+/// Email threads auto-replied to, so each gets at most one reply even
+/// across restarts
+pub struct RepliedThreadRepository {
+    connection: Arc<dyn DatabaseConnection>,
+}
+
+impl RepliedThreadRepository {
+    pub fn new(connection: Arc<dyn DatabaseConnection>) -> Self {
+        Self { connection }
+    }
+
+    /// Record a reply to `thread_key`, forgetting the oldest threads beyond
+    /// `MAX_REPLIED_THREADS`
+    pub async fn record(&self, thread_key: &str) -> Result<(), SystemError> {
+        let queries = vec![
+            format!(
+                "INSERT INTO replied_threads (thread_key, replied_at) VALUES ('{}', '{}') \
+                 ON CONFLICT (thread_key) DO NOTHING",
+                thread_key.replace('\'', "''"),
+                Utc::now().to_rfc3339()
+            ),
+            format!(
+                "DELETE FROM replied_threads WHERE thread_key NOT IN \
+                 (SELECT thread_key FROM replied_threads ORDER BY replied_at DESC LIMIT {})",
+                MAX_REPLIED_THREADS
+            ),
+        ];
+        self.connection.execute_in_transaction(&queries).await?;
+        Ok(())
+    }
+
+    /// Threads replied to, oldest first
+    pub async fn thread_keys(&self) -> Result<Vec<String>, SystemError> {
+        let rows = self
+            .connection
+            .fetch_all_json("SELECT thread_key FROM replied_threads ORDER BY replied_at")
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| Some(row.get("thread_key")?.as_str()?.to_string()))
+            .collect())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::connection::{create_connection, DatabaseType};
+    use crate::connection::create_connection;
     use crate::migrations::run_migrations;
     use ai_manager_shared::messages::{Message, MessageRole, UserProfile};
+    use ai_manager_shared::types::DatabaseConfig;
+    use ai_manager_shared::MockClock;
     use chrono::Utc;
     use uuid::Uuid;
 
     async fn setup_test_db() -> Arc<dyn DatabaseConnection> {
-        let connection = create_connection(DatabaseType::SQLite, ":memory:")
+        let connection = create_connection(&DatabaseConfig::sqlite(":memory:"))
             .await
             .expect("Failed to create connection");
 
@@ -243,7 +1057,7 @@ mod tests {
         ];
 
         // Store conversation
-        let result = repo.store_conversation("test_user", &messages).await;
+        let result = repo.append_to_conversation("test_user", &messages).await;
         assert!(result.is_ok());
 
         // Retrieve conversation
@@ -253,6 +1067,410 @@ mod tests {
         assert_eq!(retrieved_messages.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_concurrent_stores_share_one_conversation() {
+        // A file, so writers race on connections of their own
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conversations.db");
+        let mut config = DatabaseConfig::sqlite(format!("sqlite://{}?mode=rwc", path.display()));
+        config.max_connections = Some(4);
+        let connection = create_connection(&config).await.unwrap();
+        run_migrations(&*connection).await.unwrap();
+
+        // A repository of its own per writer, like separate data service
+        // processes sharing the database
+        let stores: Vec<_> = (0..20)
+            .map(|i| {
+                let repo = ConversationRepository::new(connection.clone());
+                tokio::spawn(async move {
+                    let message = Message {
+                        id: Uuid::new_v4(),
+                        content: format!("message {}", i),
+                        timestamp: Utc::now(),
+                        role: MessageRole::User,
+                        metadata: None,
+                    };
+                    repo.append_to_conversation("alice", &[message]).await
+                })
+            })
+            .collect();
+        for store in stores {
+            store.await.unwrap().unwrap();
+        }
+
+        let rows = connection
+            .fetch_all_json("SELECT id FROM conversations WHERE user_id = 'alice'")
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        let repo = ConversationRepository::new(connection);
+        let history = repo.get_conversation_history("alice", None).await.unwrap();
+        assert_eq!(history.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_summary_replaces_older_messages() {
+        let connection = setup_test_db().await;
+        let repo = ConversationRepository::new(connection);
+
+        let start = Utc::now();
+        let message = |content: &str, offset: i64| Message {
+            id: Uuid::new_v4(),
+            content: content.to_string(),
+            timestamp: start + chrono::Duration::seconds(offset),
+            role: MessageRole::User,
+            metadata: None,
+        };
+
+        // Each store appends to the conversation
+        repo.append_to_conversation("test_user", &[message("one", 0), message("two", 1)])
+            .await
+            .unwrap();
+        repo.append_to_conversation("test_user", &[message("three", 2)])
+            .await
+            .unwrap();
+        let history = repo.get_conversation_history("test_user", None).await;
+        assert_eq!(history.unwrap().len(), 3);
+
+        let summary = Message::summary("one and two".to_string());
+        repo.store_summary("test_user", &summary, start + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+
+        let history = repo
+            .get_conversation_history("test_user", None)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["one and two", "three"]);
+        assert!(history[0].is_summary());
+        assert!(!history[1].is_summary());
+    }
+
+    #[tokio::test]
+    async fn test_delete_conversations() {
+        let connection = setup_test_db().await;
+        let repo = ConversationRepository::new(connection);
+
+        let messages = vec![Message {
+            id: Uuid::new_v4(),
+            content: "Hello".to_string(),
+            timestamp: Utc::now(),
+            role: MessageRole::User,
+            metadata: None,
+        }];
+
+        repo.append_to_conversation("test_user", &messages)
+            .await
+            .unwrap();
+        repo.append_to_conversation("other_user", &messages)
+            .await
+            .unwrap();
+
+        // Delete conversations for one user only
+        let result = repo.delete_conversations("test_user").await;
+        assert!(result.is_ok());
+
+        let retrieved = repo.get_conversation_history("test_user", None).await;
+        assert!(retrieved.unwrap().is_empty());
+
+        let other = repo.get_conversation_history("other_user", None).await;
+        assert_eq!(other.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_truncate_after_keeps_discarded_branch() {
+        let connection = setup_test_db().await;
+        let repo = ConversationRepository::new(connection);
+
+        let start = Utc::now();
+        let messages: Vec<Message> = ["question", "answer", "follow-up", "second answer"]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| Message {
+                id: Uuid::new_v4(),
+                content: content.to_string(),
+                timestamp: start + chrono::Duration::seconds(i as i64),
+                role: if i % 2 == 0 {
+                    MessageRole::User
+                } else {
+                    MessageRole::Assistant
+                },
+                metadata: None,
+            })
+            .collect();
+        repo.append_to_conversation("test_user", &messages)
+            .await
+            .unwrap();
+
+        let dropped = repo
+            .truncate_after("test_user", messages[0].id)
+            .await
+            .unwrap();
+        assert_eq!(dropped, 3);
+
+        let history = repo
+            .get_conversation_history("test_user", None)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["question"]);
+
+        let branches = repo.discarded_branches("test_user").await.unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].len(), 3);
+        assert_eq!(branches[0][0].content, "answer");
+
+        // Messages outside the current conversation can't be branched from
+        let err = repo
+            .truncate_after("test_user", messages[3].id)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SystemError::InvalidInput(_)));
+
+        repo.delete_conversations("test_user").await.unwrap();
+        assert!(repo
+            .discarded_branches("test_user")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_export_markdown() {
+        let connection = setup_test_db().await;
+        let repo = ConversationRepository::new(connection);
+
+        let err = repo.export_markdown("test_user", None).await.unwrap_err();
+        assert!(matches!(err, SystemError::InvalidInput(_)));
+
+        let message = Message {
+            id: Uuid::new_v4(),
+            content: "Plan *everything*".to_string(),
+            timestamp: Utc::now(),
+            role: MessageRole::User,
+            metadata: None,
+        };
+        repo.append_to_conversation("test_user", &[message])
+            .await
+            .unwrap();
+
+        let markdown = repo.export_markdown("test_user", None).await.unwrap();
+        assert!(markdown.starts_with("---\nuser_id: \"test_user\"\n"));
+        assert!(markdown.contains("created_at: "));
+        assert!(markdown.ends_with("Plan \\*everything\\*\n"));
+
+        // Other users' conversations are not found by id
+        let id: i64 = markdown
+            .lines()
+            .find_map(|line| line.strip_prefix("conversation_id: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(repo.export_markdown("other_user", Some(id)).await.is_err());
+        assert!(repo.export_markdown("test_user", Some(id)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_store_conversations_bulk() {
+        let connection = setup_test_db().await;
+        let repo = ConversationRepository::new(connection);
+
+        let start = Utc::now();
+        let conversations: Vec<(String, Vec<Message>)> = (0..1200)
+            .map(|i| {
+                let messages = (0..3)
+                    .map(|j| Message {
+                        id: Uuid::new_v4(),
+                        content: format!("user {}'s message {}", i, j),
+                        timestamp: start + chrono::Duration::seconds(j),
+                        role: MessageRole::User,
+                        metadata: None,
+                    })
+                    .collect();
+                (format!("user_{}", i % 600), messages)
+            })
+            .chain([("empty_user".to_string(), Vec::new())])
+            .collect();
+
+        assert_eq!(
+            repo.store_conversations_bulk(conversations).await.unwrap(),
+            3600
+        );
+
+        // Two imported conversations each for every user
+        let history = repo
+            .get_conversation_history("user_42", None)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 6);
+        assert!(history[0].content.ends_with("message 0"));
+        assert!(repo
+            .get_conversation_history("empty_user", None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(repo.store_conversations_bulk(Vec::new()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_round_trip() {
+        let repo = ConversationRepository::new(setup_test_db().await);
+
+        let start = Utc::now();
+        let messages: Vec<Message> = (0..2500)
+            .map(|i| Message {
+                id: Uuid::new_v4(),
+                content: format!("line {}\nwith 'quotes'", i),
+                timestamp: start + chrono::Duration::seconds(i),
+                role: if i % 2 == 0 {
+                    MessageRole::User
+                } else {
+                    MessageRole::Assistant
+                },
+                metadata: Some(serde_json::json!({ "index": i })),
+            })
+            .collect();
+        repo.append_to_conversation("alice", &messages)
+            .await
+            .unwrap();
+
+        let mut exported = Vec::new();
+        assert_eq!(
+            repo.export_jsonl("alice", &mut exported).await.unwrap(),
+            2500
+        );
+
+        let fresh = ConversationRepository::new(setup_test_db().await);
+        assert_eq!(
+            fresh
+                .import_jsonl("bob", exported.as_slice())
+                .await
+                .unwrap(),
+            2500
+        );
+
+        let restored = fresh.get_conversation_history("bob", None).await.unwrap();
+        assert_eq!(restored.len(), messages.len());
+        for (original, restored) in messages.iter().zip(&restored) {
+            assert_eq!(original.id, restored.id);
+            assert_eq!(original.content, restored.content);
+            assert_eq!(original.timestamp, restored.timestamp);
+            assert_eq!(original.metadata, restored.metadata);
+        }
+
+        let invalid = fresh.import_jsonl("bob", "\n{not json}\n".as_bytes()).await;
+        assert!(matches!(invalid, Err(SystemError::Serialization(_))));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_old_conversations() {
+        let start = "2024-01-01T00:00:00Z".parse().unwrap();
+        let clock = Arc::new(MockClock::new(start));
+        let repo = ConversationRepository::new(setup_test_db().await).with_clock(clock.clone());
+
+        let messages: Vec<Message> = (0..5)
+            .map(|i| Message {
+                id: Uuid::new_v4(),
+                content: format!("message {}", i),
+                timestamp: start + chrono::Duration::seconds(i),
+                role: MessageRole::User,
+                metadata: None,
+            })
+            .collect();
+        repo.append_to_conversation("idle_user", &messages[..1])
+            .await
+            .unwrap();
+
+        clock.advance(chrono::Duration::days(100));
+        repo.append_to_conversation("active_user", &messages)
+            .await
+            .unwrap();
+
+        assert_eq!(repo.cleanup_old_conversations(90).await.unwrap(), 1);
+        let idle = repo.get_conversation_history("idle_user", None).await;
+        assert!(idle.unwrap().is_empty());
+
+        assert_eq!(repo.trim_conversations(3).await.unwrap(), 1);
+        assert_eq!(repo.trim_conversations(3).await.unwrap(), 0);
+        let history = repo
+            .get_conversation_history("active_user", None)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["message 2", "message 3", "message 4"]);
+    }
+
+    #[tokio::test]
+    async fn test_trim_pages_through_all_conversations() {
+        let repo = ConversationRepository::new(setup_test_db().await);
+        let start = Utc::now();
+        let conversations = (0..TRIM_PAGE_ROWS + 20)
+            .map(|user| {
+                let messages = (0..3)
+                    .map(|i| Message {
+                        id: Uuid::new_v4(),
+                        content: format!("message {}", i),
+                        timestamp: start + chrono::Duration::seconds(i),
+                        role: MessageRole::User,
+                        metadata: None,
+                    })
+                    .collect();
+                (format!("user_{}", user), messages)
+            })
+            .collect();
+        repo.store_conversations_bulk(conversations).await.unwrap();
+
+        assert_eq!(
+            repo.trim_conversations(2).await.unwrap(),
+            TRIM_PAGE_ROWS + 20
+        );
+        let history = repo.get_conversation_history("user_0", None).await;
+        assert_eq!(history.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_semantic_search_ranks_by_similarity() {
+        let connection = setup_test_db().await;
+        let repo = ConversationRepository::new(connection);
+
+        let weather = Uuid::new_v4();
+        let cooking = Uuid::new_v4();
+        repo.store_embedding("test_user", weather, "It's raining today", &[1.0, 0.1, 0.0])
+            .await
+            .unwrap();
+        repo.store_embedding("test_user", cooking, "Pasta recipe", &[0.0, 1.0, 0.2])
+            .await
+            .unwrap();
+        repo.store_embedding("other_user", Uuid::new_v4(), "Sunny", &[1.0, 0.0, 0.0])
+            .await
+            .unwrap();
+
+        let results = repo
+            .semantic_search("test_user", &[0.9, 0.0, 0.0], 5)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].message_id, weather);
+        assert_eq!(results[1].message_id, cooking);
+        assert!(results[0].score > results[1].score);
+
+        let top = repo
+            .semantic_search("test_user", &[0.0, 1.0, 0.0], 1)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].content, "Pasta recipe");
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
     #[tokio::test]
     async fn test_user_profile_repository() {
         let connection = setup_test_db().await;
@@ -277,4 +1495,122 @@ mod tests {
         assert!(retrieved_profile.is_some());
         assert_eq!(retrieved_profile.unwrap().id, "test_user");
     }
+
+    #[tokio::test]
+    async fn test_get_or_create_profile() {
+        let connection = setup_test_db().await;
+        let repo = UserProfileRepository::new(connection);
+
+        let created = repo.get_or_create_profile("new_user").await.unwrap();
+        assert_eq!(created.id, "new_user");
+        assert_eq!(created.preferences, serde_json::json!({}));
+
+        repo.merge_preferences("new_user", &serde_json::json!({"theme": "dark"}))
+            .await
+            .unwrap();
+        let existing = repo.get_or_create_profile("new_user").await.unwrap();
+        assert_eq!(existing.preferences, serde_json::json!({"theme": "dark"}));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_first_profile_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.db");
+        let mut config = DatabaseConfig::sqlite(format!("sqlite://{}?mode=rwc", path.display()));
+        config.max_connections = Some(4);
+        let connection = create_connection(&config).await.unwrap();
+        run_migrations(&*connection).await.unwrap();
+
+        let loads: Vec<_> = (0..10)
+            .map(|_| {
+                let repo = UserProfileRepository::new(connection.clone());
+                tokio::spawn(async move { repo.get_or_create_profile("alice").await })
+            })
+            .collect();
+        for load in loads {
+            assert_eq!(load.await.unwrap().unwrap().id, "alice");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_profile_ids_are_escaped() {
+        let repo = UserProfileRepository::new(setup_test_db().await);
+        let user_id = "o'brien' OR '1'='1";
+
+        repo.merge_preferences(user_id, &serde_json::json!({"theme": "dark"}))
+            .await
+            .unwrap();
+        repo.get_or_create_profile("bob").await.unwrap();
+
+        let profile = repo.get_profile(user_id).await.unwrap().unwrap();
+        assert_eq!(profile.id, user_id);
+        assert_eq!(profile.preferences, serde_json::json!({"theme": "dark"}));
+
+        repo.delete_profile(user_id).await.unwrap();
+        assert!(repo.get_profile(user_id).await.unwrap().is_none());
+        assert!(repo.get_profile("bob").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_merge_preferences() {
+        let connection = setup_test_db().await;
+        let repo = UserProfileRepository::new(connection);
+
+        // Creates the profile on first use
+        repo.merge_preferences(
+            "test_user",
+            &serde_json::json!({"provider": "claude", "model": "claude-3-opus-20240229"}),
+        )
+        .await
+        .unwrap();
+        repo.merge_preferences("test_user", &serde_json::json!({"model": null}))
+            .await
+            .unwrap();
+
+        let profile = repo.get_profile("test_user").await.unwrap().unwrap();
+        assert_eq!(
+            profile.preferences,
+            serde_json::json!({"provider": "claude"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sender_rules() {
+        let repo = SenderRuleRepository::new(setup_test_db().await);
+
+        repo.set_rule("Spam@Example.com", Some(SenderRule::Block))
+            .await
+            .unwrap();
+        repo.set_rule("boss@example.com", Some(SenderRule::Block))
+            .await
+            .unwrap();
+        repo.set_rule("boss@example.com", Some(SenderRule::Allow))
+            .await
+            .unwrap();
+        repo.set_rule("o'brien@example.com", Some(SenderRule::Block))
+            .await
+            .unwrap();
+        repo.set_rule("o'brien@example.com", None).await.unwrap();
+
+        let rules = repo.rules().await.unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules["spam@example.com"], SenderRule::Block);
+        assert_eq!(rules["boss@example.com"], SenderRule::Allow);
+    }
+
+    #[tokio::test]
+    async fn test_replied_threads() {
+        let repo = RepliedThreadRepository::new(setup_test_db().await);
+
+        repo.record("alice@example.com\nplanning").await.unwrap();
+        repo.record("o'brien@example.com\nlunch").await.unwrap();
+        // Recording a thread again keeps one entry
+        repo.record("alice@example.com\nplanning").await.unwrap();
+
+        let thread_keys = repo.thread_keys().await.unwrap();
+        assert_eq!(
+            thread_keys,
+            vec!["alice@example.com\nplanning", "o'brien@example.com\nlunch"]
+        );
+    }
 }
@@ -0,0 +1,79 @@
+use ai_manager_shared::{errors::SystemError, messages::ServiceMessage, MessageTransport};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// Exchange `ServiceMessage`s with a connected core: incoming messages go to
+/// `inbound` and `replies` are sent back. Returns when the peer disconnects.
+pub async fn serve_connection(
+    transport: &mut dyn MessageTransport,
+    inbound: &mpsc::Sender<ServiceMessage>,
+    replies: &mut mpsc::Receiver<ServiceMessage>,
+) -> Result<(), SystemError> {
+    loop {
+        tokio::select! {
+            message = transport.recv() => {
+                let Some(message) = message? else {
+                    debug!("Peer disconnected");
+                    return Ok(());
+                };
+                inbound.send(message).await.map_err(|e| {
+                    SystemError::ServiceCommunication(format!(
+                        "Failed to queue incoming message: {}",
+                        e
+                    ))
+                })?;
+            }
+            Some(reply) = replies.recv() => {
+                transport.send(reply).await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_manager_shared::StreamTransport;
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn test_messages_exchanged_as_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (inbound_tx, mut inbound_rx) = mpsc::channel(10);
+        let (reply_tx, mut reply_rx) = mpsc::channel(10);
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut transport = StreamTransport::new(stream);
+            serve_connection(&mut transport, &inbound_tx, &mut reply_rx).await
+        });
+
+        let mut client = StreamTransport::new(TcpStream::connect(addr).await.unwrap());
+        client
+            .send(ServiceMessage::ClearConversation {
+                user_id: "alice".to_string(),
+            })
+            .await
+            .unwrap();
+
+        match inbound_rx.recv().await.unwrap() {
+            ServiceMessage::ClearConversation { user_id } => assert_eq!(user_id, "alice"),
+            other => panic!("expected ClearConversation, got {:?}", other),
+        }
+
+        reply_tx
+            .send(ServiceMessage::ShutdownService {
+                service_id: "data".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.recv().await.unwrap(),
+            Some(ServiceMessage::ShutdownService { .. })
+        ));
+
+        drop(client);
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use crate::email::{EmailCategory, ProcessedEmail};
+use ai_manager_shared::messages::EmailData;
+use ai_manager_shared::MAX_REPLIED_THREADS;
+use std::collections::{HashSet, VecDeque};
+
+/// Sender address fragments of mail that must never get a reply
+const NO_REPLY_SENDERS: &[&str] = &[
+    "noreply",
+    "no-reply",
+    "donotreply",
+    "do-not-reply",
+    "mailer-daemon",
+    "postmaster",
+    "bounce",
+    "notifications@",
+    "list-",
+    "-list@",
+    "lists.",
+];
+
+/// Subject and body fragments of automatic or mailing list mail
+const BULK_MARKERS: &[&str] = &[
+    "unsubscribe",
+    "mailing list",
+    "out of office",
+    "automatic reply",
+    "auto-reply",
+    "autoreply",
+    "auto:",
+];
+
+/// Decides which processed emails get their generated auto-reply sent, and
+/// remembers the threads already replied to so a reply is sent at most once
+/// per thread. Replies to nothing until categories are enabled.
+#[derive(Debug, Default)]
+pub struct AutoReplier {
+    categories: HashSet<EmailCategory>,
+    replied: HashSet<String>,
+    replied_order: VecDeque<String>,
+}
+
+impl AutoReplier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send auto-replies to mail in `categories`. Newsletters and spam are
+    /// never replied to, even if listed.
+    pub fn with_categories(mut self, categories: impl IntoIterator<Item = EmailCategory>) -> Self {
+        self.categories = categories
+            .into_iter()
+            .filter(|category| !matches!(category, EmailCategory::Newsletter | EmailCategory::Spam))
+            .collect();
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.categories.is_empty()
+    }
+
+    /// The reply to send to `email`, if it qualifies for one
+    pub fn reply_for<'a>(
+        &self,
+        email: &EmailData,
+        processed: &'a ProcessedEmail,
+    ) -> Option<&'a str> {
+        let reply = processed.auto_reply.as_deref()?;
+        if !self.categories.contains(&processed.category) {
+            return None;
+        }
+
+        let from = sender_address(&email.from).to_lowercase();
+        if NO_REPLY_SENDERS
+            .iter()
+            .any(|fragment| from.contains(fragment))
+        {
+            return None;
+        }
+        let text = format!("{} {}", email.subject, email.body).to_lowercase();
+        if BULK_MARKERS.iter().any(|marker| text.contains(marker)) {
+            return None;
+        }
+
+        (!self.replied.contains(&thread_key(email))).then_some(reply)
+    }
+
+    /// Remember that `email`'s thread was replied to, returning the key to
+    /// persist it by
+    pub fn record_reply(&mut self, email: &EmailData) -> String {
+        let key = thread_key(email);
+        self.remember(key.clone());
+        key
+    }
+
+    /// Remember threads replied to before, e.g. those stored in the
+    /// database, oldest first
+    pub fn restore_replied(&mut self, thread_keys: impl IntoIterator<Item = String>) {
+        for key in thread_keys {
+            self.remember(key);
+        }
+    }
+
+    fn remember(&mut self, key: String) {
+        if !self.replied.insert(key.clone()) {
+            return;
+        }
+        self.replied_order.push_back(key);
+        if self.replied_order.len() > MAX_REPLIED_THREADS {
+            if let Some(oldest) = self.replied_order.pop_front() {
+                self.replied.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// The address in a sender such as "Alice <alice@example.com>"
+pub fn sender_address(from: &str) -> &str {
+    match (from.find('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end => from[start + 1..end].trim(),
+        _ => from.trim(),
+    }
+}
+
+/// Sender and subject without reply and forward prefixes, identifying a
+/// conversation with one correspondent
+fn thread_key(email: &EmailData) -> String {
+    let mut subject = email.subject.trim().to_lowercase();
+    while let Some(rest) = ["re:", "fwd:", "fw:"]
+        .iter()
+        .find_map(|prefix| subject.strip_prefix(prefix))
+    {
+        subject = rest.trim_start().to_string();
+    }
+    format!(
+        "{}\n{}",
+        sender_address(&email.from).to_lowercase(),
+        subject
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::EmailPriority;
+
+    fn email(from: &str, subject: &str, body: &str) -> EmailData {
+        EmailData {
+            id: "1".to_string(),
+            from: from.to_string(),
+            to: vec!["me@example.com".to_string()],
+            subject: subject.to_string(),
+            body: body.to_string(),
+            timestamp: chrono::Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
+        }
+    }
+
+    fn processed(category: EmailCategory) -> ProcessedEmail {
+        ProcessedEmail {
+            email_id: "1".to_string(),
+            category,
+            priority: EmailPriority::Medium,
+            is_high_priority: false,
+            suggested_actions: vec![],
+            auto_reply: Some("Thanks, I'll get back to you.".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_replies_once_per_thread() {
+        let mut replier = AutoReplier::new().with_categories([EmailCategory::Meeting]);
+        let invite = email("Alice <alice@example.com>", "Planning meeting", "");
+        let meeting = processed(EmailCategory::Meeting);
+
+        assert!(replier.reply_for(&invite, &meeting).is_some());
+        replier.record_reply(&invite);
+        assert!(replier.reply_for(&invite, &meeting).is_none());
+
+        let follow_up = email("alice@example.com", "RE: Re: Planning meeting", "");
+        assert!(replier.reply_for(&follow_up, &meeting).is_none());
+
+        // Only enabled categories are replied to
+        let work = email("bob@example.com", "Report", "");
+        assert!(replier
+            .reply_for(&work, &processed(EmailCategory::Work))
+            .is_none());
+    }
+
+    #[test]
+    fn test_restored_threads_not_replied_again() {
+        let mut replier = AutoReplier::new().with_categories([EmailCategory::Meeting]);
+        let invite = email("Alice <alice@example.com>", "Planning meeting", "");
+        let key = replier.record_reply(&invite);
+
+        let mut restarted = AutoReplier::new().with_categories([EmailCategory::Meeting]);
+        restarted.restore_replied([key]);
+        assert!(restarted
+            .reply_for(&invite, &processed(EmailCategory::Meeting))
+            .is_none());
+    }
+
+    #[test]
+    fn test_never_replies_to_bulk_mail() {
+        let replier = AutoReplier::new().with_categories([
+            EmailCategory::Other,
+            EmailCategory::Newsletter,
+            EmailCategory::Spam,
+        ]);
+        let other = processed(EmailCategory::Other);
+
+        for bulk in [
+            email("No Reply <noreply@shop.example>", "Your order", ""),
+            email("dev-list@lists.example.org", "Release", ""),
+            email("carol@example.com", "Weekly news", "Click to unsubscribe"),
+            email("dave@example.com", "Automatic reply: Lunch", ""),
+        ] {
+            assert!(replier.reply_for(&bulk, &other).is_none(), "{}", bulk.from);
+        }
+
+        let news = email("erin@example.com", "Hello", "");
+        assert!(replier
+            .reply_for(&news, &processed(EmailCategory::Newsletter))
+            .is_none());
+        assert!(AutoReplier::new().reply_for(&news, &other).is_none());
+    }
+}
@@ -0,0 +1,748 @@
+use crate::calendar::{time_zone_from_env, CalendarEvent, CalendarProvider};
+use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::http;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use tracing::warn;
+
+const ICAL_DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const ICAL_DATE_FORMAT: &str = "%Y%m%d";
+
+pub struct CalDavClient {
+    client: Client,
+    calendar_url: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    time_zone: Tz,
+}
+
+impl CalDavClient {
+    pub async fn new() -> Result<Self, SystemError> {
+        let calendar_url = std::env::var("CALDAV_URL").ok();
+        let username = std::env::var("CALDAV_USERNAME").ok();
+        let password = std::env::var("CALDAV_PASSWORD").ok();
+        let time_zone = time_zone_from_env()?;
+
+        if calendar_url.is_none() {
+            warn!("CalDAV calendar URL not configured. Set CALDAV_URL environment variable.");
+        }
+
+        Ok(Self {
+            client: http::client(std::time::Duration::from_secs(
+                ai_manager_shared::CALENDAR_REQUEST_TIMEOUT,
+            )),
+            calendar_url,
+            username,
+            password,
+            time_zone,
+        })
+    }
+
+    /// Set the calendar collection URL and credentials explicitly
+    pub fn with_calendar(
+        mut self,
+        calendar_url: &str,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> Self {
+        self.calendar_url = Some(calendar_url.to_string());
+        self.username = username.map(|s| s.to_string());
+        self.password = password.map(|s| s.to_string());
+        self
+    }
+
+    /// Set the user's time zone, used for events that carry no zone of their own
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    fn calendar_url(&self) -> Result<&str, SystemError> {
+        self.calendar_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .ok_or_else(|| caldav_error("Calendar URL not configured".to_string()))
+    }
+
+    fn event_url(&self, event_id: &str) -> Result<String, SystemError> {
+        Ok(format!("{}/{}.ics", self.calendar_url()?, event_id))
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let builder = self.client.request(method, url);
+        match &self.username {
+            Some(username) => builder.basic_auth(username, self.password.as_ref()),
+            None => builder,
+        }
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> Result<reqwest::Response, SystemError> {
+        builder
+            .send()
+            .await
+            .map_err(|e| caldav_error(format!("Request error: {}", e)))
+    }
+
+    /// Parse the first VEVENT of an iCalendar object into a `CalendarEvent`
+    fn parse_vevent(&self, id: &str, ics: &str) -> Option<CalendarEvent> {
+        let mut in_event = false;
+        let mut summary = None;
+        let mut description = None;
+        let mut location = None;
+        let mut start = None;
+        let mut end = None;
+        let mut attendees = Vec::new();
+
+        for line in unfold_lines(ics) {
+            let (name, params, value) = match split_property(&line) {
+                Some(parts) => parts,
+                None => continue,
+            };
+
+            match (name.as_str(), in_event) {
+                ("BEGIN", _) if value.eq_ignore_ascii_case("VEVENT") => in_event = true,
+                ("END", true) if value.eq_ignore_ascii_case("VEVENT") => break,
+                ("SUMMARY", true) => summary = Some(unescape_text(&value)),
+                ("DESCRIPTION", true) => description = Some(unescape_text(&value)),
+                ("LOCATION", true) => location = Some(unescape_text(&value)),
+                ("DTSTART", true) => start = self.parse_ical_datetime(&params, &value),
+                ("DTEND", true) => end = self.parse_ical_datetime(&params, &value),
+                ("ATTENDEE", true) => {
+                    let email = value
+                        .strip_prefix("mailto:")
+                        .or_else(|| value.strip_prefix("MAILTO:"))
+                        .unwrap_or(&value);
+                    attendees.push(email.to_string());
+                }
+                _ => {}
+            }
+        }
+
+        let (start, tz, all_day) = start?;
+        let end = match end {
+            Some((end, _, _)) => end,
+            // RFC 5545: a date-only DTSTART without DTEND lasts one day
+            None if all_day => start + Duration::days(1),
+            None => start,
+        };
+
+        Some(CalendarEvent {
+            id: id.to_string(),
+            summary: summary.unwrap_or_else(|| "Untitled Event".to_string()),
+            description,
+            start,
+            end,
+            tz,
+            all_day,
+            location,
+            attendees,
+        })
+    }
+
+    /// Parse a DTSTART/DTEND value, returning the instant, its zone and
+    /// whether it is a date-only value
+    fn parse_ical_datetime(
+        &self,
+        params: &[(String, String)],
+        value: &str,
+    ) -> Option<(DateTime<Utc>, Tz, bool)> {
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.as_str())
+        };
+        let tz = param("TZID")
+            .and_then(|name| name.trim_matches('"').parse::<Tz>().ok())
+            .unwrap_or(self.time_zone);
+
+        if param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || value.len() == 8 {
+            let date = NaiveDate::parse_from_str(value, ICAL_DATE_FORMAT).ok()?;
+            let local = tz
+                .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+                .earliest()?;
+            return Some((local.with_timezone(&Utc), tz, true));
+        }
+
+        if let Some(utc) = value.strip_suffix('Z') {
+            let naive = NaiveDateTime::parse_from_str(utc, ICAL_DATETIME_FORMAT).ok()?;
+            return Some((Utc.from_utc_datetime(&naive), tz, false));
+        }
+
+        // Floating or TZID-qualified local time
+        let naive = NaiveDateTime::parse_from_str(value, ICAL_DATETIME_FORMAT).ok()?;
+        let local = tz.from_local_datetime(&naive).earliest()?;
+        Some((local.with_timezone(&Utc), tz, false))
+    }
+}
+
+#[async_trait]
+impl CalendarProvider for CalDavClient {
+    async fn list_events(
+        &self,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+    ) -> Result<Vec<CalendarEvent>, SystemError> {
+        let url = self.calendar_url()?;
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:getetag/>
+    <C:calendar-data/>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{}" end="{}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#,
+            format_utc(start_date),
+            format_utc(end_date)
+        );
+
+        let response = self
+            .send(
+                self.request(report_method(), url)
+                    .header("Depth", "1")
+                    .header("Content-Type", "application/xml; charset=utf-8")
+                    .body(body),
+            )
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(caldav_error(format!(
+                "REPORT returned status: {}",
+                response.status()
+            )));
+        }
+
+        let xml = response
+            .text()
+            .await
+            .map_err(|e| caldav_error(format!("Failed to read response: {}", e)))?;
+
+        let events = parse_multistatus(&xml)?
+            .into_iter()
+            .filter_map(|(href, ics)| self.parse_vevent(&event_id_from_href(&href), &ics))
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn create_event(
+        &self,
+        title: &str,
+        description: Option<&str>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        attendees: &[String],
+    ) -> Result<String, SystemError> {
+        let uid = uuid::Uuid::new_v4().to_string();
+        let url = self.event_url(&uid)?;
+        let ics = build_vcalendar(&uid, title, description, start_time, end_time, attendees);
+
+        let response = self
+            .send(
+                self.request(Method::PUT, &url)
+                    .header("Content-Type", "text/calendar; charset=utf-8")
+                    .header("If-None-Match", "*")
+                    .body(ics),
+            )
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(caldav_error(format!(
+                "Failed to create event: {}",
+                response.status()
+            )));
+        }
+
+        Ok(uid)
+    }
+
+    async fn update_event(
+        &self,
+        event_id: &str,
+        title: Option<&str>,
+        description: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<(), SystemError> {
+        let url = self.event_url(event_id)?;
+
+        let response = self.send(self.request(Method::GET, &url)).await?;
+        if !response.status().is_success() {
+            return Err(caldav_error(format!(
+                "Failed to fetch event: {}",
+                response.status()
+            )));
+        }
+
+        let etag = response
+            .headers()
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+        let ics = response
+            .text()
+            .await
+            .map_err(|e| caldav_error(format!("Failed to read event: {}", e)))?;
+
+        let mut updates = Vec::new();
+        if let Some(title) = title {
+            updates.push(("SUMMARY", format!("SUMMARY:{}", escape_text(title))));
+        }
+        if let Some(description) = description {
+            updates.push((
+                "DESCRIPTION",
+                format!("DESCRIPTION:{}", escape_text(description)),
+            ));
+        }
+        if let Some(start) = start_time {
+            updates.push(("DTSTART", format!("DTSTART:{}", format_utc(start))));
+        }
+        if let Some(end) = end_time {
+            updates.push(("DTEND", format!("DTEND:{}", format_utc(end))));
+        }
+
+        let mut request = self
+            .request(Method::PUT, &url)
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .body(patch_vevent(&ics, &updates));
+        if let Some(etag) = etag {
+            request = request.header("If-Match", etag);
+        }
+
+        let response = self.send(request).await?;
+        if !response.status().is_success() {
+            return Err(caldav_error(format!(
+                "Failed to update event: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn delete_event(&self, event_id: &str) -> Result<(), SystemError> {
+        let url = self.event_url(event_id)?;
+        let response = self.send(self.request(Method::DELETE, &url)).await?;
+
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(caldav_error(format!(
+                "Failed to delete event: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), SystemError> {
+        let url = self.calendar_url()?;
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:propfind xmlns:D="DAV:">
+  <D:prop>
+    <D:resourcetype/>
+  </D:prop>
+</D:propfind>"#;
+
+        let response = self
+            .send(
+                self.request(propfind_method(), url)
+                    .header("Depth", "0")
+                    .header("Content-Type", "application/xml; charset=utf-8")
+                    .body(body),
+            )
+            .await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(caldav_error(format!(
+                "Health check failed with status: {}",
+                response.status()
+            )))
+        }
+    }
+}
+
+fn caldav_error(message: String) -> SystemError {
+    SystemError::ExternalService {
+        service: "CalDAV".to_string(),
+        message,
+    }
+}
+
+fn report_method() -> Method {
+    Method::from_bytes(b"REPORT").expect("REPORT is a valid method")
+}
+
+fn propfind_method() -> Method {
+    Method::from_bytes(b"PROPFIND").expect("PROPFIND is a valid method")
+}
+
+fn format_utc(time: DateTime<Utc>) -> String {
+    format!("{}Z", time.format(ICAL_DATETIME_FORMAT))
+}
+
+/// Event id is the resource name without the `.ics` extension
+fn event_id_from_href(href: &str) -> String {
+    let name = href
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(href);
+    name.strip_suffix(".ics").unwrap_or(name).to_string()
+}
+
+/// Extract `(href, calendar-data)` pairs from a multistatus response
+fn parse_multistatus(xml: &str) -> Result<Vec<(String, String)>, SystemError> {
+    #[derive(PartialEq)]
+    enum Field {
+        None,
+        Href,
+        CalendarData,
+    }
+
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut results = Vec::new();
+    let mut href = String::new();
+    let mut data = String::new();
+    let mut field = Field::None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => match e.local_name().as_ref() {
+                b"response" => {
+                    href.clear();
+                    data.clear();
+                }
+                b"href" => field = Field::Href,
+                b"calendar-data" => field = Field::CalendarData,
+                _ => {}
+            },
+            Ok(Event::Text(text)) => {
+                let text = text
+                    .unescape()
+                    .map_err(|e| caldav_error(format!("Invalid XML text: {}", e)))?;
+                match field {
+                    Field::Href => href.push_str(&text),
+                    Field::CalendarData => data.push_str(&text),
+                    Field::None => {}
+                }
+            }
+            Ok(Event::CData(cdata)) => {
+                if field == Field::CalendarData {
+                    data.push_str(&String::from_utf8_lossy(&cdata.into_inner()));
+                }
+            }
+            Ok(Event::End(e)) => match e.local_name().as_ref() {
+                b"response" if !data.is_empty() => {
+                    results.push((href.clone(), data.clone()));
+                }
+                b"href" | b"calendar-data" => field = Field::None,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(caldav_error(format!("Invalid multistatus XML: {}", e))),
+        }
+    }
+
+    Ok(results)
+}
+
+/// Join folded iCalendar content lines (RFC 5545 section 3.1)
+fn unfold_lines(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in ics.lines() {
+        let raw = raw.trim_end_matches('\r');
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    lines
+}
+
+/// Parameters of a content line, with upper-cased names
+type PropertyParams = Vec<(String, String)>;
+
+/// Split a content line into its upper-cased name, parameters and value
+fn split_property(line: &str) -> Option<(String, PropertyParams, String)> {
+    let colon = line.find(':')?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.to_string()))
+        .collect();
+    Some((name, params, value.to_string()))
+}
+
+/// Fold a content line at 75 octets
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for ch in line.chars() {
+        if width + ch.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(ch);
+        width += ch.len_utf8();
+    }
+    folded
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn build_vcalendar(
+    uid: &str,
+    title: &str,
+    description: Option<&str>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    attendees: &[String],
+) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//AI Manager//CalDAV Client//EN".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", format_utc(Utc::now())),
+        format!("DTSTART:{}", format_utc(start_time)),
+        format!("DTEND:{}", format_utc(end_time)),
+        format!("SUMMARY:{}", escape_text(title)),
+    ];
+    if let Some(description) = description {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    for attendee in attendees {
+        lines.push(format!("ATTENDEE;RSVP=TRUE:mailto:{}", attendee));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics = lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    ics.push_str("\r\n");
+    ics
+}
+
+/// Replace properties of the first VEVENT, keeping everything else intact;
+/// properties the event lacks are appended before `END:VEVENT`
+fn patch_vevent(ics: &str, updates: &[(&str, String)]) -> String {
+    let mut output = Vec::new();
+    let mut applied = vec![false; updates.len()];
+    let mut in_event = false;
+    let mut patched = false;
+
+    for line in unfold_lines(ics) {
+        if line.is_empty() {
+            continue;
+        }
+        let name = split_property(&line).map(|(name, _, value)| (name, value));
+
+        if !patched {
+            match &name {
+                Some((name, value)) if name == "BEGIN" && value.eq_ignore_ascii_case("VEVENT") => {
+                    in_event = true;
+                }
+                Some((name, value)) if name == "END" && value.eq_ignore_ascii_case("VEVENT") => {
+                    for (i, (_, replacement)) in updates.iter().enumerate() {
+                        if !applied[i] {
+                            output.push(replacement.clone());
+                        }
+                    }
+                    in_event = false;
+                    patched = true;
+                }
+                Some((name, _)) if in_event => {
+                    if let Some(i) = updates.iter().position(|(key, _)| *key == name.as_str()) {
+                        if !applied[i] {
+                            output.push(updates[i].1.clone());
+                            applied[i] = true;
+                        }
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        output.push(line);
+    }
+
+    let mut patched_ics = output
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    patched_ics.push_str("\r\n");
+    patched_ics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_ICS: &str = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:abc-123\r\n\
+DTSTART;TZID=Europe/Berlin:20240115T090000\r\n\
+DTEND;TZID=Europe/Berlin:20240115T100000\r\n\
+SUMMARY:Team sync\\, weekly\r\n\
+DESCRIPTION:First line\\nsecond line that is folded \r\n \
+across two lines\r\n\
+ATTENDEE;CN=Alice:mailto:alice@example.com\r\n\
+RRULE:FREQ=WEEKLY\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+    async fn test_client() -> CalDavClient {
+        CalDavClient::new().await.unwrap().with_calendar(
+            "https://dav.example.com/calendars/user/personal/",
+            None,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_parse_vevent() {
+        let client = test_client().await;
+        let event = client.parse_vevent("abc-123", SAMPLE_ICS).unwrap();
+
+        assert_eq!(event.summary, "Team sync, weekly");
+        assert_eq!(
+            event.description.as_deref(),
+            Some("First line\nsecond line that is folded across two lines")
+        );
+        assert_eq!(event.tz, chrono_tz::Europe::Berlin);
+        assert!(!event.all_day);
+        assert_eq!(event.start.to_rfc3339(), "2024-01-15T08:00:00+00:00");
+        assert_eq!(event.end.to_rfc3339(), "2024-01-15T09:00:00+00:00");
+        assert_eq!(event.attendees, vec!["alice@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_parse_all_day_vevent() {
+        let client = test_client().await.with_time_zone(chrono_tz::Asia::Tokyo);
+        let ics = "BEGIN:VEVENT\nUID:x\nDTSTART;VALUE=DATE:20240301\nSUMMARY:Holiday\nEND:VEVENT\n";
+        let event = client.parse_vevent("x", ics).unwrap();
+
+        assert!(event.all_day);
+        assert_eq!(event.tz, chrono_tz::Asia::Tokyo);
+        assert_eq!(
+            event.local_start().to_rfc3339(),
+            "2024-03-01T00:00:00+09:00"
+        );
+        assert_eq!(event.local_end().to_rfc3339(), "2024-03-02T00:00:00+09:00");
+    }
+
+    #[test]
+    fn test_parse_multistatus() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response>
+    <d:href>/calendars/user/personal/abc-123.ics</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:getetag>"1"</d:getetag>
+        <cal:calendar-data>BEGIN:VCALENDAR&#13;
+BEGIN:VEVENT&#13;
+UID:abc-123&#13;
+END:VEVENT&#13;
+END:VCALENDAR</cal:calendar-data>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+        let results = parse_multistatus(xml).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(event_id_from_href(&results[0].0), "abc-123");
+        assert!(results[0].1.contains("UID:abc-123"));
+    }
+
+    #[test]
+    fn test_patch_vevent() {
+        let updates = vec![
+            ("SUMMARY", "SUMMARY:Renamed".to_string()),
+            ("LOCATION", "LOCATION:Room 1".to_string()),
+        ];
+        let patched = patch_vevent(SAMPLE_ICS, &updates);
+
+        assert!(patched.contains("SUMMARY:Renamed\r\n"));
+        assert!(!patched.contains("Team sync"));
+        assert!(patched.contains("LOCATION:Room 1\r\nEND:VEVENT"));
+        // Properties we don't touch survive the round trip
+        assert!(patched.contains("RRULE:FREQ=WEEKLY"));
+        assert!(patched.contains("UID:abc-123"));
+    }
+
+    #[tokio::test]
+    async fn test_build_vcalendar() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let end = start + Duration::hours(1);
+        let ics = build_vcalendar(
+            "uid-1",
+            "Lunch; with team",
+            None,
+            start,
+            end,
+            &["bob@example.com".to_string()],
+        );
+
+        assert!(ics.contains("UID:uid-1\r\n"));
+        assert!(ics.contains("DTSTART:20240101T100000Z\r\n"));
+        assert!(ics.contains("DTEND:20240101T110000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Lunch\\; with team\r\n"));
+        assert!(!ics.contains("DESCRIPTION"));
+        assert!(ics.contains("ATTENDEE;RSVP=TRUE:mailto:bob@example.com\r\n"));
+
+        // Attendees survive a parse of the generated event
+        let client = test_client().await;
+        let event = client.parse_vevent("uid-1", &ics).unwrap();
+        assert_eq!(event.attendees, vec!["bob@example.com"]);
+    }
+}
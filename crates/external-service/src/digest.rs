@@ -0,0 +1,233 @@
+use crate::email::{EmailCategory, ProcessedEmail};
+use ai_manager_llm_service::{LLMProvider, LLMRequest, PromptManager};
+use ai_manager_shared::messages::EmailData;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// A low-priority email held back for the next digest
+#[derive(Debug, Clone)]
+pub struct DigestEntry {
+    pub from: String,
+    pub subject: String,
+    pub category: EmailCategory,
+    pub received_at: DateTime<Utc>,
+}
+
+/// Low-priority emails collected between digests, sent as one notification
+/// instead of one per email
+#[derive(Debug, Default)]
+pub struct EmailDigest {
+    entries: Vec<DigestEntry>,
+}
+
+impl EmailDigest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold `email` for the next digest
+    pub fn add(&mut self, email: &EmailData, processed: &ProcessedEmail) {
+        self.entries.push(DigestEntry {
+            from: email.from.clone(),
+            subject: email.subject.clone(),
+            category: processed.category.clone(),
+            received_at: email.timestamp,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Take the collected emails and turn them into the digest text. The
+    /// listing is summarized with `provider` through the `summarize` template
+    /// when one is given, falling back to the plain listing if that fails.
+    pub async fn compose(&mut self, provider: Option<&dyn LLMProvider>) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let entries = std::mem::take(&mut self.entries);
+        let listing = entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "- {} from {} [{:?}]",
+                    entry.subject, entry.from, entry.category
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let body = match provider {
+            Some(provider) => summarize(&listing, provider).await.unwrap_or(listing),
+            None => listing,
+        };
+
+        Some(format!(
+            "Email digest ({} emails):\n{}",
+            entries.len(),
+            body
+        ))
+    }
+}
+
+async fn summarize(listing: &str, provider: &dyn LLMProvider) -> Option<String> {
+    let prompt = PromptManager::new()
+        .render_template(
+            "summarize",
+            &HashMap::from([("content".to_string(), listing.to_string())]),
+        )
+        .ok()?;
+
+    let request = LLMRequest {
+        prompt,
+        context: vec![],
+        model: String::new(), // Use the provider's default model
+        max_tokens: Some(500),
+        temperature: Some(0.3),
+        stop_sequences: None,
+        stream: false,
+        response_format: None,
+        images: vec![],
+    };
+
+    match provider.send_request(request).await {
+        Ok(response) if !response.content.trim().is_empty() => {
+            Some(response.content.trim().to_string())
+        }
+        Ok(_) => None,
+        Err(e) => {
+            warn!(
+                "Email digest summary via '{}' failed, sending the listing: {}",
+                provider.provider_name(),
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::EmailPriority;
+    use ai_manager_shared::errors::SystemError;
+
+    fn newsletter(subject: &str) -> (EmailData, ProcessedEmail) {
+        let email = EmailData {
+            id: subject.to_string(),
+            from: "news@example.com".to_string(),
+            to: vec!["user@example.com".to_string()],
+            subject: subject.to_string(),
+            body: "This week's updates".to_string(),
+            timestamp: Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
+        };
+        let processed = ProcessedEmail {
+            email_id: email.id.clone(),
+            category: EmailCategory::Newsletter,
+            priority: EmailPriority::Low,
+            is_high_priority: false,
+            suggested_actions: vec![],
+            auto_reply: None,
+        };
+        (email, processed)
+    }
+
+    struct MockLLMProvider {
+        reply: std::result::Result<String, String>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for MockLLMProvider {
+        async fn send_request(
+            &self,
+            request: LLMRequest,
+        ) -> ai_manager_shared::Result<ai_manager_llm_service::LLMResponse> {
+            assert!(request.prompt.contains("Weekly news"));
+            match &self.reply {
+                Ok(content) => Ok(ai_manager_llm_service::LLMResponse {
+                    content: content.clone(),
+                    model: "mock-model".to_string(),
+                    usage: ai_manager_shared::TokenUsage {
+                        prompt_tokens: 10,
+                        completion_tokens: 10,
+                        total_tokens: 20,
+                    },
+                    finish_reason: ai_manager_llm_service::FinishReason::Stop,
+                    provider: "mock".to_string(),
+                }),
+                Err(message) => Err(SystemError::LLMApi {
+                    provider: "mock".to_string(),
+                    message: message.clone(),
+                }),
+            }
+        }
+
+        async fn get_usage(&self) -> ai_manager_shared::TokenUsage {
+            ai_manager_shared::TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            "mock"
+        }
+
+        async fn health_check(&self) -> ai_manager_shared::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_digest_listing() {
+        let mut digest = EmailDigest::new();
+        assert!(digest.compose(None).await.is_none());
+
+        let (email, processed) = newsletter("Weekly news");
+        digest.add(&email, &processed);
+        let (email, processed) = newsletter("Product launch");
+        digest.add(&email, &processed);
+        assert_eq!(digest.len(), 2);
+
+        let text = digest.compose(None).await.unwrap();
+        assert!(text.starts_with("Email digest (2 emails):"));
+        assert!(text.contains("- Weekly news from news@example.com [Newsletter]"));
+        assert!(digest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_digest_summarized() {
+        let mut digest = EmailDigest::new();
+        let (email, processed) = newsletter("Weekly news");
+        digest.add(&email, &processed);
+
+        let provider = MockLLMProvider {
+            reply: Ok("One newsletter about weekly news.".to_string()),
+        };
+        let text = digest.compose(Some(&provider)).await.unwrap();
+        assert_eq!(
+            text,
+            "Email digest (1 emails):\nOne newsletter about weekly news."
+        );
+
+        // A failed summary still sends the listing
+        digest.add(&email, &processed);
+        let provider = MockLLMProvider {
+            reply: Err("unavailable".to_string()),
+        };
+        let text = digest.compose(Some(&provider)).await.unwrap();
+        assert!(text.contains("- Weekly news"));
+    }
+}
@@ -1,36 +1,26 @@
+use crate::auto_reply::sender_address;
+use crate::sender_rules::SenderRules;
+use ai_manager_llm_service::{
+    send_request_typed, LLMProvider, LLMRequest, PromptManager, ResponseFormat,
+};
 use ai_manager_shared::errors::SystemError;
-use chrono::Utc;
+use ai_manager_shared::messages::{EmailAttachment, SenderRule};
+pub use ai_manager_shared::messages::{EmailCategory, EmailPriority, ProcessedEmail};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use imap_proto::types::{BodyContentCommon, BodyStructure, MessageSection, SectionPath};
+use lettre::message::header::{ContentType, HeaderName, HeaderValue};
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use mailparse::MailHeaderMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 use tracing::{info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProcessedEmail {
-    pub email_id: String,
-    pub category: EmailCategory,
-    pub priority: EmailPriority,
-    pub is_high_priority: bool,
-    pub suggested_actions: Vec<String>,
-    pub auto_reply: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum EmailCategory {
-    Work,
-    Personal,
-    Spam,
-    Newsletter,
-    Meeting,
-    Urgent,
-    Other,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum EmailPriority {
-    High,
-    Medium,
-    Low,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ImapConfig {
     server: String,
@@ -38,6 +28,9 @@ struct ImapConfig {
     username: String,
     password: String,
     use_tls: bool,
+    archive_folder: String,
+    /// Limit on each step of opening a session and on each command
+    timeout_seconds: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,15 +40,27 @@ struct SmtpConfig {
     username: String,
     password: String,
     use_tls: bool,
+    /// Sender address, e.g. "Assistant <me@example.com>"
+    from: String,
 }
 
+/// Stream types an IMAP session can run over (plain TCP or TLS)
+trait ImapStream: AsyncRead + AsyncWrite + Unpin + std::fmt::Debug + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + std::fmt::Debug + Send> ImapStream for T {}
+
+type ImapSession = async_imap::Session<Box<dyn ImapStream>>;
+
+/// Attachments larger than this on the server are fetched with metadata
+/// only; use `EmailClient::download_attachment` to load their contents.
+const MAX_INLINE_ATTACHMENT_BYTES: usize = 1024 * 1024;
+
 pub struct EmailClient {
-    #[allow(dead_code)]
     imap_config: Option<ImapConfig>,
-    #[allow(dead_code)]
     smtp_config: Option<SmtpConfig>,
     // In a real implementation, this would contain IMAP/SMTP connections
     mock_mode: bool,
+    sender_rules: SenderRules,
 }
 
 impl EmailClient {
@@ -74,9 +79,43 @@ impl EmailClient {
             imap_config,
             smtp_config,
             mock_mode,
+            sender_rules: SenderRules::new(),
         })
     }
 
+    /// A client outside mock mode with no servers configured, so every
+    /// operation that needs one fails
+    #[cfg(test)]
+    pub(crate) fn unconfigured() -> Self {
+        Self {
+            imap_config: None,
+            smtp_config: None,
+            mock_mode: false,
+            sender_rules: SenderRules::new(),
+        }
+    }
+
+    /// Replace the allowlist and blocklist, e.g. with those stored in the
+    /// database
+    pub fn set_sender_rules(&mut self, rules: SenderRules) {
+        self.sender_rules = rules;
+    }
+
+    /// Treat all mail from `address` as spam
+    pub fn block_sender(&mut self, address: &str) {
+        self.sender_rules.set(address, Some(SenderRule::Block));
+    }
+
+    /// Never treat mail from `address` as spam
+    pub fn allow_sender(&mut self, address: &str) {
+        self.sender_rules.set(address, Some(SenderRule::Allow));
+    }
+
+    /// Drop any rule for `address`, so its mail is categorized normally
+    pub fn unblock_sender(&mut self, address: &str) {
+        self.sender_rules.set(address, None);
+    }
+
     fn load_imap_config() -> Option<ImapConfig> {
         let server = std::env::var("IMAP_SERVER").ok()?;
         let port = std::env::var("IMAP_PORT").ok()?.parse().ok()?;
@@ -85,6 +124,12 @@ impl EmailClient {
         let use_tls = std::env::var("IMAP_USE_TLS")
             .map(|s| s.to_lowercase() == "true")
             .unwrap_or(true);
+        let archive_folder =
+            std::env::var("IMAP_ARCHIVE_FOLDER").unwrap_or_else(|_| "Archive".to_string());
+        let timeout_seconds = std::env::var("IMAP_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(ai_manager_shared::EMAIL_REQUEST_TIMEOUT);
 
         Some(ImapConfig {
             server,
@@ -92,6 +137,8 @@ impl EmailClient {
             username,
             password,
             use_tls,
+            archive_folder,
+            timeout_seconds,
         })
     }
 
@@ -103,6 +150,7 @@ impl EmailClient {
         let use_tls = std::env::var("SMTP_USE_TLS")
             .map(|s| s.to_lowercase() == "true")
             .unwrap_or(true);
+        let from = std::env::var("SMTP_FROM").unwrap_or_else(|_| username.clone());
 
         Some(SmtpConfig {
             server,
@@ -110,11 +158,15 @@ impl EmailClient {
             username,
             password,
             use_tls,
+            from,
         })
     }
 
+    /// Fetch unseen emails from the INBOX, optionally only those received
+    /// on or after `since`.
     pub async fn fetch_emails(
         &self,
+        since: Option<DateTime<Utc>>,
     ) -> Result<Vec<ai_manager_shared::messages::EmailData>, SystemError> {
         if self.mock_mode {
             // Return mock emails for testing
@@ -126,30 +178,295 @@ impl EmailClient {
                 body: "This is a test email body.".to_string(),
                 timestamp: Utc::now(),
                 is_read: false,
+                attachments: vec![],
+                message_id: None,
+                references: vec![],
             }]);
         }
 
-        // In a real implementation, this would:
-        // 1. Connect to IMAP server
-        // 2. Authenticate
-        // 3. Select inbox
-        // 4. Fetch new emails
-        // 5. Parse email content
-        // 6. Return email data
+        let config = self.imap_config()?;
+        let mut session = self.connect_imap().await?;
+        select_inbox(&mut session, config).await?;
 
-        Err(SystemError::ExternalService {
-            service: "Email".to_string(),
-            message: "IMAP email fetching not implemented yet".to_string(),
-        })
+        let query = match since {
+            Some(since) => format!("UNSEEN SINCE {}", since.format("%d-%b-%Y")),
+            None => "UNSEEN".to_string(),
+        };
+        let uids = imap_timeout(config, "search", session.uid_search(&query))
+            .await?
+            .map_err(imap_error)?;
+
+        let mut emails = Vec::new();
+
+        if !uids.is_empty() {
+            let mut uids: Vec<u32> = uids.into_iter().collect();
+            uids.sort_unstable();
+            let uid_set = uids
+                .iter()
+                .map(|uid| uid.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            // Structure and headers first, so only the text and the small
+            // attachments get downloaded. BODY.PEEK leaves the \Seen flag
+            // untouched.
+            let outlines = uid_fetch(
+                &mut session,
+                config,
+                &uid_set,
+                "(UID BODYSTRUCTURE BODY.PEEK[HEADER])",
+            )
+            .await?;
+
+            for outline in &outlines {
+                let header = outline.section(&SectionPath::Full(MessageSection::Header));
+                let (Some(uid), Some(structure), Some(header)) =
+                    (outline.uid, outline.bodystructure(), header)
+                else {
+                    continue;
+                };
+                let layout = MessageLayout::from_structure(structure);
+
+                let parts = match layout.fetch_items() {
+                    Some(items) => {
+                        uid_fetch(&mut session, config, &uid.to_string(), &items).await?
+                    }
+                    None => Vec::new(),
+                };
+
+                match assemble_email(&uid.to_string(), header, &layout, |path| {
+                    parts.iter().find_map(|fetch| fetch.section(path))
+                }) {
+                    Ok(email) => emails.push(email),
+                    Err(e) => warn!("Skipping unparseable email {}: {}", uid, e),
+                }
+            }
+        }
+
+        logout(session, config).await;
+
+        info!("Fetched {} unseen emails", emails.len());
+        Ok(emails)
+    }
+
+    /// Download the full contents of an attachment whose data was not
+    /// loaded when the email was fetched.
+    pub async fn download_attachment(
+        &self,
+        email_id: &str,
+        attachment_id: &str,
+    ) -> Result<Vec<u8>, SystemError> {
+        if self.mock_mode {
+            info!(
+                "Mock: Downloading attachment {} of email {}",
+                attachment_id, email_id
+            );
+            return Ok(Vec::new());
+        }
+
+        let uid = parse_uid(email_id)?;
+        let config = self.imap_config()?;
+        let mut session = self.connect_imap().await?;
+        select_inbox(&mut session, config).await?;
+
+        let outlines = uid_fetch(&mut session, config, &uid, "(UID BODYSTRUCTURE)").await?;
+        let layout = outlines
+            .iter()
+            .find_map(|fetch| fetch.bodystructure())
+            .map(MessageLayout::from_structure)
+            .ok_or_else(|| SystemError::ExternalService {
+                service: "Email".to_string(),
+                message: format!("Email {} not found", email_id),
+            })?;
+
+        let attachment = attachment_id
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| layout.attachments.get(index))
+            .ok_or_else(|| SystemError::ExternalService {
+                service: "Email".to_string(),
+                message: format!(
+                    "Attachment {} not found in email {}",
+                    attachment_id, email_id
+                ),
+            })?;
+
+        let section = section_name(&attachment.section);
+        let parts = uid_fetch(
+            &mut session,
+            config,
+            &uid,
+            &format!("(UID BODY.PEEK[{0}.MIME] BODY.PEEK[{0}])", section),
+        )
+        .await?;
+
+        logout(session, config).await;
+
+        let fetched = |path: &SectionPath| parts.iter().find_map(|fetch| fetch.section(path));
+        let (mime, body) = fetched_part(&fetched, &attachment.section).ok_or_else(|| {
+            SystemError::ExternalService {
+                service: "Email".to_string(),
+                message: format!(
+                    "Attachment {} of email {} was not returned",
+                    attachment_id, email_id
+                ),
+            }
+        })?;
+        decode_part(mime, body, |part| part.get_body_raw())
+    }
+
+    /// Mark an email as read by setting its \Seen flag
+    pub async fn mark_read(&self, email_id: &str) -> Result<(), SystemError> {
+        if self.mock_mode {
+            info!("Mock: Marking email {} as read", email_id);
+            return Ok(());
+        }
+
+        let uid = parse_uid(email_id)?;
+        let config = self.imap_config()?;
+        let mut session = self.connect_imap().await?;
+        select_inbox(&mut session, config).await?;
+        store_flags(&mut session, config, &uid, "+FLAGS (\\Seen)").await?;
+        logout(session, config).await;
+
+        info!("Marked email {} as read", email_id);
+        Ok(())
+    }
+
+    /// Move an email from the INBOX to another folder. Needs a server with
+    /// MOVE or UIDPLUS.
+    pub async fn move_to_folder(&self, email_id: &str, folder: &str) -> Result<(), SystemError> {
+        if self.mock_mode {
+            info!("Mock: Moving email {} to folder {}", email_id, folder);
+            return Ok(());
+        }
+
+        let uid = parse_uid(email_id)?;
+        let config = self.imap_config()?;
+        let mut session = self.connect_imap().await?;
+        select_inbox(&mut session, config).await?;
+        let capabilities = imap_timeout(config, "capability", session.capabilities())
+            .await?
+            .map_err(imap_error)?;
+        if capabilities.has_str("MOVE") {
+            imap_timeout(config, "move", session.uid_mv(&uid, folder))
+                .await?
+                .map_err(imap_error)?;
+        } else {
+            if let Err(e) = require_uidplus(capabilities.has_str("UIDPLUS"), email_id) {
+                logout(session, config).await;
+                return Err(e);
+            }
+            imap_timeout(config, "copy", session.uid_copy(&uid, folder))
+                .await?
+                .map_err(imap_error)?;
+            store_flags(&mut session, config, &uid, "+FLAGS (\\Deleted)").await?;
+            uid_expunge(&mut session, config, &uid).await?;
+        }
+        logout(session, config).await;
+
+        info!("Moved email {} to folder {}", email_id, folder);
+        Ok(())
+    }
+
+    /// Move an email to the configured archive folder
+    pub async fn archive(&self, email_id: &str) -> Result<(), SystemError> {
+        let folder = self
+            .imap_config
+            .as_ref()
+            .map(|config| config.archive_folder.clone())
+            .unwrap_or_else(|| "Archive".to_string());
+
+        self.move_to_folder(email_id, &folder).await
+    }
+
+    /// Permanently delete an email from the INBOX. Needs a server with
+    /// UIDPLUS.
+    pub async fn delete(&self, email_id: &str) -> Result<(), SystemError> {
+        if self.mock_mode {
+            info!("Mock: Deleting email {}", email_id);
+            return Ok(());
+        }
+
+        let uid = parse_uid(email_id)?;
+        let config = self.imap_config()?;
+        let mut session = self.connect_imap().await?;
+        select_inbox(&mut session, config).await?;
+        let capabilities = imap_timeout(config, "capability", session.capabilities())
+            .await?
+            .map_err(imap_error)?;
+        if let Err(e) = require_uidplus(capabilities.has_str("UIDPLUS"), email_id) {
+            logout(session, config).await;
+            return Err(e);
+        }
+        store_flags(&mut session, config, &uid, "+FLAGS (\\Deleted)").await?;
+        uid_expunge(&mut session, config, &uid).await?;
+        logout(session, config).await;
+
+        info!("Deleted email {}", email_id);
+        Ok(())
+    }
+
+    fn imap_config(&self) -> Result<&ImapConfig, SystemError> {
+        self.imap_config
+            .as_ref()
+            .ok_or_else(|| SystemError::Configuration("IMAP not configured".to_string()))
+    }
+
+    /// Connect and log in to the configured IMAP server
+    async fn connect_imap(&self) -> Result<ImapSession, SystemError> {
+        let config = self.imap_config()?;
+
+        let tcp_stream = imap_timeout(
+            config,
+            "connect",
+            TcpStream::connect((config.server.as_str(), config.port)),
+        )
+        .await?
+        .map_err(|e| {
+            SystemError::Network(format!(
+                "Failed to connect to IMAP server {}:{}: {}",
+                config.server, config.port, e
+            ))
+        })?;
+
+        let stream: Box<dyn ImapStream> = if config.use_tls {
+            let tls_stream = imap_timeout(
+                config,
+                "TLS handshake",
+                async_native_tls::TlsConnector::new().connect(config.server.as_str(), tcp_stream),
+            )
+            .await?
+            .map_err(|e| SystemError::Network(format!("IMAP TLS handshake failed: {}", e)))?;
+            Box::new(tls_stream)
+        } else {
+            Box::new(tcp_stream)
+        };
+
+        let mut client = async_imap::Client::new(stream);
+        imap_timeout(config, "greeting", client.read_response())
+            .await?
+            .ok_or_else(|| {
+                SystemError::Network(
+                    "IMAP server closed the connection before greeting".to_string(),
+                )
+            })?
+            .map_err(|e| SystemError::Network(format!("Failed to read IMAP greeting: {}", e)))?;
+
+        imap_timeout(
+            config,
+            "login",
+            client.login(&config.username, &config.password),
+        )
+        .await?
+        .map_err(|(e, _)| SystemError::Authentication(format!("IMAP login failed: {}", e)))
     }
 
     pub async fn process_email(
         &self,
         email: &ai_manager_shared::messages::EmailData,
     ) -> Result<ProcessedEmail, SystemError> {
-        // AI-powered email processing would happen here
-        // For now, we'll implement basic rule-based processing
-
+        // Rule-based processing, used when no LLM provider is available
         let category = self.categorize_email(email);
         let priority = self.assess_priority(email);
         let is_high_priority = matches!(priority, EmailPriority::High);
@@ -166,27 +483,160 @@ impl EmailClient {
         })
     }
 
+    /// Categorize and prioritize an email using an LLM provider.
+    ///
+    /// Falls back to rule-based processing if the request fails or the model
+    /// does not return a usable JSON analysis.
+    pub async fn process_email_with_llm(
+        &self,
+        email: &ai_manager_shared::messages::EmailData,
+        provider: &dyn LLMProvider,
+    ) -> Result<ProcessedEmail, SystemError> {
+        // Mail from blocked senders is spam whatever it says
+        if self.sender_rules.rule_for(&email.from) == Some(SenderRule::Block) {
+            return self.process_email(email).await;
+        }
+
+        let prompt = match self.build_analysis_prompt(email) {
+            Some(prompt) => prompt,
+            None => {
+                warn!("email_assistant template unavailable, using rule-based processing");
+                return self.process_email(email).await;
+            }
+        };
+
+        let request = LLMRequest {
+            prompt,
+            context: vec![],
+            model: String::new(), // Use the provider's default model
+            max_tokens: Some(500),
+            temperature: Some(0.0),
+            stop_sequences: None,
+            stream: false,
+            response_format: Some(ResponseFormat::Json { schema: None }),
+            images: vec![],
+        };
+
+        let analysis = match send_request_typed::<LlmEmailAnalysis>(provider, request).await {
+            Ok(analysis) => analysis.into_analysis(),
+            Err(e) => {
+                warn!(
+                    "LLM email analysis via '{}' failed, using rule-based processing: {}",
+                    provider.provider_name(),
+                    e
+                );
+                return self.process_email(email).await;
+            }
+        };
+
+        match analysis {
+            Some(analysis)
+                if matches!(analysis.category, EmailCategory::Spam)
+                    && self.sender_rules.rule_for(&email.from) == Some(SenderRule::Allow) =>
+            {
+                self.process_email(email).await
+            }
+            Some(analysis) => {
+                let is_high_priority = matches!(analysis.priority, EmailPriority::High);
+                Ok(ProcessedEmail {
+                    email_id: email.id.clone(),
+                    category: analysis.category,
+                    priority: analysis.priority,
+                    is_high_priority,
+                    suggested_actions: analysis.suggested_actions,
+                    auto_reply: analysis.auto_reply,
+                })
+            }
+            None => {
+                warn!(
+                    "Unrecognized LLM email analysis for '{}', using rule-based processing",
+                    email.subject
+                );
+                self.process_email(email).await
+            }
+        }
+    }
+
+    fn build_analysis_prompt(
+        &self,
+        email: &ai_manager_shared::messages::EmailData,
+    ) -> Option<String> {
+        let email_context = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}",
+            email.from,
+            email.to.join(", "),
+            email.subject,
+            email.body
+        );
+
+        let mut variables = HashMap::new();
+        variables.insert("email_context".to_string(), email_context);
+        variables.insert(
+            "user_input".to_string(),
+            EMAIL_ANALYSIS_INSTRUCTIONS.to_string(),
+        );
+
+        PromptManager::new()
+            .render_template("email_assistant", &variables)
+            .ok()
+    }
+
     pub async fn send_email(
         &self,
         to: &[String],
         subject: &str,
-        _body: &str,
+        body: &str,
+        attachments: &[EmailAttachment],
+    ) -> Result<(), SystemError> {
+        self.send_message(to, subject, body, attachments, &[]).await
+    }
+
+    /// Reply to the sender of `email` with `body`, threaded under it
+    pub async fn send_reply(
+        &self,
+        email: &ai_manager_shared::messages::EmailData,
+        body: &str,
+    ) -> Result<(), SystemError> {
+        let to = vec![sender_address(&email.from).to_string()];
+        let subject = reply_subject(&email.subject);
+        self.send_message(&to, &subject, body, &[], &reply_headers(email))
+            .await
+    }
+
+    async fn send_message(
+        &self,
+        to: &[String],
+        subject: &str,
+        body: &str,
+        attachments: &[EmailAttachment],
+        headers: &[(&str, String)],
     ) -> Result<(), SystemError> {
         if self.mock_mode {
-            info!("Mock: Sending email to {:?} with subject: {}", to, subject);
+            info!(
+                "Mock: Sending email to {:?} with subject: {} ({} attachment(s), headers {:?})",
+                to,
+                subject,
+                attachments.len(),
+                headers
+            );
             return Ok(());
         }
 
-        // In a real implementation, this would:
-        // 1. Connect to SMTP server
-        // 2. Authenticate
-        // 3. Compose email
-        // 4. Send email
+        let config = self.smtp_config.as_ref().ok_or_else(|| {
+            SystemError::Configuration("SMTP configuration not found".to_string())
+        })?;
+        let message = build_message(&config.from, to, subject, body, attachments, headers)?;
 
-        Err(SystemError::ExternalService {
-            service: "Email".to_string(),
-            message: "SMTP email sending not implemented yet".to_string(),
-        })
+        smtp_transport(config)?
+            .send(message)
+            .await
+            .map_err(|e| SystemError::ExternalService {
+                service: "Email".to_string(),
+                message: format!("Failed to send email: {}", e),
+            })?;
+
+        info!("Sent email to {:?} with subject: {}", to, subject);
+        Ok(())
     }
 
     pub async fn health_check(&self) -> Result<(), SystemError> {
@@ -202,6 +652,11 @@ impl EmailClient {
     }
 
     fn categorize_email(&self, email: &ai_manager_shared::messages::EmailData) -> EmailCategory {
+        // No rule below makes mail spam, so allowed senders never are
+        if self.sender_rules.rule_for(&email.from) == Some(SenderRule::Block) {
+            return EmailCategory::Spam;
+        }
+
         let subject_lower = email.subject.to_lowercase();
         let body_lower = email.body.to_lowercase();
         let combined = format!("{} {}", subject_lower, body_lower);
@@ -317,10 +772,581 @@ impl EmailClient {
     }
 }
 
+const EMAIL_ANALYSIS_INSTRUCTIONS: &str = "Categorize this email and assess its priority. \
+Respond with only a JSON object of the form \
+{\"category\": \"Work|Personal|Spam|Newsletter|Meeting|Urgent|Other\", \
+\"priority\": \"High|Medium|Low\", \
+\"suggested_actions\": [\"...\"], \
+\"auto_reply\": \"...\" or null}";
+
+#[derive(Debug, Deserialize)]
+struct LlmEmailAnalysis {
+    category: String,
+    priority: String,
+    #[serde(default)]
+    suggested_actions: Vec<String>,
+    #[serde(default)]
+    auto_reply: Option<String>,
+}
+
+struct EmailAnalysis {
+    category: EmailCategory,
+    priority: EmailPriority,
+    suggested_actions: Vec<String>,
+    auto_reply: Option<String>,
+}
+
+impl LlmEmailAnalysis {
+    /// Map the model's labels onto ours; `None` if either is unrecognized
+    fn into_analysis(self) -> Option<EmailAnalysis> {
+        let category = match self.category.trim().to_lowercase().as_str() {
+            "work" => EmailCategory::Work,
+            "personal" => EmailCategory::Personal,
+            "spam" => EmailCategory::Spam,
+            "newsletter" => EmailCategory::Newsletter,
+            "meeting" => EmailCategory::Meeting,
+            "urgent" => EmailCategory::Urgent,
+            "other" => EmailCategory::Other,
+            _ => return None,
+        };
+
+        let priority = match self.priority.trim().to_lowercase().as_str() {
+            "high" => EmailPriority::High,
+            "medium" => EmailPriority::Medium,
+            "low" => EmailPriority::Low,
+            _ => return None,
+        };
+
+        Some(EmailAnalysis {
+            category,
+            priority,
+            suggested_actions: self.suggested_actions,
+            auto_reply: self.auto_reply.filter(|reply| !reply.trim().is_empty()),
+        })
+    }
+}
+
+/// The UID an email id stands for. Ids are passed to IMAP commands as UID
+/// sets, so anything but a single UID, e.g. `1:*`, is rejected rather than
+/// acting on several messages.
+fn parse_uid(email_id: &str) -> Result<String, SystemError> {
+    email_id
+        .parse::<u32>()
+        .ok()
+        .filter(|uid| *uid != 0)
+        .map(|uid| uid.to_string())
+        .ok_or_else(|| SystemError::InvalidInput(format!("Invalid email id: {}", email_id)))
+}
+
+async fn select_inbox(session: &mut ImapSession, config: &ImapConfig) -> Result<(), SystemError> {
+    imap_timeout(config, "select", session.select("INBOX"))
+        .await?
+        .map_err(imap_error)?;
+    Ok(())
+}
+
+async fn uid_fetch(
+    session: &mut ImapSession,
+    config: &ImapConfig,
+    uid_set: &str,
+    query: &str,
+) -> Result<Vec<async_imap::types::Fetch>, SystemError> {
+    imap_timeout(config, "fetch", async {
+        session
+            .uid_fetch(uid_set, query)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+    })
+    .await?
+    .map_err(imap_error)
+}
+
+async fn store_flags(
+    session: &mut ImapSession,
+    config: &ImapConfig,
+    uid: &str,
+    flags: &str,
+) -> Result<(), SystemError> {
+    imap_timeout(config, "store", async {
+        session
+            .uid_store(uid, flags)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+    })
+    .await?
+    .map_err(imap_error)?;
+    Ok(())
+}
+
+/// Fail unless the server has UIDPLUS. Without it only a plain EXPUNGE is
+/// possible, which would also remove every other message flagged \Deleted.
+fn require_uidplus(uidplus: bool, email_id: &str) -> Result<(), SystemError> {
+    if uidplus {
+        return Ok(());
+    }
+    Err(SystemError::ExternalService {
+        service: "Email".to_string(),
+        message: format!(
+            "IMAP server lacks UIDPLUS, so email {} can't be removed without expunging every other deleted message",
+            email_id
+        ),
+    })
+}
+
+/// Remove `uid`, already flagged \Deleted, from the mailbox with UID EXPUNGE
+async fn uid_expunge(
+    session: &mut ImapSession,
+    config: &ImapConfig,
+    uid: &str,
+) -> Result<(), SystemError> {
+    imap_timeout(config, "expunge", async {
+        session
+            .uid_expunge(uid)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+    })
+    .await?
+    .map_err(imap_error)?;
+    Ok(())
+}
+
+async fn logout(mut session: ImapSession, config: &ImapConfig) {
+    match imap_timeout(config, "logout", session.logout()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("IMAP logout failed: {}", e),
+        Err(e) => warn!("{}", e),
+    }
+}
+
+/// Await one step of an IMAP session, giving up once the configured timeout
+/// has passed so that an unresponsive server can't stall processing
+async fn imap_timeout<T>(
+    config: &ImapConfig,
+    step: &str,
+    future: impl std::future::Future<Output = T>,
+) -> Result<T, SystemError> {
+    tokio::time::timeout(Duration::from_secs(config.timeout_seconds), future)
+        .await
+        .map_err(|_| SystemError::ExternalService {
+            service: "Email".to_string(),
+            message: format!(
+                "IMAP {} timed out after {} seconds",
+                step, config.timeout_seconds
+            ),
+        })
+}
+
+fn imap_error(e: async_imap::error::Error) -> SystemError {
+    match e {
+        async_imap::error::Error::Io(e) => SystemError::Network(format!("IMAP I/O error: {}", e)),
+        other => SystemError::ExternalService {
+            service: "Email".to_string(),
+            message: format!("IMAP error: {}", other),
+        },
+    }
+}
+
+/// Where the parts of a message worth downloading are, from its
+/// BODYSTRUCTURE
+#[derive(Debug, PartialEq)]
+struct MessageLayout {
+    text: TextPart,
+    attachments: Vec<AttachmentPart>,
+}
+
+#[derive(Debug, PartialEq)]
+enum TextPart {
+    /// The message is a single text part: BODY[TEXT]
+    Whole,
+    /// The part at this section path
+    Section(Vec<u32>),
+    /// The message has no text to show
+    Missing,
+}
+
+#[derive(Debug, PartialEq)]
+struct AttachmentPart {
+    section: Vec<u32>,
+    filename: String,
+    content_type: String,
+    /// Size on the server, in its transfer encoding
+    octets: usize,
+}
+
+impl AttachmentPart {
+    /// Small enough to download along with the message
+    fn is_inline(&self) -> bool {
+        self.octets <= MAX_INLINE_ATTACHMENT_BYTES
+    }
+}
+
+impl MessageLayout {
+    fn from_structure(structure: &BodyStructure) -> Self {
+        let BodyStructure::Multipart { bodies, .. } = structure else {
+            let text = if matches!(structure, BodyStructure::Text { .. }) {
+                TextPart::Whole
+            } else {
+                TextPart::Missing
+            };
+            return Self {
+                text,
+                attachments: Vec::new(),
+            };
+        };
+
+        let mut attachments = Vec::new();
+        collect_attachment_sections(bodies, &[], &mut attachments);
+        let text = find_text_section(bodies, &[], "plain")
+            .or_else(|| find_text_section(bodies, &[], ""))
+            .map_or(TextPart::Missing, TextPart::Section);
+        Self { text, attachments }
+    }
+
+    /// FETCH items for the text and the attachments small enough to
+    /// download, each part with its MIME header so it can be decoded; `None`
+    /// if there's nothing to download
+    fn fetch_items(&self) -> Option<String> {
+        let mut sections: Vec<&[u32]> = Vec::new();
+        let mut items = Vec::new();
+        match &self.text {
+            TextPart::Whole => items.push("BODY.PEEK[TEXT]".to_string()),
+            TextPart::Section(section) => sections.push(section),
+            TextPart::Missing => {}
+        }
+        sections.extend(
+            self.attachments
+                .iter()
+                .filter(|attachment| attachment.is_inline())
+                .map(|attachment| attachment.section.as_slice()),
+        );
+        for section in sections {
+            let section = section_name(section);
+            items.push(format!("BODY.PEEK[{}.MIME]", section));
+            items.push(format!("BODY.PEEK[{}]", section));
+        }
+
+        (!items.is_empty()).then(|| format!("(UID {})", items.join(" ")))
+    }
+}
+
+/// Add the parts under `parent` that are attachments, depth first; an
+/// attachment's id is its index in this list
+fn collect_attachment_sections(
+    bodies: &[BodyStructure],
+    parent: &[u32],
+    attachments: &mut Vec<AttachmentPart>,
+) {
+    for (body, number) in bodies.iter().zip(1..) {
+        let section = [parent, &[number]].concat();
+        match body {
+            BodyStructure::Multipart { bodies, .. } => {
+                collect_attachment_sections(bodies, &section, attachments)
+            }
+            BodyStructure::Basic { common, other, .. }
+            | BodyStructure::Text { common, other, .. }
+            | BodyStructure::Message { common, other, .. } => {
+                if !is_attachment(common) {
+                    continue;
+                }
+
+                let filename = attachment_filename(common)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("attachment-{}", attachments.len()));
+                attachments.push(AttachmentPart {
+                    section,
+                    filename,
+                    content_type: format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase(),
+                    octets: other.octets as usize,
+                });
+            }
+        }
+    }
+}
+
+/// Section of the first text part under `parent` of the given subtype, or
+/// of any subtype if `subtype` is empty, that isn't an attachment
+fn find_text_section(bodies: &[BodyStructure], parent: &[u32], subtype: &str) -> Option<Vec<u32>> {
+    bodies.iter().zip(1..).find_map(|(body, number)| {
+        let section = [parent, &[number]].concat();
+        match body {
+            BodyStructure::Multipart { bodies, .. } => find_text_section(bodies, &section, subtype),
+            BodyStructure::Text { common, .. }
+                if !is_attachment(common)
+                    && (subtype.is_empty() || common.ty.subtype.eq_ignore_ascii_case(subtype)) =>
+            {
+                Some(section)
+            }
+            _ => None,
+        }
+    })
+}
+
+fn is_attachment(common: &BodyContentCommon) -> bool {
+    common
+        .disposition
+        .as_ref()
+        .is_some_and(|disposition| disposition.ty.eq_ignore_ascii_case("attachment"))
+        || attachment_filename(common).is_some()
+}
+
+fn attachment_filename<'a>(common: &'a BodyContentCommon) -> Option<&'a str> {
+    common
+        .disposition
+        .as_ref()
+        .and_then(|disposition| body_param(&disposition.params, "filename"))
+        .or_else(|| body_param(&common.ty.params, "name"))
+}
+
+/// Value of the `name` parameter, ignoring case as MIME does
+fn body_param<'a>(params: &'a imap_proto::types::BodyParams, name: &str) -> Option<&'a str> {
+    params
+        .as_ref()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_ref())
+}
+
+/// A section path as written in FETCH, like `1.2`
+fn section_name(section: &[u32]) -> String {
+    section
+        .iter()
+        .map(|number| number.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Build an email from its header, layout and the sections downloaded for
+/// it, which `fetched` looks up
+fn assemble_email<'a>(
+    id: &str,
+    header: &[u8],
+    layout: &MessageLayout,
+    fetched: impl Fn(&SectionPath) -> Option<&'a [u8]>,
+) -> Result<ai_manager_shared::messages::EmailData, SystemError> {
+    let (headers, _) = mailparse::parse_headers(header).map_err(parse_error)?;
+    let from = headers.get_first_value("From").unwrap_or_default();
+    let subject = headers.get_first_value("Subject").unwrap_or_default();
+    let to = headers
+        .get_first_value("To")
+        .map(|to| {
+            to.split(',')
+                .map(|addr| addr.trim().to_string())
+                .filter(|addr| !addr.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let timestamp = headers
+        .get_first_value("Date")
+        .and_then(|date| mailparse::dateparse(&date).ok())
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .unwrap_or_else(Utc::now);
+    let message_id = headers
+        .get_first_value("Message-ID")
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty());
+    let references = headers
+        .get_first_value("References")
+        .map(|ids| ids.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let body = match &layout.text {
+        TextPart::Whole => fetched(&SectionPath::Full(MessageSection::Text))
+            .map(|text| decode_part(header, text, |part| part.get_body()))
+            .transpose()?,
+        TextPart::Section(section) => fetched_part(&fetched, section)
+            .map(|(mime, body)| decode_part(mime, body, |part| part.get_body()))
+            .transpose()?,
+        TextPart::Missing => None,
+    };
+
+    let mut attachments = Vec::new();
+    for (index, attachment) in layout.attachments.iter().enumerate() {
+        let data = if attachment.is_inline() {
+            fetched_part(&fetched, &attachment.section)
+                .map(|(mime, body)| decode_part(mime, body, |part| part.get_body_raw()))
+                .transpose()?
+        } else {
+            None
+        };
+        attachments.push(EmailAttachment {
+            id: index.to_string(),
+            filename: attachment.filename.clone(),
+            content_type: attachment.content_type.clone(),
+            size: data.as_ref().map_or(attachment.octets, Vec::len),
+            data,
+        });
+    }
+
+    Ok(ai_manager_shared::messages::EmailData {
+        id: id.to_string(),
+        from,
+        to,
+        subject,
+        body: body.unwrap_or_default(),
+        timestamp,
+        is_read: false,
+        attachments,
+        message_id,
+        references,
+    })
+}
+
+/// `subject` with a single "Re: " prefix, however many it had
+fn reply_subject(subject: &str) -> String {
+    let mut rest = subject.trim();
+    while let Some(prefix) = rest.get(..3) {
+        if !prefix.eq_ignore_ascii_case("re:") {
+            break;
+        }
+        rest = rest[3..].trim_start();
+    }
+    format!("Re: {}", rest)
+}
+
+/// In-Reply-To and References headers threading a reply under `email`,
+/// none if it has no Message-ID
+fn reply_headers(email: &ai_manager_shared::messages::EmailData) -> Vec<(&'static str, String)> {
+    let Some(message_id) = &email.message_id else {
+        return Vec::new();
+    };
+    let mut references = email.references.clone();
+    references.push(message_id.clone());
+    vec![
+        ("In-Reply-To", message_id.clone()),
+        ("References", references.join(" ")),
+    ]
+}
+
+/// A plain-text email, with `attachments` and the extra `headers`, e.g.
+/// those from `reply_headers`
+fn build_message(
+    from: &str,
+    to: &[String],
+    subject: &str,
+    body: &str,
+    attachments: &[EmailAttachment],
+    headers: &[(&str, String)],
+) -> Result<Message, SystemError> {
+    let mut builder = Message::builder()
+        .from(parse_mailbox(from)?)
+        .subject(subject);
+    for address in to {
+        builder = builder.to(parse_mailbox(address)?);
+    }
+
+    let text = SinglePart::plain(body.to_string());
+    let message = if attachments.is_empty() {
+        builder.singlepart(text)
+    } else {
+        let mut parts = MultiPart::mixed().singlepart(text);
+        for attachment in attachments {
+            let data = attachment.data.clone().ok_or_else(|| {
+                SystemError::InvalidInput(format!(
+                    "Attachment {} has no data loaded",
+                    attachment.filename
+                ))
+            })?;
+            let content_type = ContentType::parse(&attachment.content_type).map_err(|e| {
+                SystemError::InvalidInput(format!(
+                    "Invalid content type {}: {}",
+                    attachment.content_type, e
+                ))
+            })?;
+            parts = parts
+                .singlepart(Attachment::new(attachment.filename.clone()).body(data, content_type));
+        }
+        builder.multipart(parts)
+    };
+    let mut message =
+        message.map_err(|e| SystemError::InvalidInput(format!("Failed to build email: {}", e)))?;
+
+    for (name, value) in headers {
+        let name = HeaderName::new_from_ascii(name.to_string()).map_err(|e| {
+            SystemError::InvalidInput(format!("Invalid header name {}: {}", name, e))
+        })?;
+        message
+            .headers_mut()
+            .insert_raw(HeaderValue::new(name, value.clone()));
+    }
+    Ok(message)
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, SystemError> {
+    address
+        .parse()
+        .map_err(|e| SystemError::InvalidInput(format!("Invalid email address {}: {}", address, e)))
+}
+
+/// An SMTP transport for `config`: implicit TLS on port 465, STARTTLS on
+/// other ports, or plain text when TLS is off
+fn smtp_transport(config: &SmtpConfig) -> Result<AsyncSmtpTransport<Tokio1Executor>, SystemError> {
+    let builder = if !config.use_tls {
+        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.server)
+    } else if config.port == lettre::transport::smtp::SUBMISSIONS_PORT {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&config.server).map_err(smtp_error)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.server).map_err(smtp_error)?
+    };
+
+    Ok(builder
+        .port(config.port)
+        .credentials(Credentials::new(
+            config.username.clone(),
+            config.password.clone(),
+        ))
+        .timeout(Some(Duration::from_secs(
+            ai_manager_shared::EMAIL_REQUEST_TIMEOUT,
+        )))
+        .build())
+}
+
+fn smtp_error(e: lettre::transport::smtp::Error) -> SystemError {
+    SystemError::ExternalService {
+        service: "Email".to_string(),
+        message: format!("Failed to set up SMTP: {}", e),
+    }
+}
+
+/// The MIME header and body of the part at `section`, if they were
+/// downloaded. A missing MIME header reads as an empty one.
+fn fetched_part<'a>(
+    fetched: &impl Fn(&SectionPath) -> Option<&'a [u8]>,
+    section: &[u32],
+) -> Option<(&'a [u8], &'a [u8])> {
+    let body = fetched(&SectionPath::Part(section.to_vec(), None))?;
+    let mime = fetched(&SectionPath::Part(
+        section.to_vec(),
+        Some(MessageSection::Mime),
+    ))
+    .unwrap_or(b"\r\n");
+    Some((mime, body))
+}
+
+/// Decode a part from its header and body, undoing its transfer encoding
+/// and charset
+fn decode_part<T>(
+    header: &[u8],
+    body: &[u8],
+    decode: impl FnOnce(&mailparse::ParsedMail) -> Result<T, mailparse::MailParseError>,
+) -> Result<T, SystemError> {
+    let raw = [header, body].concat();
+    let part = mailparse::parse_mail(&raw).map_err(parse_error)?;
+    decode(&part).map_err(parse_error)
+}
+
+fn parse_error(e: mailparse::MailParseError) -> SystemError {
+    SystemError::ExternalService {
+        service: "Email".to_string(),
+        message: format!("Failed to parse email: {}", e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
 
     #[tokio::test]
     async fn test_email_client_creation() {
@@ -328,6 +1354,34 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_imap_step_times_out() {
+        let config = ImapConfig {
+            server: "imap.example.com".to_string(),
+            port: 993,
+            username: "user".to_string(),
+            password: "secret".to_string(),
+            use_tls: true,
+            archive_folder: "Archive".to_string(),
+            timeout_seconds: 0,
+        };
+
+        let result = imap_timeout(&config, "login", std::future::pending::<()>()).await;
+        assert!(matches!(
+            result,
+            Err(SystemError::ExternalService { message, .. }) if message.contains("login timed out")
+        ));
+    }
+
+    #[test]
+    fn test_removal_requires_uidplus() {
+        assert!(require_uidplus(true, "42").is_ok());
+        assert!(matches!(
+            require_uidplus(false, "42"),
+            Err(SystemError::ExternalService { message, .. }) if message.contains("UIDPLUS")
+        ));
+    }
+
     #[tokio::test]
     async fn test_email_categorization() {
         let client = EmailClient::new().await.unwrap();
@@ -340,6 +1394,9 @@ mod tests {
             body: "Let's discuss the project in the meeting room.".to_string(),
             timestamp: Utc::now(),
             is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
         };
 
         let processed = client.process_email(&meeting_email).await.unwrap();
@@ -358,6 +1415,9 @@ mod tests {
             body: "This is an emergency situation.".to_string(),
             timestamp: Utc::now(),
             is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
         };
 
         let processed = client.process_email(&urgent_email).await.unwrap();
@@ -365,10 +1425,386 @@ mod tests {
         assert!(processed.is_high_priority);
     }
 
+    struct MockLLMProvider {
+        reply: std::result::Result<String, String>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for MockLLMProvider {
+        async fn send_request(
+            &self,
+            request: LLMRequest,
+        ) -> ai_manager_shared::Result<ai_manager_llm_service::LLMResponse> {
+            assert!(request.prompt.contains("Email context"));
+            match &self.reply {
+                Ok(content) => Ok(ai_manager_llm_service::LLMResponse {
+                    content: content.clone(),
+                    model: "mock-model".to_string(),
+                    usage: ai_manager_shared::TokenUsage {
+                        prompt_tokens: 10,
+                        completion_tokens: 10,
+                        total_tokens: 20,
+                    },
+                    finish_reason: ai_manager_llm_service::FinishReason::Stop,
+                    provider: "mock".to_string(),
+                }),
+                Err(message) => Err(SystemError::LLMApi {
+                    provider: "mock".to_string(),
+                    message: message.clone(),
+                }),
+            }
+        }
+
+        async fn get_usage(&self) -> ai_manager_shared::TokenUsage {
+            ai_manager_shared::TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            "mock"
+        }
+
+        async fn health_check(&self) -> ai_manager_shared::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn reply_email() -> ai_manager_shared::messages::EmailData {
+        ai_manager_shared::messages::EmailData {
+            id: "1".to_string(),
+            from: "friend@example.com".to_string(),
+            to: vec!["user@example.com".to_string()],
+            subject: "Re: dinner on Friday?".to_string(),
+            body: "Sounds good, see you then!".to_string(),
+            timestamp: Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_email_with_llm() {
+        let client = EmailClient::new().await.unwrap();
+        let provider = MockLLMProvider {
+            reply: Ok("```json\n{\"category\": \"Personal\", \"priority\": \"Low\", \"suggested_actions\": [\"Reply later\"], \"auto_reply\": null}\n```".to_string()),
+        };
+
+        let processed = client
+            .process_email_with_llm(&reply_email(), &provider)
+            .await
+            .unwrap();
+        assert!(matches!(processed.category, EmailCategory::Personal));
+        assert!(matches!(processed.priority, EmailPriority::Low));
+        assert!(!processed.is_high_priority);
+        assert_eq!(processed.suggested_actions, vec!["Reply later"]);
+        assert!(processed.auto_reply.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_process_email_with_llm_falls_back_to_rules() {
+        let client = EmailClient::new().await.unwrap();
+
+        // Rule-based path treats any "re:" subject as high priority
+        let failing = MockLLMProvider {
+            reply: Err("service unavailable".to_string()),
+        };
+        let processed = client
+            .process_email_with_llm(&reply_email(), &failing)
+            .await
+            .unwrap();
+        assert!(matches!(processed.priority, EmailPriority::High));
+
+        let unparseable = MockLLMProvider {
+            reply: Ok("I think this is a personal email.".to_string()),
+        };
+        let processed = client
+            .process_email_with_llm(&reply_email(), &unparseable)
+            .await
+            .unwrap();
+        assert!(matches!(processed.priority, EmailPriority::High));
+    }
+
+    #[tokio::test]
+    async fn test_sender_rules_override_categorization() {
+        let mut client = EmailClient::new().await.unwrap();
+        let spam_verdict = MockLLMProvider {
+            reply: Ok("{\"category\": \"Spam\", \"priority\": \"Low\", \"suggested_actions\": [], \"auto_reply\": null}".to_string()),
+        };
+
+        client.block_sender("Friend <FRIEND@example.com>");
+        let processed = client.process_email(&reply_email()).await.unwrap();
+        assert!(matches!(processed.category, EmailCategory::Spam));
+        assert!(processed
+            .suggested_actions
+            .contains(&"Block sender".to_string()));
+
+        client.allow_sender("friend@example.com");
+        let processed = client
+            .process_email_with_llm(&reply_email(), &spam_verdict)
+            .await
+            .unwrap();
+        assert!(!matches!(processed.category, EmailCategory::Spam));
+
+        client.unblock_sender("friend@example.com");
+        let processed = client
+            .process_email_with_llm(&reply_email(), &spam_verdict)
+            .await
+            .unwrap();
+        assert!(matches!(processed.category, EmailCategory::Spam));
+    }
+
+    #[tokio::test]
+    async fn test_folder_operations_mock_mode() {
+        let client = EmailClient::new().await.unwrap();
+
+        assert!(client.mark_read("1").await.is_ok());
+        assert!(client.move_to_folder("1", "Receipts").await.is_ok());
+        assert!(client.archive("1").await.is_ok());
+        assert!(client.delete("1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_uid_set_email_ids_rejected() {
+        let client = EmailClient::unconfigured();
+
+        for email_id in ["1:*", "1,2,3", "0", ""] {
+            assert!(matches!(
+                client.delete(email_id).await,
+                Err(SystemError::InvalidInput(_))
+            ));
+        }
+        assert!(matches!(
+            client.mark_read("1:*").await,
+            Err(SystemError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            client.archive("1:*").await,
+            Err(SystemError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            client.download_attachment("1:*", "0").await,
+            Err(SystemError::InvalidInput(_))
+        ));
+
+        // A single UID gets as far as connecting
+        assert!(matches!(
+            client.delete("42").await,
+            Err(SystemError::Configuration(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_fetch_emails_mock_mode() {
         let client = EmailClient::new().await.unwrap();
-        let emails = client.fetch_emails().await.unwrap();
+        let emails = client.fetch_emails(None).await.unwrap();
         assert!(!emails.is_empty());
     }
+
+    /// Parse the BODYSTRUCTURE out of a FETCH response line
+    fn with_structure<T>(response: &str, f: impl FnOnce(&BodyStructure) -> T) -> T {
+        let (_, response) = imap_proto::parser::parse_response(response.as_bytes()).unwrap();
+        let imap_proto::Response::Fetch(_, attributes) = response else {
+            panic!("expected a FETCH response");
+        };
+        let structure = attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                imap_proto::AttributeValue::BodyStructure(structure) => Some(structure),
+                _ => None,
+            })
+            .unwrap();
+        f(structure)
+    }
+
+    const MIXED_STRUCTURE: &str = concat!(
+        "* 1 FETCH (UID 7 BODYSTRUCTURE (",
+        "((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"QUOTED-PRINTABLE\" 20 1 NIL (\"INLINE\" NIL) NIL NIL)",
+        "(\"TEXT\" \"HTML\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 30 1 NIL NIL NIL NIL)",
+        " \"ALTERNATIVE\" (\"BOUNDARY\" \"alt\") NIL NIL NIL)",
+        "(\"APPLICATION\" \"PDF\" (\"NAME\" \"invoice.pdf\") NIL NIL \"BASE64\" 8 NIL (\"ATTACHMENT\" (\"FILENAME\" \"invoice.pdf\")) NIL NIL)",
+        "(\"VIDEO\" \"MP4\" NIL NIL NIL \"BASE64\" 5000000 NIL (\"ATTACHMENT\" (\"FILENAME\" \"talk.mp4\")) NIL NIL)",
+        " \"MIXED\" (\"BOUNDARY\" \"sep\") NIL NIL NIL))\r\n",
+    );
+
+    #[test]
+    fn test_message_layout_from_bodystructure() {
+        let layout = with_structure(MIXED_STRUCTURE, MessageLayout::from_structure);
+
+        assert_eq!(layout.text, TextPart::Section(vec![1, 1]));
+        assert_eq!(
+            layout.attachments,
+            vec![
+                AttachmentPart {
+                    section: vec![2],
+                    filename: "invoice.pdf".to_string(),
+                    content_type: "application/pdf".to_string(),
+                    octets: 8,
+                },
+                AttachmentPart {
+                    section: vec![3],
+                    filename: "talk.mp4".to_string(),
+                    content_type: "video/mp4".to_string(),
+                    octets: 5_000_000,
+                },
+            ]
+        );
+        // The video is too large to download with the message
+        assert_eq!(
+            layout.fetch_items().unwrap(),
+            "(UID BODY.PEEK[1.1.MIME] BODY.PEEK[1.1] BODY.PEEK[2.MIME] BODY.PEEK[2])"
+        );
+    }
+
+    #[test]
+    fn test_assemble_email_from_fetched_sections() {
+        let layout = with_structure(MIXED_STRUCTURE, MessageLayout::from_structure);
+        let header = concat!(
+            "From: Alice <alice@example.com>\r\n",
+            "To: bob@example.com, carol@example.com\r\n",
+            "Subject: Invoice\r\n",
+            "Date: Tue, 1 Oct 2024 10:00:00 +0000\r\n",
+            "Message-ID: <invoice-2@example.com>\r\n",
+            "References: <order@example.com>\r\n <invoice-1@example.com>\r\n",
+            "MIME-Version: 1.0\r\n",
+            "Content-Type: multipart/mixed; boundary=\"sep\"\r\n",
+            "\r\n",
+        );
+        let sections: Vec<(SectionPath, &[u8])> = vec![
+            (
+                SectionPath::Part(vec![1, 1], Some(MessageSection::Mime)),
+                &b"Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n"[..],
+            ),
+            (SectionPath::Part(vec![1, 1], None), &b"See attached=2E\r\n"[..]),
+            (
+                SectionPath::Part(vec![2], Some(MessageSection::Mime)),
+                &b"Content-Type: application/pdf\r\nContent-Transfer-Encoding: base64\r\n\r\n"[..],
+            ),
+            (SectionPath::Part(vec![2], None), &b"SGVsbG8=\r\n"[..]),
+        ];
+
+        let email = assemble_email("7", header.as_bytes(), &layout, |path| {
+            sections
+                .iter()
+                .find(|(section, _)| section == path)
+                .map(|(_, data)| *data)
+        })
+        .unwrap();
+        assert_eq!(email.id, "7");
+        assert_eq!(email.from, "Alice <alice@example.com>");
+        assert_eq!(email.to, vec!["bob@example.com", "carol@example.com"]);
+        assert_eq!(email.subject, "Invoice");
+        assert_eq!(email.message_id.as_deref(), Some("<invoice-2@example.com>"));
+        assert_eq!(
+            email.references,
+            vec!["<order@example.com>", "<invoice-1@example.com>"]
+        );
+        assert_eq!(email.timestamp.to_rfc3339(), "2024-10-01T10:00:00+00:00");
+        assert_eq!(email.body.trim(), "See attached.");
+
+        assert_eq!(email.attachments.len(), 2);
+        let invoice = &email.attachments[0];
+        assert_eq!(invoice.id, "0");
+        assert_eq!(invoice.filename, "invoice.pdf");
+        assert_eq!(invoice.size, 5);
+        assert_eq!(invoice.data.as_deref(), Some(&b"Hello"[..]));
+        let video = &email.attachments[1];
+        assert_eq!(video.id, "1");
+        assert_eq!(video.size, 5_000_000);
+        assert!(video.data.is_none());
+    }
+
+    #[test]
+    fn test_reply_threading() {
+        assert_eq!(reply_subject("Lunch"), "Re: Lunch");
+        assert_eq!(reply_subject("RE: re:Lunch"), "Re: Lunch");
+        assert_eq!(reply_subject("Réunion"), "Re: Réunion");
+
+        let mut email = reply_email();
+        assert!(reply_headers(&email).is_empty());
+
+        email.message_id = Some("<2@example.com>".to_string());
+        email.references = vec!["<1@example.com>".to_string()];
+        assert_eq!(
+            reply_headers(&email),
+            vec![
+                ("In-Reply-To", "<2@example.com>".to_string()),
+                ("References", "<1@example.com> <2@example.com>".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_reply_message() {
+        let mut email = reply_email();
+        email.message_id = Some("<2@example.com>".to_string());
+        let attachment = EmailAttachment {
+            id: "0".to_string(),
+            filename: "notes.txt".to_string(),
+            content_type: "text/plain".to_string(),
+            size: 5,
+            data: Some(b"Hello".to_vec()),
+        };
+
+        let message = build_message(
+            "me@example.com",
+            &["friend@example.com".to_string()],
+            &reply_subject(&email.subject),
+            "See you at seven",
+            std::slice::from_ref(&attachment),
+            &reply_headers(&email),
+        )
+        .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("In-Reply-To: <2@example.com>"));
+        assert!(formatted.contains("References: <2@example.com>"));
+        assert!(formatted.contains("Subject: Re: dinner on Friday?"));
+        assert!(formatted.contains("See you at seven"));
+        assert!(formatted.contains("notes.txt"));
+
+        let unloaded = EmailAttachment {
+            data: None,
+            ..attachment
+        };
+        assert!(matches!(
+            build_message(
+                "me@example.com",
+                &["friend@example.com".to_string()],
+                "Notes",
+                "",
+                &[unloaded],
+                &[],
+            ),
+            Err(SystemError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_without_smtp_config() {
+        let client = EmailClient::unconfigured();
+        assert!(matches!(
+            client.send_reply(&reply_email(), "Thanks!").await,
+            Err(SystemError::Configuration(_))
+        ));
+    }
+
+    #[test]
+    fn test_single_part_message_text() {
+        let structure = "* 1 FETCH (UID 3 BODYSTRUCTURE (\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 12 1 NIL NIL NIL NIL))\r\n";
+        let layout = with_structure(structure, MessageLayout::from_structure);
+        assert_eq!(layout.text, TextPart::Whole);
+        assert_eq!(layout.fetch_items().unwrap(), "(UID BODY.PEEK[TEXT])");
+
+        let header = b"From: alice@example.com\r\nSubject: Hi\r\n\r\n";
+        let email = assemble_email("3", header, &layout, |path| {
+            (*path == SectionPath::Full(MessageSection::Text)).then_some(&b"Plain text\r\n"[..])
+        })
+        .unwrap();
+        assert_eq!(email.body.trim(), "Plain text");
+        assert!(email.attachments.is_empty());
+    }
 }
@@ -1,15 +1,38 @@
+pub mod auto_reply;
+pub mod caldav;
 pub mod calendar;
+pub mod digest;
 pub mod email;
 pub mod notifications;
+pub mod sender_rules;
 
-use ai_manager_shared::{errors::SystemError, messages::ServiceMessage};
+use ai_manager_llm_service::LLMProvider;
+use ai_manager_shared::{
+    constants::{
+        DEFAULT_EMAIL_CONCURRENCY, DEFAULT_EMAIL_DIGEST_INTERVAL_HOURS,
+        REPLIED_THREADS_LOAD_TIMEOUT_SECONDS,
+    },
+    errors::SystemError,
+    messages::{EmailData, SenderRule, ServiceMessage},
+    types::EmailAccountConfig,
+    EXTERNAL_SERVICE_ID,
+};
 use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
-pub use calendar::GoogleCalendarClient;
-pub use email::EmailClient;
-pub use notifications::NotificationClient;
+pub use auto_reply::AutoReplier;
+pub use caldav::CalDavClient;
+pub use calendar::{CalendarProvider, GoogleCalendarClient};
+pub use digest::EmailDigest;
+pub use email::{EmailClient, ProcessedEmail};
+pub use notifications::{
+    DiscordChannel, NotificationChannel, NotificationClient, SlackChannel, TelegramChannel,
+};
+pub use sender_rules::SenderRules;
 
 #[async_trait]
 pub trait Service {
@@ -20,26 +43,98 @@ pub trait Service {
 }
 
 pub struct ExternalService {
-    calendar: GoogleCalendarClient,
+    calendar: Box<dyn CalendarProvider>,
     email: EmailClient,
     notifications: NotificationClient,
+    llm_provider: Option<Box<dyn LLMProvider>>,
+    email_concurrency: usize,
+    digest: EmailDigest,
+    /// How often low-priority mail is summarized; `None` disables the digest
+    digest_interval: Option<Duration>,
+    auto_reply: AutoReplier,
+    /// Whether the threads already auto-replied to have been loaded; no
+    /// auto-reply is sent before, so none is sent twice
+    replied_threads_loaded: bool,
+    shutdown: CancellationToken,
     tx: Option<mpsc::Sender<ServiceMessage>>,
 }
 
 impl ExternalService {
     pub async fn new(tx: mpsc::Sender<ServiceMessage>) -> Result<Self, SystemError> {
-        let calendar = GoogleCalendarClient::new().await?;
+        // Prefer a CalDAV server when one is configured, otherwise Google Calendar
+        let calendar: Box<dyn CalendarProvider> = if std::env::var("CALDAV_URL").is_ok() {
+            Box::new(CalDavClient::new().await?)
+        } else {
+            Box::new(GoogleCalendarClient::new().await?)
+        };
         let email = EmailClient::new().await?;
         let notifications = NotificationClient::new().await?;
-
+        let email_concurrency = std::env::var("EMAIL_PROCESS_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_EMAIL_CONCURRENCY);
+        // Zero hours turns the digest off
+        let digest_hours = std::env::var("EMAIL_DIGEST_INTERVAL_HOURS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_EMAIL_DIGEST_INTERVAL_HOURS);
+        let digest_interval =
+            (digest_hours > 0).then(|| Duration::from_secs(digest_hours * 60 * 60));
         Ok(Self {
             calendar,
             email,
             notifications,
+            llm_provider: None,
+            email_concurrency,
+            digest: EmailDigest::new(),
+            digest_interval,
+            auto_reply: AutoReplier::new(),
+            replied_threads_loaded: false,
+            shutdown: CancellationToken::new(),
             tx: Some(tx),
         })
     }
 
+    /// Use a specific calendar backend instead of the one picked from the environment
+    pub fn with_calendar_provider(mut self, provider: Box<dyn CalendarProvider>) -> Self {
+        self.calendar = provider;
+        self
+    }
+
+    /// Use an LLM provider for email categorization instead of keyword rules
+    pub fn with_llm_provider(mut self, provider: Box<dyn LLMProvider>) -> Self {
+        self.llm_provider = Some(provider);
+        self
+    }
+
+    /// Set how many emails of a batch are processed at once
+    pub fn with_email_concurrency(mut self, concurrency: usize) -> Self {
+        self.email_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set how often low-priority mail is sent as a digest, or `None` to
+    /// stop collecting it
+    pub fn with_digest_interval(mut self, interval: Option<Duration>) -> Self {
+        self.digest_interval = interval.filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Send the generated auto-reply to mail in the categories `account`
+    /// has auto-replies enabled for
+    pub fn with_email_account(mut self, account: &EmailAccountConfig) -> Self {
+        self.auto_reply =
+            AutoReplier::new().with_categories(account.auto_reply_categories.iter().cloned());
+        self
+    }
+
+    /// Stop taking messages and exit once those queued are handled when
+    /// `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     async fn handle_calendar_sync(
         &mut self,
         action: ai_manager_shared::messages::CalendarAction,
@@ -52,13 +147,9 @@ impl ExternalService {
                 let events = self.calendar.list_events(start_date, end_date).await?;
                 info!("Retrieved {} calendar events", events.len());
 
-                // Send response back to core service
+                // Send the events back for the UI to show
                 if let Some(tx) = &self.tx {
-                    let response = ServiceMessage::SystemResponse {
-                        content: format!("Found {} calendar events", events.len()),
-                        message_type: ai_manager_shared::messages::ResponseType::Info,
-                        timestamp: chrono::Utc::now(),
-                    };
+                    let response = ServiceMessage::CalendarEventsResponse { events };
                     tx.send(response).await.map_err(|e| {
                         SystemError::ServiceCommunication(format!(
                             "Failed to send calendar response: {}",
@@ -72,19 +163,22 @@ impl ExternalService {
                 description,
                 start_time,
                 end_time,
+                attendees,
             } => {
                 let event_id = self
                     .calendar
-                    .create_event(&title, description.as_deref(), start_time, end_time)
+                    .create_event(
+                        &title,
+                        description.as_deref(),
+                        start_time,
+                        end_time,
+                        &attendees,
+                    )
                     .await?;
                 info!("Created calendar event: {}", event_id);
 
                 if let Some(tx) = &self.tx {
-                    let response = ServiceMessage::SystemResponse {
-                        content: format!("Created calendar event: {}", event_id),
-                        message_type: ai_manager_shared::messages::ResponseType::Success,
-                        timestamp: chrono::Utc::now(),
-                    };
+                    let response = ServiceMessage::CalendarEventCreated { event_id };
                     tx.send(response).await.map_err(|e| {
                         SystemError::ServiceCommunication(format!(
                             "Failed to send calendar response: {}",
@@ -116,6 +210,50 @@ impl ExternalService {
                         content: format!("Updated calendar event: {}", event_id),
                         message_type: ai_manager_shared::messages::ResponseType::Success,
                         timestamp: chrono::Utc::now(),
+                        request_id: None,
+                    };
+                    tx.send(response).await.map_err(|e| {
+                        SystemError::ServiceCommunication(format!(
+                            "Failed to send calendar response: {}",
+                            e
+                        ))
+                    })?;
+                }
+            }
+            ai_manager_shared::messages::CalendarAction::FindFreeSlot {
+                duration,
+                within_range,
+            } => {
+                let duration = chrono::Duration::from_std(duration).map_err(|e| {
+                    SystemError::InvalidInput(format!("Invalid slot duration: {}", e))
+                })?;
+                let slots = self
+                    .calendar
+                    .find_free_slots(duration, within_range)
+                    .await?;
+                info!("Found {} free calendar slots", slots.len());
+
+                if let Some(tx) = &self.tx {
+                    let content = if slots.is_empty() {
+                        "No free slots found in the requested range".to_string()
+                    } else {
+                        let listed: Vec<String> = slots
+                            .iter()
+                            .map(|slot| {
+                                format!(
+                                    "{} - {}",
+                                    slot.start.format("%Y-%m-%d %H:%M UTC"),
+                                    slot.end.format("%H:%M UTC")
+                                )
+                            })
+                            .collect();
+                        format!("Found {} free slots: {}", slots.len(), listed.join(", "))
+                    };
+                    let response = ServiceMessage::SystemResponse {
+                        content,
+                        message_type: ai_manager_shared::messages::ResponseType::Info,
+                        timestamp: chrono::Utc::now(),
+                        request_id: None,
                     };
                     tx.send(response).await.map_err(|e| {
                         SystemError::ServiceCommunication(format!(
@@ -134,6 +272,7 @@ impl ExternalService {
                         content: format!("Deleted calendar event: {}", event_id),
                         message_type: ai_manager_shared::messages::ResponseType::Success,
                         timestamp: chrono::Utc::now(),
+                        request_id: None,
                     };
                     tx.send(response).await.map_err(|e| {
                         SystemError::ServiceCommunication(format!(
@@ -153,25 +292,87 @@ impl ExternalService {
     ) -> Result<(), SystemError> {
         info!("Processing {} emails", emails.len());
 
-        let email_count = emails.len();
-        for email in emails {
-            // Process each email (categorization, priority assessment, etc.)
-            let processed = self.email.process_email(&email).await?;
-            info!("Processed email: {}", email.subject);
+        let email_client = &self.email;
+        let notifications = &self.notifications;
+        let provider = self.llm_provider.as_deref();
 
-            // Send notification if high priority
-            if processed.is_high_priority {
-                self.notifications
-                    .send_notification(&format!("High priority email: {}", email.subject))
-                    .await?;
+        let results: Vec<(EmailData, Result<ProcessedEmail, SystemError>)> = stream::iter(emails)
+            .map(|email| async move {
+                // Categorization, priority assessment, etc.
+                let processed = match provider {
+                    Some(provider) => email_client.process_email_with_llm(&email, provider).await,
+                    None => email_client.process_email(&email).await,
+                };
+
+                match &processed {
+                    Ok(processed) => {
+                        info!("Processed email: {}", email.subject);
+                        if processed.is_high_priority {
+                            if let Err(e) = notifications
+                                .send_notification(&format!(
+                                    "High priority email: {}",
+                                    email.subject
+                                ))
+                                .await
+                            {
+                                warn!("Failed to notify about email {}: {}", email.subject, e);
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Failed to process email {}: {}", email.subject, e),
+                }
+                (email, processed)
+            })
+            .buffer_unordered(self.email_concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut failed: Vec<&str> = Vec::new();
+        for (email, processed) in &results {
+            match processed {
+                // High-priority mail was already notified about
+                Ok(processed) if !processed.is_high_priority => {
+                    if self.digest_interval.is_some() {
+                        self.digest.add(email, processed);
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => failed.push(email.subject.as_str()),
+            }
+        }
+
+        if self.auto_reply.is_enabled() && !self.replied_threads_loaded {
+            warn!("Auto-replied threads not loaded yet, sending no auto-replies");
+        } else if self.auto_reply.is_enabled() {
+            for (email, processed) in &results {
+                if let Ok(processed) = processed {
+                    self.send_auto_reply(email, processed).await;
+                }
             }
         }
 
         if let Some(tx) = &self.tx {
+            let processed = processed_emails(&results);
+            if !processed.is_empty() {
+                let result = ServiceMessage::EmailProcessResult { emails: processed };
+                tx.send(result).await.map_err(|e| {
+                    SystemError::ServiceCommunication(format!(
+                        "Failed to send email results: {}",
+                        e
+                    ))
+                })?;
+            }
+
+            let message_type = if failed.is_empty() {
+                ai_manager_shared::messages::ResponseType::Info
+            } else {
+                ai_manager_shared::messages::ResponseType::Warning
+            };
             let response = ServiceMessage::SystemResponse {
-                content: format!("Processed {} emails", email_count),
-                message_type: ai_manager_shared::messages::ResponseType::Info,
+                content: batch_summary(results.len(), &failed),
+                message_type,
                 timestamp: chrono::Utc::now(),
+                request_id: None,
             };
             tx.send(response).await.map_err(|e| {
                 SystemError::ServiceCommunication(format!("Failed to send email response: {}", e))
@@ -180,6 +381,181 @@ impl ExternalService {
 
         Ok(())
     }
+
+    /// Reply to `email` with its generated auto-reply if it qualifies
+    async fn send_auto_reply(&mut self, email: &EmailData, processed: &ProcessedEmail) {
+        let Some(reply) = self.auto_reply.reply_for(email, processed) else {
+            return;
+        };
+
+        let to = auto_reply::sender_address(&email.from);
+        match self.email.send_reply(email, reply).await {
+            Ok(()) => {
+                info!("Sent auto-reply to {}", to);
+                let thread_key = self.auto_reply.record_reply(email);
+                self.store_replied_thread(thread_key).await;
+            }
+            Err(e) => warn!("Failed to send auto-reply to {}: {}", to, e),
+        }
+    }
+
+    /// Wait until the threads already auto-replied to are loaded, handling
+    /// other messages meanwhile but holding back mail to process so none is
+    /// replied to twice. Returns the mail held back, or `None` if `rx` closed.
+    async fn await_replied_threads(
+        &mut self,
+        rx: &mut mpsc::Receiver<ServiceMessage>,
+    ) -> Option<Vec<ServiceMessage>> {
+        let mut held_back = Vec::new();
+        let deadline =
+            tokio::time::sleep(Duration::from_secs(REPLIED_THREADS_LOAD_TIMEOUT_SECONDS));
+        tokio::pin!(deadline);
+        while !self.replied_threads_loaded {
+            tokio::select! {
+                message = rx.recv() => match message? {
+                    message @ ServiceMessage::EmailProcess { .. } => held_back.push(message),
+                    message => {
+                        if let Err(e) = self.handle_message(message).await {
+                            error!("Error handling message: {}", e);
+                        }
+                    }
+                },
+                _ = &mut deadline => {
+                    warn!("Auto-replied threads not loaded in time");
+                    break;
+                }
+                _ = self.shutdown.cancelled() => break,
+            }
+        }
+        Some(held_back)
+    }
+
+    /// Have the data service persist that a thread was replied to, so it
+    /// isn't replied to again after a restart
+    async fn store_replied_thread(&self, thread_key: String) {
+        if let Some(tx) = &self.tx {
+            let message = ServiceMessage::StoreRepliedThread { thread_key };
+            if let Err(e) = tx.send(message).await {
+                warn!("Failed to store replied thread: {}", e);
+            }
+        }
+    }
+
+    /// Have the data service persist the rule for `address`
+    async fn store_sender_rule(
+        &self,
+        address: &str,
+        rule: Option<SenderRule>,
+    ) -> Result<(), SystemError> {
+        if let Some(tx) = &self.tx {
+            let message = ServiceMessage::StoreSenderRule {
+                address: sender_rules::normalize(address),
+                rule,
+            };
+            tx.send(message).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!("Failed to store sender rule: {}", e))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Send the collected low-priority mail as one notification
+    async fn send_digest(&mut self) {
+        let count = self.digest.len();
+        let Some(digest) = self.digest.compose(self.llm_provider.as_deref()).await else {
+            return;
+        };
+
+        match self.notifications.send_notification(&digest).await {
+            Ok(()) => info!("Sent email digest of {} emails", count),
+            Err(e) => warn!("Failed to send email digest: {}", e),
+        }
+    }
+
+    async fn handle_email_action(
+        &mut self,
+        action: ai_manager_shared::messages::EmailAction,
+    ) -> Result<(), SystemError> {
+        use ai_manager_shared::messages::EmailAction;
+
+        let content = match action {
+            EmailAction::MarkRead { email_id } => {
+                self.email.mark_read(&email_id).await?;
+                format!("Marked email {} as read", email_id)
+            }
+            EmailAction::MoveToFolder { email_id, folder } => {
+                self.email.move_to_folder(&email_id, &folder).await?;
+                format!("Moved email {} to {}", email_id, folder)
+            }
+            EmailAction::Archive { email_id } => {
+                self.email.archive(&email_id).await?;
+                format!("Archived email {}", email_id)
+            }
+            EmailAction::Delete { email_id } => {
+                self.email.delete(&email_id).await?;
+                format!("Deleted email {}", email_id)
+            }
+            EmailAction::BlockSender { address } => {
+                self.email.block_sender(&address);
+                self.store_sender_rule(&address, Some(SenderRule::Block))
+                    .await?;
+                format!("Blocked sender {}", address)
+            }
+            EmailAction::AllowSender { address } => {
+                self.email.allow_sender(&address);
+                self.store_sender_rule(&address, Some(SenderRule::Allow))
+                    .await?;
+                format!("Allowed sender {}", address)
+            }
+            EmailAction::UnblockSender { address } => {
+                self.email.unblock_sender(&address);
+                self.store_sender_rule(&address, None).await?;
+                format!("Removed the rule for sender {}", address)
+            }
+        };
+        info!("{}", content);
+
+        if let Some(tx) = &self.tx {
+            let response = ServiceMessage::SystemResponse {
+                content,
+                message_type: ai_manager_shared::messages::ResponseType::Success,
+                timestamp: chrono::Utc::now(),
+                request_id: None,
+            };
+            tx.send(response).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!(
+                    "Failed to send email action response: {}",
+                    e
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The emails in a processed batch that were processed successfully
+fn processed_emails(
+    results: &[(EmailData, Result<ProcessedEmail, SystemError>)],
+) -> Vec<ProcessedEmail> {
+    results
+        .iter()
+        .filter_map(|(_, processed)| processed.as_ref().ok().cloned())
+        .collect()
+}
+
+/// Summary of a processed batch of `total` emails, naming any that failed
+fn batch_summary(total: usize, failed: &[&str]) -> String {
+    if failed.is_empty() {
+        format!("Processed {} emails", total)
+    } else {
+        format!(
+            "Processed {} of {} emails; failed: {}",
+            total - failed.len(),
+            total,
+            failed.join(", ")
+        )
+    }
 }
 
 #[async_trait]
@@ -187,25 +563,92 @@ impl Service for ExternalService {
     async fn start(&mut self, mut rx: mpsc::Receiver<ServiceMessage>) -> Result<(), SystemError> {
         info!("External Service starting...");
 
-        while let Some(message) = rx.recv().await {
+        // Periodically retry notifications that failed to deliver
+        let mut retry_interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        // Without a digest the timer still runs but has nothing to send
+        let digest_period = self.digest_interval.unwrap_or(Duration::from_secs(
+            DEFAULT_EMAIL_DIGEST_INTERVAL_HOURS * 60 * 60,
+        ));
+        let mut digest_interval =
+            tokio::time::interval_at(tokio::time::Instant::now() + digest_period, digest_period);
+
+        // Load the stored allowlist and blocklist, and the threads already
+        // auto-replied to
+        let mut held_back = Vec::new();
+        if let Some(tx) = &self.tx {
+            if let Err(e) = tx.send(ServiceMessage::LoadSenderRules).await {
+                warn!("Failed to request sender rules: {}", e);
+            }
+            if let Err(e) = tx.send(ServiceMessage::LoadRepliedThreads).await {
+                warn!("Failed to request replied threads: {}", e);
+            } else if self.auto_reply.is_enabled() {
+                match self.await_replied_threads(&mut rx).await {
+                    Some(emails) => held_back = emails,
+                    None => return Ok(()),
+                }
+            }
+        }
+        for message in held_back {
             if let Err(e) = self.handle_message(message).await {
                 error!("Error handling message: {}", e);
             }
         }
 
-        warn!("External Service message receiver closed");
-        Ok(())
+        loop {
+            tokio::select! {
+                message = rx.recv() => {
+                    let Some(message) = message else {
+                        warn!("External Service message receiver closed");
+                        return Ok(());
+                    };
+                    if let Err(e) = self.handle_message(message).await {
+                        error!("Error handling message: {}", e);
+                    }
+                }
+                _ = self.shutdown.cancelled() => break,
+                _ = retry_interval.tick() => {
+                    let delivered = self.notifications.retry_due().await;
+                    if delivered > 0 {
+                        info!("Delivered {} queued notification(s)", delivered);
+                    }
+                    self.notifications.flush_suppressed().await;
+                }
+                _ = digest_interval.tick() => self.send_digest().await,
+            }
+        }
+
+        // Take no more messages, but handle the ones already queued
+        rx.close();
+        while let Some(message) = rx.recv().await {
+            if let Err(e) = self.handle_message(message).await {
+                error!("Error handling message: {}", e);
+            }
+        }
+        self.shutdown().await
     }
 
     async fn handle_message(&mut self, msg: ServiceMessage) -> Result<(), SystemError> {
         match msg {
             ServiceMessage::CalendarSync { action } => self.handle_calendar_sync(action).await,
             ServiceMessage::EmailProcess { emails } => self.handle_email_process(emails).await,
+            ServiceMessage::EmailAction { action } => self.handle_email_action(action).await,
+            ServiceMessage::SenderRulesLoaded { rules } => {
+                let rules = SenderRules::from_rules(rules);
+                info!("Loaded {} sender rules", rules.len());
+                self.email.set_sender_rules(rules);
+                Ok(())
+            }
+            ServiceMessage::RepliedThreadsLoaded { thread_keys } => {
+                info!("Loaded {} auto-replied threads", thread_keys.len());
+                self.auto_reply.restore_replied(thread_keys);
+                self.replied_threads_loaded = true;
+                Ok(())
+            }
             ServiceMessage::ServiceHealthCheck { service_id: _ } => {
                 if let Some(tx) = &self.tx {
                     let health = self.health_check().await;
                     let response = ServiceMessage::ServiceHealthResponse {
-                        service_id: "external-service".to_string(),
+                        service_id: EXTERNAL_SERVICE_ID.to_string(),
                         status: health,
                     };
                     tx.send(response).await.map_err(|e| {
@@ -241,6 +684,8 @@ impl Service for ExternalService {
 
     async fn shutdown(&mut self) -> Result<(), SystemError> {
         info!("External Service shutting down...");
+        // Don't lose mail collected since the last digest
+        self.send_digest().await;
         Ok(())
     }
 }
@@ -248,6 +693,7 @@ impl Service for ExternalService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::email::{EmailCategory, EmailPriority};
     use tokio::sync::mpsc;
 
     #[tokio::test]
@@ -259,4 +705,158 @@ mod tests {
         // This will fail without proper credentials, but tests the structure
         assert!(result.is_err() || result.is_ok());
     }
+
+    #[test]
+    fn test_processed_emails_skip_failures() {
+        let email = |id: &str| EmailData {
+            id: id.to_string(),
+            from: "alice@example.com".to_string(),
+            to: vec!["bob@example.com".to_string()],
+            subject: "Lunch".to_string(),
+            body: String::new(),
+            timestamp: chrono::Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
+        };
+        let processed = ProcessedEmail {
+            email_id: "1".to_string(),
+            category: EmailCategory::Personal,
+            priority: EmailPriority::Low,
+            is_high_priority: false,
+            suggested_actions: vec!["Reply".to_string()],
+            auto_reply: None,
+        };
+        let results = vec![
+            (email("1"), Ok(processed)),
+            (email("2"), Err(SystemError::Timeout)),
+        ];
+
+        let emails = processed_emails(&results);
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].email_id, "1");
+        assert_eq!(emails[0].suggested_actions, vec!["Reply"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_auto_reply_not_recorded() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut service = ExternalService::new(tx).await.unwrap();
+        service.email = EmailClient::unconfigured();
+        service.auto_reply = AutoReplier::new().with_categories([EmailCategory::Personal]);
+
+        let email = EmailData {
+            id: "1".to_string(),
+            from: "alice@example.com".to_string(),
+            to: vec!["bob@example.com".to_string()],
+            subject: "Lunch".to_string(),
+            body: "Lunch on Friday?".to_string(),
+            timestamp: chrono::Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: Some("<1@example.com>".to_string()),
+            references: vec![],
+        };
+        let processed = ProcessedEmail {
+            email_id: "1".to_string(),
+            category: EmailCategory::Personal,
+            priority: EmailPriority::Low,
+            is_high_priority: false,
+            suggested_actions: vec![],
+            auto_reply: Some("Sounds good!".to_string()),
+        };
+
+        service.send_auto_reply(&email, &processed).await;
+
+        // The send failed, so the thread can still be replied to
+        assert!(service.auto_reply.reply_for(&email, &processed).is_some());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mail_held_back_until_replied_threads_loaded() {
+        let (tx, mut out) = mpsc::channel(100);
+        let account = EmailAccountConfig {
+            name: "work".to_string(),
+            email: "bob@example.com".to_string(),
+            imap_server: "imap.example.com".to_string(),
+            imap_port: 993,
+            smtp_server: "smtp.example.com".to_string(),
+            smtp_port: 587,
+            username: "bob".to_string(),
+            password: "secret".to_string(),
+            use_tls: true,
+            auto_reply_categories: vec![
+                EmailCategory::Work,
+                EmailCategory::Personal,
+                EmailCategory::Other,
+            ],
+        };
+        let mut service = ExternalService::new(tx)
+            .await
+            .unwrap()
+            .with_email_account(&account);
+        let (service_tx, rx) = mpsc::channel(100);
+        tokio::spawn(async move { service.start(rx).await });
+
+        let email = |id: &str, from: &str, subject: &str| EmailData {
+            id: id.to_string(),
+            from: from.to_string(),
+            to: vec!["bob@example.com".to_string()],
+            subject: subject.to_string(),
+            body: "Are you free on Friday?".to_string(),
+            timestamp: chrono::Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
+        };
+        let emails = vec![
+            email("1", "alice@example.com", "Re: Lunch"),
+            email("2", "carol@example.com", "Hello"),
+        ];
+        service_tx
+            .send(ServiceMessage::EmailProcess { emails })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            out.recv().await,
+            Some(ServiceMessage::LoadSenderRules)
+        ));
+        assert!(matches!(
+            out.recv().await,
+            Some(ServiceMessage::LoadRepliedThreads)
+        ));
+        // Nothing is processed before the replied threads arrive
+        let early = tokio::time::timeout(Duration::from_millis(100), out.recv()).await;
+        assert!(early.is_err());
+
+        service_tx
+            .send(ServiceMessage::RepliedThreadsLoaded {
+                thread_keys: vec!["alice@example.com\nlunch".to_string()],
+            })
+            .await
+            .unwrap();
+
+        let mut replied = Vec::new();
+        loop {
+            match out.recv().await.unwrap() {
+                ServiceMessage::StoreRepliedThread { thread_key } => replied.push(thread_key),
+                ServiceMessage::EmailProcessResult { .. } => break,
+                _ => {}
+            }
+        }
+        assert_eq!(replied, vec!["carol@example.com\nhello".to_string()]);
+    }
+
+    #[test]
+    fn test_batch_summary() {
+        assert_eq!(batch_summary(3, &[]), "Processed 3 emails");
+        assert_eq!(
+            batch_summary(3, &["Invoice"]),
+            "Processed 2 of 3 emails; failed: Invoice"
+        );
+    }
 }
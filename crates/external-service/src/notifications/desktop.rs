@@ -0,0 +1,68 @@
+use super::{Notification, NotificationChannel, NotificationType};
+use ai_manager_shared::errors::SystemError;
+use async_trait::async_trait;
+
+/// Native desktop notifications via `notify-rust`
+pub struct DesktopChannel;
+
+#[async_trait]
+impl NotificationChannel for DesktopChannel {
+    fn name(&self) -> &str {
+        "Desktop"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+        let mut desktop = notify_rust::Notification::new();
+        desktop
+            .appname("AI Manager")
+            .summary(&notification.title)
+            .body(&notification.message);
+
+        // Urgency is only understood by the XDG notification spec
+        #[cfg(all(unix, not(target_os = "macos")))]
+        desktop.urgency(urgency_for_type(&notification.notification_type));
+
+        // show() blocks on the platform notification service, keep it off the runtime
+        tokio::task::spawn_blocking(move || desktop.show().map(|_| ()))
+            .await
+            .map_err(|e| SystemError::ExternalService {
+                service: "Notifications".to_string(),
+                message: format!("Desktop notification task failed: {}", e),
+            })?
+            .map_err(|e| SystemError::ExternalService {
+                service: "Notifications".to_string(),
+                message: format!("Failed to show desktop notification: {}", e),
+            })
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn urgency_for_type(notification_type: &NotificationType) -> notify_rust::Urgency {
+    match notification_type {
+        NotificationType::Error => notify_rust::Urgency::Critical,
+        NotificationType::Warning => notify_rust::Urgency::Normal,
+        NotificationType::Info | NotificationType::Success => notify_rust::Urgency::Low,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_urgency_mapping() {
+        assert_eq!(
+            urgency_for_type(&NotificationType::Error),
+            notify_rust::Urgency::Critical
+        );
+        assert_eq!(
+            urgency_for_type(&NotificationType::Warning),
+            notify_rust::Urgency::Normal
+        );
+        assert_eq!(
+            urgency_for_type(&NotificationType::Info),
+            notify_rust::Urgency::Low
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use super::{Notification, NotificationChannel};
+use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::http;
+use ai_manager_shared::NOTIFICATION_REQUEST_TIMEOUT;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::time::Duration;
+
+/// Posts notifications as embeds to a Discord webhook
+pub struct DiscordChannel {
+    client: Client,
+    webhook_url: String,
+}
+
+impl DiscordChannel {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: http::client(Duration::from_secs(NOTIFICATION_REQUEST_TIMEOUT)),
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+
+    /// Build a channel from `DISCORD_WEBHOOK_URL`, if set
+    pub fn from_env() -> Option<Self> {
+        std::env::var("DISCORD_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| Self::new(&url))
+    }
+
+    fn build_payload(notification: &Notification) -> serde_json::Value {
+        serde_json::json!({
+            "embeds": [{
+                "title": notification.title,
+                "description": notification.message,
+                "color": notification.notification_type.color(),
+                "timestamp": notification.timestamp.to_rfc3339()
+            }]
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for DiscordChannel {
+    fn name(&self) -> &str {
+        "Discord"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(&Self::build_payload(notification))
+            .send()
+            .await
+            .map_err(|e| SystemError::ExternalService {
+                service: "Discord".to_string(),
+                message: format!("Webhook request failed: {}", e),
+            })?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(SystemError::ExternalService {
+                service: "Discord".to_string(),
+                message: format!("Webhook returned status: {}", response.status()),
+            })
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::NotificationType;
+
+    #[test]
+    fn test_build_payload() {
+        let notification = Notification {
+            title: "AI Manager - Warning".to_string(),
+            message: "Disk almost full".to_string(),
+            notification_type: NotificationType::Warning,
+            timestamp: chrono::Utc::now(),
+        };
+
+        let payload = DiscordChannel::build_payload(&notification);
+        let embed = &payload["embeds"][0];
+
+        assert_eq!(embed["title"], "AI Manager - Warning");
+        assert_eq!(embed["description"], "Disk almost full");
+        assert_eq!(embed["color"], 0xECB22E);
+    }
+}
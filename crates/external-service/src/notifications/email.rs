@@ -0,0 +1,24 @@
+use super::{Notification, NotificationChannel};
+use ai_manager_shared::errors::SystemError;
+use async_trait::async_trait;
+use tracing::info;
+
+/// Notification emails
+pub struct EmailChannel;
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn name(&self) -> &str {
+        "Email"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+        // This would integrate with the email client to send notification emails
+        // For now, we'll just log it
+        info!(
+            "Email notification: {} - {}",
+            notification.title, notification.message
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,675 @@
+pub mod desktop;
+pub mod discord;
+pub mod email;
+pub mod slack;
+pub mod telegram;
+pub mod webhook;
+
+use ai_manager_shared::errors::SystemError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+pub use desktop::DesktopChannel;
+pub use discord::DiscordChannel;
+pub use email::EmailChannel;
+pub use slack::SlackChannel;
+pub use telegram::TelegramChannel;
+pub use webhook::WebhookChannel;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NotificationType {
+    Info,
+    Warning,
+    Error,
+    Success,
+}
+
+impl NotificationType {
+    /// Accent colour used by chat channels, as `0xRRGGBB`
+    pub fn color(&self) -> u32 {
+        match self {
+            NotificationType::Info => 0x439FE0,
+            NotificationType::Warning => 0xECB22E,
+            NotificationType::Error => 0xE01E5A,
+            NotificationType::Success => 0x2EB67D,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+    pub notification_type: NotificationType,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// A destination notifications can be delivered to
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    fn name(&self) -> &str;
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError>;
+
+    /// Whether failed deliveries should be queued for retry
+    fn retryable(&self) -> bool {
+        false
+    }
+}
+
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_PER_MINUTE: usize = 10;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A failed delivery waiting to be retried on one channel
+#[derive(Debug, Clone)]
+pub struct PendingNotification {
+    pub notification: Notification,
+    pub channel: String,
+    pub attempts: u32,
+    pub last_error: String,
+    channel_index: usize,
+    next_attempt: Instant,
+}
+
+/// Recent sends used for deduplication and the per-minute ceiling
+#[derive(Debug, Default)]
+struct ThrottleState {
+    last_sent: HashMap<(String, String), Instant>,
+    window: VecDeque<Instant>,
+    suppressed: usize,
+}
+
+enum Admission {
+    Send,
+    Duplicate,
+    Throttled,
+}
+
+pub struct NotificationClient {
+    channels: Vec<Box<dyn NotificationChannel>>,
+    pending: Mutex<VecDeque<PendingNotification>>,
+    dead_letters: Mutex<Vec<PendingNotification>>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    throttle: Mutex<ThrottleState>,
+    cooldown: Duration,
+    max_per_minute: usize,
+}
+
+impl NotificationClient {
+    pub async fn new() -> Result<Self, SystemError> {
+        let desktop_notifications = std::env::var("ENABLE_DESKTOP_NOTIFICATIONS")
+            .map(|s| s.to_lowercase() == "true")
+            .unwrap_or(true);
+
+        let email_notifications = std::env::var("ENABLE_EMAIL_NOTIFICATIONS")
+            .map(|s| s.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let mut channels: Vec<Box<dyn NotificationChannel>> = Vec::new();
+        if desktop_notifications {
+            channels.push(Box::new(DesktopChannel));
+        }
+        if email_notifications {
+            channels.push(Box::new(EmailChannel));
+        }
+        if let Some(webhook) = WebhookChannel::from_env() {
+            channels.push(Box::new(webhook));
+        }
+        if let Some(slack) = SlackChannel::from_env() {
+            channels.push(Box::new(slack));
+        }
+        if let Some(discord) = DiscordChannel::from_env() {
+            channels.push(Box::new(discord));
+        }
+        if let Some(telegram) = TelegramChannel::from_env() {
+            channels.push(Box::new(telegram));
+        }
+
+        let max_retries = std::env::var("NOTIFICATION_MAX_RETRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_RETRIES);
+
+        let cooldown = std::env::var("NOTIFICATION_COOLDOWN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_COOLDOWN);
+
+        let max_per_minute = std::env::var("NOTIFICATION_MAX_PER_MINUTE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PER_MINUTE);
+
+        Ok(Self {
+            channels,
+            pending: Mutex::new(VecDeque::new()),
+            dead_letters: Mutex::new(Vec::new()),
+            max_retries,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            throttle: Mutex::new(ThrottleState::default()),
+            cooldown,
+            max_per_minute,
+        })
+    }
+
+    /// Set the window in which identical notifications are dropped and the
+    /// maximum number of notifications sent per minute
+    pub fn with_throttle(mut self, cooldown: Duration, max_per_minute: usize) -> Self {
+        self.cooldown = cooldown;
+        self.max_per_minute = max_per_minute;
+        self
+    }
+
+    /// Set how many delivery attempts a notification gets and the initial
+    /// backoff, which doubles after each failure
+    pub fn with_retry_policy(mut self, max_retries: u32, retry_backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Register an additional delivery channel
+    pub fn with_channel(mut self, channel: Box<dyn NotificationChannel>) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    pub async fn send_notification(&self, message: &str) -> Result<(), SystemError> {
+        self.send_notification_with_type(message, NotificationType::Info)
+            .await
+    }
+
+    pub async fn send_notification_with_type(
+        &self,
+        message: &str,
+        notification_type: NotificationType,
+    ) -> Result<(), SystemError> {
+        let notification = Notification {
+            title: self.get_title_for_type(&notification_type),
+            message: message.to_string(),
+            notification_type: notification_type.clone(),
+            timestamp: chrono::Utc::now(),
+        };
+
+        match self.admit(&notification).await {
+            Admission::Send => {}
+            Admission::Duplicate => {
+                debug!("Dropping duplicate notification: {}", notification.message);
+                return Ok(());
+            }
+            Admission::Throttled => {
+                debug!("Throttling notification: {}", notification.message);
+                return Ok(());
+            }
+        }
+
+        let result = self.deliver(&notification).await;
+        self.flush_suppressed().await;
+        result
+    }
+
+    /// Send a single "N more notifications suppressed" summary for anything
+    /// dropped by the per-minute ceiling
+    pub async fn flush_suppressed(&self) {
+        let suppressed = std::mem::take(&mut self.throttle.lock().await.suppressed);
+        if suppressed == 0 {
+            return;
+        }
+
+        let summary = Notification {
+            title: self.get_title_for_type(&NotificationType::Warning),
+            message: format!("{} more notifications suppressed", suppressed),
+            notification_type: NotificationType::Warning,
+            timestamp: chrono::Utc::now(),
+        };
+        if let Err(e) = self.deliver(&summary).await {
+            warn!("Failed to send suppressed notification summary: {}", e);
+        }
+    }
+
+    async fn admit(&self, notification: &Notification) -> Admission {
+        let now = Instant::now();
+        let mut throttle = self.throttle.lock().await;
+
+        let cooldown = self.cooldown;
+        throttle
+            .last_sent
+            .retain(|_, sent| now.duration_since(*sent) < cooldown);
+        while throttle
+            .window
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_WINDOW)
+        {
+            throttle.window.pop_front();
+        }
+
+        let key = (notification.title.clone(), notification.message.clone());
+        if throttle.last_sent.contains_key(&key) {
+            return Admission::Duplicate;
+        }
+        if throttle.window.len() >= self.max_per_minute {
+            throttle.suppressed += 1;
+            return Admission::Throttled;
+        }
+
+        throttle.last_sent.insert(key, now);
+        throttle.window.push_back(now);
+        Admission::Send
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<(), SystemError> {
+        let mut success_count = 0;
+        let mut errors = Vec::new();
+
+        for (index, channel) in self.channels.iter().enumerate() {
+            match channel.send(notification).await {
+                Ok(_) => success_count += 1,
+                Err(e) => {
+                    errors.push(format!("{} notification failed: {}", channel.name(), e));
+                    if channel.retryable() {
+                        self.enqueue_retry(index, notification, e.to_string()).await;
+                    }
+                }
+            }
+        }
+
+        if success_count > 0 {
+            info!(
+                "Notification sent successfully via {} method(s)",
+                success_count
+            );
+            Ok(())
+        } else {
+            Err(SystemError::ExternalService {
+                service: "Notifications".to_string(),
+                message: format!("All notification methods failed: {}", errors.join(", ")),
+            })
+        }
+    }
+
+    /// Number of failed deliveries waiting to be retried
+    pub async fn pending_count(&self) -> usize {
+        self.pending.lock().await.len()
+    }
+
+    /// Deliveries that exhausted their retries
+    pub async fn dead_letters(&self) -> Vec<PendingNotification> {
+        self.dead_letters.lock().await.clone()
+    }
+
+    /// Retry every pending delivery now, regardless of backoff.
+    /// Returns how many were delivered.
+    pub async fn flush_pending(&self) -> usize {
+        let batch: Vec<_> = self.pending.lock().await.drain(..).collect();
+        self.retry_batch(batch).await
+    }
+
+    /// Retry pending deliveries whose backoff has elapsed.
+    /// Returns how many were delivered.
+    pub async fn retry_due(&self) -> usize {
+        let now = Instant::now();
+        let batch: Vec<_> = {
+            let mut pending = self.pending.lock().await;
+            let (due, waiting): (VecDeque<_>, VecDeque<_>) =
+                pending.drain(..).partition(|p| p.next_attempt <= now);
+            *pending = waiting;
+            due.into_iter().collect()
+        };
+        self.retry_batch(batch).await
+    }
+
+    async fn retry_batch(&self, batch: Vec<PendingNotification>) -> usize {
+        let mut delivered = 0;
+        for mut pending in batch {
+            let channel = &self.channels[pending.channel_index];
+            match channel.send(&pending.notification).await {
+                Ok(_) => {
+                    info!(
+                        "Delivered queued notification via {} after {} attempt(s)",
+                        pending.channel,
+                        pending.attempts + 1
+                    );
+                    delivered += 1;
+                }
+                Err(e) => {
+                    pending.attempts += 1;
+                    pending.last_error = e.to_string();
+                    self.reschedule(pending).await;
+                }
+            }
+        }
+        delivered
+    }
+
+    async fn enqueue_retry(
+        &self,
+        channel_index: usize,
+        notification: &Notification,
+        error: String,
+    ) {
+        let pending = PendingNotification {
+            notification: notification.clone(),
+            channel: self.channels[channel_index].name().to_string(),
+            attempts: 1,
+            last_error: error,
+            channel_index,
+            next_attempt: Instant::now(),
+        };
+        self.reschedule(pending).await;
+    }
+
+    /// Queue a failed delivery with exponential backoff, or dead-letter it
+    /// once it has used up its attempts
+    async fn reschedule(&self, mut pending: PendingNotification) {
+        if pending.attempts >= self.max_retries {
+            warn!(
+                "Giving up on {} notification '{}' after {} attempts: {}",
+                pending.channel, pending.notification.message, pending.attempts, pending.last_error
+            );
+            self.dead_letters.lock().await.push(pending);
+            return;
+        }
+
+        let backoff = self.retry_backoff * 2u32.saturating_pow(pending.attempts - 1);
+        pending.next_attempt = Instant::now() + backoff;
+        self.pending.lock().await.push_back(pending);
+    }
+
+    fn get_title_for_type(&self, notification_type: &NotificationType) -> String {
+        match notification_type {
+            NotificationType::Info => "AI Manager - Info".to_string(),
+            NotificationType::Warning => "AI Manager - Warning".to_string(),
+            NotificationType::Error => "AI Manager - Error".to_string(),
+            NotificationType::Success => "AI Manager - Success".to_string(),
+        }
+    }
+
+    pub async fn send_error_notification(&self, error: &str) -> Result<(), SystemError> {
+        self.send_notification_with_type(error, NotificationType::Error)
+            .await
+    }
+
+    pub async fn send_warning_notification(&self, warning: &str) -> Result<(), SystemError> {
+        self.send_notification_with_type(warning, NotificationType::Warning)
+            .await
+    }
+
+    pub async fn send_success_notification(&self, message: &str) -> Result<(), SystemError> {
+        self.send_notification_with_type(message, NotificationType::Success)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_notification_client_creation() {
+        let client = NotificationClient::new().await;
+        assert!(client.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_notification_creation() {
+        let notification = Notification {
+            title: "Test".to_string(),
+            message: "Test message".to_string(),
+            notification_type: NotificationType::Info,
+            timestamp: chrono::Utc::now(),
+        };
+
+        assert_eq!(notification.title, "Test");
+        assert_eq!(notification.message, "Test message");
+    }
+
+    #[tokio::test]
+    async fn test_title_generation() {
+        let client = NotificationClient::new().await.unwrap();
+
+        assert_eq!(
+            client.get_title_for_type(&NotificationType::Info),
+            "AI Manager - Info"
+        );
+        assert_eq!(
+            client.get_title_for_type(&NotificationType::Error),
+            "AI Manager - Error"
+        );
+    }
+
+    struct MockChannel {
+        fail: bool,
+        sent: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NotificationChannel for MockChannel {
+        fn name(&self) -> &str {
+            "Mock"
+        }
+
+        async fn send(&self, _notification: &Notification) -> Result<(), SystemError> {
+            if self.fail {
+                return Err(SystemError::ExternalService {
+                    service: "Mock".to_string(),
+                    message: "unavailable".to_string(),
+                });
+            }
+            self.sent.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn channel_only_client() -> NotificationClient {
+        NotificationClient {
+            channels: Vec::new(),
+            pending: Mutex::new(VecDeque::new()),
+            dead_letters: Mutex::new(Vec::new()),
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            throttle: Mutex::new(ThrottleState::default()),
+            cooldown: DEFAULT_COOLDOWN,
+            max_per_minute: DEFAULT_MAX_PER_MINUTE,
+        }
+    }
+
+    /// Fails the first `failures` sends, then succeeds
+    struct FlakyChannel {
+        failures: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl NotificationChannel for FlakyChannel {
+        fn name(&self) -> &str {
+            "Flaky"
+        }
+
+        async fn send(&self, _notification: &Notification) -> Result<(), SystemError> {
+            let remaining = self.failures.load(std::sync::atomic::Ordering::SeqCst);
+            if remaining > 0 {
+                self.failures
+                    .store(remaining - 1, std::sync::atomic::Ordering::SeqCst);
+                return Err(SystemError::ExternalService {
+                    service: "Flaky".to_string(),
+                    message: "503 Service Unavailable".to_string(),
+                });
+            }
+            Ok(())
+        }
+
+        fn retryable(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried() {
+        let client = channel_only_client().with_channel(Box::new(FlakyChannel {
+            failures: std::sync::atomic::AtomicUsize::new(1),
+        }));
+
+        assert!(client
+            .send_notification("High priority email")
+            .await
+            .is_err());
+        assert_eq!(client.pending_count().await, 1);
+
+        // Backoff hasn't elapsed yet
+        assert_eq!(client.retry_due().await, 0);
+        assert_eq!(client.pending_count().await, 1);
+
+        assert_eq!(client.flush_pending().await, 1);
+        assert_eq!(client.pending_count().await, 0);
+        assert!(client.dead_letters().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted_go_to_dead_letters() {
+        let client = channel_only_client()
+            .with_retry_policy(3, Duration::from_millis(1))
+            .with_channel(Box::new(FlakyChannel {
+                failures: std::sync::atomic::AtomicUsize::new(10),
+            }));
+
+        let _ = client.send_notification("High priority email").await;
+        assert_eq!(client.flush_pending().await, 0);
+        assert_eq!(client.pending_count().await, 1);
+        assert_eq!(client.flush_pending().await, 0);
+
+        assert_eq!(client.pending_count().await, 0);
+        let dead = client.dead_letters().await;
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 3);
+        assert_eq!(dead[0].channel, "Flaky");
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_failures_are_not_queued() {
+        let client = channel_only_client().with_channel(Box::new(MockChannel {
+            fail: true,
+            sent: Default::default(),
+        }));
+
+        let _ = client.send_notification("Test").await;
+        assert_eq!(client.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failing_channel_does_not_suppress_others() {
+        let sent = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let client = channel_only_client()
+            .with_channel(Box::new(MockChannel {
+                fail: true,
+                sent: sent.clone(),
+            }))
+            .with_channel(Box::new(MockChannel {
+                fail: false,
+                sent: sent.clone(),
+            }));
+
+        let result = client.send_notification("Test").await;
+        assert!(result.is_ok());
+        assert_eq!(sent.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_all_channels_failing() {
+        let client = channel_only_client().with_channel(Box::new(MockChannel {
+            fail: true,
+            sent: Default::default(),
+        }));
+
+        let err = client.send_notification("Test").await.unwrap_err();
+        assert!(err.to_string().contains("Mock notification failed"));
+    }
+
+    /// Records every message it is asked to send
+    struct RecordingChannel {
+        messages: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl NotificationChannel for RecordingChannel {
+        fn name(&self) -> &str {
+            "Recording"
+        }
+
+        async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+            self.messages
+                .lock()
+                .unwrap()
+                .push(notification.message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_notifications_are_dropped() {
+        let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = channel_only_client().with_channel(Box::new(RecordingChannel {
+            messages: messages.clone(),
+        }));
+
+        for _ in 0..3 {
+            client
+                .send_notification("High priority email: Outage")
+                .await
+                .unwrap();
+        }
+        client.send_notification("Different message").await.unwrap();
+
+        // Same text with a different type has a different title, so it isn't a duplicate
+        client
+            .send_error_notification("High priority email: Outage")
+            .await
+            .unwrap();
+
+        assert_eq!(messages.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_excess_notifications_are_summarized() {
+        let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = channel_only_client()
+            .with_throttle(DEFAULT_COOLDOWN, 2)
+            .with_channel(Box::new(RecordingChannel {
+                messages: messages.clone(),
+            }));
+
+        for i in 0..5 {
+            client
+                .send_notification(&format!("Email {}", i))
+                .await
+                .unwrap();
+        }
+        assert_eq!(*messages.lock().unwrap(), vec!["Email 0", "Email 1"]);
+
+        client.flush_suppressed().await;
+        let sent = messages.lock().unwrap().clone();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2], "3 more notifications suppressed");
+
+        // Nothing left to summarize
+        client.flush_suppressed().await;
+        assert_eq!(messages.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_send_notification() {
+        let client = NotificationClient::new().await.unwrap();
+
+        // This will try to send notifications, but may fail depending on the environment
+        // The test primarily verifies the interface works
+        let result = client.send_notification("Test notification").await;
+        // Don't assert success/failure as it depends on the environment
+        assert!(result.is_ok() || result.is_err());
+    }
+}
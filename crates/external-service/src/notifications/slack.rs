@@ -0,0 +1,123 @@
+use super::{Notification, NotificationChannel};
+use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::http;
+use ai_manager_shared::NOTIFICATION_REQUEST_TIMEOUT;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::time::Duration;
+
+/// Posts notifications to a Slack incoming webhook
+pub struct SlackChannel {
+    client: Client,
+    webhook_url: String,
+}
+
+impl SlackChannel {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: http::client(Duration::from_secs(NOTIFICATION_REQUEST_TIMEOUT)),
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+
+    /// Build a channel from `SLACK_WEBHOOK_URL`, if set
+    pub fn from_env() -> Option<Self> {
+        std::env::var("SLACK_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| Self::new(&url))
+    }
+
+    fn build_payload(notification: &Notification) -> serde_json::Value {
+        serde_json::json!({
+            // Fallback text for clients that can't render blocks
+            "text": format!("{}: {}", notification.title, notification.message),
+            "attachments": [{
+                "color": format!("#{:06X}", notification.notification_type.color()),
+                "blocks": [
+                    {
+                        "type": "header",
+                        "text": { "type": "plain_text", "text": notification.title }
+                    },
+                    {
+                        "type": "section",
+                        "text": { "type": "mrkdwn", "text": notification.message }
+                    },
+                    {
+                        "type": "context",
+                        "elements": [{
+                            "type": "mrkdwn",
+                            "text": notification.timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+                        }]
+                    }
+                ]
+            }]
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for SlackChannel {
+    fn name(&self) -> &str {
+        "Slack"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(&Self::build_payload(notification))
+            .send()
+            .await
+            .map_err(|e| SystemError::ExternalService {
+                service: "Slack".to_string(),
+                message: format!("Webhook request failed: {}", e),
+            })?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(SystemError::ExternalService {
+                service: "Slack".to_string(),
+                message: format!("Webhook returned status: {}", response.status()),
+            })
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::NotificationType;
+
+    #[test]
+    fn test_build_payload() {
+        let notification = Notification {
+            title: "AI Manager - Error".to_string(),
+            message: "Database unreachable".to_string(),
+            notification_type: NotificationType::Error,
+            timestamp: chrono::Utc::now(),
+        };
+
+        let payload = SlackChannel::build_payload(&notification);
+        let attachment = &payload["attachments"][0];
+
+        assert_eq!(attachment["color"], "#E01E5A");
+        assert_eq!(
+            attachment["blocks"][0]["text"]["text"],
+            "AI Manager - Error"
+        );
+        assert_eq!(
+            attachment["blocks"][1]["text"]["text"],
+            "Database unreachable"
+        );
+        assert!(payload["text"]
+            .as_str()
+            .unwrap()
+            .contains("Database unreachable"));
+    }
+}
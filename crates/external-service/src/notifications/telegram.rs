@@ -0,0 +1,117 @@
+use super::{Notification, NotificationChannel};
+use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::http;
+use ai_manager_shared::NOTIFICATION_REQUEST_TIMEOUT;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Sends notifications to a chat through the Telegram Bot API
+pub struct TelegramChannel {
+    client: Client,
+    bot_token: String,
+    chat_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramResponse {
+    ok: bool,
+    description: Option<String>,
+}
+
+impl TelegramChannel {
+    pub fn new(bot_token: &str, chat_id: &str) -> Self {
+        Self {
+            client: http::client(Duration::from_secs(NOTIFICATION_REQUEST_TIMEOUT)),
+            bot_token: bot_token.to_string(),
+            chat_id: chat_id.to_string(),
+        }
+    }
+
+    /// Build a channel from `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`, if both are set
+    pub fn from_env() -> Option<Self> {
+        let bot_token = std::env::var("TELEGRAM_BOT_TOKEN").ok()?;
+        let chat_id = std::env::var("TELEGRAM_CHAT_ID").ok()?;
+        Some(Self::new(&bot_token, &chat_id))
+    }
+
+    fn build_payload(&self, notification: &Notification) -> serde_json::Value {
+        // Plain text avoids having to escape Markdown in arbitrary messages
+        serde_json::json!({
+            "chat_id": self.chat_id,
+            "text": format!("{}\n{}", notification.title, notification.message),
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for TelegramChannel {
+    fn name(&self) -> &str {
+        "Telegram"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(&self.build_payload(notification))
+            .send()
+            .await
+            .map_err(|e| SystemError::ExternalService {
+                service: "Telegram".to_string(),
+                // Don't leak the bot token embedded in the URL
+                message: format!("Bot API request failed: {}", e.without_url()),
+            })?;
+
+        let status = response.status();
+        let body: TelegramResponse =
+            response
+                .json()
+                .await
+                .map_err(|e| SystemError::ExternalService {
+                    service: "Telegram".to_string(),
+                    message: format!("Failed to parse response ({}): {}", status, e.without_url()),
+                })?;
+
+        if body.ok {
+            Ok(())
+        } else {
+            Err(SystemError::ExternalService {
+                service: "Telegram".to_string(),
+                message: format!(
+                    "Bot API returned {}: {}",
+                    status,
+                    body.description.unwrap_or_default()
+                ),
+            })
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::NotificationType;
+
+    #[test]
+    fn test_build_payload() {
+        let channel = TelegramChannel::new("token", "12345");
+        let notification = Notification {
+            title: "AI Manager - Info".to_string(),
+            message: "Sync complete".to_string(),
+            notification_type: NotificationType::Info,
+            timestamp: chrono::Utc::now(),
+        };
+
+        let payload = channel.build_payload(&notification);
+        assert_eq!(payload["chat_id"], "12345");
+        assert_eq!(payload["text"], "AI Manager - Info\nSync complete");
+    }
+}
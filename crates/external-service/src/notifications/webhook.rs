@@ -0,0 +1,70 @@
+use super::{Notification, NotificationChannel};
+use ai_manager_shared::errors::SystemError;
+use ai_manager_shared::http;
+use ai_manager_shared::NOTIFICATION_REQUEST_TIMEOUT;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::time::Duration;
+
+/// Posts the raw notification as JSON to a generic webhook
+pub struct WebhookChannel {
+    client: Client,
+    webhook_url: String,
+}
+
+impl WebhookChannel {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: http::client(Duration::from_secs(NOTIFICATION_REQUEST_TIMEOUT)),
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+
+    /// Build a channel from `NOTIFICATION_WEBHOOK_URL`, if set
+    pub fn from_env() -> Option<Self> {
+        std::env::var("NOTIFICATION_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| Self::new(&url))
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn name(&self) -> &str {
+        "Webhook"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SystemError> {
+        let payload = serde_json::json!({
+            "title": notification.title,
+            "message": notification.message,
+            "type": notification.notification_type,
+            "timestamp": notification.timestamp
+        });
+
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| SystemError::ExternalService {
+                service: "Notifications".to_string(),
+                message: format!("Webhook request failed: {}", e),
+            })?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(SystemError::ExternalService {
+                service: "Notifications".to_string(),
+                message: format!("Webhook returned status: {}", response.status()),
+            })
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        true
+    }
+}
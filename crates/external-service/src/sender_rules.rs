@@ -0,0 +1,79 @@
+use crate::auto_reply::sender_address;
+use ai_manager_shared::messages::SenderRule;
+use std::collections::HashMap;
+
+/// The email allowlist and blocklist: mail from blocked senders is always
+/// spam, mail from allowed senders never is. Keyed by lowercase address.
+#[derive(Debug, Clone, Default)]
+pub struct SenderRules {
+    rules: HashMap<String, SenderRule>,
+}
+
+impl SenderRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rules as stored by the data service
+    pub fn from_rules(rules: HashMap<String, SenderRule>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|(address, rule)| (normalize(&address), rule))
+                .collect(),
+        }
+    }
+
+    /// Set the rule for `address`, or drop it if `rule` is `None`
+    pub fn set(&mut self, address: &str, rule: Option<SenderRule>) {
+        let address = normalize(address);
+        match rule {
+            Some(rule) => self.rules.insert(address, rule),
+            None => self.rules.remove(&address),
+        };
+    }
+
+    /// The rule for the sender of a mail from `from`, which may include a
+    /// display name
+    pub fn rule_for(&self, from: &str) -> Option<SenderRule> {
+        self.rules.get(&normalize(from)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// Lowercase address of a sender such as "Alice <Alice@Example.com>"
+pub fn normalize(address: &str) -> String {
+    sender_address(address).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_match_any_form_of_address() {
+        let mut rules = SenderRules::from_rules(HashMap::from([(
+            "Deals@Shop.example".to_string(),
+            SenderRule::Block,
+        )]));
+        rules.set("Alice <alice@example.com>", Some(SenderRule::Allow));
+
+        assert_eq!(
+            rules.rule_for("Shop Deals <deals@shop.example>"),
+            Some(SenderRule::Block)
+        );
+        assert_eq!(rules.rule_for("ALICE@example.com"), Some(SenderRule::Allow));
+        assert_eq!(rules.rule_for("bob@example.com"), None);
+
+        rules.set("deals@shop.example", None);
+        assert_eq!(rules.rule_for("deals@shop.example"), None);
+        assert_eq!(rules.len(), 1);
+    }
+}
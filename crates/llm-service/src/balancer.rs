@@ -0,0 +1,325 @@
+use crate::provider::{LLMProvider, LLMRequest, LLMResponse};
+use ai_manager_shared::{Result, SystemError, TokenUsage};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How a `LoadBalancedProvider` picks the backend for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BalanceStrategy {
+    /// Each backend in turn
+    #[default]
+    RoundRobin,
+    /// The backend with the most rate-limit budget left after its in-flight
+    /// requests; backends that don't report a budget are treated as
+    /// unlimited, and ties go to the fewest in-flight requests
+    LeastLoaded,
+}
+
+struct Backend {
+    provider: Box<dyn LLMProvider>,
+    in_flight: AtomicUsize,
+}
+
+impl Backend {
+    fn headroom(&self) -> usize {
+        let in_flight = self.in_flight.load(Ordering::SeqCst);
+        self.provider
+            .rate_limit_remaining()
+            .map_or(usize::MAX, |remaining| remaining as usize)
+            .saturating_sub(in_flight)
+    }
+}
+
+/// Counts a request as in flight until dropped, so cancelled and failed
+/// requests are released too
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    fn start(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Several providers registered under one name, such as two OpenAI keys,
+/// with requests spread across them
+pub struct LoadBalancedProvider {
+    name: String,
+    backends: Vec<Backend>,
+    strategy: BalanceStrategy,
+    next: AtomicUsize,
+}
+
+impl LoadBalancedProvider {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            backends: Vec::new(),
+            strategy: BalanceStrategy::default(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: BalanceStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn set_strategy(&mut self, strategy: BalanceStrategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn add_backend(&mut self, provider: Box<dyn LLMProvider>) {
+        self.backends.push(Backend {
+            provider,
+            in_flight: AtomicUsize::new(0),
+        });
+    }
+
+    pub fn backend_count(&self) -> usize {
+        self.backends.len()
+    }
+
+    /// Requests currently in flight on each backend, in registration order
+    pub fn in_flight(&self) -> Vec<usize> {
+        self.backends
+            .iter()
+            .map(|backend| backend.in_flight.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    fn pick(&self) -> Option<&Backend> {
+        let count = self.backends.len();
+        if count == 0 {
+            return None;
+        }
+
+        // Rotating the starting point also breaks least-loaded ties in turn
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % count;
+        match self.strategy {
+            BalanceStrategy::RoundRobin => self.backends.get(start),
+            BalanceStrategy::LeastLoaded => (0..count)
+                .map(|offset| &self.backends[(start + offset) % count])
+                .min_by_key(|backend| {
+                    (
+                        Reverse(backend.headroom()),
+                        backend.in_flight.load(Ordering::SeqCst),
+                    )
+                }),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for LoadBalancedProvider {
+    async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+        let backend = self.pick().ok_or_else(|| {
+            SystemError::Configuration(format!("Provider '{}' has no backends", self.name))
+        })?;
+
+        let _in_flight = InFlight::start(&backend.in_flight);
+        backend.provider.send_request(request).await
+    }
+
+    async fn get_usage(&self) -> TokenUsage {
+        let mut total = TokenUsage {
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            total_tokens: 0,
+        };
+        for backend in &self.backends {
+            let usage = backend.provider.get_usage().await;
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.total_tokens += usage.total_tokens;
+        }
+        total
+    }
+
+    fn provider_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Healthy only if every backend is, since any of them may be picked
+    async fn health_check(&self) -> Result<()> {
+        for backend in &self.backends {
+            backend.provider.health_check().await?;
+        }
+        Ok(())
+    }
+
+    fn rate_limit_remaining(&self) -> Option<u32> {
+        self.backends
+            .iter()
+            .filter_map(|backend| backend.provider.rate_limit_remaining())
+            .reduce(u32::saturating_add)
+    }
+}
+
+/// Parse a rate-limit budget header such as `x-ratelimit-remaining-requests`
+pub(crate) fn remaining_from_header(headers: &HeaderMap, name: &str) -> Option<u32> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::FinishReason;
+    use std::sync::Arc;
+    use tokio::sync::Notify;
+
+    /// Answers with its own label; optionally waits for `release` first
+    struct LabelProvider {
+        label: &'static str,
+        remaining: Option<u32>,
+        release: Option<Arc<Notify>>,
+    }
+
+    impl LabelProvider {
+        fn new(label: &'static str, remaining: Option<u32>) -> Box<Self> {
+            Box::new(Self {
+                label,
+                remaining,
+                release: None,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl LLMProvider for LabelProvider {
+        async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+            if let Some(release) = &self.release {
+                release.notified().await;
+            }
+            Ok(LLMResponse {
+                content: self.label.to_string(),
+                model: request.model,
+                usage: TokenUsage {
+                    prompt_tokens: 1,
+                    completion_tokens: 1,
+                    total_tokens: 2,
+                },
+                finish_reason: FinishReason::Stop,
+                provider: self.label.to_string(),
+            })
+        }
+
+        async fn get_usage(&self) -> TokenUsage {
+            TokenUsage {
+                prompt_tokens: 3,
+                completion_tokens: 2,
+                total_tokens: 5,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            self.label
+        }
+
+        async fn health_check(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn rate_limit_remaining(&self) -> Option<u32> {
+            self.remaining
+        }
+    }
+
+    fn request() -> LLMRequest {
+        LLMRequest {
+            prompt: "Hello".to_string(),
+            context: vec![],
+            model: "mock-model".to_string(),
+            max_tokens: None,
+            temperature: None,
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![],
+        }
+    }
+
+    async fn answers(provider: &LoadBalancedProvider, count: usize) -> Vec<String> {
+        let mut labels = Vec::new();
+        for _ in 0..count {
+            labels.push(provider.send_request(request()).await.unwrap().content);
+        }
+        labels
+    }
+
+    #[tokio::test]
+    async fn test_round_robin() {
+        let mut provider = LoadBalancedProvider::new("openai");
+        provider.add_backend(LabelProvider::new("a", None));
+        provider.add_backend(LabelProvider::new("b", None));
+
+        assert_eq!(answers(&provider, 4).await, vec!["a", "b", "a", "b"]);
+        assert_eq!(provider.get_usage().await.total_tokens, 10);
+        assert_eq!(provider.provider_name(), "openai");
+    }
+
+    #[tokio::test]
+    async fn test_least_loaded_prefers_remaining_budget() {
+        let mut provider =
+            LoadBalancedProvider::new("openai").with_strategy(BalanceStrategy::LeastLoaded);
+        provider.add_backend(LabelProvider::new("a", Some(3)));
+        provider.add_backend(LabelProvider::new("b", Some(50)));
+
+        assert_eq!(answers(&provider, 3).await, vec!["b", "b", "b"]);
+        assert_eq!(provider.rate_limit_remaining(), Some(53));
+    }
+
+    #[tokio::test]
+    async fn test_least_loaded_counts_in_flight_requests() {
+        let release = Arc::new(Notify::new());
+        let mut provider =
+            LoadBalancedProvider::new("openai").with_strategy(BalanceStrategy::LeastLoaded);
+        provider.add_backend(Box::new(LabelProvider {
+            label: "a",
+            remaining: None,
+            release: Some(release.clone()),
+        }));
+        provider.add_backend(LabelProvider::new("b", None));
+        let provider = Arc::new(provider);
+
+        // Held open on "a"
+        let pending = tokio::spawn({
+            let provider = provider.clone();
+            async move { provider.send_request(request()).await }
+        });
+        while provider.in_flight() != vec![1, 0] {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(answers(&provider, 2).await, vec!["b", "b"]);
+
+        release.notify_one();
+        assert_eq!(pending.await.unwrap().unwrap().content, "a");
+        assert_eq!(provider.in_flight(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_remaining_from_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", "42".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-tokens", "lots".parse().unwrap());
+
+        assert_eq!(
+            remaining_from_header(&headers, "x-ratelimit-remaining-requests"),
+            Some(42)
+        );
+        assert_eq!(
+            remaining_from_header(&headers, "x-ratelimit-remaining-tokens"),
+            None
+        );
+        assert_eq!(remaining_from_header(&headers, "retry-after"), None);
+    }
+}
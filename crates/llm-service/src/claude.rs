@@ -1,15 +1,26 @@
-use crate::provider::{FinishReason, LLMProvider, LLMRequest, LLMResponse};
-use ai_manager_shared::{Result, SystemError, TokenUsage};
+use crate::balancer::remaining_from_header;
+use crate::metrics;
+use crate::provider::{
+    validate_images, validate_request, FinishReason, ImageSource, LLMProvider, LLMRequest,
+    LLMResponse, ResponseFormat,
+};
+use ai_manager_shared::http;
+use ai_manager_shared::{Result, SystemError, TokenUsage, PROVIDER_HEALTH_TTL_SECONDS};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, warn};
 
 const CLAUDE_API_BASE: &str = "https://api.anthropic.com/v1";
+/// Prefix of every Anthropic API key
+const API_KEY_PREFIX: &str = "sk-ant-";
 const DEFAULT_MODEL: &str = "claude-3-haiku-20240307";
 const DEFAULT_MAX_TOKENS: u32 = 2000;
 const DEFAULT_TEMPERATURE: f32 = 0.7;
+/// Claude models that only take text
+const TEXT_ONLY_MODEL_PREFIXES: &[&str] = &["claude-2", "claude-instant", "claude-3-5-haiku"];
 
 pub struct ClaudeProvider {
     client: Client,
@@ -19,14 +30,16 @@ pub struct ClaudeProvider {
     max_tokens: u32,
     temperature: f32,
     total_usage: TokenUsage,
+    /// From the `anthropic-ratelimit-requests-remaining` header of the latest response
+    rate_limit_remaining: Mutex<Option<u32>>,
+    /// When a request last succeeded, which stands in for a health check
+    last_success: Mutex<Option<Instant>>,
+    live_health_check: bool,
 }
 
 impl ClaudeProvider {
     pub fn new(api_key: String) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(ai_manager_shared::LLM_REQUEST_TIMEOUT))
-            .build()
-            .expect("Failed to create HTTP client");
+        let client = http::client(Duration::from_secs(ai_manager_shared::LLM_REQUEST_TIMEOUT));
 
         Self {
             client,
@@ -40,6 +53,9 @@ impl ClaudeProvider {
                 completion_tokens: 0,
                 total_tokens: 0,
             },
+            rate_limit_remaining: Mutex::new(None),
+            last_success: Mutex::new(None),
+            live_health_check: false,
         }
     }
 
@@ -68,6 +84,39 @@ impl ClaudeProvider {
         provider
     }
 
+    /// Health-check with a real one-token request, which is billed and
+    /// counts against rate limits. Otherwise only reachability of the API
+    /// and the key's format are checked.
+    pub fn with_live_health_check(mut self, live: bool) -> Self {
+        self.live_health_check = live;
+        self
+    }
+
+    /// Anthropic keys start with `sk-ant-`; proxies behind a custom base
+    /// URL may use their own keys
+    fn check_api_key_format(&self) -> Result<()> {
+        if self.base_url == CLAUDE_API_BASE && !self.api_key.starts_with(API_KEY_PREFIX) {
+            return Err(SystemError::Authentication(format!(
+                "Claude API key should start with '{}'",
+                API_KEY_PREFIX
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the API answers at all; any HTTP status will do
+    async fn check_reachable(&self) -> Result<()> {
+        self.client
+            .head(&self.base_url)
+            .timeout(Duration::from_secs(
+                ai_manager_shared::HEALTH_CHECK_TIMEOUT_SECONDS,
+            ))
+            .send()
+            .await
+            .map_err(|e| SystemError::Network(format!("Claude API unreachable: {}", e)))?;
+        Ok(())
+    }
+
     fn build_messages(&self, request: &LLMRequest) -> Vec<ClaudeMessage> {
         let mut messages = Vec::new();
 
@@ -75,39 +124,86 @@ impl ClaudeProvider {
         for context in &request.context {
             messages.push(ClaudeMessage {
                 role: "user".to_string(),
-                content: context.clone(),
+                content: ClaudeMessageContent::Text(context.clone()),
             });
         }
 
-        // Add current prompt
+        // Add current prompt, with any images before the text as Anthropic
+        // recommends
+        let content = if request.images.is_empty() {
+            ClaudeMessageContent::Text(request.prompt.clone())
+        } else {
+            let mut blocks: Vec<ClaudeInputBlock> = request
+                .images
+                .iter()
+                .map(|image| ClaudeInputBlock::Image {
+                    source: match &image.source {
+                        ImageSource::Base64 { data } => ClaudeImageSource::Base64 {
+                            media_type: image.mime_type.clone(),
+                            data: data.clone(),
+                        },
+                        ImageSource::Url { url } => ClaudeImageSource::Url { url: url.clone() },
+                    },
+                })
+                .collect();
+            blocks.push(ClaudeInputBlock::Text {
+                text: request.prompt.clone(),
+            });
+            ClaudeMessageContent::Blocks(blocks)
+        };
         messages.push(ClaudeMessage {
             role: "user".to_string(),
-            content: request.prompt.clone(),
+            content,
         });
 
         messages
     }
-}
 
-#[async_trait]
-impl LLMProvider for ClaudeProvider {
-    async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+    fn supports_vision(model: &str) -> bool {
+        !TEXT_ONLY_MODEL_PREFIXES
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+    }
+
+    /// The model `request` asks for, or the configured default
+    fn model_for(&self, request: &LLMRequest) -> String {
+        if request.model.is_empty() {
+            self.default_model.clone()
+        } else {
+            request.model.clone()
+        }
+    }
+
+    /// Send `request` and turn the reply into an `LLMResponse`
+    async fn complete(&self, mut request: LLMRequest) -> Result<LLMResponse> {
         debug!("Sending Claude request: {}", request.prompt);
+        validate_request(&request)?;
+        let model = self.model_for(&request);
+        let max_tokens = request.max_tokens.unwrap_or(self.max_tokens);
+        let dropped = request.fit_context_window(self.provider_name(), &model, max_tokens)?;
+        if dropped > 0 {
+            debug!(
+                "Dropped {} context messages to fit the context window of '{}'",
+                dropped, model
+            );
+        }
 
         let messages = self.build_messages(&request);
 
         let claude_request = ClaudeRequest {
-            model: if request.model.is_empty() {
-                self.default_model.clone()
-            } else {
-                request.model.clone()
-            },
-            max_tokens: request.max_tokens.unwrap_or(self.max_tokens),
+            model,
+            max_tokens,
             messages,
             temperature: request.temperature.or(Some(self.temperature)),
             stop_sequences: request.stop_sequences.clone(),
             stream: Some(request.stream),
+            system: request.response_format.as_ref().map(json_instruction),
         };
+        validate_images(
+            &request,
+            &claude_request.model,
+            Self::supports_vision(&claude_request.model),
+        )?;
 
         let response = self
             .client
@@ -119,6 +215,14 @@ impl LLMProvider for ClaudeProvider {
             .send()
             .await
             .map_err(|e| SystemError::Network(format!("Claude request failed: {}", e)))?;
+        if let Some(remaining) =
+            remaining_from_header(response.headers(), "anthropic-ratelimit-requests-remaining")
+        {
+            *self
+                .rate_limit_remaining
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(remaining);
+        }
 
         if !response.status().is_success() {
             let status = response.status();
@@ -181,6 +285,23 @@ impl LLMProvider for ClaudeProvider {
             provider: "claude".to_string(),
         })
     }
+}
+
+#[async_trait]
+impl LLMProvider for ClaudeProvider {
+    async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+        let model = self.model_for(&request);
+        let started = Instant::now();
+        let result = self.complete(request).await;
+        metrics::global().record(self.provider_name(), &model, started.elapsed(), &result);
+        if result.is_ok() {
+            *self
+                .last_success
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+        }
+        result
+    }
 
     async fn get_usage(&self) -> TokenUsage {
         self.total_usage.clone()
@@ -191,19 +312,37 @@ impl LLMProvider for ClaudeProvider {
     }
 
     async fn health_check(&self) -> Result<()> {
-        debug!("Performing Claude health check");
+        // A recent successful request proves the API is reachable
+        let recently_succeeded = self
+            .last_success
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|at| at.elapsed() < Duration::from_secs(PROVIDER_HEALTH_TTL_SECONDS));
+        if recently_succeeded {
+            debug!("Claude served a request recently; skipping health check");
+            return Ok(());
+        }
+
+        self.check_api_key_format()?;
+        if !self.live_health_check {
+            debug!("Checking that the Claude API is reachable");
+            return self.check_reachable().await;
+        }
+
+        debug!("Performing live Claude health check");
 
-        // Claude doesn't have a simple health check endpoint, so we'll do a minimal request
+        // Claude has no free ping endpoint, so send a minimal request
         let test_request = ClaudeRequest {
             model: self.default_model.clone(),
             max_tokens: 1,
             messages: vec![ClaudeMessage {
                 role: "user".to_string(),
-                content: "Hi".to_string(),
+                content: ClaudeMessageContent::Text("Hi".to_string()),
             }],
             temperature: Some(0.0),
             stop_sequences: None,
             stream: Some(false),
+            system: None,
         };
 
         let response = self
@@ -229,6 +368,13 @@ impl LLMProvider for ClaudeProvider {
             })
         }
     }
+
+    fn rate_limit_remaining(&self) -> Option<u32> {
+        *self
+            .rate_limit_remaining
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -242,12 +388,52 @@ struct ClaudeRequest {
     stop_sequences: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Claude has no JSON mode, so the format is enforced with a system prompt
+fn json_instruction(format: &ResponseFormat) -> String {
+    match format {
+        ResponseFormat::Json { schema: None } => "Respond with a single valid JSON value and \
+nothing else: no prose, no explanation and no Markdown code fences."
+            .to_string(),
+        ResponseFormat::Json {
+            schema: Some(schema),
+        } => format!(
+            "Respond with a single valid JSON value that conforms to this JSON Schema, and \
+nothing else: no prose, no explanation and no Markdown code fences.\n\nSchema:\n{}",
+            schema
+        ),
+    }
+}
+
+#[derive(Debug, Serialize)]
 struct ClaudeMessage {
     role: String,
-    content: String,
+    content: ClaudeMessageContent,
+}
+
+/// Plain text, or text mixed with images
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ClaudeMessageContent {
+    Text(String),
+    Blocks(Vec<ClaudeInputBlock>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClaudeInputBlock {
+    Text { text: String },
+    Image { source: ClaudeImageSource },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClaudeImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
 }
 
 #[derive(Debug, Deserialize)]
@@ -284,6 +470,71 @@ mod tests {
     // Note: These tests require a valid Claude API key to run
     // They are disabled by default to avoid unnecessary API calls
 
+    #[tokio::test]
+    async fn test_image_request() {
+        let provider = ClaudeProvider::new("key".to_string());
+        let mut request = LLMRequest {
+            prompt: "Read this receipt".to_string(),
+            context: vec![],
+            model: "claude-3-haiku-20240307".to_string(),
+            max_tokens: None,
+            temperature: None,
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![crate::ImageInput::base64("image/jpeg", "/9j/4AAQ")],
+        };
+
+        let messages = serde_json::to_value(provider.build_messages(&request)).unwrap();
+        assert_eq!(
+            messages[0]["content"],
+            serde_json::json!([
+                {
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": "image/jpeg", "data": "/9j/4AAQ" },
+                },
+                { "type": "text", "text": "Read this receipt" },
+            ])
+        );
+
+        // Rejected before anything is sent
+        request.model = "claude-3-5-haiku-20241022".to_string();
+        let err = provider.send_request(request).await.unwrap_err();
+        assert!(matches!(err, SystemError::InvalidInput(_)));
+    }
+
+    #[test]
+    fn test_json_instruction() {
+        let plain = json_instruction(&ResponseFormat::Json { schema: None });
+        assert!(plain.contains("valid JSON"));
+        assert!(!plain.contains("Schema"));
+
+        let schema = serde_json::json!({ "type": "object", "required": ["category"] });
+        let with_schema = json_instruction(&ResponseFormat::Json {
+            schema: Some(schema.clone()),
+        });
+        assert!(with_schema.ends_with(&schema.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_rejects_malformed_key() {
+        let provider = ClaudeProvider::new("not-a-claude-key".to_string());
+        assert!(!provider.live_health_check);
+
+        // Fails before any request is made
+        let err = provider.health_check().await.unwrap_err();
+        assert!(matches!(err, SystemError::Authentication(_)));
+
+        let proxied = ClaudeProvider::with_config(
+            "proxy-key".to_string(),
+            Some("http://localhost:8080/v1".to_string()),
+            None,
+            None,
+            None,
+        );
+        assert!(proxied.check_api_key_format().is_ok());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_claude_provider() {
@@ -298,6 +549,8 @@ mod tests {
             temperature: Some(0.7),
             stop_sequences: None,
             stream: false,
+            response_format: None,
+            images: vec![],
         };
 
         let response = provider.send_request(request).await.unwrap();
@@ -0,0 +1,310 @@
+use crate::provider::{LLMRequest, LLMResponse};
+use ai_manager_shared::{Result, SystemError};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::broadcast;
+use tracing::debug;
+
+/// The user a request is for, and a hash of what it asks
+type RequestKey = (String, u64);
+
+type SharedResult = std::result::Result<LLMResponse, Arc<SystemError>>;
+
+/// A response, and whether it was shared from an identical request that was
+/// already in flight rather than requested for this caller
+#[derive(Debug)]
+pub struct CoalescedResponse {
+    pub response: LLMResponse,
+    pub shared: bool,
+}
+
+/// Merges concurrent identical requests from the same user, e.g. from a
+/// double click: the second waits for the first's response instead of
+/// making a billable request of its own. Finished requests aren't cached.
+#[derive(Default)]
+pub struct RequestCoalescer {
+    in_flight: Mutex<HashMap<RequestKey, broadcast::Sender<SharedResult>>>,
+}
+
+impl RequestCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `request` with `send`, unless the same request for `user_id`
+    /// is already in flight, in which case its result is returned
+    pub async fn coalesce<F, Fut>(
+        &self,
+        user_id: &str,
+        provider: &str,
+        request: &LLMRequest,
+        send: F,
+    ) -> Result<CoalescedResponse>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<LLMResponse>>,
+    {
+        let key = (user_id.to_string(), request_hash(provider, request));
+        let waiting = {
+            let mut in_flight = self.lock();
+            match in_flight.get(&key) {
+                Some(sender) => Some(sender.subscribe()),
+                None => {
+                    in_flight.insert(key.clone(), broadcast::channel(1).0);
+                    None
+                }
+            }
+        };
+
+        if let Some(mut receiver) = waiting {
+            debug!("Waiting for an identical request from '{}'", user_id);
+            match receiver.recv().await {
+                Ok(Ok(response)) => {
+                    return Ok(CoalescedResponse {
+                        response,
+                        shared: true,
+                    })
+                }
+                Ok(Err(error)) => return Err(duplicate_error(&error)),
+                // The first request was dropped before finishing
+                Err(_) => {
+                    return send().await.map(|response| CoalescedResponse {
+                        response,
+                        shared: false,
+                    })
+                }
+            }
+        }
+
+        let mut guard = InFlight {
+            coalescer: self,
+            key: Some(key),
+        };
+        let result = send().await;
+        if let Some(sender) = guard.finish() {
+            let shared = match &result {
+                Ok(response) => Some(Ok(response.clone())),
+                // Waiters didn't cancel; dropping the sender has them send
+                // their own request instead
+                Err(SystemError::Cancelled) => None,
+                Err(error) => Some(Err(Arc::new(duplicate_error(error)))),
+            };
+            // No one may be waiting
+            if let Some(shared) = shared {
+                let _ = sender.send(shared);
+            }
+        }
+        result.map(|response| CoalescedResponse {
+            response,
+            shared: false,
+        })
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<RequestKey, broadcast::Sender<SharedResult>>> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Removes a request from the in-flight set when it finishes or is dropped,
+/// so waiters aren't left waiting for a request that was cancelled
+struct InFlight<'a> {
+    coalescer: &'a RequestCoalescer,
+    key: Option<RequestKey>,
+}
+
+impl InFlight<'_> {
+    fn finish(&mut self) -> Option<broadcast::Sender<SharedResult>> {
+        let key = self.key.take()?;
+        self.coalescer.lock().remove(&key)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Hash of what `request` asks. The context is left out: by the time a
+/// repeated prompt is sent, the first one is already in its history.
+fn request_hash(provider: &str, request: &LLMRequest) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    provider.hash(&mut hasher);
+    request.model.hash(&mut hasher);
+    request.prompt.hash(&mut hasher);
+    request.max_tokens.hash(&mut hasher);
+    request.temperature.map(f32::to_bits).hash(&mut hasher);
+    request.stop_sequences.hash(&mut hasher);
+    request.stream.hash(&mut hasher);
+    serde_json::to_string(&request.response_format)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    serde_json::to_string(&request.images)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A copy of `error` for another caller. IO and JSON errors can't be
+/// copied, so they are passed on by their message.
+fn duplicate_error(error: &SystemError) -> SystemError {
+    match error {
+        SystemError::ServiceCommunication(message) => {
+            SystemError::ServiceCommunication(message.clone())
+        }
+        SystemError::LLMApi { provider, message } => SystemError::LLMApi {
+            provider: provider.clone(),
+            message: message.clone(),
+        },
+        SystemError::Database(message) => SystemError::Database(message.clone()),
+        SystemError::ExternalService { service, message } => SystemError::ExternalService {
+            service: service.clone(),
+            message: message.clone(),
+        },
+        SystemError::Configuration(message) => SystemError::Configuration(message.clone()),
+        SystemError::Authentication(message) => SystemError::Authentication(message.clone()),
+        SystemError::Network(message) => SystemError::Network(message.clone()),
+        SystemError::Serialization(message) => SystemError::Serialization(message.clone()),
+        SystemError::Io(e) => SystemError::Io(std::io::Error::new(e.kind(), e.to_string())),
+        SystemError::Json(e) => SystemError::Serialization(e.to_string()),
+        SystemError::Timeout => SystemError::Timeout,
+        SystemError::Cancelled => SystemError::Cancelled,
+        SystemError::ServiceUnavailable { service } => SystemError::ServiceUnavailable {
+            service: service.clone(),
+        },
+        SystemError::RateLimitExceeded { service } => SystemError::RateLimitExceeded {
+            service: service.clone(),
+        },
+        SystemError::InvalidInput(message) => SystemError::InvalidInput(message.clone()),
+        SystemError::Unknown(message) => SystemError::Unknown(message.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::FinishReason;
+    use ai_manager_shared::TokenUsage;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    fn request(prompt: &str) -> LLMRequest {
+        LLMRequest {
+            prompt: prompt.to_string(),
+            context: vec![],
+            model: String::new(),
+            max_tokens: None,
+            temperature: None,
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![],
+        }
+    }
+
+    /// Count the call and answer after a moment, so calls overlap
+    async fn slow_send(calls: &AtomicU32, prompt: &str) -> Result<LLMResponse> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if prompt == "fail" {
+            return Err(SystemError::Authentication("bad key".to_string()));
+        }
+        Ok(LLMResponse {
+            content: format!("echo: {}", prompt),
+            model: "echo-1".to_string(),
+            usage: TokenUsage {
+                prompt_tokens: 4,
+                completion_tokens: 2,
+                total_tokens: 6,
+            },
+            finish_reason: FinishReason::Stop,
+            provider: "echo".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_duplicates_share_one_request() {
+        let coalescer = RequestCoalescer::new();
+        let calls = AtomicU32::new(0);
+        let hi = request("hi");
+
+        let (first, second, other_user) = tokio::join!(
+            coalescer.coalesce("alice", "echo", &hi, || slow_send(&calls, "hi")),
+            coalescer.coalesce("alice", "echo", &hi, || slow_send(&calls, "hi")),
+            coalescer.coalesce("bob", "echo", &hi, || slow_send(&calls, "hi")),
+        );
+
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.response.content, "echo: hi");
+        assert_eq!(second.response.content, "echo: hi");
+        assert!(first.shared != second.shared);
+        assert!(!other_user.unwrap().shared);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Once finished, the same request is made again
+        coalescer
+            .coalesce("alice", "echo", &hi, || slow_send(&calls, "hi"))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_with_longer_context_shares_request() {
+        let coalescer = RequestCoalescer::new();
+        let calls = AtomicU32::new(0);
+        let first = request("hi");
+        let mut repeated = request("hi");
+        repeated.context = vec!["User: hi".to_string()];
+
+        let (first, repeated) = tokio::join!(
+            coalescer.coalesce("alice", "echo", &first, || slow_send(&calls, "hi")),
+            coalescer.coalesce("alice", "echo", &repeated, || slow_send(&calls, "hi")),
+        );
+
+        assert!(first.unwrap().shared != repeated.unwrap().shared);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_not_shared_with_waiters() {
+        let coalescer = RequestCoalescer::new();
+        let calls = AtomicU32::new(0);
+        let hi = request("hi");
+
+        let cancelled = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err::<LLMResponse, _>(SystemError::Cancelled)
+        };
+        let (first, second) = tokio::join!(
+            coalescer.coalesce("alice", "echo", &hi, || cancelled),
+            coalescer.coalesce("alice", "echo", &hi, || slow_send(&calls, "hi")),
+        );
+
+        assert!(matches!(first, Err(SystemError::Cancelled)));
+        assert!(!second.unwrap().shared);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_error_shared_with_waiters() {
+        let coalescer = RequestCoalescer::new();
+        let calls = AtomicU32::new(0);
+        let fail = request("fail");
+
+        let (first, second) = tokio::join!(
+            coalescer.coalesce("alice", "echo", &fail, || slow_send(&calls, "fail")),
+            coalescer.coalesce("alice", "echo", &fail, || slow_send(&calls, "fail")),
+        );
+        assert!(matches!(first, Err(SystemError::Authentication(_))));
+        assert!(matches!(second, Err(SystemError::Authentication(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use ai_manager_shared::http;
+use ai_manager_shared::{Result, SystemError};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{debug, error};
+
+pub const OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+const OLLAMA_API_BASE: &str = "http://localhost:11434";
+
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Embed each text, returning one vector per input in the same order
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>>;
+
+    /// Get provider name
+    fn embedding_provider_name(&self) -> &str;
+}
+
+/// Embeddings from a local Ollama server
+pub struct OllamaEmbeddings {
+    client: Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaEmbeddings {
+    pub fn new() -> Self {
+        let client = http::client(Duration::from_secs(ai_manager_shared::LLM_REQUEST_TIMEOUT));
+
+        Self {
+            client,
+            base_url: OLLAMA_API_BASE.to_string(),
+            model: OLLAMA_EMBEDDING_MODEL.to_string(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+}
+
+impl Default for OllamaEmbeddings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        debug!("Requesting {} Ollama embeddings", texts.len());
+
+        let expected = texts.len();
+        let response = self
+            .client
+            .post(format!("{}/api/embed", self.base_url))
+            .json(&OllamaEmbeddingRequest {
+                model: self.model.clone(),
+                input: texts,
+            })
+            .send()
+            .await
+            .map_err(|e| {
+                SystemError::Network(format!("Ollama embeddings request failed: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            error!("Ollama embeddings error {}: {}", status, error_text);
+
+            return Err(SystemError::LLMApi {
+                provider: "ollama".to_string(),
+                message: format!("HTTP {}: {}", status, error_text),
+            });
+        }
+
+        let body: OllamaEmbeddingResponse = response.json().await.map_err(|e| {
+            SystemError::Serialization(format!("Failed to parse Ollama embeddings: {}", e))
+        })?;
+
+        if body.embeddings.len() != expected {
+            return Err(SystemError::LLMApi {
+                provider: "ollama".to_string(),
+                message: format!(
+                    "Expected {} embeddings, got {}",
+                    expected,
+                    body.embeddings.len()
+                ),
+            });
+        }
+
+        Ok(body.embeddings)
+    }
+
+    fn embedding_provider_name(&self) -> &str {
+        "ollama"
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaEmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddingResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_empty_input_skips_request() {
+        // Unroutable address: any request would fail
+        let provider = OllamaEmbeddings::new().with_base_url("http://127.0.0.1:1");
+        let embeddings = provider.embed(Vec::new()).await.unwrap();
+        assert!(embeddings.is_empty());
+    }
+}
@@ -1,11 +1,26 @@
+pub mod balancer;
 pub mod claude;
+pub mod coalescer;
+pub mod embeddings;
+pub mod metrics;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod openai;
 pub mod prompt_manager;
 pub mod provider;
+pub mod service;
+pub mod tokens;
 pub mod usage_tracker;
 
+pub use balancer::*;
 pub use claude::*;
+pub use coalescer::*;
+pub use embeddings::*;
+#[cfg(any(test, feature = "mock"))]
+pub use mock::*;
 pub use openai::*;
 pub use prompt_manager::*;
 pub use provider::*;
+pub use service::*;
+pub use tokens::*;
 pub use usage_tracker::*;
@@ -0,0 +1,225 @@
+use ai_manager_shared::{ErrorCode, Result};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+/// Upper bounds of the request latency buckets
+const LATENCY_BUCKETS: [Duration; 10] = [
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(20),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+];
+
+/// What a request is counted under
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RequestLabels {
+    pub provider: String,
+    pub model: String,
+    /// `ok`, or the error's code in snake case, e.g. `rate_limited`
+    pub status: String,
+}
+
+/// Latency histogram of provider requests, with fixed bucket bounds
+#[derive(Debug, Clone)]
+pub struct RequestLatency {
+    /// Upper bound of each bucket
+    pub bounds: Vec<Duration>,
+    /// Observations per bucket; the extra last entry counts those above every bound
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+impl RequestLatency {
+    pub fn observe(&mut self, latency: Duration) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum += latency;
+    }
+
+    /// Add another histogram's observations, which must use the same bounds
+    fn merge(&mut self, other: &RequestLatency) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+
+    /// Estimated `q` quantile (0.5 for p50), interpolated within its
+    /// bucket the way Prometheus' `histogram_quantile` does
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = q.clamp(0.0, 1.0) * self.count as f64;
+        let mut below = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            if *count > 0 && (below + count) as f64 >= rank {
+                // Past the last bound there is nothing to interpolate towards
+                let Some(upper) = self.bounds.get(i) else {
+                    return self.bounds.last().copied();
+                };
+                let lower = i
+                    .checked_sub(1)
+                    .map_or(Duration::ZERO, |previous| self.bounds[previous]);
+                let fraction = ((rank - below as f64) / *count as f64).clamp(0.0, 1.0);
+                return Some(lower + (*upper - lower).mul_f64(fraction));
+            }
+            below += count;
+        }
+        self.bounds.last().copied()
+    }
+}
+
+impl Default for RequestLatency {
+    fn default() -> Self {
+        Self {
+            bounds: LATENCY_BUCKETS.to_vec(),
+            counts: vec![0; LATENCY_BUCKETS.len() + 1],
+            count: 0,
+            sum: Duration::ZERO,
+        }
+    }
+}
+
+/// Duration and outcome of provider requests, by provider, model and status
+#[derive(Debug, Default)]
+pub struct LlmMetrics {
+    requests: Mutex<HashMap<RequestLabels, RequestLatency>>,
+}
+
+impl LlmMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a request to `model` on `provider` that took `latency` and
+    /// ended with `result`
+    pub fn record<T>(&self, provider: &str, model: &str, latency: Duration, result: &Result<T>) {
+        let labels = RequestLabels {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            status: status_label(result).to_string(),
+        };
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(labels)
+            .or_default()
+            .observe(latency);
+    }
+
+    /// Every label set seen so far with its histogram, sorted by label
+    pub fn snapshot(&self) -> Vec<(RequestLabels, RequestLatency)> {
+        let mut requests: Vec<_> = self
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(labels, latency)| (labels.clone(), latency.clone()))
+            .collect();
+        requests.sort_by(|a, b| a.0.cmp(&b.0));
+        requests
+    }
+
+    /// Latency of every request to `provider`, across models and outcomes
+    pub fn provider_latency(&self, provider: &str) -> RequestLatency {
+        let mut total = RequestLatency::default();
+        for (labels, latency) in self
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            if labels.provider == provider {
+                total.merge(latency);
+            }
+        }
+        total
+    }
+}
+
+/// Metrics recorded by every provider in the process
+pub fn global() -> &'static LlmMetrics {
+    static METRICS: OnceLock<LlmMetrics> = OnceLock::new();
+    METRICS.get_or_init(LlmMetrics::new)
+}
+
+fn status_label<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(e) => match e.code() {
+            ErrorCode::AuthFailed => "auth_failed",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::ProviderDown => "provider_down",
+            ErrorCode::BadInput => "bad_input",
+            ErrorCode::Internal => "internal",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_manager_shared::SystemError;
+
+    #[test]
+    fn test_requests_recorded_by_outcome() {
+        let metrics = LlmMetrics::new();
+        metrics.record("claude", "claude-3", Duration::from_millis(300), &Ok(()));
+        metrics.record("claude", "claude-3", Duration::from_secs(2), &Ok(()));
+        metrics.record::<()>(
+            "claude",
+            "claude-3",
+            Duration::from_secs(1),
+            &Err(SystemError::RateLimitExceeded {
+                service: "claude".to_string(),
+            }),
+        );
+
+        let snapshot = metrics.snapshot();
+        let statuses: Vec<(&str, u64)> = snapshot
+            .iter()
+            .map(|(labels, latency)| (labels.status.as_str(), latency.count))
+            .collect();
+        assert_eq!(statuses, vec![("ok", 2), ("rate_limited", 1)]);
+        assert_eq!(metrics.provider_latency("claude").count, 3);
+        assert_eq!(metrics.provider_latency("openai").count, 0);
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let mut latency = RequestLatency::default();
+        assert_eq!(latency.quantile(0.5), None);
+
+        for _ in 0..90 {
+            latency.observe(Duration::from_millis(800));
+        }
+        for _ in 0..10 {
+            latency.observe(Duration::from_secs(8));
+        }
+
+        // Both fall inside a bucket: (500ms, 1s] and (5s, 10s]
+        let p50 = latency.quantile(0.5).unwrap();
+        assert!(p50 > Duration::from_millis(500) && p50 <= Duration::from_secs(1));
+        let p95 = latency.quantile(0.95).unwrap();
+        assert!(p95 > Duration::from_secs(5) && p95 <= Duration::from_secs(10));
+
+        latency.observe(Duration::from_secs(300));
+        assert_eq!(latency.quantile(1.0), Some(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::provider::{FinishReason, LLMProvider, LLMRequest, LLMResponse};
+use ai_manager_shared::{Result, SystemError, TokenUsage};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Model reported when a request doesn't name one
+pub const MOCK_MODEL: &str = "mock-model";
+
+enum Reply {
+    Text(String),
+    Error(SystemError),
+}
+
+/// A provider that answers without any network access, for tests and
+/// offline demos. Queued replies and errors are returned in order; once
+/// they run out, every request gets `Mock response to: <prompt>`.
+pub struct MockProvider {
+    name: String,
+    replies: Mutex<VecDeque<Reply>>,
+    latency: Duration,
+    usage: TokenUsage,
+    total_usage: Mutex<TokenUsage>,
+    requests: Mutex<Vec<LLMRequest>>,
+}
+
+impl MockProvider {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            replies: Mutex::new(VecDeque::new()),
+            latency: Duration::ZERO,
+            usage: TokenUsage {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                total_tokens: 15,
+            },
+            total_usage: Mutex::new(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answer a request with `content`, after the replies already scripted
+    pub fn with_response(self, content: impl Into<String>) -> Self {
+        self.push(Reply::Text(content.into()));
+        self
+    }
+
+    /// Fail a request with `error`, after the replies already scripted
+    pub fn with_error(self, error: SystemError) -> Self {
+        self.push(Reply::Error(error));
+        self
+    }
+
+    /// Wait `latency` before answering each request
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Token counts reported for each request
+    pub fn with_usage(mut self, prompt_tokens: u32, completion_tokens: u32) -> Self {
+        self.usage = TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        };
+        self
+    }
+
+    /// Queue `content` as a reply after those already scripted
+    pub fn push_response(&self, content: impl Into<String>) {
+        self.push(Reply::Text(content.into()));
+    }
+
+    /// Queue `error` after the replies already scripted
+    pub fn push_error(&self, error: SystemError) {
+        self.push(Reply::Error(error));
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<LLMRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn push(&self, reply: Reply) {
+        self.replies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back(reply);
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request.clone());
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let reply = self
+            .replies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front();
+        let content = match reply {
+            Some(Reply::Text(content)) => content,
+            Some(Reply::Error(error)) => return Err(error),
+            None => format!("Mock response to: {}", request.prompt),
+        };
+
+        {
+            let mut total = self
+                .total_usage
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            total.prompt_tokens += self.usage.prompt_tokens;
+            total.completion_tokens += self.usage.completion_tokens;
+            total.total_tokens += self.usage.total_tokens;
+        }
+
+        Ok(LLMResponse {
+            content,
+            model: if request.model.is_empty() {
+                MOCK_MODEL.to_string()
+            } else {
+                request.model
+            },
+            usage: self.usage.clone(),
+            finish_reason: FinishReason::Stop,
+            provider: self.name.clone(),
+        })
+    }
+
+    async fn get_usage(&self) -> TokenUsage {
+        self.total_usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn provider_name(&self) -> &str {
+        &self.name
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(prompt: &str) -> LLMRequest {
+        LLMRequest {
+            prompt: prompt.to_string(),
+            context: vec![],
+            model: String::new(),
+            max_tokens: None,
+            temperature: None,
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_replies_then_echo() {
+        let provider = MockProvider::new("mock")
+            .with_response("first")
+            .with_error(SystemError::Timeout)
+            .with_usage(7, 3);
+
+        let response = provider.send_request(request("a")).await.unwrap();
+        assert_eq!(response.content, "first");
+        assert_eq!(response.model, MOCK_MODEL);
+        assert_eq!(response.usage.total_tokens, 10);
+
+        assert!(matches!(
+            provider.send_request(request("b")).await,
+            Err(SystemError::Timeout)
+        ));
+
+        let response = provider.send_request(request("c")).await.unwrap();
+        assert_eq!(response.content, "Mock response to: c");
+
+        let prompts: Vec<String> = provider
+            .requests()
+            .into_iter()
+            .map(|request| request.prompt)
+            .collect();
+        assert_eq!(prompts, vec!["a", "b", "c"]);
+        // Failed requests use no tokens
+        assert_eq!(provider.get_usage().await.total_tokens, 20);
+    }
+
+    #[tokio::test]
+    async fn test_latency() {
+        let provider = MockProvider::new("mock").with_latency(Duration::from_millis(50));
+
+        let started = std::time::Instant::now();
+        provider.send_request(request("slow")).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+}
@@ -1,15 +1,35 @@
-use crate::provider::{FinishReason, LLMProvider, LLMRequest, LLMResponse};
-use ai_manager_shared::{Result, SystemError, TokenUsage};
+use crate::balancer::remaining_from_header;
+use crate::embeddings::EmbeddingProvider;
+use crate::metrics;
+use crate::provider::{
+    validate_images, validate_request, FinishReason, LLMProvider, LLMRequest, LLMResponse,
+    ResponseFormat,
+};
+use ai_manager_shared::http;
+use ai_manager_shared::{AzureOpenAIConfig, Result, SystemError, TokenUsage};
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, warn};
 
 const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+const OPENAI_PROVIDER_LABEL: &str = "openai";
 const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
 const DEFAULT_MAX_TOKENS: u32 = 2000;
 const DEFAULT_TEMPERATURE: f32 = 0.7;
+/// OpenAI chat models that accept image input
+const VISION_MODEL_PREFIXES: &[&str] = &[
+    "gpt-4o",
+    "gpt-4-turbo",
+    "gpt-4-vision",
+    "gpt-4.1",
+    "o1",
+    "o3",
+    "o4",
+];
+pub const OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
 
 pub struct OpenAIProvider {
     client: Client,
@@ -19,14 +39,16 @@ pub struct OpenAIProvider {
     max_tokens: u32,
     temperature: f32,
     total_usage: TokenUsage,
+    /// From the `x-ratelimit-remaining-requests` header of the latest response
+    rate_limit_remaining: Mutex<Option<u32>>,
+    azure: Option<AzureOpenAIConfig>,
+    /// Backend named in responses and usage records
+    label: String,
 }
 
 impl OpenAIProvider {
     pub fn new(api_key: String) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(ai_manager_shared::LLM_REQUEST_TIMEOUT))
-            .build()
-            .expect("Failed to create HTTP client");
+        let client = http::client(Duration::from_secs(ai_manager_shared::LLM_REQUEST_TIMEOUT));
 
         Self {
             client,
@@ -40,20 +62,29 @@ impl OpenAIProvider {
                 completion_tokens: 0,
                 total_tokens: 0,
             },
+            rate_limit_remaining: Mutex::new(None),
+            azure: None,
+            label: OPENAI_PROVIDER_LABEL.to_string(),
         }
     }
 
+    /// `provider_label` names an OpenAI-compatible backend at `base_url`,
+    /// such as "groq", in responses and usage records
     pub fn with_config(
         api_key: String,
         base_url: Option<String>,
         model: Option<String>,
         max_tokens: Option<u32>,
         temperature: Option<f32>,
+        provider_label: Option<String>,
     ) -> Self {
         let mut provider = Self::new(api_key);
 
         if let Some(url) = base_url {
-            provider.base_url = url;
+            provider.base_url = url.trim_end_matches('/').to_string();
+        }
+        if let Some(label) = provider_label {
+            provider.label = label;
         }
         if let Some(model) = model {
             provider.default_model = model;
@@ -68,6 +99,56 @@ impl OpenAIProvider {
         provider
     }
 
+    /// Talk to an Azure OpenAI resource instead: requests go to the
+    /// deployment serving the model and authenticate with an `api-key`
+    /// header. A configured base URL replaces the resource's default endpoint.
+    pub fn with_azure(mut self, azure: AzureOpenAIConfig) -> Self {
+        if self.base_url == OPENAI_API_BASE {
+            self.base_url = format!("https://{}.openai.azure.com", azure.resource);
+        }
+        if self.label == OPENAI_PROVIDER_LABEL {
+            self.label = "azure".to_string();
+        }
+        self.azure = Some(azure);
+        self
+    }
+
+    /// URL of `operation` (e.g. `chat/completions`) for `model`
+    fn endpoint(&self, model: &str, operation: &str) -> String {
+        match &self.azure {
+            Some(azure) => {
+                let deployment = azure
+                    .deployments
+                    .get(model)
+                    .map(String::as_str)
+                    .unwrap_or(model);
+                format!(
+                    "{}/openai/deployments/{}/{}?api-version={}",
+                    self.base_url, deployment, operation, azure.api_version
+                )
+            }
+            None => format!("{}/{}", self.base_url, operation),
+        }
+    }
+
+    fn models_endpoint(&self) -> String {
+        match &self.azure {
+            Some(azure) => format!(
+                "{}/openai/models?api-version={}",
+                self.base_url, azure.api_version
+            ),
+            None => format!("{}/models", self.base_url),
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        if self.azure.is_some() {
+            request.header("api-key", &self.api_key)
+        } else {
+            request.header("Authorization", format!("Bearer {}", self.api_key))
+        }
+    }
+
     fn build_messages(&self, request: &LLMRequest) -> Vec<OpenAIMessage> {
         let mut messages = Vec::new();
 
@@ -75,49 +156,102 @@ impl OpenAIProvider {
         for context in &request.context {
             messages.push(OpenAIMessage {
                 role: "user".to_string(),
-                content: context.clone(),
+                content: OpenAIContent::Text(context.clone()),
             });
         }
 
-        // Add current prompt
+        // Add current prompt, with any images after the text
+        let content = if request.images.is_empty() {
+            OpenAIContent::Text(request.prompt.clone())
+        } else {
+            let mut parts = vec![OpenAIContentPart::Text {
+                text: request.prompt.clone(),
+            }];
+            parts.extend(
+                request
+                    .images
+                    .iter()
+                    .map(|image| OpenAIContentPart::ImageUrl {
+                        image_url: OpenAIImageUrl {
+                            url: image.to_url(),
+                        },
+                    }),
+            );
+            OpenAIContent::Parts(parts)
+        };
         messages.push(OpenAIMessage {
             role: "user".to_string(),
-            content: request.prompt.clone(),
+            content,
         });
 
         messages
     }
-}
 
-#[async_trait]
-impl LLMProvider for OpenAIProvider {
-    async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+    /// Whether `model` accepts images. Models on OpenAI-compatible backends
+    /// aren't known here, so they are left to the backend to reject.
+    fn supports_vision(&self, model: &str) -> bool {
+        (self.label != OPENAI_PROVIDER_LABEL && self.azure.is_none())
+            || VISION_MODEL_PREFIXES
+                .iter()
+                .any(|prefix| model.starts_with(prefix))
+    }
+
+    /// The model `request` asks for, or the configured default
+    fn model_for(&self, request: &LLMRequest) -> String {
+        if request.model.is_empty() {
+            self.default_model.clone()
+        } else {
+            request.model.clone()
+        }
+    }
+
+    /// Send `request` and turn the reply into an `LLMResponse`
+    async fn complete(&self, mut request: LLMRequest) -> Result<LLMResponse> {
         debug!("Sending OpenAI request: {}", request.prompt);
+        validate_request(&request)?;
+        let model = self.model_for(&request);
+        let max_tokens = request.max_tokens.unwrap_or(self.max_tokens);
+        let dropped = request.fit_context_window(self.provider_name(), &model, max_tokens)?;
+        if dropped > 0 {
+            debug!(
+                "Dropped {} context messages to fit the context window of '{}'",
+                dropped, model
+            );
+        }
 
         let messages = self.build_messages(&request);
 
         let openai_request = OpenAIRequest {
-            model: if request.model.is_empty() {
-                self.default_model.clone()
-            } else {
-                request.model.clone()
-            },
+            model,
             messages,
-            max_tokens: request.max_tokens.or(Some(self.max_tokens)),
+            max_tokens: Some(max_tokens),
             temperature: request.temperature.or(Some(self.temperature)),
             stop: request.stop_sequences.clone(),
             stream: Some(request.stream),
+            response_format: request.response_format.as_ref().map(response_format_body),
         };
+        validate_images(
+            &request,
+            &openai_request.model,
+            self.supports_vision(&openai_request.model),
+        )?;
 
+        let url = self.endpoint(&openai_request.model, "chat/completions");
         let response = self
-            .client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authorize(self.client.post(url))
             .header("Content-Type", "application/json")
             .json(&openai_request)
             .send()
             .await
             .map_err(|e| SystemError::Network(format!("OpenAI request failed: {}", e)))?;
+        if let Some(remaining) =
+            remaining_from_header(response.headers(), "x-ratelimit-remaining-requests")
+        {
+            *self
+                .rate_limit_remaining
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(remaining);
+        }
 
         if !response.status().is_success() {
             let status = response.status();
@@ -128,7 +262,7 @@ impl LLMProvider for OpenAIProvider {
             error!("OpenAI API error {}: {}", status, error_text);
 
             return Err(SystemError::LLMApi {
-                provider: "openai".to_string(),
+                provider: self.label.clone(),
                 message: format!("HTTP {}: {}", status, error_text),
             });
         }
@@ -142,7 +276,7 @@ impl LLMProvider for OpenAIProvider {
             .choices
             .first()
             .ok_or_else(|| SystemError::LLMApi {
-                provider: "openai".to_string(),
+                provider: self.label.clone(),
                 message: "No choices in OpenAI response".to_string(),
             })?;
 
@@ -174,16 +308,27 @@ impl LLMProvider for OpenAIProvider {
             model: openai_response.model,
             usage,
             finish_reason,
-            provider: "openai".to_string(),
+            provider: self.label.clone(),
         })
     }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAIProvider {
+    async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+        let model = self.model_for(&request);
+        let started = Instant::now();
+        let result = self.complete(request).await;
+        metrics::global().record(self.provider_name(), &model, started.elapsed(), &result);
+        result
+    }
 
     async fn get_usage(&self) -> TokenUsage {
         self.total_usage.clone()
     }
 
     fn provider_name(&self) -> &str {
-        "openai"
+        &self.label
     }
 
     async fn health_check(&self) -> Result<()> {
@@ -191,9 +336,7 @@ impl LLMProvider for OpenAIProvider {
 
         // Simple request to check if API is accessible
         let response = self
-            .client
-            .get(format!("{}/models", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authorize(self.client.get(self.models_endpoint()))
             .send()
             .await
             .map_err(|e| SystemError::Network(format!("OpenAI health check failed: {}", e)))?;
@@ -205,11 +348,69 @@ impl LLMProvider for OpenAIProvider {
             let status = response.status();
             error!("OpenAI health check failed with status: {}", status);
             Err(SystemError::LLMApi {
-                provider: "openai".to_string(),
+                provider: self.label.clone(),
                 message: format!("Health check failed with HTTP {}", status),
             })
         }
     }
+
+    fn rate_limit_remaining(&self) -> Option<u32> {
+        *self
+            .rate_limit_remaining
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIProvider {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        debug!("Requesting {} OpenAI embeddings", texts.len());
+
+        let response = self
+            .authorize(
+                self.client
+                    .post(self.endpoint(OPENAI_EMBEDDING_MODEL, "embeddings")),
+            )
+            .json(&OpenAIEmbeddingRequest {
+                model: OPENAI_EMBEDDING_MODEL.to_string(),
+                input: texts,
+            })
+            .send()
+            .await
+            .map_err(|e| {
+                SystemError::Network(format!("OpenAI embeddings request failed: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            error!("OpenAI embeddings error {}: {}", status, error_text);
+
+            return Err(SystemError::LLMApi {
+                provider: self.label.clone(),
+                message: format!("HTTP {}: {}", status, error_text),
+            });
+        }
+
+        let mut body: OpenAIEmbeddingResponse = response.json().await.map_err(|e| {
+            SystemError::Serialization(format!("Failed to parse OpenAI embeddings: {}", e))
+        })?;
+
+        // The API documents ordering by index; sort rather than rely on it
+        body.data.sort_by_key(|item| item.index);
+        Ok(body.data.into_iter().map(|item| item.embedding).collect())
+    }
+
+    fn embedding_provider_name(&self) -> &str {
+        &self.label
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -224,10 +425,53 @@ struct OpenAIRequest {
     stop: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The chat completions `response_format` parameter: JSON mode, or
+/// structured outputs when there is a schema to follow
+fn response_format_body(format: &ResponseFormat) -> serde_json::Value {
+    match format {
+        ResponseFormat::Json { schema: None } => serde_json::json!({ "type": "json_object" }),
+        ResponseFormat::Json {
+            schema: Some(schema),
+        } => serde_json::json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema },
+        }),
+    }
+}
+
+#[derive(Debug, Serialize)]
 struct OpenAIMessage {
+    role: String,
+    content: OpenAIContent,
+}
+
+/// Plain text, or text mixed with images
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum OpenAIContent {
+    Text(String),
+    Parts(Vec<OpenAIContentPart>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OpenAIContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAIImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct OpenAIReply {
     role: String,
     content: String,
 }
@@ -247,7 +491,7 @@ struct OpenAIResponse {
 #[allow(dead_code)]
 struct OpenAIChoice {
     index: u32,
-    message: OpenAIMessage,
+    message: OpenAIReply,
     finish_reason: String,
 }
 
@@ -258,10 +502,152 @@ struct OpenAIUsage {
     total_tokens: u32,
 }
 
+#[derive(Debug, Serialize)]
+struct OpenAIEmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbeddingResponse {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_image_request() {
+        let provider = OpenAIProvider::new("key".to_string());
+        let mut request = LLMRequest {
+            prompt: "What's in this picture?".to_string(),
+            context: vec![],
+            model: "gpt-4o".to_string(),
+            max_tokens: None,
+            temperature: None,
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![
+                crate::ImageInput::base64("image/png", "iVBORw0KGgo="),
+                crate::ImageInput::url("image/jpeg", "https://example.com/cat.jpg"),
+            ],
+        };
+
+        let messages = serde_json::to_value(provider.build_messages(&request)).unwrap();
+        assert_eq!(
+            messages[0]["content"],
+            serde_json::json!([
+                { "type": "text", "text": "What's in this picture?" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } },
+                { "type": "image_url", "image_url": { "url": "https://example.com/cat.jpg" } },
+            ])
+        );
+
+        // Rejected before anything is sent
+        request.model = "gpt-3.5-turbo".to_string();
+        let err = provider.send_request(request).await.unwrap_err();
+        assert!(matches!(err, SystemError::InvalidInput(_)));
+    }
+
+    #[test]
+    fn test_response_format_body() {
+        assert_eq!(
+            response_format_body(&ResponseFormat::Json { schema: None }),
+            serde_json::json!({ "type": "json_object" })
+        );
+
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "category": { "type": "string" } },
+        });
+        let body = response_format_body(&ResponseFormat::Json {
+            schema: Some(schema.clone()),
+        });
+        assert_eq!(body["type"], "json_schema");
+        assert_eq!(body["json_schema"]["schema"], schema);
+    }
+
+    #[test]
+    fn test_azure_endpoints() {
+        let openai = OpenAIProvider::new("key".to_string());
+        assert_eq!(
+            openai.endpoint("gpt-4", "chat/completions"),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(openai.provider_name(), "openai");
+
+        let azure = OpenAIProvider::new("key".to_string()).with_azure(AzureOpenAIConfig {
+            resource: "contoso".to_string(),
+            api_version: "2024-02-01".to_string(),
+            deployments: [("gpt-4".to_string(), "prod-gpt4".to_string())].into(),
+        });
+        assert_eq!(
+            azure.endpoint("gpt-4", "chat/completions"),
+            "https://contoso.openai.azure.com/openai/deployments/prod-gpt4/chat/completions?api-version=2024-02-01"
+        );
+        // Models without a mapped deployment are used as the deployment name
+        assert_eq!(
+            azure.endpoint(OPENAI_EMBEDDING_MODEL, "embeddings"),
+            "https://contoso.openai.azure.com/openai/deployments/text-embedding-3-small/embeddings?api-version=2024-02-01"
+        );
+        assert_eq!(azure.provider_name(), "azure");
+
+        let request = azure
+            .authorize(azure.client.get(azure.models_endpoint()))
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["api-key"], "key");
+        assert!(request.headers().get("Authorization").is_none());
+    }
+
+    #[test]
+    fn test_compatible_provider_label() {
+        let groq = OpenAIProvider::with_config(
+            "key".to_string(),
+            Some("https://api.groq.com/openai/v1/".to_string()),
+            Some("llama3-8b-8192".to_string()),
+            None,
+            None,
+            Some("groq".to_string()),
+        );
+        assert_eq!(groq.provider_name(), "groq");
+        assert_eq!(
+            groq.models_endpoint(),
+            "https://api.groq.com/openai/v1/models"
+        );
+        assert_eq!(
+            groq.endpoint("llama3-8b-8192", "chat/completions"),
+            "https://api.groq.com/openai/v1/chat/completions"
+        );
+
+        let request = groq
+            .authorize(groq.client.get(groq.models_endpoint()))
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["Authorization"], "Bearer key");
+    }
+
+    #[test]
+    fn test_openai_embeddings_are_ordered_by_index() {
+        let json = r#"{"data": [
+            {"index": 1, "embedding": [0.0, 1.0]},
+            {"index": 0, "embedding": [1.0, 0.0]}
+        ]}"#;
+        let mut body: OpenAIEmbeddingResponse = serde_json::from_str(json).unwrap();
+        body.data.sort_by_key(|item| item.index);
+
+        assert_eq!(body.data[0].embedding, vec![1.0, 0.0]);
+        assert_eq!(body.data[1].embedding, vec![0.0, 1.0]);
+    }
+
     // Note: These tests require a valid OpenAI API key to run
     // They are disabled by default to avoid unnecessary API calls
 
@@ -279,6 +665,8 @@ mod tests {
             temperature: Some(0.7),
             stop_sequences: None,
             stream: false,
+            response_format: None,
+            images: vec![],
         };
 
         let response = provider.send_request(request).await.unwrap();
@@ -295,4 +683,18 @@ mod tests {
         let result = provider.health_check().await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_openai_embeddings() {
+        let api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
+        let provider = OpenAIProvider::new(api_key);
+
+        let embeddings = provider
+            .embed(vec!["hello".to_string(), "world".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert!(!embeddings[0].is_empty());
+    }
 }
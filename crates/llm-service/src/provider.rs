@@ -1,13 +1,39 @@
-use ai_manager_shared::{Result, SystemError, TokenUsage};
+use crate::{BalanceStrategy, ClaudeProvider, LoadBalancedProvider, OpenAIProvider};
+use ai_manager_shared::{
+    LLMConfig, LLMProviderConfig, Result, ServiceHealth, SystemError, TokenUsage,
+    BACKOFF_MULTIPLIER, MAX_PROMPT_LENGTH, MAX_RETRY_ATTEMPTS, PROVIDER_HEALTH_TTL_SECONDS,
+    RETRY_DELAY_MS,
+};
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
 
 #[async_trait]
 pub trait LLMProvider: Send + Sync {
     /// Send a request to the LLM provider
     async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse>;
 
+    /// Send a request that is abandoned with `SystemError::Cancelled` once
+    /// `cancel` fires. The in-flight HTTP request is dropped, closing its
+    /// connection so the provider stops generating.
+    async fn send_request_cancellable(
+        &self,
+        request: LLMRequest,
+        cancel: CancellationToken,
+    ) -> Result<LLMResponse> {
+        tokio::select! {
+            result = self.send_request(request) => result,
+            _ = cancel.cancelled() => Err(SystemError::Cancelled),
+        }
+    }
+
     /// Get usage statistics
     async fn get_usage(&self) -> TokenUsage;
 
@@ -16,6 +42,12 @@ pub trait LLMProvider: Send + Sync {
 
     /// Check if provider is available
     async fn health_check(&self) -> Result<()>;
+
+    /// Requests left in the current rate-limit window, as last reported by
+    /// the provider
+    fn rate_limit_remaining(&self) -> Option<u32> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +59,71 @@ pub struct LLMRequest {
     pub temperature: Option<f32>,
     pub stop_sequences: Option<Vec<String>>,
     pub stream: bool,
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    /// Images sent along with the prompt; needs a vision model
+    #[serde(default)]
+    pub images: Vec<ImageInput>,
+}
+
+/// An image attached to a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageInput {
+    /// e.g. `image/png`
+    pub mime_type: String,
+    pub source: ImageSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Base64-encoded image bytes
+    Base64 { data: String },
+    /// An image the provider fetches itself
+    Url { url: String },
+}
+
+impl ImageInput {
+    pub fn base64(mime_type: impl Into<String>, data: impl Into<String>) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            source: ImageSource::Base64 { data: data.into() },
+        }
+    }
+
+    pub fn url(mime_type: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            source: ImageSource::Url { url: url.into() },
+        }
+    }
+
+    /// A `data:` URL holding the image, or the image's URL
+    pub fn to_url(&self) -> String {
+        match &self.source {
+            ImageSource::Base64 { data } => format!("data:{};base64,{}", self.mime_type, data),
+            ImageSource::Url { url } => url.clone(),
+        }
+    }
+}
+
+/// Reject requests with images for a model that can't read them
+pub fn validate_images(request: &LLMRequest, model: &str, supports_vision: bool) -> Result<()> {
+    if !request.images.is_empty() && !supports_vision {
+        return Err(SystemError::InvalidInput(format!(
+            "Model '{}' does not accept image input; use a vision model",
+            model
+        )));
+    }
+    Ok(())
+}
+
+/// Constrains what a provider may reply with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// A single JSON value, matching `schema` (a JSON Schema) when given
+    Json { schema: Option<serde_json::Value> },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,9 +143,160 @@ pub enum FinishReason {
     Error(String),
 }
 
+/// Characters in a request's prompt and context combined
+pub fn request_length(request: &LLMRequest) -> usize {
+    request.prompt.chars().count()
+        + request
+            .context
+            .iter()
+            .map(|message| message.chars().count())
+            .sum::<usize>()
+}
+
+/// Reject requests whose prompt and context exceed `MAX_PROMPT_LENGTH`
+pub fn validate_request(request: &LLMRequest) -> Result<()> {
+    let length = request_length(request);
+    if length > MAX_PROMPT_LENGTH {
+        return Err(SystemError::InvalidInput(format!(
+            "Prompt is {} characters, over the limit of {}",
+            length, MAX_PROMPT_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Drop context messages, oldest first, until the request fits within
+/// `MAX_PROMPT_LENGTH`. The prompt itself is never trimmed. Returns how many
+/// context messages were dropped.
+pub fn truncate_to_budget(request: &mut LLMRequest) -> usize {
+    let mut length = request_length(request);
+    let mut dropped = 0;
+
+    while length > MAX_PROMPT_LENGTH && dropped < request.context.len() {
+        length -= request.context[dropped].chars().count();
+        dropped += 1;
+    }
+
+    request.context.drain(..dropped);
+    dropped
+}
+
+/// Deserialize a JSON reply, ignoring any prose or code fence around the
+/// outermost object or array
+pub fn parse_json_content<T: DeserializeOwned>(content: &str) -> Result<T> {
+    let start = content.find(['{', '[']);
+    let end = content.rfind(['}', ']']);
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => content.trim(),
+    };
+
+    serde_json::from_str(json).map_err(|e| {
+        SystemError::Serialization(format!("Response is not the expected JSON: {}", e))
+    })
+}
+
+/// Send `request` asking for JSON and deserialize the reply into `T`. A reply
+/// that doesn't parse is requested once more before giving up.
+pub async fn send_request_typed<T: DeserializeOwned>(
+    provider: &dyn LLMProvider,
+    mut request: LLMRequest,
+) -> Result<T> {
+    if request.response_format.is_none() {
+        request.response_format = Some(ResponseFormat::Json { schema: None });
+    }
+
+    let response = provider.send_request(request.clone()).await?;
+    match parse_json_content(&response.content) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            warn!(
+                "Malformed JSON from '{}', retrying once: {}",
+                provider.provider_name(),
+                e
+            );
+            let response = provider.send_request(request).await?;
+            parse_json_content(&response.content)
+        }
+    }
+}
+
+/// How failed provider calls are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub backoff_multiplier: f64,
+}
+
+impl RetryPolicy {
+    /// Delay before the retry following the given failed attempt (1-based)
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let multiplier = self
+            .backoff_multiplier
+            .powi(attempt.saturating_sub(1) as i32);
+        self.initial_delay.mul_f64(multiplier)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: MAX_RETRY_ATTEMPTS,
+            initial_delay: Duration::from_millis(RETRY_DELAY_MS),
+            backoff_multiplier: BACKOFF_MULTIPLIER,
+        }
+    }
+}
+
+/// Run `operation`, retrying with backoff while it fails with errors that
+/// `SystemError::should_retry` allows, up to `policy.max_attempts` attempts.
+/// Gives up with `SystemError::Cancelled` if `cancel` fires while waiting to
+/// retry.
+pub async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    cancel: &CancellationToken,
+    mut operation: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if e.should_retry() && attempt < policy.max_attempts => {
+                let delay = policy.delay_for(attempt);
+                warn!(
+                    "Attempt {} of {} failed, retrying in {:?}: {}",
+                    attempt, policy.max_attempts, delay, e
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = cancel.cancelled() => return Err(SystemError::Cancelled),
+                }
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// A provider's latest health check result
+struct CachedHealth {
+    status: ServiceHealth,
+    checked_at: Instant,
+}
+
+/// Each name maps to one or more providers, with requests spread across them
 pub struct LLMService {
-    providers: HashMap<String, Box<dyn LLMProvider>>,
+    providers: HashMap<String, LoadBalancedProvider>,
     default_provider: String,
+    retry_policy: RetryPolicy,
+    balance_strategy: BalanceStrategy,
+    health: RwLock<HashMap<String, CachedHealth>>,
+    health_ttl: Duration,
 }
 
 impl LLMService {
@@ -56,12 +304,68 @@ impl LLMService {
         Self {
             providers: HashMap::new(),
             default_provider: "openai".to_string(),
+            retry_policy: RetryPolicy::default(),
+            balance_strategy: BalanceStrategy::default(),
+            health: RwLock::new(HashMap::new()),
+            health_ttl: Duration::from_secs(PROVIDER_HEALTH_TTL_SECONDS),
         }
     }
 
-    /// Add a provider to the service
+    /// Build a service with the providers in `config`, one per API key.
+    /// Providers other than `openai`, `azure`, `claude` and OpenAI-compatible
+    /// ones with a base URL are skipped, as is `mock` unless the `mock`
+    /// feature is enabled.
+    pub fn from_config(config: &LLMConfig) -> Result<Self> {
+        let mut service = Self::new();
+
+        for (name, settings) in &config.providers {
+            let api_keys = std::iter::once(&settings.api_key).chain(&settings.extra_api_keys);
+            for api_key in api_keys {
+                match build_provider(name, settings, api_key.clone()) {
+                    Some(provider) => service.add_provider(name.clone(), provider),
+                    None => break,
+                }
+            }
+        }
+
+        service.set_default_provider(config.default_provider.clone())?;
+        Ok(service)
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Reuse a provider's health check result for `ttl` before checking again
+    pub fn with_health_ttl(mut self, ttl: Duration) -> Self {
+        self.health_ttl = ttl;
+        self
+    }
+
+    pub fn health_ttl(&self) -> Duration {
+        self.health_ttl
+    }
+
+    /// How requests are spread across providers sharing a name
+    pub fn with_balance_strategy(mut self, strategy: BalanceStrategy) -> Self {
+        self.balance_strategy = strategy;
+        for provider in self.providers.values_mut() {
+            provider.set_strategy(strategy);
+        }
+        self
+    }
+
+    /// Add a provider to the service. Providers added under a name already in
+    /// use share its requests with the earlier ones.
     pub fn add_provider(&mut self, name: String, provider: Box<dyn LLMProvider>) {
-        self.providers.insert(name, provider);
+        let strategy = self.balance_strategy;
+        self.providers
+            .entry(name)
+            .or_insert_with_key(|name| {
+                LoadBalancedProvider::new(name.clone()).with_strategy(strategy)
+            })
+            .add_backend(provider);
     }
 
     /// Set the default provider
@@ -93,7 +397,42 @@ impl LLMService {
             SystemError::Configuration(format!("Provider '{}' not found", provider_name))
         })?;
 
-        provider.send_request(request).await
+        with_retry(&self.retry_policy, &CancellationToken::new(), || {
+            provider.send_request(request.clone())
+        })
+        .await
+    }
+
+    /// Like `send_request_with_provider`, but gives up with
+    /// `SystemError::Cancelled` as soon as `cancel` fires, including while
+    /// waiting to retry
+    pub async fn send_request_cancellable(
+        &self,
+        request: LLMRequest,
+        provider_name: &str,
+        cancel: CancellationToken,
+    ) -> Result<LLMResponse> {
+        let provider = self.providers.get(provider_name).ok_or_else(|| {
+            SystemError::Configuration(format!("Provider '{}' not found", provider_name))
+        })?;
+
+        with_retry(&self.retry_policy, &cancel, || {
+            provider.send_request_cancellable(request.clone(), cancel.clone())
+        })
+        .await
+    }
+
+    /// Send `request` to the default provider and deserialize its JSON
+    /// reply, see [`send_request_typed`]
+    pub async fn send_request_typed<T: DeserializeOwned>(&self, request: LLMRequest) -> Result<T> {
+        let provider = self.providers.get(&self.default_provider).ok_or_else(|| {
+            SystemError::Configuration(format!("Provider '{}' not found", self.default_provider))
+        })?;
+
+        with_retry(&self.retry_policy, &CancellationToken::new(), || {
+            send_request_typed(provider, request.clone())
+        })
+        .await
     }
 
     /// Get available providers
@@ -106,18 +445,94 @@ impl LLMService {
         &self.default_provider
     }
 
-    /// Check health of all providers
+    /// Check health of all providers over the network, updating the cache
     pub async fn health_check_all(&self) -> HashMap<String, Result<()>> {
         let mut results = HashMap::new();
 
         for (name, provider) in &self.providers {
             let result = provider.health_check().await;
+            self.store_health(name, &result);
             results.insert(name.clone(), result);
         }
 
         results
     }
 
+    /// Health of every provider, only checking those whose cached result
+    /// is older than the health TTL
+    pub async fn provider_health(&self) -> HashMap<String, ServiceHealth> {
+        let mut health = HashMap::new();
+
+        for (name, provider) in &self.providers {
+            let status = match self.fresh_health(name) {
+                Some(status) => status,
+                None => {
+                    let result = provider.health_check().await;
+                    self.store_health(name, &result)
+                }
+            };
+            health.insert(name.clone(), status);
+        }
+
+        health
+    }
+
+    /// Last known health of `provider`, however old, without a network
+    /// call. `None` until it has been checked.
+    pub fn cached_health(&self, provider: &str) -> Option<ServiceHealth> {
+        self.health
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(provider)
+            .map(|cached| cached.status.clone())
+    }
+
+    /// Check providers now and then every `interval` in the background, so
+    /// health polls are answered from the cache. Stops once the service is
+    /// dropped.
+    pub fn spawn_health_refresh(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let service = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let Some(service) = service.upgrade() else {
+                    break;
+                };
+                service.provider_health().await;
+                drop(service);
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    fn fresh_health(&self, provider: &str) -> Option<ServiceHealth> {
+        self.health
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(provider)
+            .filter(|cached| cached.checked_at.elapsed() < self.health_ttl)
+            .map(|cached| cached.status.clone())
+    }
+
+    fn store_health(&self, provider: &str, result: &Result<()>) -> ServiceHealth {
+        let status = match result {
+            Ok(()) => ServiceHealth::Healthy,
+            Err(e) => ServiceHealth::Unhealthy {
+                error: e.to_string(),
+            },
+        };
+        self.health
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                provider.to_string(),
+                CachedHealth {
+                    status: status.clone(),
+                    checked_at: Instant::now(),
+                },
+            );
+        status
+    }
+
     /// Get usage statistics for all providers
     pub async fn get_usage_all(&self) -> HashMap<String, TokenUsage> {
         let mut usage = HashMap::new();
@@ -137,40 +552,402 @@ impl Default for LLMService {
     }
 }
 
+/// The provider `name` in `config` stands for, using `api_key`; `None` if
+/// it can't be built
+fn build_provider(
+    name: &str,
+    settings: &LLMProviderConfig,
+    api_key: String,
+) -> Option<Box<dyn LLMProvider>> {
+    let base_url = settings.base_url.clone();
+    let model = Some(settings.model.clone());
+    let provider: Box<dyn LLMProvider> = match name {
+        "openai" => Box::new(OpenAIProvider::with_config(
+            api_key,
+            base_url,
+            model,
+            settings.max_tokens,
+            settings.temperature,
+            None,
+        )),
+        "azure" => {
+            let Some(azure) = settings.azure.clone() else {
+                warn!("Skipping LLM provider 'azure': no [azure] resource configured");
+                return None;
+            };
+            Box::new(
+                OpenAIProvider::with_config(
+                    api_key,
+                    base_url,
+                    model,
+                    settings.max_tokens,
+                    settings.temperature,
+                    None,
+                )
+                .with_azure(azure),
+            )
+        }
+        "claude" => Box::new(
+            ClaudeProvider::with_config(
+                api_key,
+                base_url,
+                model,
+                settings.max_tokens,
+                settings.temperature,
+            )
+            .with_live_health_check(settings.live_health_check),
+        ),
+        #[cfg(any(test, feature = "mock"))]
+        "mock" => Box::new(crate::mock::MockProvider::new(name)),
+        // Any other provider with a base URL speaks the OpenAI API
+        other if base_url.is_some() => Box::new(OpenAIProvider::with_config(
+            api_key,
+            base_url,
+            model,
+            settings.max_tokens,
+            settings.temperature,
+            Some(other.to_string()),
+        )),
+        other => {
+            warn!("Skipping unsupported LLM provider '{}'", other);
+            return None;
+        }
+    };
+    Some(provider)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::MockProvider;
 
-    struct MockProvider {
-        name: String,
+    #[tokio::test]
+    async fn test_llm_service() {
+        let mut service = LLMService::new();
+
+        // Add mock provider
+        let mock_provider = MockProvider::new("mock");
+        service.add_provider("mock".to_string(), Box::new(mock_provider));
+        service.set_default_provider("mock".to_string()).unwrap();
+
+        // Test request
+        let request = LLMRequest {
+            prompt: "Hello".to_string(),
+            context: vec![],
+            model: "mock-model".to_string(),
+            max_tokens: Some(100),
+            temperature: Some(0.7),
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![],
+        };
+
+        let response = service.send_request(request).await.unwrap();
+        assert!(response.content.contains("Hello"));
+        assert_eq!(response.provider, "mock");
+    }
+
+    /// Fails with each error in turn, then succeeds
+    struct FlakyProvider {
+        errors: std::sync::Mutex<Vec<SystemError>>,
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    impl FlakyProvider {
+        fn new(errors: Vec<SystemError>) -> Self {
+            Self {
+                errors: std::sync::Mutex::new(errors),
+                calls: std::sync::atomic::AtomicU32::new(0),
+            }
+        }
     }
 
     #[async_trait]
-    impl LLMProvider for MockProvider {
+    impl LLMProvider for FlakyProvider {
         async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
-            Ok(LLMResponse {
-                content: format!("Mock response to: {}", request.prompt),
-                model: request.model,
-                usage: TokenUsage {
-                    prompt_tokens: 10,
-                    completion_tokens: 5,
-                    total_tokens: 15,
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if let Some(error) = self.errors.lock().unwrap().pop() {
+                return Err(error);
+            }
+            MockProvider::new("flaky").send_request(request).await
+        }
+
+        async fn get_usage(&self) -> TokenUsage {
+            TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn health_check(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(1),
+            backoff_multiplier: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_from_config_skips_unknown_providers() {
+        let provider = |model: &str| ai_manager_shared::LLMProviderConfig {
+            api_key: "key".to_string(),
+            base_url: None,
+            model: model.to_string(),
+            max_tokens: None,
+            temperature: None,
+            models: vec![],
+            azure: None,
+            extra_api_keys: vec![],
+            live_health_check: false,
+        };
+        let config = LLMConfig {
+            default_provider: "claude".to_string(),
+            providers: HashMap::from([
+                ("claude".to_string(), provider("claude-3-haiku")),
+                ("mystery".to_string(), provider("m-1")),
+            ]),
+            summarization: Default::default(),
+        };
+
+        let service = LLMService::from_config(&config).unwrap();
+        assert_eq!(service.get_providers(), vec!["claude".to_string()]);
+        assert_eq!(service.get_default_provider(), "claude");
+    }
+
+    #[tokio::test]
+    async fn test_from_config_mock_provider_needs_no_key() {
+        let config = LLMConfig {
+            default_provider: "mock".to_string(),
+            providers: HashMap::from([(
+                "mock".to_string(),
+                ai_manager_shared::LLMProviderConfig {
+                    api_key: String::new(),
+                    base_url: None,
+                    model: "mock-model".to_string(),
+                    max_tokens: None,
+                    temperature: None,
+                    models: vec![],
+                    azure: None,
+                    extra_api_keys: vec![],
+                    live_health_check: false,
                 },
-                finish_reason: FinishReason::Stop,
-                provider: self.name.clone(),
-            })
+            )]),
+            summarization: Default::default(),
+        };
+
+        let service = LLMService::from_config(&config).unwrap();
+        let response = service
+            .send_request(request_with("Hello", vec![]))
+            .await
+            .unwrap();
+        assert_eq!(response.provider, "mock");
+        assert_eq!(response.content, "Mock response to: Hello");
+    }
+
+    #[test]
+    fn test_from_config_adds_openai_compatible_providers() {
+        let config = LLMConfig {
+            default_provider: "groq".to_string(),
+            providers: HashMap::from([(
+                "groq".to_string(),
+                ai_manager_shared::LLMProviderConfig {
+                    api_key: "key".to_string(),
+                    base_url: Some("https://api.groq.com/openai/v1".to_string()),
+                    model: "llama3-8b-8192".to_string(),
+                    max_tokens: None,
+                    temperature: None,
+                    models: vec![],
+                    azure: None,
+                    extra_api_keys: vec![],
+                    live_health_check: false,
+                },
+            )]),
+            summarization: Default::default(),
+        };
+
+        let service = LLMService::from_config(&config).unwrap();
+        assert_eq!(service.get_providers(), vec!["groq".to_string()]);
+        assert_eq!(service.providers["groq"].provider_name(), "groq");
+    }
+
+    #[test]
+    fn test_from_config_balances_extra_api_keys() {
+        let config = LLMConfig {
+            default_provider: "openai".to_string(),
+            providers: HashMap::from([(
+                "openai".to_string(),
+                ai_manager_shared::LLMProviderConfig {
+                    api_key: "key-1".to_string(),
+                    base_url: None,
+                    model: "gpt-4o".to_string(),
+                    max_tokens: None,
+                    temperature: None,
+                    models: vec![],
+                    azure: None,
+                    extra_api_keys: vec!["key-2".to_string()],
+                    live_health_check: false,
+                },
+            )]),
+            summarization: Default::default(),
+        };
+
+        let service = LLMService::from_config(&config).unwrap();
+        assert_eq!(service.get_providers(), vec!["openai".to_string()]);
+        assert_eq!(service.providers["openai"].backend_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_providers_sharing_a_name_take_turns() {
+        let mut service = LLMService::new();
+        for name in ["first", "second"] {
+            service.add_provider("mock".to_string(), Box::new(MockProvider::new(name)));
+        }
+
+        let mut answered_by = Vec::new();
+        for _ in 0..4 {
+            let response = service
+                .send_request_with_provider(request_with("Hello", vec![]), "mock")
+                .await
+                .unwrap();
+            answered_by.push(response.provider);
+        }
+        assert_eq!(answered_by, vec!["first", "second", "first", "second"]);
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        assert!(SystemError::Timeout.should_retry());
+        assert!(SystemError::Network("reset".to_string()).should_retry());
+        assert!(SystemError::ServiceUnavailable {
+            service: "openai".to_string()
+        }
+        .should_retry());
+
+        assert!(!SystemError::Authentication("bad key".to_string()).should_retry());
+        assert!(!SystemError::InvalidInput("empty".to_string()).should_retry());
+        assert!(!SystemError::LLMApi {
+            provider: "openai".to_string(),
+            message: "HTTP 400".to_string()
+        }
+        .should_retry());
+
+        // Recoverable, but left to the caller rather than retried at once
+        let rate_limited = SystemError::RateLimitExceeded {
+            service: "openai".to_string(),
+        };
+        assert!(rate_limited.is_recoverable());
+        assert!(!rate_limited.should_retry());
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            initial_delay: Duration::from_millis(100),
+            backoff_multiplier: 2.0,
+        };
+        assert_eq!(policy.delay_for(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(3), Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_send_request_retries_transient_errors() {
+        let provider = FlakyProvider::new(vec![
+            SystemError::Timeout,
+            SystemError::Network("reset".to_string()),
+        ]);
+        let mut service = LLMService::new().with_retry_policy(fast_retries());
+        service.add_provider("flaky".to_string(), Box::new(provider));
+
+        let response = service
+            .send_request_with_provider(request_with("Hello", vec![]), "flaky")
+            .await
+            .unwrap();
+        assert!(response.content.contains("Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_send_request_gives_up_on_terminal_errors() {
+        let provider = FlakyProvider::new(vec![SystemError::Authentication("bad key".to_string())]);
+
+        let result = with_retry(&fast_retries(), &CancellationToken::new(), || {
+            provider.send_request(request_with("Hello", vec![]))
+        })
+        .await;
+
+        assert!(matches!(result, Err(SystemError::Authentication(_))));
+        assert_eq!(provider.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_retry_backoff() {
+        let provider = FlakyProvider::new(vec![SystemError::Timeout]);
+        let slow_retries = RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(60),
+            backoff_multiplier: 1.0,
+        };
+
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            with_retry(&slow_retries, &cancel, || {
+                provider.send_request(request_with("Hello", vec![]))
+            }),
+        )
+        .await
+        .expect("cancellation should end the backoff");
+        assert!(matches!(result, Err(SystemError::Cancelled)));
+        assert_eq!(provider.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    /// Never answers; records when its request is dropped
+    struct HangingProvider {
+        dropped: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    struct SetOnDrop(Arc<std::sync::atomic::AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl LLMProvider for HangingProvider {
+        async fn send_request(&self, _request: LLMRequest) -> Result<LLMResponse> {
+            let _guard = SetOnDrop(self.dropped.clone());
+            std::future::pending().await
         }
 
         async fn get_usage(&self) -> TokenUsage {
             TokenUsage {
-                prompt_tokens: 100,
-                completion_tokens: 50,
-                total_tokens: 150,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
             }
         }
 
         fn provider_name(&self) -> &str {
-            &self.name
+            "hanging"
         }
 
         async fn health_check(&self) -> Result<()> {
@@ -179,29 +956,206 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_llm_service() {
+    async fn test_cancelled_request_is_dropped() {
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
         let mut service = LLMService::new();
+        service.add_provider(
+            "hanging".to_string(),
+            Box::new(HangingProvider {
+                dropped: dropped.clone(),
+            }),
+        );
 
-        // Add mock provider
-        let mock_provider = MockProvider {
-            name: "mock".to_string(),
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            service.send_request_cancellable(request_with("Hello", vec![]), "hanging", cancel),
+        )
+        .await
+        .expect("cancellation should end the request");
+        assert!(matches!(result, Err(SystemError::Cancelled)));
+        assert!(dropped.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    /// Replies with each string in turn, checking JSON was asked for
+    struct ScriptedProvider {
+        replies: std::sync::Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for ScriptedProvider {
+        async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+            assert_eq!(
+                request.response_format,
+                Some(ResponseFormat::Json { schema: None })
+            );
+            let mut response = MockProvider::new("scripted").send_request(request).await?;
+            response.content = self.replies.lock().unwrap().remove(0).to_string();
+            Ok(response)
+        }
+
+        async fn get_usage(&self) -> TokenUsage {
+            TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            "scripted"
+        }
+
+        async fn health_check(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Label {
+        label: String,
+    }
+
+    #[test]
+    fn test_parse_json_content() {
+        let fenced: Label = parse_json_content("```json\n{\"label\": \"work\"}\n```").unwrap();
+        assert_eq!(fenced.label, "work");
+
+        let list: Vec<u32> = parse_json_content("[1, 2]").unwrap();
+        assert_eq!(list, vec![1, 2]);
+
+        assert!(matches!(
+            parse_json_content::<Label>("Probably work."),
+            Err(SystemError::Serialization(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_request_typed_retries_malformed_json_once() {
+        let provider = ScriptedProvider {
+            replies: std::sync::Mutex::new(vec!["Sure! It's work.", "{\"label\": \"work\"}"]),
         };
-        service.add_provider("mock".to_string(), Box::new(mock_provider));
-        service.set_default_provider("mock".to_string()).unwrap();
+        let label: Label = send_request_typed(&provider, request_with("Label this", vec![]))
+            .await
+            .unwrap();
+        assert_eq!(label.label, "work");
 
-        // Test request
-        let request = LLMRequest {
-            prompt: "Hello".to_string(),
-            context: vec![],
+        let provider = ScriptedProvider {
+            replies: std::sync::Mutex::new(vec!["work", "{\"label\": "]),
+        };
+        let result =
+            send_request_typed::<Label>(&provider, request_with("Label this", vec![])).await;
+        assert!(matches!(result, Err(SystemError::Serialization(_))));
+        assert!(provider.replies.lock().unwrap().is_empty());
+    }
+
+    fn request_with(prompt: &str, context: Vec<String>) -> LLMRequest {
+        LLMRequest {
+            prompt: prompt.to_string(),
+            context,
             model: "mock-model".to_string(),
-            max_tokens: Some(100),
-            temperature: Some(0.7),
+            max_tokens: None,
+            temperature: None,
             stop_sequences: None,
             stream: false,
-        };
+            response_format: None,
+            images: vec![],
+        }
+    }
 
-        let response = service.send_request(request).await.unwrap();
-        assert!(response.content.contains("Hello"));
-        assert_eq!(response.provider, "mock");
+    #[test]
+    fn test_validate_request_rejects_oversized_prompt() {
+        let request = request_with(&"x".repeat(MAX_PROMPT_LENGTH + 1), vec![]);
+
+        let err = validate_request(&request).unwrap_err().to_string();
+        assert!(err.contains(&(MAX_PROMPT_LENGTH + 1).to_string()));
+
+        let request = request_with("Hello", vec!["earlier turn".to_string()]);
+        assert!(validate_request(&request).is_ok());
+    }
+
+    #[test]
+    fn test_truncate_to_budget_drops_oldest_context() {
+        let half = MAX_PROMPT_LENGTH / 2;
+        let mut request = request_with(
+            &"p".repeat(half),
+            vec!["a".repeat(half), "b".repeat(10), "c".repeat(10)],
+        );
+        assert!(validate_request(&request).is_err());
+
+        let dropped = truncate_to_budget(&mut request);
+
+        assert_eq!(dropped, 1);
+        assert_eq!(request.context, vec!["b".repeat(10), "c".repeat(10)]);
+        assert!(validate_request(&request).is_ok());
+    }
+
+    /// Fails every health check, counting how often it is asked
+    struct DownProvider {
+        checks: Arc<std::sync::atomic::AtomicU32>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for DownProvider {
+        async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+            MockProvider::new("down").send_request(request).await
+        }
+
+        async fn get_usage(&self) -> TokenUsage {
+            TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            "down"
+        }
+
+        async fn health_check(&self) -> Result<()> {
+            self.checks
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(SystemError::Network("unreachable".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_health_is_cached() {
+        let checks = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let mut service = LLMService::new();
+        service.add_provider(
+            "down".to_string(),
+            Box::new(DownProvider {
+                checks: checks.clone(),
+            }),
+        );
+        service.add_provider("mock".to_string(), Box::new(MockProvider::new("mock")));
+        assert!(service.cached_health("down").is_none());
+
+        let health = service.provider_health().await;
+        assert!(matches!(health["mock"], ServiceHealth::Healthy));
+        assert!(matches!(
+            &health["down"],
+            ServiceHealth::Unhealthy { error } if error.contains("unreachable")
+        ));
+
+        service.provider_health().await;
+        assert_eq!(checks.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(matches!(
+            service.cached_health("down"),
+            Some(ServiceHealth::Unhealthy { .. })
+        ));
+
+        // With no TTL every call checks again
+        let service = service.with_health_ttl(Duration::ZERO);
+        service.provider_health().await;
+        assert_eq!(checks.load(std::sync::atomic::Ordering::SeqCst), 2);
     }
 }
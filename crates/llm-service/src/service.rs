@@ -0,0 +1,559 @@
+use crate::coalescer::{CoalescedResponse, RequestCoalescer};
+use crate::provider::{truncate_to_budget, LLMRequest, LLMService};
+use crate::usage_tracker::UsageTracker;
+use ai_manager_shared::{
+    errors::SystemError,
+    messages::{ServiceHealth, ServiceMessage},
+    LLM_SERVICE_ID,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn, Instrument};
+use uuid::Uuid;
+
+#[async_trait]
+pub trait Service {
+    async fn start(&mut self, mut rx: mpsc::Receiver<ServiceMessage>) -> Result<(), SystemError>;
+    async fn handle_message(&mut self, msg: ServiceMessage) -> Result<(), SystemError>;
+    async fn health_check(&self) -> ServiceHealth;
+    async fn shutdown(&mut self) -> Result<(), SystemError>;
+}
+
+/// Runs an `LLMService` as the LLM service: answers `LLMRequest`s with
+/// `LLMResponse`s sent on `tx` and records their token usage. Requests are
+/// answered concurrently; identical ones from a user in flight at the same
+/// time share one provider request. A `CancelLLMRequest` abandons the
+/// request it names.
+#[derive(Clone)]
+pub struct LlmServiceRunner {
+    llm: Arc<LLMService>,
+    usage_tracker: Arc<UsageTracker>,
+    coalescer: Arc<RequestCoalescer>,
+    shutdown: CancellationToken,
+    /// Requests being answered, waited for on shutdown
+    tasks: TaskTracker,
+    /// Tokens that cancel the requests being answered, by request id
+    cancellations: Arc<Mutex<HashMap<Uuid, CancellationToken>>>,
+    tx: Option<mpsc::Sender<ServiceMessage>>,
+}
+
+impl LlmServiceRunner {
+    pub fn new(llm: LLMService, tx: mpsc::Sender<ServiceMessage>) -> Self {
+        Self {
+            llm: Arc::new(llm),
+            usage_tracker: Arc::new(UsageTracker::new()),
+            coalescer: Arc::new(RequestCoalescer::new()),
+            shutdown: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+            cancellations: Arc::new(Mutex::new(HashMap::new())),
+            tx: Some(tx),
+        }
+    }
+
+    /// Stop taking requests and exit once those received are answered when
+    /// `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Record usage in a tracker shared with other components
+    pub fn with_usage_tracker(mut self, usage_tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = usage_tracker;
+        self
+    }
+
+    pub fn usage_tracker(&self) -> Arc<UsageTracker> {
+        self.usage_tracker.clone()
+    }
+
+    async fn handle_llm_request(
+        &self,
+        mut request: LLMRequest,
+        provider: String,
+        request_id: Uuid,
+        user_id: String,
+        stream: bool,
+        cancel: CancellationToken,
+    ) -> Result<(), SystemError> {
+        // Unknown providers fall back to the configured default
+        let provider = if self.llm.get_providers().contains(&provider) {
+            provider
+        } else {
+            self.llm.get_default_provider().to_string()
+        };
+        debug!("LLM request {} using provider '{}'", request_id, provider);
+
+        let dropped = truncate_to_budget(&mut request);
+        if dropped > 0 {
+            debug!("Dropped {} context messages to fit the prompt", dropped);
+        }
+
+        let coalesced = self.coalescer.coalesce(&user_id, &provider, &request, || {
+            self.llm
+                .send_request_cancellable(request.clone(), &provider, cancel.clone())
+        });
+        // Also stops waiting on an identical request this one was merged into
+        let result = tokio::select! {
+            result = coalesced => result,
+            _ = cancel.cancelled() => Err(SystemError::Cancelled),
+        };
+
+        let reply = match result {
+            Ok(CoalescedResponse { response, shared }) => {
+                // A shared response was paid for by the request it came from
+                if !shared {
+                    self.usage_tracker
+                        .record_usage(
+                            &user_id,
+                            &response.provider,
+                            &response.model,
+                            &response.usage,
+                        )
+                        .await;
+                }
+                if stream {
+                    // Providers answer in one piece, so it streams as one chunk
+                    let chunks = [
+                        (response.content.clone(), false, None),
+                        (String::new(), true, Some(response.usage.clone())),
+                    ];
+                    for (delta, done, usage) in chunks {
+                        let chunk = ServiceMessage::LLMResponseChunk {
+                            request_id,
+                            delta,
+                            done,
+                            usage,
+                        };
+                        self.send(chunk).await?;
+                    }
+                }
+                ServiceMessage::LLMResponse {
+                    content: response.content,
+                    model: Some(response.model),
+                    usage: response.usage,
+                    request_id,
+                    user_id,
+                    streamed: stream,
+                }
+            }
+            Err(SystemError::Cancelled) => {
+                info!("LLM request {} cancelled", request_id);
+                ServiceMessage::error_reply(&SystemError::Cancelled, Some(request_id))
+            }
+            Err(e) => {
+                // Retries already happened in `LLMService`; tell the user
+                error!("LLM request {} failed: {}", request_id, e);
+                ServiceMessage::error_reply(&e, Some(request_id))
+            }
+        };
+
+        self.send(reply).await
+    }
+
+    fn lock_cancellations(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, CancellationToken>> {
+        self.cancellations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    async fn process(&mut self, message: ServiceMessage) {
+        let span = message.span();
+        if let Err(e) = self.handle_message(message).instrument(span.clone()).await {
+            span.in_scope(|| error!("Error handling message: {}", e));
+        }
+    }
+
+    async fn send(&self, message: ServiceMessage) -> Result<(), SystemError> {
+        if let Some(tx) = &self.tx {
+            tx.send(message).await.map_err(|e| {
+                SystemError::ServiceCommunication(format!("Failed to send LLM reply: {}", e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Service for LlmServiceRunner {
+    async fn start(&mut self, mut rx: mpsc::Receiver<ServiceMessage>) -> Result<(), SystemError> {
+        info!("LLM Service starting...");
+        // Warm up provider health so the first poll is answered from cache
+        let health_refresh = self.llm.spawn_health_refresh(self.llm.health_ttl());
+
+        loop {
+            let message = tokio::select! {
+                message = rx.recv() => message,
+                _ = self.shutdown.cancelled() => break,
+            };
+            let Some(message) = message else {
+                warn!("LLM Service message receiver closed");
+                health_refresh.abort();
+                return Ok(());
+            };
+            self.process(message).await;
+        }
+
+        // Take no more requests, but answer the ones already received
+        rx.close();
+        while let Some(message) = rx.recv().await {
+            self.process(message).await;
+        }
+        self.tasks.close();
+        self.tasks.wait().await;
+        health_refresh.abort();
+        self.shutdown().await
+    }
+
+    async fn handle_message(&mut self, msg: ServiceMessage) -> Result<(), SystemError> {
+        match msg {
+            ServiceMessage::LLMRequest {
+                prompt,
+                context,
+                provider,
+                request_id,
+                user_id,
+                model,
+                temperature,
+                stream,
+            } => {
+                let request = LLMRequest {
+                    prompt,
+                    context,
+                    // Empty means the provider's configured model
+                    model: model.unwrap_or_default(),
+                    max_tokens: None,
+                    temperature,
+                    stop_sequences: None,
+                    stream: false,
+                    response_format: None,
+                    images: vec![],
+                };
+                let cancel = CancellationToken::new();
+                self.lock_cancellations().insert(request_id, cancel.clone());
+
+                // Answered in its own task so identical requests can overlap
+                let runner = self.clone();
+                self.tasks.spawn(
+                    async move {
+                        if let Err(e) = runner
+                            .handle_llm_request(
+                                request, provider, request_id, user_id, stream, cancel,
+                            )
+                            .await
+                        {
+                            error!("Failed to answer LLM request {}: {}", request_id, e);
+                        }
+                        runner.lock_cancellations().remove(&request_id);
+                    }
+                    .in_current_span(),
+                );
+                Ok(())
+            }
+            ServiceMessage::CancelLLMRequest { request_id } => {
+                match self.lock_cancellations().remove(&request_id) {
+                    Some(cancel) => cancel.cancel(),
+                    None => debug!("LLM request {} already finished", request_id),
+                }
+                Ok(())
+            }
+            ServiceMessage::ServiceHealthCheck { service_id: _ } => {
+                let response = ServiceMessage::ServiceHealthResponse {
+                    service_id: LLM_SERVICE_ID.to_string(),
+                    status: self.health_check().await,
+                };
+                self.send(response).await
+            }
+            _ => {
+                warn!("LLM Service received unhandled message: {:?}", msg);
+                Ok(())
+            }
+        }
+    }
+
+    async fn health_check(&self) -> ServiceHealth {
+        let results = self.llm.provider_health().await;
+        if results.is_empty() {
+            return ServiceHealth::Unhealthy {
+                error: "No LLM providers configured".to_string(),
+            };
+        }
+
+        let failed: Vec<String> = results
+            .iter()
+            .filter_map(|(name, status)| match status {
+                ServiceHealth::Healthy => None,
+                ServiceHealth::Degraded { reason } => Some(format!("{}: {}", name, reason)),
+                ServiceHealth::Unhealthy { error } => Some(format!("{}: {}", name, error)),
+            })
+            .collect();
+
+        if failed.is_empty() {
+            ServiceHealth::Healthy
+        } else if failed.len() < results.len() {
+            ServiceHealth::Degraded {
+                reason: failed.join("; "),
+            }
+        } else {
+            ServiceHealth::Unhealthy {
+                error: failed.join("; "),
+            }
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<(), SystemError> {
+        info!("LLM Service shutting down...");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{FinishReason, LLMProvider, LLMResponse};
+    use ai_manager_shared::{ErrorCode, Result, TokenUsage};
+
+    struct EchoProvider;
+
+    #[async_trait]
+    impl LLMProvider for EchoProvider {
+        async fn send_request(&self, request: LLMRequest) -> Result<LLMResponse> {
+            match request.prompt.as_str() {
+                "fail" => return Err(SystemError::Authentication("bad key".to_string())),
+                "hang" => std::future::pending().await,
+                _ => {}
+            }
+            Ok(LLMResponse {
+                content: format!("echo: {}", request.prompt),
+                model: if request.model.is_empty() {
+                    "echo-1".to_string()
+                } else {
+                    request.model
+                },
+                usage: TokenUsage {
+                    prompt_tokens: 4,
+                    completion_tokens: 2,
+                    total_tokens: 6,
+                },
+                finish_reason: FinishReason::Stop,
+                provider: "echo".to_string(),
+            })
+        }
+
+        async fn get_usage(&self) -> TokenUsage {
+            TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }
+        }
+
+        fn provider_name(&self) -> &str {
+            "echo"
+        }
+
+        async fn health_check(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn runner() -> (LlmServiceRunner, mpsc::Receiver<ServiceMessage>) {
+        let mut llm = LLMService::new();
+        llm.add_provider("echo".to_string(), Box::new(EchoProvider));
+        llm.set_default_provider("echo".to_string()).unwrap();
+
+        let (tx, rx) = mpsc::channel(10);
+        (LlmServiceRunner::new(llm, tx), rx)
+    }
+
+    fn llm_request(prompt: &str, request_id: Uuid) -> ServiceMessage {
+        ServiceMessage::LLMRequest {
+            prompt: prompt.to_string(),
+            context: vec![],
+            provider: "openai".to_string(),
+            request_id,
+            user_id: "alice".to_string(),
+            model: None,
+            temperature: None,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_answered_and_usage_recorded() {
+        let (mut runner, mut rx) = runner();
+        let request_id = Uuid::new_v4();
+
+        runner
+            .handle_message(llm_request("hi", request_id))
+            .await
+            .unwrap();
+
+        match rx.recv().await.unwrap() {
+            ServiceMessage::LLMResponse {
+                content,
+                request_id: response_id,
+                user_id,
+                ..
+            } => {
+                assert_eq!(content, "echo: hi");
+                assert_eq!(response_id, request_id);
+                assert_eq!(user_id, "alice");
+            }
+            other => panic!("expected an LLMResponse, got {:?}", other),
+        }
+
+        let stats = runner.usage_tracker().get_stats().await;
+        assert_eq!(stats.total_requests, 1);
+        assert_eq!(stats.total_tokens, 6);
+    }
+
+    #[tokio::test]
+    async fn test_stream_request_sent_as_chunks() {
+        let (mut runner, mut rx) = runner();
+        let request_id = Uuid::new_v4();
+        let mut request = llm_request("hi", request_id);
+        if let ServiceMessage::LLMRequest { stream, .. } = &mut request {
+            *stream = true;
+        }
+
+        runner.handle_message(request).await.unwrap();
+
+        let mut streamed = String::new();
+        loop {
+            match rx.recv().await.unwrap() {
+                ServiceMessage::LLMResponseChunk {
+                    request_id: chunk_id,
+                    delta,
+                    done,
+                    usage,
+                } => {
+                    assert_eq!(chunk_id, request_id);
+                    streamed.push_str(&delta);
+                    if done {
+                        assert_eq!(usage.unwrap().total_tokens, 6);
+                        break;
+                    }
+                }
+                other => panic!("expected a stream chunk, got {:?}", other),
+            }
+        }
+        assert_eq!(streamed, "echo: hi");
+
+        // The whole response follows, for the core to store
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            ServiceMessage::LLMResponse { content, streamed: true, .. } if content == "echo: hi"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_model_overrides_provider_model() {
+        let (mut runner, mut rx) = runner();
+        let mut request = llm_request("hi", Uuid::new_v4());
+        if let ServiceMessage::LLMRequest { model, .. } = &mut request {
+            *model = Some("echo-mini".to_string());
+        }
+
+        runner.handle_message(request).await.unwrap();
+        rx.recv().await.unwrap();
+
+        let stats = runner.usage_tracker().get_stats().await;
+        assert!(stats.by_model.contains_key("echo-mini"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_request_reported_by_code() {
+        let (mut runner, mut rx) = runner();
+        let request_id = Uuid::new_v4();
+
+        runner
+            .handle_message(llm_request("fail", request_id))
+            .await
+            .unwrap();
+
+        match rx.recv().await.unwrap() {
+            ServiceMessage::SystemError {
+                code,
+                request_id: response_id,
+                ..
+            } => {
+                assert_eq!(code, ErrorCode::AuthFailed);
+                assert_eq!(response_id, Some(request_id));
+            }
+            other => panic!("expected a SystemError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_answered_with_error() {
+        let (mut runner, mut rx) = runner();
+        let request_id = Uuid::new_v4();
+
+        runner
+            .handle_message(llm_request("hang", request_id))
+            .await
+            .unwrap();
+        runner
+            .handle_message(ServiceMessage::CancelLLMRequest { request_id })
+            .await
+            .unwrap();
+
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(1), rx.recv())
+            .await
+            .expect("cancelling should answer the request")
+            .unwrap();
+        assert!(matches!(
+            reply,
+            ServiceMessage::SystemError { request_id: Some(id), .. } if id == request_id
+        ));
+        assert_eq!(runner.usage_tracker().get_stats().await.total_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_health_requires_a_provider() {
+        let (tx, _rx) = mpsc::channel(10);
+        let runner = LlmServiceRunner::new(LLMService::new(), tx);
+        assert!(matches!(
+            runner.health_check().await,
+            ServiceHealth::Unhealthy { .. }
+        ));
+
+        let (runner, _rx) = self::runner();
+        assert!(matches!(
+            runner.health_check().await,
+            ServiceHealth::Healthy
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_received_requests_answered_on_shutdown() {
+        let (runner, mut replies) = runner();
+        let shutdown = CancellationToken::new();
+        let mut runner = runner.with_shutdown(shutdown.clone());
+
+        let (inbound_tx, inbound_rx) = mpsc::channel(10);
+        for prompt in ["one", "two"] {
+            inbound_tx
+                .send(llm_request(prompt, Uuid::new_v4()))
+                .await
+                .unwrap();
+        }
+
+        shutdown.cancel();
+        runner.start(inbound_rx).await.unwrap();
+
+        let mut answered = Vec::new();
+        while let Ok(reply) = replies.try_recv() {
+            if let ServiceMessage::LLMResponse { content, .. } = reply {
+                answered.push(content);
+            }
+        }
+        answered.sort();
+        assert_eq!(answered, vec!["echo: one", "echo: two"]);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::provider::LLMRequest;
+use ai_manager_shared::{Result, SystemError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use tiktoken_rs::CoreBPE;
+
+/// Context windows of OpenAI models by model prefix; the first match wins,
+/// so more specific prefixes come first
+const OPENAI_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-vision", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo-instruct", 4_096),
+    ("gpt-3.5-turbo", 16_385),
+];
+
+/// Context windows of Claude models by model prefix
+const CLAUDE_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("claude-2.0", 100_000),
+    ("claude-2.1", 200_000),
+    ("claude-instant", 100_000),
+    ("claude-", 200_000),
+];
+
+/// Average characters per token used when no tokenizer is known
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimate how many tokens `text` is for `model`. OpenAI models are counted
+/// exactly with their tiktoken encoding; other models fall back to roughly
+/// four characters per token.
+pub fn token_count(text: &str, model: &str) -> usize {
+    match bpe_for_model(model) {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => text.chars().count().div_ceil(CHARS_PER_TOKEN),
+    }
+}
+
+/// Maximum tokens (prompt and completion) `model` accepts on `provider`, or
+/// `None` when unknown, e.g. for OpenAI-compatible third-party providers
+pub fn model_context_window(provider: &str, model: &str) -> Option<usize> {
+    let windows = match provider {
+        "openai" | "azure" => OPENAI_CONTEXT_WINDOWS,
+        "claude" => CLAUDE_CONTEXT_WINDOWS,
+        _ => return None,
+    };
+    windows
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+impl LLMRequest {
+    /// Estimated tokens in the prompt and context
+    pub fn estimated_prompt_tokens(&self) -> usize {
+        token_count(&self.prompt, &self.model)
+            + self
+                .context
+                .iter()
+                .map(|message| token_count(message, &self.model))
+                .sum::<usize>()
+    }
+
+    /// Check that the prompt, context and `max_tokens` fit the model's
+    /// context window on `provider`. Models with an unknown window pass.
+    pub fn check_context_window(&self, provider: &str) -> Result<()> {
+        let Some(window) = model_context_window(provider, &self.model) else {
+            return Ok(());
+        };
+        let prompt_tokens = self.estimated_prompt_tokens();
+        let completion_tokens = self.max_tokens.unwrap_or(0) as usize;
+
+        if prompt_tokens + completion_tokens > window {
+            return Err(window_exceeded(
+                prompt_tokens,
+                completion_tokens,
+                window,
+                &self.model,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Drop context messages, oldest first, until the prompt, context and
+    /// `completion_tokens` fit `model`'s context window on `provider`.
+    /// Returns how many were dropped, or `InvalidInput` when the prompt
+    /// alone is too long. Models with an unknown window are left as is.
+    pub fn fit_context_window(
+        &mut self,
+        provider: &str,
+        model: &str,
+        completion_tokens: u32,
+    ) -> Result<usize> {
+        let Some(window) = model_context_window(provider, model) else {
+            return Ok(0);
+        };
+        let completion_tokens = completion_tokens as usize;
+        let context_tokens: Vec<usize> = self
+            .context
+            .iter()
+            .map(|message| token_count(message, model))
+            .collect();
+        let mut prompt_tokens =
+            token_count(&self.prompt, model) + context_tokens.iter().sum::<usize>();
+
+        let mut dropped = 0;
+        while prompt_tokens + completion_tokens > window && dropped < context_tokens.len() {
+            prompt_tokens -= context_tokens[dropped];
+            dropped += 1;
+        }
+        if prompt_tokens + completion_tokens > window {
+            return Err(window_exceeded(
+                prompt_tokens,
+                completion_tokens,
+                window,
+                model,
+            ));
+        }
+
+        self.context.drain(..dropped);
+        Ok(dropped)
+    }
+}
+
+fn window_exceeded(
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    window: usize,
+    model: &str,
+) -> SystemError {
+    SystemError::InvalidInput(format!(
+        "Request needs about {} prompt + {} completion tokens, over the {} token context window of '{}'",
+        prompt_tokens, completion_tokens, window, model
+    ))
+}
+
+/// Tokenizer for an OpenAI model, loaded once per model
+fn bpe_for_model(model: &str) -> Option<Arc<CoreBPE>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Option<Arc<CoreBPE>>>>> = OnceLock::new();
+
+    let mut cache = CACHE
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    cache
+        .entry(model.to_string())
+        .or_insert_with(|| tiktoken_rs::get_bpe_from_model(model).ok().map(Arc::new))
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(model: &str, prompt: &str, max_tokens: Option<u32>) -> LLMRequest {
+        LLMRequest {
+            prompt: prompt.to_string(),
+            context: vec![],
+            model: model.to_string(),
+            max_tokens,
+            temperature: None,
+            stop_sequences: None,
+            stream: false,
+            response_format: None,
+            images: vec![],
+        }
+    }
+
+    #[test]
+    fn test_token_count() {
+        assert_eq!(token_count("hello world", "gpt-4"), 2);
+        // Unknown models use the character heuristic
+        assert_eq!(token_count("hello world", "claude-3-haiku-20240307"), 3);
+        assert_eq!(token_count("", "claude-3-haiku-20240307"), 0);
+    }
+
+    #[test]
+    fn test_model_context_window() {
+        assert_eq!(model_context_window("openai", "gpt-4"), Some(8_192));
+        assert_eq!(model_context_window("azure", "gpt-4-turbo"), Some(128_000));
+        assert_eq!(
+            model_context_window("openai", "gpt-3.5-turbo"),
+            Some(16_385)
+        );
+        assert_eq!(
+            model_context_window("claude", "claude-3-haiku-20240307"),
+            Some(200_000)
+        );
+        assert_eq!(model_context_window("claude", "claude-2.0"), Some(100_000));
+        assert_eq!(model_context_window("groq", "llama3-8b-8192"), None);
+    }
+
+    #[test]
+    fn test_check_context_window() {
+        let fits = request("gpt-4", "Summarize my week", Some(1000));
+        assert!(fits.check_context_window("openai").is_ok());
+
+        let too_long = request("gpt-4", "Summarize my week", Some(10_000));
+        let err = too_long
+            .check_context_window("openai")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("8192"));
+
+        let claude = request("claude-3-haiku-20240307", "Summarize my week", Some(10_000));
+        assert!(claude.check_context_window("claude").is_ok());
+    }
+
+    #[test]
+    fn test_fit_context_window_drops_oldest_context() {
+        let mut request = request("gpt-4", "Summarize my week", None);
+        request.context = vec!["old ".repeat(8000), "recent".to_string()];
+
+        let dropped = request.fit_context_window("openai", "gpt-4", 1000).unwrap();
+        assert_eq!(dropped, 1);
+        assert_eq!(request.context, vec!["recent".to_string()]);
+
+        // The prompt itself is never dropped
+        request.prompt = "word ".repeat(9000);
+        let err = request
+            .fit_context_window("openai", "gpt-4", 1000)
+            .unwrap_err();
+        assert!(matches!(err, SystemError::InvalidInput(_)));
+        assert_eq!(request.context.len(), 1);
+
+        // Unknown windows are not enforced
+        let dropped = request
+            .fit_context_window("groq", "llama3-8b-8192", 1000)
+            .unwrap();
+        assert_eq!(dropped, 0);
+    }
+}
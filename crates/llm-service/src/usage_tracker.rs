@@ -1,4 +1,4 @@
-use ai_manager_shared::TokenUsage;
+use ai_manager_shared::{Clock, SystemClock, TokenUsage};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -8,6 +8,9 @@ use tokio::sync::RwLock;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsageRecord {
     pub timestamp: DateTime<Utc>,
+    /// Empty for records exported before usage was tracked per user
+    #[serde(default)]
+    pub user_id: String,
     pub provider: String,
     pub model: String,
     pub prompt_tokens: u32,
@@ -44,6 +47,7 @@ pub struct ModelStats {
 pub struct UsageTracker {
     records: Arc<RwLock<Vec<UsageRecord>>>,
     pricing: Arc<RwLock<HashMap<String, PricingInfo>>>,
+    clock: Arc<dyn Clock>,
 }
 
 #[derive(Debug, Clone)]
@@ -57,6 +61,7 @@ impl UsageTracker {
         let mut tracker = Self {
             records: Arc::new(RwLock::new(Vec::new())),
             pricing: Arc::new(RwLock::new(HashMap::new())),
+            clock: Arc::new(SystemClock),
         };
 
         // Set up default pricing (as of 2024 - these should be updated regularly)
@@ -64,12 +69,25 @@ impl UsageTracker {
         tracker
     }
 
-    /// Record usage for a request
-    pub async fn record_usage(&self, provider: &str, model: &str, usage: &TokenUsage) {
+    /// Timestamp records with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record usage for a request made on behalf of `user_id`
+    pub async fn record_usage(
+        &self,
+        user_id: &str,
+        provider: &str,
+        model: &str,
+        usage: &TokenUsage,
+    ) {
         let cost_estimate = self.calculate_cost(provider, model, usage).await;
 
         let record = UsageRecord {
-            timestamp: Utc::now(),
+            timestamp: self.clock.now(),
+            user_id: user_id.to_string(),
             provider: provider.to_string(),
             model: model.to_string(),
             prompt_tokens: usage.prompt_tokens,
@@ -106,7 +124,19 @@ impl UsageTracker {
     /// Get usage statistics
     pub async fn get_stats(&self) -> UsageStats {
         let records = self.records.read().await;
+        Self::summarize(records.iter())
+    }
 
+    /// Usage statistics for one user, counting only requests made at or
+    /// after `since` if given
+    pub async fn get_user_stats(&self, user_id: &str, since: Option<DateTime<Utc>>) -> UsageStats {
+        let records = self.records.read().await;
+        Self::summarize(records.iter().filter(|record| {
+            record.user_id == user_id && since.is_none_or(|since| record.timestamp >= since)
+        }))
+    }
+
+    fn summarize<'a>(records: impl Iterator<Item = &'a UsageRecord>) -> UsageStats {
         let mut stats = UsageStats {
             total_requests: 0,
             total_tokens: 0,
@@ -115,7 +145,7 @@ impl UsageTracker {
             by_model: HashMap::new(),
         };
 
-        for record in records.iter() {
+        for record in records {
             stats.total_requests += 1;
             stats.total_tokens += record.total_tokens as u64;
             if let Some(cost) = record.cost_estimate {
@@ -209,6 +239,15 @@ impl UsageTracker {
         serde_json::to_string_pretty(&*records)
     }
 
+    /// Append records previously written by `export_json`, returning how
+    /// many were added
+    pub async fn import_json(&self, json: &str) -> serde_json::Result<usize> {
+        let imported: Vec<UsageRecord> = serde_json::from_str(json)?;
+        let count = imported.len();
+        self.records.write().await.extend(imported);
+        Ok(count)
+    }
+
     /// Add default pricing information
     fn add_default_pricing(&mut self) {
         // Note: These prices are estimates and should be updated regularly
@@ -282,6 +321,7 @@ impl Default for UsageTracker {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ai_manager_shared::MockClock;
     use tokio::time::{sleep, Duration};
 
     #[tokio::test]
@@ -296,7 +336,7 @@ mod tests {
         };
 
         tracker
-            .record_usage("openai", "gpt-3.5-turbo", &usage1)
+            .record_usage("alice", "openai", "gpt-3.5-turbo", &usage1)
             .await;
 
         let usage2 = TokenUsage {
@@ -306,7 +346,7 @@ mod tests {
         };
 
         tracker
-            .record_usage("claude", "claude-3-haiku-20240307", &usage2)
+            .record_usage("alice", "claude", "claude-3-haiku-20240307", &usage2)
             .await;
 
         // Wait a bit for async operations
@@ -351,7 +391,7 @@ mod tests {
         };
 
         tracker
-            .record_usage("openai", "gpt-3.5-turbo", &usage)
+            .record_usage("alice", "openai", "gpt-3.5-turbo", &usage)
             .await;
 
         let recent = tracker.get_recent_records(10).await;
@@ -362,4 +402,57 @@ mod tests {
         let in_range = tracker.get_records_in_range(one_hour_ago, now).await;
         assert_eq!(in_range.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_user_stats() {
+        let start = "2024-03-01T09:00:00Z".parse().unwrap();
+        let clock = Arc::new(MockClock::new(start));
+        let tracker = UsageTracker::new().with_clock(clock.clone());
+        let usage = TokenUsage {
+            prompt_tokens: 100,
+            completion_tokens: 50,
+            total_tokens: 150,
+        };
+        tracker
+            .record_usage("alice", "openai", "gpt-3.5-turbo", &usage)
+            .await;
+        clock.advance(chrono::Duration::hours(1));
+        tracker
+            .record_usage("alice", "openai", "gpt-4", &usage)
+            .await;
+        tracker.record_usage("bob", "openai", "gpt-4", &usage).await;
+
+        let stats = tracker.get_user_stats("alice", None).await;
+        assert_eq!(stats.total_requests, 2);
+        assert_eq!(stats.total_tokens, 300);
+        assert_eq!(stats.by_model.len(), 2);
+
+        let stats = tracker.get_user_stats("alice", Some(clock.now())).await;
+        assert_eq!(stats.total_requests, 1);
+        let stats = tracker.get_user_stats("bob", Some(clock.now())).await;
+        assert_eq!(stats.total_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let tracker = UsageTracker::new();
+        let usage = TokenUsage {
+            prompt_tokens: 100,
+            completion_tokens: 50,
+            total_tokens: 150,
+        };
+        tracker
+            .record_usage("alice", "openai", "gpt-3.5-turbo", &usage)
+            .await;
+
+        let exported = tracker.export_json().await.unwrap();
+
+        let restored = UsageTracker::new();
+        assert_eq!(restored.import_json(&exported).await.unwrap(), 1);
+
+        let stats = restored.get_stats().await;
+        assert_eq!(stats.total_requests, 1);
+        assert_eq!(stats.total_tokens, 150);
+        assert!(restored.import_json("not json").await.is_err());
+    }
 }
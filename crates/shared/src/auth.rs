@@ -0,0 +1,140 @@
+use crate::constants::SERVICE_AUTH_TIMEOUT_SECONDS;
+use crate::errors::{Result, SystemError};
+use crate::messages::ServiceMessage;
+use crate::transport::MessageTransport;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Compare a secret in time that doesn't depend on where the inputs differ
+pub fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Proof that `service_id` holds `token`, bound to the peer's `nonce`
+fn proof(token: &str, nonce: Uuid, service_id: &str) -> String {
+    format!(
+        "{:x}",
+        Sha256::digest(format!("{}:{}:{}", token, nonce, service_id))
+    )
+}
+
+/// Show the peer on a newly connected `transport` that this end, as
+/// `service_id`, holds `token`, and check that the peer holds it too. The
+/// token itself is never sent. Returns the peer's service id.
+pub async fn authenticate(
+    transport: &mut dyn MessageTransport,
+    service_id: &str,
+    token: &str,
+) -> Result<String> {
+    let timeout = Duration::from_secs(SERVICE_AUTH_TIMEOUT_SECONDS);
+    tokio::time::timeout(timeout, handshake(transport, service_id, token))
+        .await
+        .map_err(|_| SystemError::Authentication("Peer did not authenticate in time".to_string()))?
+}
+
+async fn handshake(
+    transport: &mut dyn MessageTransport,
+    service_id: &str,
+    token: &str,
+) -> Result<String> {
+    let nonce = Uuid::new_v4();
+    transport
+        .send(ServiceMessage::AuthChallenge {
+            service_id: service_id.to_string(),
+            nonce,
+        })
+        .await?;
+
+    let (peer_id, peer_nonce) = match transport.recv().await? {
+        Some(ServiceMessage::AuthChallenge { service_id, nonce }) => (service_id, nonce),
+        other => return Err(unexpected(other)),
+    };
+    transport
+        .send(ServiceMessage::AuthProof {
+            proof: proof(token, peer_nonce, service_id),
+        })
+        .await?;
+
+    match transport.recv().await? {
+        Some(ServiceMessage::AuthProof { proof: given })
+            if tokens_match(&proof(token, nonce, &peer_id), &given) =>
+        {
+            Ok(peer_id)
+        }
+        Some(ServiceMessage::AuthProof { .. }) => Err(SystemError::Authentication(format!(
+            "Service '{}' does not hold the service token",
+            peer_id
+        ))),
+        other => Err(unexpected(other)),
+    }
+}
+
+fn unexpected(message: Option<ServiceMessage>) -> SystemError {
+    match message {
+        Some(message) => SystemError::Authentication(format!(
+            "Expected authentication from peer, got {}",
+            message.message_type()
+        )),
+        None => SystemError::Authentication(
+            "Peer closed the connection before authenticating".to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::ChannelTransport;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secret2"));
+        assert!(!tokens_match("secret", ""));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_shared_token() {
+        let (mut core, mut data) = ChannelTransport::pair(4);
+
+        let (core_peer, data_peer) = tokio::join!(
+            authenticate(&mut core, "core", "secret"),
+            authenticate(&mut data, "data", "secret"),
+        );
+        assert_eq!(core_peer.unwrap(), "data");
+        assert_eq!(data_peer.unwrap(), "core");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_wrong_token_fails_both_ways() {
+        let (mut core, mut data) = ChannelTransport::pair(4);
+
+        let (core_peer, data_peer) = tokio::join!(
+            authenticate(&mut core, "core", "secret"),
+            authenticate(&mut data, "data", "guess"),
+        );
+        assert!(matches!(core_peer, Err(SystemError::Authentication(_))));
+        assert!(matches!(data_peer, Err(SystemError::Authentication(_))));
+    }
+
+    #[tokio::test]
+    async fn test_messages_before_authentication_are_rejected() {
+        let (mut core, mut intruder) = ChannelTransport::pair(4);
+        intruder
+            .send(ServiceMessage::ClearConversation {
+                user_id: "alice".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let err = authenticate(&mut core, "core", "secret").await.unwrap_err();
+        assert!(err.to_string().contains("ClearConversation"));
+    }
+}
@@ -4,6 +4,8 @@ pub const LLM_SERVICE_ID: &str = "llm";
 pub const DATA_SERVICE_ID: &str = "data";
 pub const EXTERNAL_SERVICE_ID: &str = "external";
 pub const UI_SERVICE_ID: &str = "ui";
+/// Service id used in messages broadcast to every service
+pub const ALL_SERVICES_ID: &str = "*";
 
 // Configuration file paths
 pub const DEFAULT_CONFIG_PATH: &str = "config/default.toml";
@@ -11,12 +13,35 @@ pub const USER_CONFIG_PATH: &str = "config/user.toml";
 
 // Database constants
 pub const DEFAULT_SQLITE_PATH: &str = "data/ai_manager.db";
+pub const MIGRATIONS_DIR: &str = "migrations";
+pub const DEFAULT_DB_MAX_CONNECTIONS: u32 = 10;
+pub const DATABASE_CONNECT_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_QUERY_TIMEOUT_SECONDS: u64 = 30;
+/// Queries slower than this are logged at `warn`
+pub const SLOW_QUERY_THRESHOLD_MS: u64 = 500;
 pub const MAX_MESSAGE_HISTORY: usize = 1000;
+pub const CONVERSATION_CONTEXT_MESSAGES: usize = 10;
+pub const CONTEXT_LOAD_TIMEOUT_SECONDS: u64 = 5;
+/// How long input moderation may hold up the core's message loop; kept
+/// below `HEALTH_CHECK_TIMEOUT_SECONDS` so health checks still get answered
+pub const MODERATION_TIMEOUT_SECONDS: u64 = 2;
 pub const CONVERSATION_CLEANUP_INTERVAL_HOURS: u64 = 24;
+/// Usage records kept in memory by the periodic cleanup
+pub const MAX_USAGE_RECORDS: usize = 100_000;
+pub const SUMMARIZATION_THRESHOLD_MESSAGES: usize = 40;
+/// How long messages dropped by regenerating a reply stay recoverable
+pub const DISCARDED_BRANCH_RETAIN_HOURS: u32 = 24;
+pub const DEFAULT_EXPORT_DIR: &str = "exports";
+/// Larger `/export` results are written to a file instead of inlined
+pub const EXPORT_INLINE_MAX_BYTES: usize = 64 * 1024;
 
 // LLM provider constants
 pub const DEFAULT_LLM_PROVIDER: &str = "openai";
+pub const AZURE_OPENAI_API_VERSION: &str = "2024-02-01";
 pub const MAX_PROMPT_LENGTH: usize = 32000;
+/// Messages per minute each user may send to the assistant
+pub const USER_REQUESTS_PER_MINUTE: u32 = 20;
+pub const USER_REQUEST_BURST: u32 = 5;
 pub const DEFAULT_MAX_TOKENS: u32 = 2000;
 pub const DEFAULT_TEMPERATURE: f32 = 0.7;
 
@@ -25,6 +50,20 @@ pub const DEFAULT_REQUEST_TIMEOUT: u64 = 30;
 pub const LLM_REQUEST_TIMEOUT: u64 = 60;
 pub const CALENDAR_REQUEST_TIMEOUT: u64 = 30;
 pub const EMAIL_REQUEST_TIMEOUT: u64 = 30;
+pub const NOTIFICATION_REQUEST_TIMEOUT: u64 = 10;
+pub const HTTP_CONNECT_TIMEOUT: u64 = 10;
+// Idle connections kept per host, and for how long (in seconds)
+pub const HTTP_POOL_MAX_IDLE_PER_HOST: usize = 8;
+pub const HTTP_POOL_IDLE_TIMEOUT: u64 = 90;
+
+// Number of emails in a batch processed at once
+pub const DEFAULT_EMAIL_CONCURRENCY: usize = 4;
+// How often low-priority mail is summarized into a digest
+pub const DEFAULT_EMAIL_DIGEST_INTERVAL_HOURS: u64 = 24;
+/// Auto-replied threads remembered before the oldest are forgotten
+pub const MAX_REPLIED_THREADS: usize = 10_000;
+/// How long mail is held back at startup waiting for the auto-replied threads
+pub const REPLIED_THREADS_LOAD_TIMEOUT_SECONDS: u64 = 10;
 
 // Retry configuration
 pub const MAX_RETRY_ATTEMPTS: u32 = 3;
@@ -33,7 +72,34 @@ pub const BACKOFF_MULTIPLIER: f64 = 2.0;
 
 // Health check intervals
 pub const HEALTH_CHECK_INTERVAL_SECONDS: u64 = 30;
+pub const HEALTH_CHECK_TIMEOUT_SECONDS: u64 = 5;
+/// How long an LLM provider's health check result is reused
+pub const PROVIDER_HEALTH_TTL_SECONDS: u64 = 300;
 pub const SERVICE_RESTART_COOLDOWN_SECONDS: u64 = 5;
+pub const SERVICE_SHUTDOWN_GRACE_SECONDS: u64 = 10;
+/// How long a remote service has to complete the authentication handshake
+pub const SERVICE_AUTH_TIMEOUT_SECONDS: u64 = 10;
+
+// Health thresholds
+pub const HEALTH_MAX_ERROR_COUNT: u64 = 10;
+pub const HEALTH_MAX_MEMORY_MB: f64 = 500.0;
+pub const HEALTH_MAX_CPU_PERCENT: f64 = 80.0;
+pub const HEALTH_MAX_QUEUE_LENGTH: usize = 100;
+
+// Health and metrics HTTP endpoint
+pub const DEFAULT_HTTP_HOST: &str = "127.0.0.1";
+pub const DEFAULT_HEALTH_PORT: u16 = 9090;
+pub const DEFAULT_API_PORT: u16 = 8080;
+
+// Services running as separate processes
+pub const DEFAULT_DATA_SERVICE_ADDR: &str = "127.0.0.1:9101";
+/// Major version of the `ServiceMessage` wire schema. Bump it for changes
+/// an older peer would misparse; additive fields with serde defaults don't
+/// need one.
+pub const MESSAGE_SCHEMA_VERSION: u16 = 2;
+/// The schema before versioning, when messages were sent bare rather than
+/// in an envelope. Still accepted so peers can be upgraded one at a time.
+pub const LEGACY_MESSAGE_SCHEMA_VERSION: u16 = 1;
 
 // Message processing
 pub const MESSAGE_QUEUE_CAPACITY: usize = 1000;
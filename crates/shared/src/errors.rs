@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -35,6 +36,9 @@ pub enum SystemError {
     #[error("Request timeout")]
     Timeout,
 
+    #[error("Request cancelled")]
+    Cancelled,
+
     #[error("Service unavailable: {service}")]
     ServiceUnavailable { service: String },
 
@@ -66,6 +70,87 @@ impl SystemError {
             SystemError::Network(_) | SystemError::Timeout | SystemError::ServiceUnavailable { .. }
         )
     }
+
+    /// Stable code for reporting this error to users
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            SystemError::Authentication(_) => ErrorCode::AuthFailed,
+            SystemError::RateLimitExceeded { .. } => ErrorCode::RateLimited,
+            SystemError::LLMApi { .. }
+            | SystemError::ExternalService { .. }
+            | SystemError::Network(_)
+            | SystemError::Timeout
+            | SystemError::ServiceUnavailable { .. } => ErrorCode::ProviderDown,
+            SystemError::InvalidInput(_) => ErrorCode::BadInput,
+            SystemError::ServiceCommunication(_)
+            | SystemError::Database(_)
+            | SystemError::Configuration(_)
+            | SystemError::Serialization(_)
+            | SystemError::Io(_)
+            | SystemError::Json(_)
+            | SystemError::Cancelled
+            | SystemError::Unknown(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Error categories shown to users. Unlike `SystemError`'s messages these
+/// don't change with the underlying failure, so frontends can localize them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    AuthFailed,
+    RateLimited,
+    ProviderDown,
+    BadInput,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Default English message for the code
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            ErrorCode::AuthFailed => {
+                "I couldn't sign in to one of the services I use. Please check your credentials."
+            }
+            ErrorCode::RateLimited => {
+                "I'm getting too many requests right now. Please try again shortly."
+            }
+            ErrorCode::ProviderDown => {
+                "A service I depend on isn't responding. Please try again later."
+            }
+            ErrorCode::BadInput => "I couldn't understand that request. Please rephrase it.",
+            ErrorCode::Internal => "Sorry, something went wrong on my side.",
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, SystemError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(
+            SystemError::Authentication("bad key".to_string()).code(),
+            ErrorCode::AuthFailed
+        );
+        assert_eq!(
+            SystemError::RateLimitExceeded {
+                service: "openai".to_string()
+            }
+            .code(),
+            ErrorCode::RateLimited
+        );
+        assert_eq!(SystemError::Timeout.code(), ErrorCode::ProviderDown);
+        assert_eq!(
+            SystemError::InvalidInput("empty".to_string()).code(),
+            ErrorCode::BadInput
+        );
+        assert_eq!(
+            SystemError::Database("locked".to_string()).code(),
+            ErrorCode::Internal
+        );
+    }
+}
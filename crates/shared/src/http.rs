@@ -0,0 +1,167 @@
+use crate::constants::{HTTP_CONNECT_TIMEOUT, HTTP_POOL_IDLE_TIMEOUT, HTTP_POOL_MAX_IDLE_PER_HOST};
+use crate::errors::{Result, SystemError};
+use crate::types::HttpConfig;
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Sent with every request
+pub const USER_AGENT: &str = concat!("ai-manager/", env!("CARGO_PKG_VERSION"));
+
+/// `HttpConfig` parsed once, so building a client can't fail on it
+#[derive(Debug, Default)]
+struct HttpSettings {
+    proxy: Option<Proxy>,
+    root_certificate: Option<Certificate>,
+    accept_invalid_certs: bool,
+}
+
+static SETTINGS: OnceLock<HttpSettings> = OnceLock::new();
+
+impl HttpSettings {
+    fn parse(config: &HttpConfig) -> Result<Self> {
+        let proxy = config
+            .proxy
+            .as_deref()
+            .map(|url| {
+                Proxy::all(url).map_err(|e| {
+                    SystemError::Configuration(format!("Invalid HTTP proxy '{}': {}", url, e))
+                })
+            })
+            .transpose()?;
+
+        let root_certificate = config
+            .ca_cert_path
+            .as_ref()
+            .map(|path| {
+                let pem = std::fs::read(path).map_err(|e| {
+                    SystemError::Configuration(format!(
+                        "Failed to read CA certificate {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                Certificate::from_pem(&pem).map_err(|e| {
+                    SystemError::Configuration(format!(
+                        "Invalid CA certificate {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            proxy,
+            root_certificate,
+            accept_invalid_certs: config.danger_accept_invalid_certs,
+        })
+    }
+
+    fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let Some(certificate) = &self.root_certificate {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        builder.danger_accept_invalid_certs(self.accept_invalid_certs)
+    }
+}
+
+/// Use `config` for every HTTP client. Call it once at startup, before any
+/// clients are created; later calls are ignored.
+pub fn configure(config: &HttpConfig) -> Result<()> {
+    let settings = HttpSettings::parse(config)?;
+    if let Some(proxy) = &config.proxy {
+        info!("Sending HTTP requests through proxy {}", proxy);
+    }
+    if settings.accept_invalid_certs {
+        warn!("TLS certificate verification is disabled for outgoing HTTP requests");
+    }
+
+    if SETTINGS.set(settings).is_err() {
+        warn!("HTTP settings were already configured, ignoring new settings");
+    }
+    Ok(())
+}
+
+/// Clients built so far, one per request timeout
+static CLIENTS: OnceLock<Mutex<HashMap<Duration, Client>>> = OnceLock::new();
+
+fn builder(timeout: Duration) -> ClientBuilder {
+    let builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(timeout)
+        .connect_timeout(Duration::from_secs(HTTP_CONNECT_TIMEOUT).min(timeout))
+        .pool_max_idle_per_host(HTTP_POOL_MAX_IDLE_PER_HOST)
+        .pool_idle_timeout(Duration::from_secs(HTTP_POOL_IDLE_TIMEOUT));
+    SETTINGS.get_or_init(HttpSettings::default).apply(builder)
+}
+
+/// A client whose requests give up after `timeout`, with the configured
+/// proxy and TLS settings. Callers asking for the same timeout share one
+/// client and so its connection pool. Like `Client::new`, panics if the TLS
+/// backend can't be initialized.
+pub fn client(timeout: Duration) -> Client {
+    let mut clients = CLIENTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    clients
+        .entry(timeout)
+        .or_insert_with(|| {
+            builder(timeout)
+                .build()
+                .expect("Failed to create HTTP client")
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_settings_rejected() {
+        let config = HttpConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            HttpSettings::parse(&config),
+            Err(SystemError::Configuration(_))
+        ));
+
+        let config = HttpConfig {
+            ca_cert_path: Some("/nonexistent/corp-ca.pem".into()),
+            ..Default::default()
+        };
+        let err = HttpSettings::parse(&config).unwrap_err().to_string();
+        assert!(err.contains("corp-ca.pem"));
+    }
+
+    #[test]
+    fn test_proxy_settings_build_a_client() {
+        let config = HttpConfig {
+            proxy: Some("http://proxy.example.com:8080".to_string()),
+            ..Default::default()
+        };
+        let settings = HttpSettings::parse(&config).unwrap();
+        assert!(settings.apply(Client::builder()).build().is_ok());
+    }
+
+    #[test]
+    fn test_clients_shared_per_timeout() {
+        let timeout = Duration::from_secs(7);
+        client(timeout);
+        client(timeout);
+        client(Duration::from_secs(8));
+
+        let clients = CLIENTS.get().unwrap().lock().unwrap();
+        assert!(clients.contains_key(&timeout));
+        assert!(clients.contains_key(&Duration::from_secs(8)));
+    }
+}
@@ -1,9 +1,15 @@
+pub mod auth;
+pub mod clock;
 pub mod constants;
 pub mod errors;
+pub mod http;
 pub mod messages;
+pub mod transport;
 pub mod types;
 
+pub use clock::{Clock, MockClock, SystemClock};
 pub use constants::*;
 pub use errors::*;
 pub use messages::*;
+pub use transport::{ChannelTransport, MessageTransport, StreamTransport};
 pub use types::*;
@@ -1,5 +1,9 @@
+use crate::constants::{LEGACY_MESSAGE_SCHEMA_VERSION, MESSAGE_SCHEMA_VERSION};
+use crate::errors::{ErrorCode, Result, SystemError};
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,11 +13,35 @@ pub enum ServiceMessage {
         content: String,
         timestamp: DateTime<Utc>,
         user_id: String,
+        /// Id the replies to this input will carry, so the sender can pick
+        /// them out; the core makes one up when it isn't given
+        #[serde(default)]
+        request_id: Option<Uuid>,
+        /// Send the reply as `LLMResponseChunk`s ending with a `done` one,
+        /// rather than as a single `SystemResponse`
+        #[serde(default)]
+        stream: bool,
     },
     SystemResponse {
         content: String,
         message_type: ResponseType,
         timestamp: DateTime<Utc>,
+        /// LLM request this response belongs to, so thinking notices,
+        /// stream chunks and the answer can be matched up
+        #[serde(default)]
+        request_id: Option<Uuid>,
+    },
+    /// Drop the user's messages after `from_message_id`, one of their own
+    /// messages, and answer it again
+    RegenerateResponse {
+        user_id: String,
+        from_message_id: Uuid,
+    },
+    /// A failure reported to the user by code, without internal details
+    SystemError {
+        code: ErrorCode,
+        user_message: String,
+        request_id: Option<Uuid>,
     },
 
     // Core ↔ LLM communication
@@ -22,11 +50,47 @@ pub enum ServiceMessage {
         context: Vec<String>,
         provider: String,
         request_id: Uuid,
+        /// User the request was made for, echoed back in the response
+        user_id: String,
+        /// Model to use instead of the provider's configured one
+        #[serde(default)]
+        model: Option<String>,
+        /// Sampling temperature instead of the provider's configured one
+        #[serde(default)]
+        temperature: Option<f32>,
+        /// Send the reply as `LLMResponseChunk`s too, ahead of the
+        /// `LLMResponse`
+        #[serde(default)]
+        stream: bool,
     },
     LLMResponse {
         content: String,
+        /// Model that wrote the reply
+        #[serde(default)]
+        model: Option<String>,
         usage: TokenUsage,
         request_id: Uuid,
+        user_id: String,
+        /// The content was already sent as `LLMResponseChunk`s, so it only
+        /// needs storing
+        #[serde(default)]
+        streamed: bool,
+    },
+    /// Part of a streamed LLM response. The LLM service sends these to the
+    /// core, which forwards them to the UI service, followed by the whole
+    /// `LLMResponse`. The last chunk has `done` set and carries the token
+    /// usage of the whole response.
+    LLMResponseChunk {
+        request_id: Uuid,
+        delta: String,
+        done: bool,
+        #[serde(default)]
+        usage: Option<TokenUsage>,
+    },
+    /// Abandon the LLM request `request_id` if it's still in flight, e.g.
+    /// because the user stopped it. It's answered with a `SystemError`.
+    CancelLLMRequest {
+        request_id: Uuid,
     },
 
     // Core ↔ External service communication
@@ -36,17 +100,130 @@ pub enum ServiceMessage {
     EmailProcess {
         emails: Vec<EmailData>,
     },
+    EmailAction {
+        action: EmailAction,
+    },
+    /// Events found for a `CalendarAction::ListEvents`
+    CalendarEventsResponse {
+        events: Vec<CalendarEvent>,
+    },
+    /// Id of the event made for a `CalendarAction::CreateEvent`
+    CalendarEventCreated {
+        event_id: String,
+    },
+    /// Results of an `EmailProcess` batch, one per email that was processed,
+    /// for the UI to show categorized mail and offer the suggested actions
+    EmailProcessResult {
+        emails: Vec<ProcessedEmail>,
+    },
 
     // Core ↔ Data service communication
+    /// Append `messages` to the user's current conversation; the messages
+    /// already stored are kept
     StoreConversation {
         user_id: String,
         messages: Vec<Message>,
     },
+    /// Replace the user's stored messages up to and including
+    /// `summarized_until` with `summary`
+    StoreConversationSummary {
+        user_id: String,
+        summary: Message,
+        summarized_until: DateTime<Utc>,
+    },
     LoadUserProfile {
         user_id: String,
+        /// Nil from peers that predate request ids
+        #[serde(default)]
+        request_id: Uuid,
+    },
+    ClearConversation {
+        user_id: String,
+    },
+    /// Delete conversations not updated for `retain_days` days and trim the
+    /// rest to their last `max_messages` messages
+    CleanupConversations {
+        retain_days: u32,
+        max_messages: usize,
+    },
+    /// Merge `preferences` into the user's stored preferences, creating
+    /// their profile if needed. Keys set to `null` are removed.
+    UpdateUserPreferences {
+        user_id: String,
+        preferences: serde_json::Value,
+    },
+    /// Ask for the user's most recent `limit` messages, preceded by the
+    /// summary of older ones if there is one
+    LoadConversationHistory {
+        user_id: String,
+        limit: usize,
+        request_id: Uuid,
+    },
+    /// Drop the user's messages after `after_message_id`, keeping them
+    /// recoverable for a while, and reply with the most recent `limit`
+    /// remaining ones like `LoadConversationHistory`
+    TruncateConversation {
+        user_id: String,
+        after_message_id: Uuid,
+        limit: usize,
+        request_id: Uuid,
+    },
+    ConversationHistoryResponse {
+        user_id: String,
+        /// Oldest first
+        messages: Vec<Message>,
+        request_id: Uuid,
     },
     UserProfileResponse {
         profile: Option<UserProfile>,
+        /// Nil from peers that predate request ids
+        #[serde(default)]
+        request_id: Uuid,
+    },
+    /// Export the user's conversations. Markdown covers one conversation,
+    /// the most recent if `conversation_id` is `None`; JSON formats cover
+    /// all of them unless `conversation_id` is given.
+    ExportConversation {
+        user_id: String,
+        conversation_id: Option<i64>,
+        #[serde(default)]
+        format: ExportFormat,
+        /// Write exports over the configured inline limit to a file in the
+        /// export directory instead of returning them
+        #[serde(default)]
+        spill_to_file: bool,
+        request_id: Uuid,
+    },
+    /// An export, inline in `content` or, with `path` set, written to a
+    /// file and `content` left empty
+    ConversationExport {
+        user_id: String,
+        content: String,
+        #[serde(default)]
+        path: Option<String>,
+        request_id: Uuid,
+    },
+    /// Persist `rule` for the sender `address`, or drop its rule if `None`
+    StoreSenderRule {
+        address: String,
+        rule: Option<SenderRule>,
+    },
+    /// Ask for every stored sender rule, answered with `SenderRulesLoaded`
+    LoadSenderRules,
+    /// Stored sender rules by address, for the external service
+    SenderRulesLoaded {
+        rules: HashMap<String, SenderRule>,
+    },
+    /// Persist that the email thread `thread_key` was auto-replied to
+    StoreRepliedThread {
+        thread_key: String,
+    },
+    /// Ask for the threads auto-replied to, answered with
+    /// `RepliedThreadsLoaded`
+    LoadRepliedThreads,
+    /// Threads auto-replied to, oldest first, for the external service
+    RepliedThreadsLoaded {
+        thread_keys: Vec<String>,
     },
 
     // System management
@@ -60,6 +237,210 @@ pub enum ServiceMessage {
     ShutdownService {
         service_id: String,
     },
+
+    // Remote service authentication, exchanged before anything else when a
+    // service token is configured
+    /// A fresh `nonce` for the peer to prove it holds the token with
+    AuthChallenge {
+        service_id: String,
+        nonce: Uuid,
+    },
+    /// Answer to the peer's `AuthChallenge`
+    AuthProof {
+        proof: String,
+    },
+}
+
+impl ServiceMessage {
+    /// Name of the message variant, for metrics and logging
+    pub fn message_type(&self) -> &'static str {
+        match self {
+            ServiceMessage::UserInput { .. } => "UserInput",
+            ServiceMessage::SystemResponse { .. } => "SystemResponse",
+            ServiceMessage::RegenerateResponse { .. } => "RegenerateResponse",
+            ServiceMessage::SystemError { .. } => "SystemError",
+            ServiceMessage::LLMRequest { .. } => "LLMRequest",
+            ServiceMessage::LLMResponse { .. } => "LLMResponse",
+            ServiceMessage::LLMResponseChunk { .. } => "LLMResponseChunk",
+            ServiceMessage::CancelLLMRequest { .. } => "CancelLLMRequest",
+            ServiceMessage::CalendarSync { .. } => "CalendarSync",
+            ServiceMessage::EmailProcess { .. } => "EmailProcess",
+            ServiceMessage::EmailAction { .. } => "EmailAction",
+            ServiceMessage::CalendarEventsResponse { .. } => "CalendarEventsResponse",
+            ServiceMessage::CalendarEventCreated { .. } => "CalendarEventCreated",
+            ServiceMessage::EmailProcessResult { .. } => "EmailProcessResult",
+            ServiceMessage::StoreConversation { .. } => "StoreConversation",
+            ServiceMessage::StoreConversationSummary { .. } => "StoreConversationSummary",
+            ServiceMessage::LoadUserProfile { .. } => "LoadUserProfile",
+            ServiceMessage::ClearConversation { .. } => "ClearConversation",
+            ServiceMessage::CleanupConversations { .. } => "CleanupConversations",
+            ServiceMessage::UpdateUserPreferences { .. } => "UpdateUserPreferences",
+            ServiceMessage::LoadConversationHistory { .. } => "LoadConversationHistory",
+            ServiceMessage::TruncateConversation { .. } => "TruncateConversation",
+            ServiceMessage::ConversationHistoryResponse { .. } => "ConversationHistoryResponse",
+            ServiceMessage::UserProfileResponse { .. } => "UserProfileResponse",
+            ServiceMessage::ExportConversation { .. } => "ExportConversation",
+            ServiceMessage::ConversationExport { .. } => "ConversationExport",
+            ServiceMessage::StoreSenderRule { .. } => "StoreSenderRule",
+            ServiceMessage::LoadSenderRules => "LoadSenderRules",
+            ServiceMessage::SenderRulesLoaded { .. } => "SenderRulesLoaded",
+            ServiceMessage::StoreRepliedThread { .. } => "StoreRepliedThread",
+            ServiceMessage::LoadRepliedThreads => "LoadRepliedThreads",
+            ServiceMessage::RepliedThreadsLoaded { .. } => "RepliedThreadsLoaded",
+            ServiceMessage::ServiceHealthCheck { .. } => "ServiceHealthCheck",
+            ServiceMessage::ServiceHealthResponse { .. } => "ServiceHealthResponse",
+            ServiceMessage::ShutdownService { .. } => "ShutdownService",
+            ServiceMessage::AuthChallenge { .. } => "AuthChallenge",
+            ServiceMessage::AuthProof { .. } => "AuthProof",
+        }
+    }
+
+    /// Id that a response to this request will carry
+    pub fn request_id(&self) -> Option<Uuid> {
+        match self {
+            ServiceMessage::LLMRequest { request_id, .. }
+            | ServiceMessage::LoadUserProfile { request_id, .. }
+            | ServiceMessage::LoadConversationHistory { request_id, .. }
+            | ServiceMessage::TruncateConversation { request_id, .. }
+            | ServiceMessage::ExportConversation { request_id, .. } => Some(*request_id),
+            ServiceMessage::UserInput { request_id, .. } => *request_id,
+            _ => None,
+        }
+    }
+
+    /// User-facing report of `error` for the UI
+    pub fn error_reply(error: &SystemError, request_id: Option<Uuid>) -> Self {
+        let code = error.code();
+        ServiceMessage::SystemError {
+            code,
+            user_message: code.user_message().to_string(),
+            request_id,
+        }
+    }
+
+    /// Id of the request this message responds to
+    pub fn in_reply_to(&self) -> Option<Uuid> {
+        match self {
+            ServiceMessage::LLMResponse { request_id, .. }
+            | ServiceMessage::UserProfileResponse { request_id, .. }
+            | ServiceMessage::ConversationHistoryResponse { request_id, .. }
+            | ServiceMessage::ConversationExport { request_id, .. } => Some(*request_id),
+            ServiceMessage::SystemError { request_id, .. } => *request_id,
+            _ => None,
+        }
+    }
+
+    /// Id of the user request this message is part of, for correlating logs
+    pub fn trace_id(&self) -> Option<Uuid> {
+        match self {
+            ServiceMessage::LLMResponseChunk { request_id, .. }
+            | ServiceMessage::CancelLLMRequest { request_id } => Some(*request_id),
+            ServiceMessage::SystemResponse { request_id, .. } => *request_id,
+            // Replies are stored with the id of the request they answer
+            ServiceMessage::StoreConversation { messages, .. } => {
+                messages.iter().find_map(|message| {
+                    let metadata = message.metadata.as_ref()?;
+                    metadata.get("request_id")?.as_str()?.parse().ok()
+                })
+            }
+            _ => self.request_id().or_else(|| self.in_reply_to()),
+        }
+    }
+
+    /// User the message concerns
+    pub fn user_id(&self) -> Option<&str> {
+        match self {
+            ServiceMessage::UserInput { user_id, .. }
+            | ServiceMessage::RegenerateResponse { user_id, .. }
+            | ServiceMessage::LLMRequest { user_id, .. }
+            | ServiceMessage::LLMResponse { user_id, .. }
+            | ServiceMessage::StoreConversation { user_id, .. }
+            | ServiceMessage::StoreConversationSummary { user_id, .. }
+            | ServiceMessage::LoadUserProfile { user_id, .. }
+            | ServiceMessage::ClearConversation { user_id }
+            | ServiceMessage::UpdateUserPreferences { user_id, .. }
+            | ServiceMessage::LoadConversationHistory { user_id, .. }
+            | ServiceMessage::TruncateConversation { user_id, .. }
+            | ServiceMessage::ConversationHistoryResponse { user_id, .. }
+            | ServiceMessage::ExportConversation { user_id, .. }
+            | ServiceMessage::ConversationExport { user_id, .. } => Some(user_id),
+            _ => None,
+        }
+    }
+
+    /// Span to handle the message in, carrying its type, request id and
+    /// user so every log line can be traced back to the request
+    pub fn span(&self) -> tracing::Span {
+        let span = tracing::info_span!(
+            "message",
+            message_type = self.message_type(),
+            request_id = tracing::field::Empty,
+            user_id = tracing::field::Empty,
+        );
+        if let Some(request_id) = self.trace_id() {
+            span.record("request_id", tracing::field::display(request_id));
+        }
+        if let Some(user_id) = self.user_id() {
+            span.record("user_id", user_id);
+        }
+        span
+    }
+
+    /// Serialize for another process, wrapped in an envelope carrying the
+    /// current schema version
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let envelope = MessageEnvelope {
+            schema_version: MESSAGE_SCHEMA_VERSION,
+            payload: self.clone(),
+        };
+        serde_json::to_vec(&envelope).map_err(|e| {
+            SystemError::Serialization(format!("Failed to encode {}: {}", self.message_type(), e))
+        })
+    }
+
+    /// Parse a message from another process. Envelopes from an unknown
+    /// schema version are rejected rather than guessed at; bare messages
+    /// from peers that predate versioning are still accepted.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(bytes)
+            .map_err(|e| SystemError::Serialization(format!("Malformed message: {}", e)))?;
+
+        let Some(version) = value.get("schema_version") else {
+            return Self::from_value(value, LEGACY_MESSAGE_SCHEMA_VERSION);
+        };
+
+        match version.as_u64() {
+            Some(version) if version == MESSAGE_SCHEMA_VERSION as u64 => {
+                let envelope: MessageEnvelope = serde_json::from_value(value).map_err(|e| {
+                    SystemError::Serialization(format!(
+                        "Malformed schema version {} message: {}",
+                        version, e
+                    ))
+                })?;
+                Ok(envelope.payload)
+            }
+            _ => Err(SystemError::Serialization(format!(
+                "Unsupported message schema version {}; this build speaks {} and {}",
+                version, MESSAGE_SCHEMA_VERSION, LEGACY_MESSAGE_SCHEMA_VERSION
+            ))),
+        }
+    }
+
+    fn from_value(value: serde_json::Value, version: u16) -> Result<Self> {
+        serde_json::from_value(value).map_err(|e| {
+            SystemError::Serialization(format!(
+                "Malformed schema version {} message: {}",
+                version, e
+            ))
+        })
+    }
+}
+
+/// A `ServiceMessage` on the wire between processes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEnvelope {
+    pub schema_version: u16,
+    pub payload: ServiceMessage,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +450,41 @@ pub enum ResponseType {
     Warning,
     Error,
     Thinking,
+    /// Clears the `Thinking` notice with the same `request_id`
+    ThinkingDone,
+}
+
+/// Format of a conversation export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    /// A JSON array of messages
+    Json,
+    /// One JSON message per line
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// Parse the name used in commands: `md`, `json` or `jsonl`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "jsonl" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+
+    /// File extension for exports in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,6 +505,8 @@ pub enum CalendarAction {
         description: Option<String>,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
+        #[serde(default)]
+        attendees: Vec<String>,
     },
     UpdateEvent {
         event_id: String,
@@ -100,6 +518,97 @@ pub enum CalendarAction {
     DeleteEvent {
         event_id: String,
     },
+    FindFreeSlot {
+        duration: std::time::Duration,
+        within_range: TimeRange,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarEvent {
+    pub id: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Time zone the event is scheduled in
+    pub tz: Tz,
+    /// Date-only event; `start`/`end` are midnight in `tz`
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub attendees: Vec<String>,
+}
+
+impl CalendarEvent {
+    /// Event start in the event's own time zone
+    pub fn local_start(&self) -> DateTime<Tz> {
+        self.start.with_timezone(&self.tz)
+    }
+
+    /// Event end in the event's own time zone
+    pub fn local_end(&self) -> DateTime<Tz> {
+        self.end.with_timezone(&self.tz)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EmailAction {
+    MarkRead { email_id: String },
+    MoveToFolder { email_id: String, folder: String },
+    Archive { email_id: String },
+    Delete { email_id: String },
+    BlockSender { address: String },
+    AllowSender { address: String },
+    UnblockSender { address: String },
+}
+
+impl EmailAction {
+    /// The action behind one of `ProcessedEmail::suggested_actions` for
+    /// `email`, if it maps to one
+    pub fn from_suggestion(suggestion: &str, email: &EmailData) -> Option<Self> {
+        let email_id = email.id.clone();
+        match suggestion.to_lowercase().as_str() {
+            "mark as read" => Some(EmailAction::MarkRead { email_id }),
+            "archive" => Some(EmailAction::Archive { email_id }),
+            "delete" => Some(EmailAction::Delete { email_id }),
+            "block sender" => Some(EmailAction::BlockSender {
+                address: email.from.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Whether mail from a sender is always or never spam
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SenderRule {
+    Allow,
+    Block,
+}
+
+impl SenderRule {
+    /// Name the rule is stored under
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Block => "block",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(Self::Allow),
+            "block" => Some(Self::Block),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -111,6 +620,54 @@ pub struct EmailData {
     pub body: String,
     pub timestamp: DateTime<Utc>,
     pub is_read: bool,
+    #[serde(default)]
+    pub attachments: Vec<EmailAttachment>,
+    /// The Message-ID header, for threading replies
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Message ids from the References header, oldest first
+    #[serde(default)]
+    pub references: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailAttachment {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: usize,
+    /// Attachment contents; `None` when only metadata was fetched
+    pub data: Option<Vec<u8>>,
+}
+
+/// How an email was categorized, and what to do about it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessedEmail {
+    /// `EmailData::id` of the email
+    pub email_id: String,
+    pub category: EmailCategory,
+    pub priority: EmailPriority,
+    pub is_high_priority: bool,
+    pub suggested_actions: Vec<String>,
+    pub auto_reply: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EmailCategory {
+    Work,
+    Personal,
+    Spam,
+    Newsletter,
+    Meeting,
+    Urgent,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EmailPriority {
+    High,
+    Medium,
+    Low,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -122,6 +679,28 @@ pub struct Message {
     pub metadata: Option<serde_json::Value>,
 }
 
+impl Message {
+    /// A system message standing in for older turns of a conversation
+    pub fn summary(content: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            content,
+            timestamp: Utc::now(),
+            role: MessageRole::System,
+            metadata: Some(serde_json::json!({ "summary": true })),
+        }
+    }
+
+    /// Whether this message is a summary of older turns
+    pub fn is_summary(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("summary"))
+            .and_then(|summary| summary.as_bool())
+            .unwrap_or(false)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageRole {
     User,
@@ -138,6 +717,37 @@ pub struct UserProfile {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Typed view of `UserProfile.preferences`. Unset fields fall back to the
+/// configured defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserPreferences {
+    pub preferred_provider: Option<String>,
+    pub preferred_model: Option<String>,
+    pub temperature: Option<f32>,
+    /// Language replies should be written in
+    pub language: Option<String>,
+    /// Whether the assistant may answer emails on the user's behalf
+    pub auto_reply_enabled: Option<bool>,
+}
+
+impl UserPreferences {
+    /// Read preferences from profile JSON. Fields that are missing or
+    /// malformed are left unset rather than failing the whole profile.
+    pub fn from_value(value: &serde_json::Value) -> Self {
+        let field = |name: &str| value.get(name).cloned().unwrap_or_default();
+        Self {
+            preferred_provider: serde_json::from_value(field("preferred_provider"))
+                .unwrap_or_default(),
+            preferred_model: serde_json::from_value(field("preferred_model")).unwrap_or_default(),
+            temperature: serde_json::from_value(field("temperature")).unwrap_or_default(),
+            language: serde_json::from_value(field("language")).unwrap_or_default(),
+            auto_reply_enabled: serde_json::from_value(field("auto_reply_enabled"))
+                .unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServiceHealth {
     Healthy,
@@ -147,9 +757,200 @@ pub enum ServiceHealth {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SystemEvent {
-    ServiceStarted { service_id: String },
-    ServiceStopped { service_id: String },
-    ServiceRestarted { service_id: String },
-    ErrorOccurred { service_id: String, error: String },
-    MessageReceived { from: String, to: String },
+    ServiceStarted {
+        service_id: String,
+    },
+    ServiceStopped {
+        service_id: String,
+    },
+    ServiceRestarted {
+        service_id: String,
+    },
+    ErrorOccurred {
+        service_id: String,
+        error: String,
+    },
+    MessageReceived {
+        from: String,
+        to: String,
+    },
+    /// No response arrived for a correlated request before its deadline
+    RequestTimedOut {
+        request_id: Uuid,
+        target: String,
+    },
+}
+
+impl SystemEvent {
+    /// Name of the variant, used to filter event subscriptions
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            SystemEvent::ServiceStarted { .. } => "ServiceStarted",
+            SystemEvent::ServiceStopped { .. } => "ServiceStopped",
+            SystemEvent::ServiceRestarted { .. } => "ServiceRestarted",
+            SystemEvent::ErrorOccurred { .. } => "ErrorOccurred",
+            SystemEvent::MessageReceived { .. } => "MessageReceived",
+            SystemEvent::RequestTimedOut { .. } => "RequestTimedOut",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear() -> ServiceMessage {
+        ServiceMessage::ClearConversation {
+            user_id: "alice".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_user_preferences_from_value() {
+        let preferences = UserPreferences::from_value(&serde_json::json!({
+            "preferred_provider": "claude",
+            "temperature": "warm",
+            "theme": "dark",
+        }));
+
+        assert_eq!(preferences.preferred_provider.as_deref(), Some("claude"));
+        assert_eq!(preferences.temperature, None);
+        assert_eq!(preferences.preferred_model, None);
+        assert_eq!(
+            UserPreferences::from_value(&serde_json::Value::Null),
+            UserPreferences::default()
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let bytes = clear().encode().unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(value["schema_version"], MESSAGE_SCHEMA_VERSION);
+
+        assert!(matches!(
+            ServiceMessage::decode(&bytes).unwrap(),
+            ServiceMessage::ClearConversation { user_id } if user_id == "alice"
+        ));
+    }
+
+    #[test]
+    fn test_calendar_events_round_trip() {
+        let start = "2024-03-01T14:00:00Z".parse().unwrap();
+        let event = CalendarEvent {
+            id: "evt-1".to_string(),
+            summary: "Standup".to_string(),
+            description: None,
+            start,
+            end: start + chrono::Duration::minutes(15),
+            tz: chrono_tz::Europe::Berlin,
+            all_day: false,
+            location: None,
+            attendees: vec!["bob@example.com".to_string()],
+        };
+        let bytes = ServiceMessage::CalendarEventsResponse {
+            events: vec![event],
+        }
+        .encode()
+        .unwrap();
+
+        let ServiceMessage::CalendarEventsResponse { events } =
+            ServiceMessage::decode(&bytes).unwrap()
+        else {
+            panic!("expected calendar events");
+        };
+        assert_eq!(events[0].id, "evt-1");
+        assert_eq!(events[0].local_start().format("%H:%M").to_string(), "15:00");
+    }
+
+    #[test]
+    fn test_email_action_from_suggestion() {
+        let email = EmailData {
+            id: "42".to_string(),
+            from: "deals@shop.example".to_string(),
+            to: vec![],
+            subject: "Sale".to_string(),
+            body: String::new(),
+            timestamp: Utc::now(),
+            is_read: false,
+            attachments: vec![],
+            message_id: None,
+            references: vec![],
+        };
+
+        assert!(matches!(
+            EmailAction::from_suggestion("Block sender", &email),
+            Some(EmailAction::BlockSender { address }) if address == "deals@shop.example"
+        ));
+        assert!(matches!(
+            EmailAction::from_suggestion("Delete", &email),
+            Some(EmailAction::Delete { email_id }) if email_id == "42"
+        ));
+        assert!(EmailAction::from_suggestion("Add to calendar", &email).is_none());
+    }
+
+    #[test]
+    fn test_legacy_bare_message_accepted() {
+        let bytes = serde_json::to_vec(&clear()).unwrap();
+        assert!(matches!(
+            ServiceMessage::decode(&bytes).unwrap(),
+            ServiceMessage::ClearConversation { .. }
+        ));
+    }
+
+    #[test]
+    fn test_profile_messages_without_request_id_accepted() {
+        let envelope = serde_json::json!({
+            "schema_version": MESSAGE_SCHEMA_VERSION,
+            "payload": { "LoadUserProfile": { "user_id": "alice" } },
+        });
+        assert!(matches!(
+            ServiceMessage::decode(envelope.to_string().as_bytes()).unwrap(),
+            ServiceMessage::LoadUserProfile { request_id, .. } if request_id.is_nil()
+        ));
+
+        let envelope = serde_json::json!({
+            "schema_version": MESSAGE_SCHEMA_VERSION,
+            "payload": { "UserProfileResponse": { "profile": null } },
+        });
+        assert!(matches!(
+            ServiceMessage::decode(envelope.to_string().as_bytes()).unwrap(),
+            ServiceMessage::UserProfileResponse { request_id, .. } if request_id.is_nil()
+        ));
+    }
+
+    #[test]
+    fn test_unknown_version_rejected() {
+        let envelope = serde_json::json!({
+            "schema_version": MESSAGE_SCHEMA_VERSION + 1,
+            "payload": { "SomethingNew": {} },
+        });
+        let result = ServiceMessage::decode(envelope.to_string().as_bytes());
+        assert!(matches!(result, Err(SystemError::Serialization(_))));
+
+        let result = ServiceMessage::decode(b"not json");
+        assert!(matches!(result, Err(SystemError::Serialization(_))));
+    }
+
+    #[test]
+    fn test_trace_id() {
+        let request_id = Uuid::new_v4();
+        let mut reply = Message::summary("Hi".to_string());
+        reply.metadata = Some(serde_json::json!({ "request_id": request_id }));
+        let store = ServiceMessage::StoreConversation {
+            user_id: "alice".to_string(),
+            messages: vec![reply],
+        };
+        assert_eq!(store.trace_id(), Some(request_id));
+        assert_eq!(store.user_id(), Some("alice"));
+
+        let chunk = ServiceMessage::LLMResponseChunk {
+            request_id,
+            delta: "Hi".to_string(),
+            done: false,
+            usage: None,
+        };
+        assert_eq!(chunk.trace_id(), Some(request_id));
+        assert_eq!(clear().trace_id(), None);
+    }
 }
@@ -0,0 +1,230 @@
+use crate::errors::{Result, SystemError};
+use crate::messages::ServiceMessage;
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, WriteHalf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Frames larger than this are rejected rather than allocated
+pub const MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// Prefix marking a Unix socket path in a service address; anything else
+/// is a TCP `host:port`
+pub const UNIX_ADDR_PREFIX: &str = "unix:";
+
+/// A bidirectional channel of `ServiceMessage`s to one service
+#[async_trait]
+pub trait MessageTransport: Send {
+    async fn send(&mut self, message: ServiceMessage) -> Result<()>;
+
+    /// The next message, or `None` once the other end has closed. Must be
+    /// cancel-safe so it can be raced against other work in `select!`.
+    async fn recv(&mut self) -> Result<Option<ServiceMessage>>;
+}
+
+/// Both ends of an in-process transport
+pub struct ChannelTransport {
+    tx: mpsc::Sender<ServiceMessage>,
+    rx: mpsc::Receiver<ServiceMessage>,
+}
+
+impl ChannelTransport {
+    /// Two connected ends; what one sends the other receives
+    pub fn pair(capacity: usize) -> (Self, Self) {
+        let (a_tx, b_rx) = mpsc::channel(capacity);
+        let (b_tx, a_rx) = mpsc::channel(capacity);
+        (Self { tx: a_tx, rx: a_rx }, Self { tx: b_tx, rx: b_rx })
+    }
+}
+
+#[async_trait]
+impl MessageTransport for ChannelTransport {
+    async fn send(&mut self, message: ServiceMessage) -> Result<()> {
+        self.tx
+            .send(message)
+            .await
+            .map_err(|_| SystemError::ServiceCommunication("Transport peer has closed".to_string()))
+    }
+
+    async fn recv(&mut self) -> Result<Option<ServiceMessage>> {
+        Ok(self.rx.recv().await)
+    }
+}
+
+/// Messages over a byte stream such as a TCP or Unix socket. Each frame is
+/// a big-endian `u32` length followed by that many bytes of a JSON
+/// `MessageEnvelope`.
+pub struct StreamTransport<S> {
+    writer: WriteHalf<S>,
+    frames: mpsc::Receiver<Result<ServiceMessage>>,
+    reader: JoinHandle<()>,
+}
+
+impl<S> StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    pub fn new(stream: S) -> Self {
+        let (mut reader, writer) = tokio::io::split(stream);
+        let (frames_tx, frames) = mpsc::channel(crate::MESSAGE_QUEUE_CAPACITY);
+
+        // Read on a task of its own so `recv` never abandons half a frame
+        let reader = tokio::spawn(async move {
+            loop {
+                let frame = read_frame(&mut reader).await.transpose();
+                let Some(frame) = frame else { break };
+                let failed = frame.is_err();
+                if frames_tx.send(frame).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            writer,
+            frames,
+            reader,
+        }
+    }
+}
+
+impl<S> Drop for StreamTransport<S> {
+    fn drop(&mut self) {
+        // The reader holds half of the stream; stop it so the stream closes
+        self.reader.abort();
+    }
+}
+
+#[async_trait]
+impl<S> MessageTransport for StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    async fn send(&mut self, message: ServiceMessage) -> Result<()> {
+        write_frame(&mut self.writer, &message).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<ServiceMessage>> {
+        self.frames.recv().await.transpose()
+    }
+}
+
+/// Connect to a service at `addr`: `unix:<path>` or a TCP `host:port`
+pub async fn connect(addr: &str) -> Result<Box<dyn MessageTransport>> {
+    #[cfg(unix)]
+    if let Some(path) = addr.strip_prefix(UNIX_ADDR_PREFIX) {
+        let stream = tokio::net::UnixStream::connect(path)
+            .await
+            .map_err(|e| SystemError::Network(format!("Failed to connect to {}: {}", addr, e)))?;
+        return Ok(Box::new(StreamTransport::new(stream)));
+    }
+
+    let stream = tokio::net::TcpStream::connect(addr)
+        .await
+        .map_err(|e| SystemError::Network(format!("Failed to connect to {}: {}", addr, e)))?;
+    stream.set_nodelay(true)?;
+    Ok(Box::new(StreamTransport::new(stream)))
+}
+
+/// Write one length-prefixed frame
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &ServiceMessage,
+) -> Result<()> {
+    let payload = message.encode()?;
+    if payload.len() > MAX_FRAME_BYTES {
+        return Err(SystemError::Serialization(format!(
+            "Message of {} bytes exceeds the {} byte frame limit",
+            payload.len(),
+            MAX_FRAME_BYTES
+        )));
+    }
+
+    writer
+        .write_all(&(payload.len() as u32).to_be_bytes())
+        .await?;
+    writer.write_all(&payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read one length-prefixed frame, or `None` if the stream ended cleanly
+/// before the next one
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<ServiceMessage>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_BYTES {
+        return Err(SystemError::Serialization(format!(
+            "Frame of {} bytes exceeds the {} byte limit",
+            length, MAX_FRAME_BYTES
+        )));
+    }
+
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).await?;
+    ServiceMessage::decode(&payload).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(user_id: &str) -> ServiceMessage {
+        ServiceMessage::ClearConversation {
+            user_id: user_id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_channel_pair_is_connected() {
+        let (mut a, mut b) = ChannelTransport::pair(4);
+
+        a.send(clear("alice")).await.unwrap();
+        assert!(matches!(
+            b.recv().await.unwrap(),
+            Some(ServiceMessage::ClearConversation { user_id }) if user_id == "alice"
+        ));
+
+        drop(a);
+        assert!(b.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_transport_round_trip() {
+        let (left, right) = tokio::io::duplex(64);
+        let mut left = StreamTransport::new(left);
+        let mut right = StreamTransport::new(right);
+
+        // Larger than the duplex buffer, so frames arrive in pieces
+        let long_id = "x".repeat(500);
+        left.send(clear(&long_id)).await.unwrap();
+        left.send(clear("bob")).await.unwrap();
+
+        assert!(matches!(
+            right.recv().await.unwrap(),
+            Some(ServiceMessage::ClearConversation { user_id }) if user_id == long_id
+        ));
+        assert!(matches!(
+            right.recv().await.unwrap(),
+            Some(ServiceMessage::ClearConversation { user_id }) if user_id == "bob"
+        ));
+
+        drop(left);
+        assert!(right.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_rejected() {
+        let mut bytes = ((MAX_FRAME_BYTES + 1) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(b"{}");
+
+        let result = read_frame(&mut bytes.as_slice()).await;
+        assert!(matches!(result, Err(SystemError::Serialization(_))));
+    }
+}
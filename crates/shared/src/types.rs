@@ -1,57 +1,313 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 pub type ServiceId = String;
 pub type UserId = String;
 pub type MessageId = String;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AppConfig {
     pub llm: LLMConfig,
     pub database: DatabaseConfig,
     pub external_services: ExternalServicesConfig,
     pub ui: UIConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub health: HealthThresholds,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub input_guard: InputGuardConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LLMConfig {
     pub default_provider: String,
     pub providers: HashMap<String, LLMProviderConfig>,
+    #[serde(default)]
+    pub summarization: SummarizationConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// When long conversations are condensed into a summary, and with what
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SummarizationConfig {
+    pub enabled: bool,
+    /// Summarize once a user has more stored messages than this
+    pub threshold: usize,
+    /// Newest messages left out of the summary
+    pub keep_recent: usize,
+    /// Provider to summarize with, instead of the default provider
+    pub provider: Option<String>,
+    /// Model to summarize with, instead of the provider's configured model
+    pub model: Option<String>,
+}
+
+impl Default for SummarizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: crate::constants::SUMMARIZATION_THRESHOLD_MESSAGES,
+            keep_recent: crate::constants::CONVERSATION_CONTEXT_MESSAGES,
+            provider: None,
+            model: None,
+        }
+    }
+}
+
+/// How user input is screened before it is sent to an LLM
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputGuardConfig {
+    pub enabled: bool,
+    /// Longest message accepted, in characters
+    pub max_length: usize,
+    /// What to do with messages containing prompt-injection markers
+    pub on_injection: InjectionAction,
+    /// Phrases treated as injection markers besides the built-in ones
+    pub extra_markers: Vec<String>,
+    /// Ask the default LLM provider to moderate messages before answering
+    pub moderation: bool,
+}
+
+impl Default for InputGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_length: crate::constants::MAX_PROMPT_LENGTH,
+            on_injection: InjectionAction::Strip,
+            extra_markers: Vec::new(),
+            moderation: false,
+        }
+    }
+}
+
+/// How long stored conversations are kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    /// Conversations not updated for this many days are deleted
+    pub retain_days: u32,
+    /// Messages kept per conversation; older ones are trimmed
+    pub max_messages: usize,
+    pub cleanup_interval_hours: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retain_days: 90,
+            max_messages: crate::constants::MAX_MESSAGE_HISTORY,
+            cleanup_interval_hours: crate::constants::CONVERSATION_CLEANUP_INTERVAL_HOURS,
+        }
+    }
+}
+
+/// How often each user may send messages to the assistant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Sustained rate allowed per user
+    pub requests_per_minute: u32,
+    /// Messages a user may send at once before the rate applies
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            requests_per_minute: crate::constants::USER_REQUESTS_PER_MINUTE,
+            burst: crate::constants::USER_REQUEST_BURST,
+        }
+    }
+}
+
+/// Where conversation exports too large to show inline are written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    pub dir: PathBuf,
+    /// Exports up to this size are returned inline
+    pub inline_max_bytes: usize,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(crate::constants::DEFAULT_EXPORT_DIR),
+            inline_max_bytes: crate::constants::EXPORT_INLINE_MAX_BYTES,
+        }
+    }
+}
+
+/// Proxy and TLS settings for outgoing HTTP requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Proxy for all requests, e.g. `http://proxy.corp:8080`. When unset the
+    /// `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` environment variables apply.
+    pub proxy: Option<String>,
+    /// PEM file with an extra root certificate to trust, such as a corporate CA
+    pub ca_cert_path: Option<PathBuf>,
+    /// Skip TLS certificate verification entirely. Only for debugging.
+    pub danger_accept_invalid_certs: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InjectionAction {
+    /// Remove the markers and forward the rest
+    Strip,
+    /// Refuse the message
+    Reject,
+}
+
+/// Placeholder shown instead of secrets in `Debug` output
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct LLMProviderConfig {
+    /// API key, or a `${VAR}` / `file:<path>` reference resolved at load time
     pub api_key: String,
     pub base_url: Option<String>,
     pub model: String,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    /// Other models users may switch to with `/model`
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Azure OpenAI resource the `azure` provider connects to
+    #[serde(default)]
+    pub azure: Option<AzureOpenAIConfig>,
+    /// More API keys for the same provider; requests are spread across all
+    /// keys to share out their rate limits
+    #[serde(default)]
+    pub extra_api_keys: Vec<String>,
+    /// Health-check by sending a real, billed request rather than only
+    /// checking that the API is reachable
+    #[serde(default)]
+    pub live_health_check: bool,
+}
+
+/// Where an Azure OpenAI deployment lives
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AzureOpenAIConfig {
+    /// Resource name, the `{resource}` in `{resource}.openai.azure.com`
+    pub resource: String,
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+    /// Deployment serving each model; models without one are used as the
+    /// deployment name
+    #[serde(default)]
+    pub deployments: HashMap<String, String>,
+}
+
+fn default_azure_api_version() -> String {
+    crate::constants::AZURE_OPENAI_API_VERSION.to_string()
+}
+
+impl LLMProviderConfig {
+    /// Whether `model` is the configured model or one of the alternatives
+    pub fn offers_model(&self, model: &str) -> bool {
+        self.model == model || self.models.iter().any(|m| m == model)
+    }
+}
+
+impl std::fmt::Debug for LLMProviderConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LLMProviderConfig")
+            .field("api_key", &REDACTED)
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .field("max_tokens", &self.max_tokens)
+            .field("temperature", &self.temperature)
+            .field("models", &self.models)
+            .field("azure", &self.azure)
+            .field("extra_api_keys", &vec![REDACTED; self.extra_api_keys.len()])
+            .field("live_health_check", &self.live_health_check)
+            .finish()
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub database_type: DatabaseType,
     pub connection_string: String,
     pub max_connections: Option<u32>,
+    /// Seconds to wait for a pooled connection before giving up
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    /// Seconds a query may run before it fails with a timeout
+    #[serde(default)]
+    pub query_timeout_secs: Option<u64>,
+    /// Queries taking longer than this many milliseconds are logged
+    #[serde(default)]
+    pub slow_query_ms: Option<u64>,
+    /// Log every executed statement through sqlx. Slow queries are logged
+    /// either way, see `slow_query_ms`.
     pub enable_logging: bool,
+    /// Directory of `NNN_name.up.sql` migrations, see `migrations_path`
+    #[serde(default)]
+    pub migrations_dir: Option<PathBuf>,
+}
+
+impl DatabaseConfig {
+    /// A SQLite database at `connection_string` with default pool settings
+    pub fn sqlite(connection_string: impl Into<String>) -> Self {
+        Self {
+            database_type: DatabaseType::SQLite,
+            connection_string: connection_string.into(),
+            max_connections: None,
+            connect_timeout_secs: None,
+            query_timeout_secs: None,
+            slow_query_ms: None,
+            enable_logging: false,
+            migrations_dir: None,
+        }
+    }
+
+    /// `migrations_dir` if set, else the migrations directory next to the
+    /// executable, so it doesn't depend on where the service is started from
+    pub fn migrations_path(&self) -> PathBuf {
+        self.migrations_dir.clone().unwrap_or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| {
+                    exe.parent()
+                        .map(|dir| dir.join(crate::constants::MIGRATIONS_DIR))
+                })
+                .unwrap_or_else(|| PathBuf::from(crate::constants::MIGRATIONS_DIR))
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DatabaseType {
     SQLite,
     PostgreSQL,
     External { provider: String },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExternalServicesConfig {
     pub google_calendar: Option<GoogleCalendarConfig>,
     pub email: Option<EmailConfig>,
     pub notifications: NotificationConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GoogleCalendarConfig {
     pub client_id: String,
     pub client_secret: String,
@@ -59,12 +315,12 @@ pub struct GoogleCalendarConfig {
     pub calendar_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EmailConfig {
     pub accounts: Vec<EmailAccountConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct EmailAccountConfig {
     pub name: String,
     pub email: String,
@@ -73,32 +329,143 @@ pub struct EmailAccountConfig {
     pub smtp_server: String,
     pub smtp_port: u16,
     pub username: String,
+    /// Password, or a `${VAR}` / `file:<path>` reference resolved at load time
     pub password: String,
     pub use_tls: bool,
+    /// Categories of mail to this account that get an automatic reply;
+    /// none by default
+    #[serde(default)]
+    pub auto_reply_categories: Vec<crate::messages::EmailCategory>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl std::fmt::Debug for EmailAccountConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailAccountConfig")
+            .field("name", &self.name)
+            .field("email", &self.email)
+            .field("imap_server", &self.imap_server)
+            .field("imap_port", &self.imap_port)
+            .field("smtp_server", &self.smtp_server)
+            .field("smtp_port", &self.smtp_port)
+            .field("username", &self.username)
+            .field("password", &REDACTED)
+            .field("use_tls", &self.use_tls)
+            .field("auto_reply_categories", &self.auto_reply_categories)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NotificationConfig {
     pub enable_desktop: bool,
     pub enable_sound: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UIConfig {
     pub theme: String,
     pub window_size: WindowSize,
     pub enable_system_tray: bool,
+    /// Who the app acts for. When unset an anonymous id is generated and
+    /// saved to the user config on first run.
+    #[serde(default)]
+    pub user_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WindowSize {
     pub width: u32,
     pub height: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LoggingConfig {
     pub level: String,
     pub file_logging: bool,
     pub log_file_path: Option<String>,
 }
+
+/// Limits past which a service reports itself degraded or unhealthy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthThresholds {
+    pub max_error_count: u64,
+    pub max_memory_mb: f64,
+    pub max_cpu_percent: f64,
+    pub max_queue_length: usize,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_error_count: crate::constants::HEALTH_MAX_ERROR_COUNT,
+            max_memory_mb: crate::constants::HEALTH_MAX_MEMORY_MB,
+            max_cpu_percent: crate::constants::HEALTH_MAX_CPU_PERCENT,
+            max_queue_length: crate::constants::HEALTH_MAX_QUEUE_LENGTH,
+        }
+    }
+}
+
+/// Addresses of the health/metrics endpoint and the REST API
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub health_port: u16,
+    pub api_port: u16,
+    /// Bearer token the REST API requires, or a `${VAR}` / `file:<path>`
+    /// reference. It may act for any user. The API is only served when this
+    /// or `api_users` is set.
+    pub api_token: Option<String>,
+    /// Bearer tokens, or secret references, by the user id they act as.
+    /// Requests with one of these are limited to that user's data.
+    pub api_users: HashMap<String, String>,
+    /// Address of a data service running as its own process, a TCP
+    /// `host:port` or `unix:<path>`. The core connects to it when set; the
+    /// standalone data service listens on it.
+    pub data_service_addr: Option<String>,
+    /// Shared secret a remote service must prove it holds before it is put
+    /// on the event bus. Remote connections are unauthenticated without it.
+    pub service_token: Option<String>,
+}
+
+impl std::fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("enabled", &self.enabled)
+            .field("host", &self.host)
+            .field("health_port", &self.health_port)
+            .field("api_port", &self.api_port)
+            .field("api_token", &self.api_token.as_ref().map(|_| REDACTED))
+            .field(
+                "api_users",
+                &self
+                    .api_users
+                    .keys()
+                    .map(|user| (user, REDACTED))
+                    .collect::<HashMap<_, _>>(),
+            )
+            .field("data_service_addr", &self.data_service_addr)
+            .field(
+                "service_token",
+                &self.service_token.as_ref().map(|_| REDACTED),
+            )
+            .finish()
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: crate::constants::DEFAULT_HTTP_HOST.to_string(),
+            health_port: crate::constants::DEFAULT_HEALTH_PORT,
+            api_port: crate::constants::DEFAULT_API_PORT,
+            api_token: None,
+            api_users: HashMap::new(),
+            data_service_addr: None,
+            service_token: None,
+        }
+    }
+}
@@ -1,11 +1,24 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use ai_manager_shared::messages::ServiceMessage;
+use ai_manager_core::{
+    config::ConfigManager,
+    core_service::{run_llm_service, CoreService},
+    event_bus::EventBus,
+    replies::{PendingReplies, Replies},
+};
+use ai_manager_llm_service::UsageTracker;
+use ai_manager_shared::messages::{ResponseType, ServiceMessage, TokenUsage};
+use ai_manager_shared::{
+    CORE_SERVICE_ID, LLM_SERVICE_ID, SERVICE_SHUTDOWN_GRACE_SECONDS, UI_SERVICE_ID,
+};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tauri::State;
-use tokio::sync::{mpsc, Mutex};
+use std::time::Duration;
+use tauri::{Emitter, RunEvent, State, Window};
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct MessageResponse {
@@ -13,8 +26,39 @@ struct MessageResponse {
     timestamp: String,
 }
 
+/// Payload of the `llm-started` event, with the id to pass to
+/// `cancel_request`
+#[derive(Debug, Clone, Serialize)]
+struct StartedPayload {
+    request_id: String,
+}
+
+/// Payload of the `llm-chunk` event
+#[derive(Debug, Clone, Serialize)]
+struct ChunkPayload {
+    request_id: String,
+    delta: String,
+}
+
+/// Payload of the `llm-done` event
+#[derive(Debug, Clone, Serialize)]
+struct DonePayload {
+    request_id: String,
+    usage: Option<TokenUsage>,
+}
+
+/// Payload of the `llm-error` event
+#[derive(Debug, Clone, Serialize)]
+struct ErrorPayload {
+    request_id: String,
+    message: String,
+}
+
 struct AppState {
-    core_sender: Arc<Mutex<Option<mpsc::UnboundedSender<ServiceMessage>>>>,
+    event_bus: Arc<EventBus>,
+    // Requests waiting for the replies the core sends to the UI service
+    replies: PendingReplies,
+    user_id: String,
 }
 
 #[tauri::command]
@@ -22,25 +66,246 @@ async fn greet(name: &str) -> Result<String, String> {
     Ok(format!("Hello, {}! You've been greeted from Rust!", name))
 }
 
+/// `user_id` overrides the configured user, e.g. for a multi-user frontend
 #[tauri::command]
-async fn send_message(message: &str, state: State<'_, AppState>) -> Result<String, String> {
-    println!("Received message: {}", message);
+async fn send_message(
+    message: &str,
+    user_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let user_id = user_id.unwrap_or_else(|| state.user_id.clone());
+    let request_id = Uuid::new_v4();
+    let mut replies = state.replies.expect(request_id, &user_id);
 
-    // For now, return a simple response
-    // TODO: Connect to the core service
-    Ok(format!("Echo: {}", message))
+    let user_input = ServiceMessage::UserInput {
+        content: message.to_string(),
+        timestamp: chrono::Utc::now(),
+        user_id,
+        request_id: Some(request_id),
+        stream: false,
+    };
+    state
+        .event_bus
+        .route_message(user_input, Some(CORE_SERVICE_ID.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    loop {
+        match replies.next().await.map_err(|e| e.to_string())? {
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::Thinking | ResponseType::ThinkingDone,
+                ..
+            } => continue,
+            ServiceMessage::SystemResponse {
+                content,
+                message_type: ResponseType::Error,
+                ..
+            } => return Err(content),
+            ServiceMessage::SystemResponse { content, .. } => return Ok(content),
+            ServiceMessage::SystemError { user_message, .. } => return Err(user_message),
+            _ => continue,
+        }
+    }
+}
+
+/// Like `send_message`, but emits an `llm-started` event once the LLM request
+/// is made, an `llm-chunk` event per streamed delta and an `llm-done` event
+/// with token usage once the response is complete, or an `llm-error` event if
+/// it fails. Returns the full response text.
+#[tauri::command]
+async fn send_message_streaming(
+    message: &str,
+    user_id: Option<String>,
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let user_id = user_id.unwrap_or_else(|| state.user_id.clone());
+    let request_id = Uuid::new_v4();
+    let mut replies = state.replies.expect(request_id, &user_id);
+
+    let user_input = ServiceMessage::UserInput {
+        content: message.to_string(),
+        timestamp: chrono::Utc::now(),
+        user_id,
+        request_id: Some(request_id),
+        stream: true,
+    };
+    let result = match state
+        .event_bus
+        .route_message(user_input, Some(CORE_SERVICE_ID.to_string()))
+        .await
+    {
+        Ok(()) => stream_replies(&window, &mut replies, request_id).await,
+        Err(e) => Err(e.to_string()),
+    };
+
+    if let Err(message) = &result {
+        let payload = ErrorPayload {
+            request_id: request_id.to_string(),
+            message: message.clone(),
+        };
+        let _ = window.emit("llm-error", payload);
+    }
+    result
+}
+
+/// Emit events for the replies to `request_id` until the response is
+/// complete, returning its text
+async fn stream_replies(
+    window: &Window,
+    replies: &mut Replies,
+    request_id: Uuid,
+) -> Result<String, String> {
+    let done = |usage| {
+        let payload = DonePayload {
+            request_id: request_id.to_string(),
+            usage,
+        };
+        window.emit("llm-done", payload).map_err(|e| e.to_string())
+    };
+
+    let mut streamed = String::new();
+    loop {
+        match replies.next().await.map_err(|e| e.to_string())? {
+            ServiceMessage::LLMResponseChunk {
+                delta,
+                done: finished,
+                usage,
+                ..
+            } => {
+                if !delta.is_empty() {
+                    streamed.push_str(&delta);
+                    let payload = ChunkPayload {
+                        request_id: request_id.to_string(),
+                        delta,
+                    };
+                    window
+                        .emit("llm-chunk", payload)
+                        .map_err(|e| e.to_string())?;
+                }
+                if finished {
+                    done(usage)?;
+                    return Ok(streamed);
+                }
+            }
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::Thinking,
+                ..
+            } => {
+                let payload = StartedPayload {
+                    request_id: request_id.to_string(),
+                };
+                window
+                    .emit("llm-started", payload)
+                    .map_err(|e| e.to_string())?;
+            }
+            ServiceMessage::SystemResponse {
+                message_type: ResponseType::ThinkingDone,
+                ..
+            } => continue,
+            ServiceMessage::SystemResponse {
+                content,
+                message_type: ResponseType::Error,
+                ..
+            } => return Err(content),
+            ServiceMessage::SystemError { user_message, .. } => return Err(user_message),
+            // Any other reply is final, e.g. for system commands, which
+            // aren't streamed
+            ServiceMessage::SystemResponse { content, .. } => {
+                done(None)?;
+                return Ok(content);
+            }
+            _ => continue,
+        }
+    }
+}
+
+/// Stop generating the response to `request_id`, as given by the
+/// `llm-started` and `llm-chunk` events, so it's no longer paid for
+#[tauri::command]
+async fn cancel_request(request_id: &str, state: State<'_, AppState>) -> Result<(), String> {
+    let request_id = request_id.parse().map_err(|_| "Invalid request id")?;
+    state
+        .event_bus
+        .route_message(
+            ServiceMessage::CancelLLMRequest { request_id },
+            Some(LLM_SERVICE_ID.to_string()),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Estimated token count of a message for `model`, for showing cost before sending
+#[tauri::command]
+fn estimate_tokens(message: &str, model: &str) -> usize {
+    ai_manager_llm_service::token_count(message, model)
 }
 
 #[tokio::main]
 async fn main() {
+    let event_bus = Arc::new(EventBus::new());
+    let (_ui_sender, ui_receiver) = event_bus
+        .register_service(UI_SERVICE_ID.to_string())
+        .await
+        .expect("failed to register UI service");
+
+    // Run the core and LLM services in-process so user input has somewhere to go
+    let config_manager = ConfigManager::new().expect("failed to load configuration");
+    let app_config = config_manager
+        .get_app_config()
+        .expect("failed to load configuration");
+    ai_manager_shared::http::configure(&app_config.http).expect("invalid HTTP settings");
+    let user_id = config_manager
+        .user_id()
+        .expect("failed to determine user id");
+    let llm_config = app_config.llm;
+    let usage_tracker = Arc::new(UsageTracker::new());
+    let core_usage = usage_tracker.clone();
+    let core_bus = event_bus.clone();
+    tokio::spawn(async move {
+        let mut core_service =
+            CoreService::new(core_bus, config_manager).with_usage_tracker(core_usage);
+        if let Err(e) = core_service.start().await {
+            error!("Core service stopped: {}", e);
+        }
+    });
+    let llm_bus = event_bus.clone();
+    let shutdown = CancellationToken::new();
+    let llm_shutdown = shutdown.clone();
+    let (llm_stopped_tx, llm_stopped_rx) = std::sync::mpsc::channel::<()>();
+    tokio::spawn(async move {
+        if let Err(e) = run_llm_service(llm_bus, &llm_config, usage_tracker, llm_shutdown).await {
+            error!("LLM service stopped: {}", e);
+        }
+        let _ = llm_stopped_tx.send(());
+    });
+
+    let replies = PendingReplies::default();
+    tokio::spawn(replies.clone().dispatch(ui_receiver));
     let app_state = AppState {
-        core_sender: Arc::new(Mutex::new(None)),
+        event_bus,
+        replies,
+        user_id,
     };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(app_state)
-        .invoke_handler(tauri::generate_handler![greet, send_message])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            send_message,
+            send_message_streaming,
+            cancel_request,
+            estimate_tokens
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |_app, event| {
+            if let RunEvent::Exit = event {
+                // Let the LLM service finish the requests it already accepted
+                shutdown.cancel();
+                let _ = llm_stopped_rx
+                    .recv_timeout(Duration::from_secs(SERVICE_SHUTDOWN_GRACE_SECONDS));
+            }
+        });
 }